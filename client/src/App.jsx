@@ -65,6 +65,13 @@ export default function App() {
 
   api.getAppVersion?.().then(v => setVersion(v || '')).catch(() => {});
 
+  // Disco clonato da un altro PC: stessa identità locale → proponi di rigenerarla
+  api.machineIdStatus?.().then(st => {
+    if (st?.cloneSuspected && window.confirm("Questa installazione sembra copiata da un altro computer. Generare una nuova identità per questo dispositivo? (La licenza andrà riattivata.)")) {
+      api.regenerateMachineId().catch(e => console.error(e));
+    }
+  }).catch(() => {});
+
   // Carichiamo le impostazioni (incluso il tempo di notifica)
   api.getSettings?.().then(s => {
       if (s) {
@@ -138,11 +145,17 @@ export default function App() {
 
     const removeLockListener = api.onLock?.(() => handleLockLocal(true));        // autolock backend
     const removeVaultLockedListener = api.onVaultLocked?.(() => handleLockLocal(true)); // autolock backend
+    // closeBehavior 'ask': il backend ha annullato la chiusura e chiede cosa fare
+    const removeCloseListener = api.onCloseRequested?.(() => {
+      if (window.confirm("Chiudere LexFlow? Annulla per ridurlo nella barra di sistema.")) api.quitApp();
+      else api.windowClose();
+    });
 
     return () => {
       if (typeof removeBlurListener === 'function') removeBlurListener();
       if (typeof removeLockListener === 'function') removeLockListener();
       if (typeof removeVaultLockedListener === 'function') removeVaultLockedListener();
+      if (typeof removeCloseListener === 'function') removeCloseListener();
     };
   }, [privacyEnabled, handleLockLocal]);
 
@@ -217,8 +230,10 @@ export default function App() {
             // FIX: remindMinutes can be the string "custom" from UI — coerce to integer.
             // When "custom", use 0 (the actual fire time comes from customRemindTime).
             remindMinutes: (typeof e.remindMinutes === 'number') ? e.remindMinutes
-              : (e.remindMinutes === 'custom' ? 0 : (parseInt(e.remindMinutes, 10) || (currentSettings?.preavviso || 30))),
+              : (e.remindMinutes === 'custom' ? 0 : (parseInt(e.remindMinutes, 10) || null)),
             customRemindTime: e.customRemindTime || null,
+            timezone: e.timezone || null,
+            practiceId: e.practiceId || null,
           }));
         const deadlineItems = [];
         pracs.filter(p => p.status === 'active').forEach(p => {
@@ -241,6 +256,14 @@ export default function App() {
     }
   }, [syncDeadlinesToAgenda]);
 
+  // Archiviazione in background: ricarica l'agenda senza gli impegni spostati in archivio
+  useEffect(() => {
+    const removeArchivedListener = api.onAgendaArchived?.(() => loadAllData());
+    return () => {
+      if (typeof removeArchivedListener === 'function') removeArchivedListener();
+    };
+  }, [loadAllData]);
+
   const handleUnlock = async () => {
     setBlurred(false);
     setAutoLocked(false);
@@ -306,8 +329,10 @@ export default function App() {
         // FIX: remindMinutes can be the string "custom" from UI — coerce to integer.
         // When "custom", use 0 (the actual fire time comes from customRemindTime).
         remindMinutes: (typeof e.remindMinutes === 'number') ? e.remindMinutes
-          : (e.remindMinutes === 'custom' ? 0 : (parseInt(e.remindMinutes, 10) || (settings?.preavviso || 30))),
+          : (e.remindMinutes === 'custom' ? 0 : (parseInt(e.remindMinutes, 10) || null)),
         customRemindTime: e.customRemindTime || null,
+        timezone: e.timezone || null,
+        practiceId: e.practiceId || null,
       }));
     // B. Scadenze fascicoli attivi (notifica alle 09:00 del giorno della scadenza)
     const deadlineItems = [];
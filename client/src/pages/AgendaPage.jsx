@@ -122,6 +122,8 @@ function EventModal({ event, date, onSave, onDelete, onClose, practices }) {
       notes,
       remindMinutes,
       customRemindTime: remindMinutes === 'custom' ? customRemindTime : null,
+      // IANA zone the times are expressed in: reminders keep their instant when travelling
+      timezone: event?.timezone || Intl.DateTimeFormat().resolvedOptions().timeZone || null,
       completed: event?.completed || false,
       autoSync: event?.autoSync || false,
       practiceId: practiceId || null,
@@ -1114,8 +1116,10 @@ function NotificationSettingsPopup({ settings, agendaEvents, onSave, onClose })
           date: e.date,
           time: e.timeStart,
           title: e.title,
-          remindMinutes: e.remindMinutes ?? (preavviso || 30),
+          remindMinutes: e.remindMinutes ?? null,
           customRemindTime: e.customRemindTime || null,
+          timezone: e.timezone || null,
+          practiceId: e.practiceId || null,
         }));
       await api.syncNotificationSchedule({ briefingTimes, items });
       onSave(updated);
@@ -1215,19 +1219,38 @@ export default function AgendaPage({ agendaEvents, onSaveAgenda, practices, onSe
   const [showNotifPopup, setShowNotifPopup] = useState(false);
   const [showStats, setShowStats] = useState(false);
   const [localSettings, setLocalSettings] = useState(settings || {});
+  const [archivedEvents, setArchivedEvents] = useState([]);
   const events = agendaEvents || [];
 
+  // Impegni completati archiviati dal backend: visibili in sola lettura
+  useEffect(() => {
+    let active = true;
+    api.loadArchivedAgenda?.()
+      .then(list => { if (active) setArchivedEvents(Array.isArray(list) ? list : []); })
+      .catch(() => { if (active) setArchivedEvents([]); });
+    return () => { active = false; };
+  }, [agendaEvents]);
+  const shownEvents = useMemo(() => {
+    const liveIds = new Set(events.map(e => e.id));
+    return [...events, ...archivedEvents.filter(e => !liveIds.has(e.id)).map(e => ({ ...e, archived: true }))];
+  }, [events, archivedEvents]);
+  const notifyArchived = () => toast('Impegno archiviato: sola lettura');
+
   const toggleFilter = (cat) => setActiveFilters(prev => 
     prev.includes(cat) ? prev.filter(c => c !== cat) : [...prev, cat]
   );
 
   const handleSave = (ev) => {
+    if (ev.archived) { notifyArchived(); return; }
     const updated = events.some(e => e.id === ev.id) ? events.map(e => e.id === ev.id ? ev : e) : [...events, ev];
     onSaveAgenda(updated); setModalEvent(null);
   };
 
   const handleDelete = (id) => { onSaveAgenda(events.filter(e => e.id !== id)); setModalEvent(null); };
-  const handleToggle = (id) => onSaveAgenda(events.map(e => e.id === id ? {...e, completed: !e.completed} : e));
+  const handleToggle = (id) => {
+    if (!events.some(e => e.id === id)) { notifyArchived(); return; }
+    onSaveAgenda(events.map(e => e.id === id ? {...e, completed: !e.completed} : e));
+  };
   const openAdd = (date, tS, tE) => {
     // Ora attuale arrotondata ai prossimi 30 min
     const n = new Date();
@@ -1238,7 +1261,7 @@ export default function AgendaPage({ agendaEvents, onSaveAgenda, practices, onSe
     const nowEnd = `${String((hh + 1) % 24).padStart(2,'0')}:${String(mm).padStart(2,'0')}`;
     setModalEvent({ event: { date: date || toDateStr(new Date()), timeStart: tS || nowStart, timeEnd: tE || nowEnd }, isNew: true });
   };
-  const openEdit = (ev) => ev.autoSync && ev.practiceId && onSelectPractice ? onSelectPractice(ev.practiceId) : setModalEvent({ event: ev, isNew: false });
+  const openEdit = (ev) => ev.archived ? notifyArchived() : ev.autoSync && ev.practiceId && onSelectPractice ? onSelectPractice(ev.practiceId) : setModalEvent({ event: ev, isNew: false });
   
   const views = [ 
     { key: 'today', label: 'Oggi', icon: Clock }, 
@@ -1337,15 +1360,15 @@ export default function AgendaPage({ agendaEvents, onSaveAgenda, practices, onSe
       {/* ═══ CONTENUTO PRINCIPALE ═══ */}
       <div className={`flex-1 overflow-hidden grid gap-5 items-start ${showStats ? 'grid-cols-[1fr_260px]' : 'grid-cols-1'}`} style={{ transition: 'grid-template-columns 0.3s' }}>
         <div className="overflow-hidden h-full">
-          {view === 'today' && <TodayView events={events} onToggle={handleToggle} onEdit={openEdit} onAdd={openAdd} onSave={handleSave} activeFilters={activeFilters} />}
-          {view === 'week' && <WeekView events={events} onEdit={openEdit} onAdd={openAdd} onSave={handleSave} activeFilters={activeFilters} />}
-          {view === 'month' && <MonthView events={events} onEdit={openEdit} onAdd={openAdd} activeFilters={activeFilters} />}
+          {view === 'today' && <TodayView events={shownEvents} onToggle={handleToggle} onEdit={openEdit} onAdd={openAdd} onSave={handleSave} activeFilters={activeFilters} />}
+          {view === 'week' && <WeekView events={shownEvents} onEdit={openEdit} onAdd={openAdd} onSave={handleSave} activeFilters={activeFilters} />}
+          {view === 'month' && <MonthView events={shownEvents} onEdit={openEdit} onAdd={openAdd} activeFilters={activeFilters} />}
         </div>
         
         {/* Sidebar Destra — solo quando attivata, allineata in alto con l'agenda */}
         {showStats && (
           <div className="space-y-4 overflow-y-auto no-scrollbar pr-1 animate-slide-up self-start">
-            <StatsCard events={events} />
+            <StatsCard events={shownEvents} />
             <UpcomingPanel events={events} onEdit={openEdit} onToggle={handleToggle} />
           </div>
         )}
@@ -50,7 +50,12 @@ export default function BillingPage({ practices }) {
 
   const saveInvoices = useCallback(async (newInvoices) => {
     setInvoices(newInvoices);
-    try { await api.saveInvoices(newInvoices); } catch (e) { console.error(e); toast.error('Errore salvataggio'); }
+    try {
+      const res = await api.saveInvoices(newInvoices);
+      // Il backend ricalcola i totali incoerenti: riallinea lo stato locale
+      if (res?.repaired > 0) setInvoices(await api.loadInvoices() || newInvoices);
+      (res?.warnings || []).forEach(w => toast.error(w.messages.join(' · ')));
+    } catch (e) { console.error(e); toast.error('Errore salvataggio'); }
   }, []);
 
   const deleteInvoice = async (id) => {
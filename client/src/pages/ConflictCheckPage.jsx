@@ -1,4 +1,4 @@
-import React, { useState, useCallback, useRef } from 'react';
+import React, { useState, useCallback, useRef, useEffect } from 'react';
 import { Search, AlertTriangle, Shield, ShieldCheck, User, Briefcase, Scale, ChevronRight, X } from 'lucide-react';
 import * as api from '../tauri-api';
 
@@ -31,6 +31,25 @@ export default function ConflictCheckPage({ onSelectPractice }) {
   const [loading, setLoading] = useState(false);
   const [searched, setSearched] = useState(false);
   const debounceRef = useRef(null);
+  const sessionRef = useRef(null);
+
+  // Sessione indicizzata: il vault viene letto una volta, non ad ogni battuta
+  useEffect(() => () => {
+    if (sessionRef.current) api.endConflictSession(sessionRef.current).catch(() => {});
+  }, []);
+
+  const queryWithSession = useCallback(async (q) => {
+    for (let attempt = 0; attempt < 2; attempt++) {
+      if (!sessionRef.current) sessionRef.current = await api.beginConflictSession();
+      try {
+        return await api.conflictQuery(sessionRef.current, q);
+      } catch (e) {
+        sessionRef.current = null; // scaduta (vault modificato) o rimossa: ricrea
+        if (e?.message !== api.CONFLICT_SESSION_STALE) throw e;
+      }
+    }
+    return api.checkConflict(q);
+  }, []);
 
   const doSearch = useCallback(async (searchQuery) => {
     const q = searchQuery.trim();
@@ -41,7 +60,7 @@ export default function ConflictCheckPage({ onSelectPractice }) {
     }
     setLoading(true);
     try {
-      const res = await api.checkConflict(q);
+      const res = await queryWithSession(q);
       setResults(res);
       setSearched(true);
     } catch (e) {
@@ -50,7 +69,7 @@ export default function ConflictCheckPage({ onSelectPractice }) {
       setSearched(true);
     }
     setLoading(false);
-  }, []);
+  }, [queryWithSession]);
 
   const handleInput = (val) => {
     setQuery(val);
@@ -34,8 +34,9 @@ export default function DeadlinesPage({ practices, onSelectPractice, settings, a
           date: e.date,
           time: e.timeStart,
           title: e.title,
-          remindMinutes: e.remindMinutes ?? (settings?.preavviso || 30),
+          remindMinutes: e.remindMinutes ?? null,
           customRemindTime: e.customRemindTime || null,
+          practiceId: e.practiceId || null,
         }));
       await api.syncNotificationSchedule({ briefingTimes, items });
       setBriefingDirty(false);
@@ -15,7 +15,9 @@ import {
   ArrowLeftRight,
   KeyRound,
   Eye,
-  EyeOff
+  EyeOff,
+  Minimize2,
+  Save
 } from 'lucide-react';
 import toast from 'react-hot-toast';
 import LicenseSettings from '../components/LicenseSettings';
@@ -53,6 +55,9 @@ export default function SettingsPage({ onLock }) {
   // Stato per Sicurezza Avanzata
   const [screenshotProtection, setScreenshotProtection] = useState(true);
   const [autolockMinutes, setAutolockMinutes] = useState(5);
+  const [lockOnMinimize, setLockOnMinimize] = useState(false);
+  const [autosaveOnBlur, setAutosaveOnBlur] = useState(false);
+  const [onIdleHideWindow, setOnIdleHideWindow] = useState(false);
   
   // Modal Factory Reset
   const [showFactoryReset, setShowFactoryReset] = useState(false);
@@ -83,6 +88,9 @@ export default function SettingsPage({ onLock }) {
         if (settings.notificationTime) setNotificationTime(settings.notificationTime);
         if (typeof settings.screenshotProtection === 'boolean') setScreenshotProtection(settings.screenshotProtection);
         if (settings.autolockMinutes !== undefined) setAutolockMinutes(settings.autolockMinutes);
+        if (typeof settings.lockOnMinimize === 'boolean') setLockOnMinimize(settings.lockOnMinimize);
+        if (typeof settings.autosaveOnBlur === 'boolean') setAutosaveOnBlur(settings.autosaveOnBlur);
+        if (typeof settings.onIdleHideWindow === 'boolean') setOnIdleHideWindow(settings.onIdleHideWindow);
       }
     });
   }, []);
@@ -287,6 +295,90 @@ export default function SettingsPage({ onLock }) {
             </button>
           </div>
 
+          {/* Blocco alla minimizzazione */}
+          <div className="flex items-center justify-between group pt-4 border-t border-white/5">
+            <div className="space-y-1">
+              <div className="flex items-center gap-2">
+                <Minimize2 size={16} className="text-primary" />
+                <span className="font-medium text-white">Blocca alla Minimizzazione</span>
+              </div>
+              <p className="text-xs text-text-muted max-w-md">
+                Blocca il Vault quando la finestra viene ridotta a icona.
+              </p>
+            </div>
+            <button 
+              onClick={async () => {
+                const val = !lockOnMinimize;
+                setLockOnMinimize(val);
+                try {
+                  await api.saveSettings({ lockOnMinimize: val });
+                } catch (e) {
+                  toast.error('Errore');
+                  setLockOnMinimize(!val);
+                }
+              }}
+              className={`w-12 h-6 rounded-full transition-colors relative ${lockOnMinimize ? 'bg-primary' : 'bg-white/10'}`}
+            >
+              <div className={`absolute top-1 w-4 h-4 bg-white rounded-full transition-transform duration-200 ${lockOnMinimize ? 'left-7' : 'left-1'}`} />
+            </button>
+          </div>
+
+          {/* Salvataggio alla perdita del focus */}
+          <div className="flex items-center justify-between group pt-4 border-t border-white/5">
+            <div className="space-y-1">
+              <div className="flex items-center gap-2">
+                <Save size={16} className="text-primary" />
+                <span className="font-medium text-white">Salva al Cambio Finestra</span>
+              </div>
+              <p className="text-xs text-text-muted max-w-md">
+                Scrive su disco le modifiche in attesa quando passi a un'altra applicazione.
+              </p>
+            </div>
+            <button 
+              onClick={async () => {
+                const val = !autosaveOnBlur;
+                setAutosaveOnBlur(val);
+                try {
+                  await api.saveSettings({ autosaveOnBlur: val });
+                } catch (e) {
+                  toast.error('Errore');
+                  setAutosaveOnBlur(!val);
+                }
+              }}
+              className={`w-12 h-6 rounded-full transition-colors relative ${autosaveOnBlur ? 'bg-primary' : 'bg-white/10'}`}
+            >
+              <div className={`absolute top-1 w-4 h-4 bg-white rounded-full transition-transform duration-200 ${autosaveOnBlur ? 'left-7' : 'left-1'}`} />
+            </button>
+          </div>
+
+          {/* Nascondi finestra al blocco per inattività */}
+          <div className="flex items-center justify-between group pt-4 border-t border-white/5">
+            <div className="space-y-1">
+              <div className="flex items-center gap-2">
+                <EyeOff size={16} className="text-primary" />
+                <span className="font-medium text-white">Nascondi al Blocco Automatico</span>
+              </div>
+              <p className="text-xs text-text-muted max-w-md">
+                Quando il Vault si blocca per inattività nasconde anche la finestra (solo desktop).
+              </p>
+            </div>
+            <button 
+              onClick={async () => {
+                const val = !onIdleHideWindow;
+                setOnIdleHideWindow(val);
+                try {
+                  await api.saveSettings({ onIdleHideWindow: val });
+                } catch (e) {
+                  toast.error('Errore');
+                  setOnIdleHideWindow(!val);
+                }
+              }}
+              className={`w-12 h-6 rounded-full transition-colors relative ${onIdleHideWindow ? 'bg-primary' : 'bg-white/10'}`}
+            >
+              <div className={`absolute top-1 w-4 h-4 bg-white rounded-full transition-transform duration-200 ${onIdleHideWindow ? 'left-7' : 'left-1'}`} />
+            </button>
+          </div>
+
           {/* Auto-Lock Timer */}
           <div className="pt-4 border-t border-white/5">
             <div className="flex items-center gap-2 mb-1">
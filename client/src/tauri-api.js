@@ -19,20 +19,81 @@ function safeInvoke(cmd, args = {}) {
 
 // Vault / Auth
 export const vaultExists = () => safeInvoke('vault_exists');
+export const estimatedUnlockMs = () => safeInvoke('estimated_unlock_ms');
 export const unlockVault = (pwd) => safeInvoke('unlock_vault', { password: pwd });
+export const getVaultInfo = () => safeInvoke('get_vault_info');
 export const lockVault = () => safeInvoke('lock_vault');
+// { unlocked, idleSecs, thresholdSecs } — thresholdSecs 0 = autolock off
+export const getAutolockStatus = () => safeInvoke('get_autolock_status');
+// Vault profiles: isolated vaults on one OS account. Switching locks the current vault.
+export const listProfiles = () => safeInvoke('list_profiles');
+export const createProfile = (name) => safeInvoke('create_profile', { name });
+export const switchProfile = (name) => safeInvoke('switch_profile', { name });
 export const resetVault = (password) => safeInvoke('reset_vault', { password });
-export const exportVault = (pwd) => safeInvoke('export_vault', { pwd });
-export const importVault = (pwd) => safeInvoke('import_vault', { pwd });
-export const changePassword = (currentPassword, newPassword) =>
-  safeInvoke('change_password', { currentPassword, newPassword });
+// options: { includeAttachments, includeAudit, sections } — omitted = full backup
+export const exportVault = (pwd, options = {}) => safeInvoke('export_vault', {
+  pwd,
+  includeAttachments: options.includeAttachments ?? null,
+  includeAudit: options.includeAudit ?? null,
+  sections: options.sections ?? null,
+});
+// Unencrypted, pretty-printed JSON of the whole vault — only for moving to another tool
+export const exportPlaintextJson = (pwd) => safeInvoke('export_plaintext_json', { pwd });
+// options.newPassword: key the restored vault with a different password (default: the backup's)
+export const importVault = (pwd, options = {}) => safeInvoke('import_vault', {
+  pwd,
+  keepBackupPassword: !options.newPassword,
+  newPassword: options.newPassword || null,
+}); // → { success, passwordSource: 'backup' | 'entered' }
+// Audit log, newest first: { total, offset, limit, hasMore, entries }
+export const getAuditLogPage = (offset = 0, limit = 100) => safeInvoke('get_audit_log_page', { offset, limit });
+// category: 'auth' | 'security' | 'license' | 'practice' | 'agenda' | 'timeLog' | 'invoice' | 'contact' | 'exchange' | 'audit' | 'system' | 'general'
+export const getAuditLogFiltered = (category, targetId) => safeInvoke('get_audit_log_filtered', { category: category || null, targetId: targetId || null });
+// -1 when every audit entry links to its predecessor, else the index of the first broken link
+export const verifyAuditChain = () => safeInvoke('verify_audit_chain');
+// Audit log as CSV + companion .sig (HMAC keyed by the vault key) for regulators
+export const exportAuditCsv = (pwd) => safeInvoke('export_audit_csv', { pwd });
+export const verifyAuditCsvExport = (csv, sig) => safeInvoke('verify_audit_csv_export', { csv, sig });
+// Checks an exported CSV by its trailing HMAC line → { valid, signed }
+export const verifyAuditCsv = (path) => safeInvoke('verify_audit_csv', { path });
+// Full portable archive (.lexarchive): vault, audit log and settings with a versioned manifest.
+// Document blobs (<data>/blobs) and linked files are not included; import merges the audit logs
+export const exportArchive = (pwd) => safeInvoke('export_archive', { pwd });
+export const importArchive = (pwd) => safeInvoke('import_archive', { pwd });
+export const getSupportDiagnostics = () => safeInvoke('get_support_diagnostics');
+// Redacted support bundle sealed with a passphrase (policy-checked) shared with support → saved path
+export const exportDiagnostics = (pwd, includeLog = true) => safeInvoke('export_diagnostics', { pwd, includeLog });
+// Support side: open a .lexdiag bundle with its passphrase → bundle JSON
+export const readDiagnosticsBundle = (path, pwd) => safeInvoke('read_diagnostics_bundle', { path, pwd });
+export const migrateFromOldIdentifier = () => safeInvoke('migrate_from_old_identifier');
+// { ok, dataDir, securityDir, issues } — security dir must be outside the vault dir
+export const checkDirLayout = () => safeInvoke('check_dir_layout');
+// Structural check of a backup without the password (size, header, completeness)
+export const diagnoseBackup = (path) => safeInvoke('diagnose_backup', { path });
+// Full in-memory restore dry run → { success, counts, schemaVersion, partial, ... | error }
+export const simulateRestore = (path, pwd) => safeInvoke('simulate_restore', { path, pwd });
+// Read-only snapshot for analysts: its own password; opening it locks the live vault and rejects every save
+export const exportReadonlySnapshot = (pwd) => safeInvoke('export_readonly_snapshot', { pwd });
+export const openSnapshot = (path, pwd) => safeInvoke('open_snapshot', { path, pwd });
+// kdfParams: optional { mCost (KB), tCost, pCost } to strengthen Argon2 — omitted = keep current
+export const changePassword = (currentPassword, newPassword, kdfParams) =>
+  safeInvoke('change_password', { currentPassword, newPassword, kdfParams: kdfParams || null });
+export const dryRunChangePassword = (currentPassword, newPassword) =>
+  safeInvoke('dry_run_change_password', { currentPassword, newPassword });
 export const verifyVaultPassword = (pwd) => safeInvoke('verify_vault_password', { pwd });
+export const requestReauthToken = (pwd) => safeInvoke('request_reauth_token', { pwd });
+// kind: 'vault' | 'license' → { locked, remaining, attempts }
+export const lockoutStatus = (kind) => safeInvoke('lockout_status', { kind });
 
 // Biometrics
 export const checkBio = () => safeInvoke('check_bio');
 export const hasBioSaved = () => safeInvoke('has_bio_saved');
 export const saveBio = (pwd) => safeInvoke('save_bio', { pwd });
 export const clearBio = () => safeInvoke('clear_bio');
+// { valid, reason, reenroll } — no biometric prompt; needs the vault unlocked to compare
+export const verifyBioCredential = () => safeInvoke('verify_bio_credential');
+// Re-enrol biometrics with the current password (e.g. after verifyBioCredential reports 'stale')
+export const refreshBioAfterPasswordChange = (currentPassword) => safeInvoke('refresh_bio_after_password_change', { currentPassword });
 export const bioLogin = async () => {
   const res = await safeInvoke('bio_login');
   return (res && res.success) ? { success: true } : null;
@@ -40,35 +101,152 @@ export const bioLogin = async () => {
 export const loginBio = bioLogin;
 
 // Data
-export const loadPractices = () => safeInvoke('load_practices');
-export const savePractices = (list) => safeInvoke('save_practices', { list });
-export const loadAgenda = () => safeInvoke('load_agenda');
-export const saveAgenda = (agenda) => safeInvoke('save_agenda', { agenda });
+export const loadPractices = (reauthToken) => safeInvoke('load_practices', { reauthToken: reauthToken || null });
+// Number of confidential practices left out of loadPractices
+export const hiddenPracticesCount = (reauthToken) => safeInvoke('hidden_practices_count', { reauthToken: reauthToken || null });
+export const loadConfidentialPractices = (reauthToken) =>
+  safeInvoke('load_confidential_practices', { reauthToken });
+// includesConfidential: the list also holds the confidential practices (loaded after
+// re-authentication), so one missing from it is deleted
+export const savePractices = (list, reauthToken, includesConfidential) =>
+  safeInvoke('save_practices', { list, includesConfidential: !!includesConfidential, reauthToken: reauthToken || null });
+export const verifyVaultIntegrity = () => safeInvoke('verify_vault_integrity');
+// Malformed dates across the vault → { ok, fixable, unfixable, issues: [{ path, id, value, fixable, fixed }] }
+export const validateDates = (reauthToken) => safeInvoke('validate_dates', { reauthToken: reauthToken || null });
+// policy: 'normalize' (rewrite fixable dates) | 'clear' (also empty unfixable ones) → { fixed, cleared, unfixable }
+export const repairDates = (policy, reauthToken) => safeInvoke('repair_dates', { policy, reauthToken: reauthToken || null });
+// Vault read/write timings: { read, write } each { count, avgMs, maxMs, histogram }
+export const getPerfStats = () => safeInvoke('get_perf_stats');
+export const resetPerfStats = () => safeInvoke('reset_perf_stats');
+// Per-practice secrets (PEC password, court portal login). Empty value deletes.
+export const setPracticeSecret = (practiceId, label, value, reauthToken) =>
+  safeInvoke('set_practice_secret', { practiceId, label, value, reauthToken: reauthToken || null });
+export const getPracticeSecret = (practiceId, label, reauthToken) =>
+  safeInvoke('get_practice_secret', { practiceId, label, reauthToken: reauthToken || null });
+export const listPracticeSecrets = (practiceId, reauthToken) =>
+  safeInvoke('list_practice_secrets', { practiceId, reauthToken: reauthToken || null });
+export const updatePractice = (id, patch, reauthToken) => safeInvoke('update_practice', { id, patch, reauthToken: reauthToken || null });
+// Recycle bin: deleted practices stay in the encrypted vault until restored or purged
+export const softDeletePractice = (id, reauthToken) => safeInvoke('soft_delete_practice', { id, reauthToken: reauthToken || null });
+export const restorePractice = (id, reauthToken) => safeInvoke('restore_practice', { id, reauthToken: reauthToken || null });
+export const listTrash = (reauthToken) => safeInvoke('list_trash', { reauthToken: reauthToken || null });
+export const purgeTrash = (olderThanDays, reauthToken) =>
+  safeInvoke('purge_trash', { olderThanDays, reauthToken: reauthToken || null });
+export const loadAgenda = (reauthToken) => safeInvoke('load_agenda', { reauthToken: reauthToken || null });
+export const saveAgenda = (agenda, reauthToken) => safeInvoke('save_agenda', { agenda, reauthToken: reauthToken || null });
+// Agenda items whose practiceId no longer resolves → { dangling, items: [{ id, title, date, practiceId }] }
+export const validateAgendaLinks = (reauthToken) => safeInvoke('validate_agenda_links', { reauthToken: reauthToken || null });
+// policy: 'unlink' | 'remove' → { repaired }
+export const repairAgendaLinks = (policy, reauthToken) => safeInvoke('repair_agenda_links', { policy, reauthToken: reauthToken || null });
+// Recurring item (recurrence: 'FREQ=DAILY|WEEKLY|MONTHLY;INTERVAL=n;UNTIL=YYYYMMDD'): tick one occurrence → { completedOccurrences }
+export const setOccurrenceCompleted = (itemId, date, completed, reauthToken) =>
+  safeInvoke('set_occurrence_completed', { itemId, date, completed, reauthToken: reauthToken || null });
+// Completed items moved out of the agenda after settings.archiveCompletedAfterDays (each with archivedAt)
+export const loadArchivedAgenda = (reauthToken) => safeInvoke('load_archived_agenda', { reauthToken: reauthToken || null });
+export const importAgendaIcs = (content, reauthToken) =>
+  safeInvoke('import_agenda_ics', { content, reauthToken: reauthToken || null });
 export const getSummary = () => safeInvoke('get_summary');
 
 // Conflict Check
-export const checkConflict = (name) => safeInvoke('check_conflict', { name });
+// { contact, asClient, asCounterparty, adverseRoles, otherRoles, adverseToClients }
+export const contactAdversityMap = (contactId, reauthToken) =>
+  safeInvoke('contact_adversity_map', { contactId, reauthToken: reauthToken || null });
+export const findDuplicatePractices = (includeClosed = false, reauthToken) =>
+  safeInvoke('find_duplicate_practices', { includeClosed, reauthToken: reauthToken || null });
+// Without a reauth token, confidential practice matches are masked: { id, client: 'Fascicolo riservato' }, masked: true
+export const checkConflict = (name, reauthToken) => safeInvoke('check_conflict', { name, reauthToken: reauthToken || null });
+export const searchVault = (query, scope = [], reauthToken) => safeInvoke('search_vault', { query, scope, reauthToken: reauthToken || null });
+// Compliance: active practices without a (current) conflict-check confirmation
+export const practicesMissingConflictCheck = (reauthToken) =>
+  safeInvoke('practices_missing_conflict_check', { reauthToken: reauthToken || null });
+export const confirmConflictCheck = (practiceId, reauthToken) =>
+  safeInvoke('confirm_conflict_check', { practiceId, reauthToken: reauthToken || null });
+// As-you-type: one vault read per form session. conflictQuery rejects with
+// CONFLICT_SESSION_STALE after any vault write — begin a new session and retry.
+export const CONFLICT_SESSION_STALE = 'ConflictSessionStale';
+export const beginConflictSession = (reauthToken) =>
+  safeInvoke('begin_conflict_session', { reauthToken: reauthToken || null });
+export const conflictQuery = (sessionId, name) => safeInvoke('conflict_query', { sessionId, name });
+export const endConflictSession = (sessionId) => safeInvoke('end_conflict_session', { sessionId });
 
 // Time Tracking
-export const loadTimeLogs = () => safeInvoke('load_time_logs');
-export const saveTimeLogs = (logs) => safeInvoke('save_time_logs', { logs });
+export const loadTimeLogs = (reauthToken) => safeInvoke('load_time_logs', { reauthToken: reauthToken || null });
+// Returns {success, normalized, warnings}: durations are stored as integer minutes (durationMin)
+export const saveTimeLogs = (logs, reauthToken) => safeInvoke('save_time_logs', { logs, reauthToken: reauthToken || null });
+// Parse "1.5", "1:30", "90m" or a number of minutes → {minutes, formatted}; style 'hhmm' | 'decimal' | 'long'
+export const convertDuration = (value, style) => safeInvoke('convert_duration', { value, style: style || null });
+export const findTimelogOverlaps = (date, reauthToken) =>
+  safeInvoke('find_timelog_overlaps', { date: date || null, reauthToken: reauthToken || null });
 
 // Invoices / Billing
-export const loadInvoices = () => safeInvoke('load_invoices');
-export const saveInvoices = (invoices) => safeInvoke('save_invoices', { invoices });
+export const loadInvoices = (reauthToken) => safeInvoke('load_invoices', { reauthToken: reauthToken || null });
+export const saveInvoices = (invoices, reauthToken) =>
+  safeInvoke('save_invoices', { invoices, reauthToken: reauthToken || null });
+export const clientBillingSummary = (contactId, year, reauthToken) =>
+  safeInvoke('client_billing_summary', { contactId, year: year ?? null, reauthToken: reauthToken || null });
+export const invoiceAging = (asOf, reauthToken) =>
+  safeInvoke('invoice_aging', { asOf: asOf || null, reauthToken: reauthToken || null });
+// practices.hiddenConfidential: confidential practices counted but left out of the lists without a reauth token
+export const firmActivityReport = (from, to, reauthToken) =>
+  safeInvoke('firm_activity_report', { from, to, reauthToken: reauthToken || null });
 
 // Contacts Registry
-export const loadContacts = () => safeInvoke('load_contacts');
-export const saveContacts = (contacts) => safeInvoke('save_contacts', { contacts });
+export const loadContacts = (reauthToken) => safeInvoke('load_contacts', { reauthToken: reauthToken || null });
+export const saveContacts = (contacts, reauthToken) =>
+  safeInvoke('save_contacts', { contacts, reauthToken: reauthToken || null });
+export const exportContact = (contactId, { password, confirmPlaintext, reauthToken } = {}) =>
+  safeInvoke('export_contact', {
+    contactId, password: password || null, confirmPlaintext: !!confirmPlaintext, reauthToken: reauthToken || null,
+  });
+// GDPR access request: labelled JSON of everything held about the contact; returns { report } for PDF
+export const clientDataExport = (contactId, password) => safeInvoke('client_data_export', { contactId, password });
+
+// Tags — `tags: [..]` on practices/contacts/agenda; matching is trimmed + case-insensitive.
+// filterByTag returns { practices, contacts, agenda, locked } (locked = protected, no token)
+export const filterByTag = (tag, sections, reauthToken) =>
+  safeInvoke('filter_by_tag', { tag, sections: sections || null, reauthToken: reauthToken || null });
+export const listTags = (reauthToken) => safeInvoke('list_tags', { reauthToken: reauthToken || null });
+
+// Sections listed in settings.protectedSections reject calls without a valid token
+// (rejected Error.message === REAUTH_REQUIRED) — obtain one via requestReauthToken().
+export const REAUTH_REQUIRED = 'ReauthRequired';
+
+// Debounced saves (autosave) — coalesced per section, flushed on lock/quit
+export const queueSave = (section, value, reauthToken) =>
+  safeInvoke('queue_save', { section, value, reauthToken: reauthToken || null });
+export const flushSaves = () => safeInvoke('flush_saves');
+export const setSaveDebounceMs = (ms) => safeInvoke('set_save_debounce_ms', { ms });
+export const getSaveDebounceMs = () => safeInvoke('get_save_debounce_ms');
 
 // Settings
 export const getSettings = () => safeInvoke('get_settings');
-export const saveSettings = (settings) => safeInvoke('save_settings', { settings });
+// Merged into the stored settings (null deletes a key); changing protectedSections needs reauthToken
+export const saveSettings = (settings, reauthToken) => safeInvoke('save_settings', { settings, reauthToken: reauthToken || null });
 
 // Files
 export const selectFile = async () => (await safeInvoke('select_file')) || null;
+// Metadata-only size check before attaching: {fileBytes, projectedVaultBytes, recommendation: 'inline'|'external'}
+export const estimateAttachmentImpact = (sourcePath) => safeInvoke('estimate_attachment_impact', { sourcePath });
+// → { items, documentFiles, documentKeys, totalBytes }: vault blobs, blobs/ files and document keys no practice uses
+export const findOrphanedAttachments = (reauthToken) => safeInvoke('find_orphaned_attachments', { reauthToken: reauthToken || null });
+export const purgeOrphanedAttachments = (reauthToken) => safeInvoke('purge_orphaned_attachments', { reauthToken: reauthToken || null });
+// Encrypted copy inside the data folder (max 50 MB, no symlinks) → { blobId, name, size, sha256, addedAt }
+export const attachDocument = (practiceId, srcPath, reauthToken) => safeInvoke('attach_document', { practiceId, srcPath, reauthToken: reauthToken || null });
+// → ArrayBuffer with the decrypted content
+export const readDocument = (blobId, reauthToken) => safeInvoke('read_document', { blobId, reauthToken: reauthToken || null });
+export const deleteDocument = (blobId, reauthToken) => safeInvoke('delete_document', { blobId, reauthToken: reauthToken || null });
+// Signed proof of the practice's current content → { version, practiceId, contentHash, issuedAt, auditAnchor, signature }
+export const practiceProof = (practiceId, reauthToken) => safeInvoke('practice_proof', { practiceId, reauthToken: reauthToken || null });
+// → { valid, matchesCurrent }
+export const verifyPracticeProof = (proof, reauthToken) => safeInvoke('verify_practice_proof', { proof, reauthToken: reauthToken || null });
 export const selectFolder = async () => (await safeInvoke('select_folder')) || null;
 export const openPath = (path) => safeInvoke('open_path', { path });
+export const linkPracticeFolder = (practiceId, folderPath, reauthToken) =>
+  safeInvoke('link_practice_folder', { practiceId, folderPath, reauthToken: reauthToken || null });
+export const listPracticeDocuments = (practiceId, reauthToken) =>
+  safeInvoke('list_practice_documents', { practiceId, reauthToken: reauthToken || null });
+// Stat-only check of every stored attachment path: [{practiceId, field, path, exists, isSymlink}]
+export const auditAttachments = (reauthToken) => safeInvoke('audit_attachments', { reauthToken: reauthToken || null });
 
 // PDF export — bypasses JSON serialization via fs plugin direct write
 export const exportPDF = async (arrayBuffer, defaultName) => {
@@ -85,21 +263,63 @@ export const sendNotification = ({ title, body }) =>
   safeInvoke('send_notification', { title, body });
 export const syncNotificationSchedule = (schedule) =>
   safeInvoke('sync_notification_schedule', { schedule });
+export const setFocusMode = (untilMs) => safeInvoke('set_focus_mode', { untilMs });
+export const clearFocusMode = () => safeInvoke('clear_focus_mode');
+export const getFocusMode = () => safeInvoke('get_focus_mode');
+// Fired-notification ledger (desktop scheduler), newest first
+export const getNotificationHistory = () => safeInvoke('get_notification_history');
+export const clearNotificationHistory = () => safeInvoke('clear_notification_history');
+// Next fire events (briefings + reminders), same rules as the scheduler; works while locked
+export const upcomingNotifications = (count = 10) => safeInvoke('upcoming_notifications', { count });
+// One-off reminder not tied to an agenda item; atMs = unix ms
+export const scheduleAdhocReminder = (title, body, atMs) =>
+  safeInvoke('schedule_adhoc_reminder', { title, body: body || '', atMs });
+// Re-fire a reminder (id as fired) after `minutes` instead of at its own time → { success, snoozeUntil }
+export const snoozeNotification = (itemId, minutes) => safeInvoke('snooze_notification', { itemId, minutes });
+// Mobile: how many notifications the OS accepted in the last sync vs. requested ({requested, scheduled, failed, dropped, limitDetected})
+export const notificationCapacityReport = () => safeInvoke('notification_capacity_report');
+export const getNotificationStrategy = () => safeInvoke('get_notification_strategy');
+export const setNotificationStrategy = (strategy) => safeInvoke('set_notification_strategy', { strategy });
+export const migrateLegacyFile = (kind, sourcePath, reauthToken) =>
+  safeInvoke('migrate_legacy_file', { kind, sourcePath, reauthToken: reauthToken || null });
 
 // Licensing
 export const checkLicense = () => safeInvoke('check_license');
+// Shape-only check for validation while pasting → { valid, error? }
+export const isValidKeyFormat = (key) => safeInvoke('is_valid_key_format', { key });
 export const activateLicense = (key, clientName) =>
   safeInvoke('activate_license', { key, clientName: clientName || null });
+// Same result shape as activateLicense (+ cancelled when the picker is dismissed)
+export const activateLicenseFromFile = () => safeInvoke('activate_license_from_file');
+export const licenseSystemStatus = () => safeInvoke('license_system_status');
+export const exportBurnRegistry = (password) => safeInvoke('export_burn_registry', { password });
+export const importBurnRegistry = (password, content) =>
+  safeInvoke('import_burn_registry', { password, content });
+// Receipts for support: non-secret license data + deviceTag (an HMAC only this device can check, not a signature)
+export const getLicenseReceipt = (password) => safeInvoke('get_license_receipt', { password });
+export const deactivateLicense = (password) => safeInvoke('deactivate_license', { password });
+export const licenseWarningStatus = () => safeInvoke('license_warning_status');
+// Immediate expiry check; emits the due lf-license-expiring/expired event → license warning + { emitted }
+export const checkLicenseExpiry = () => safeInvoke('check_license_expiry');
+export const repairLicenseState = (supportCode) => safeInvoke('repair_license_state', { supportCode });
+// kind: 'seat-bundle' | 'revocation-list' → { valid, kind, summary | error }
+export const verifySignedArtifact = (content, kind) => safeInvoke('verify_signed_artifact', { content, kind });
 
 // Platform / App
 export const isMac = () => safeInvoke('is_mac');
 export const getAppVersion = () => safeInvoke('get_app_version');
 export const getPlatform = () => safeInvoke('get_platform');
+export const getCapabilities = () => safeInvoke('get_capabilities');
+// Cloned disk image detection → { cloneSuspected }; regenerate gives this install its own identity
+export const machineIdStatus = () => safeInvoke('machine_id_status');
+export const regenerateMachineId = () => safeInvoke('regenerate_machine_id');
 
 // Window controls
 export const windowMinimize = () => safeInvoke('window_minimize');
 export const windowMaximize = () => safeInvoke('window_maximize');
 export const windowClose = () => safeInvoke('window_close');
+// Lock + terminate — answer to onCloseRequested when closeBehavior is 'ask'
+export const quitApp = () => safeInvoke('quit_app');
 
 // Security & Content Protection
 export const setContentProtection = (enabled) =>
@@ -122,8 +342,34 @@ export const onVaultLocked = (cb) => {
   const p = listen('lf-vault-locked', () => cb()).catch(() => null);
   return () => p.then(fn => fn && fn());
 };
+// Emitted when the window 'X' is pressed and settings.closeBehavior === 'ask'
+export const onCloseRequested = (cb) => {
+  const p = listen('lf-close-requested', () => cb()).catch(() => null);
+  return () => p.then(fn => fn && fn());
+};
+// Emitted every second during the final idle minute: cb({ remainingSecs })
 export const onVaultWarning = (cb) => {
-  const p = listen('lf-vault-warning', () => cb()).catch(() => null);
+  const p = listen('lf-vault-warning', (e) => cb(e?.payload || {})).catch(() => null);
+  return () => p.then(fn => fn && fn());
+};
+// settings.autolockMode === 'prompt': payload {graceSecs}; verifyVaultPassword within the grace keeps the vault open
+export const onVaultReauthRequired = (cb) => {
+  const p = listen('lf-vault-reauth-required', (e) => cb(e?.payload || {})).catch(() => null);
+  return () => p.then(fn => fn && fn());
+};
+// Once per threshold, when first reached (settings.licenseExpiryThresholds, default 30/7/1): cb({ daysRemaining, threshold, expiryMs, message })
+export const onLicenseExpiring = (cb) => {
+  const p = listen('lf-license-expiring', (e) => cb(e?.payload || {})).catch(() => null);
+  return () => p.then(fn => fn && fn());
+};
+// Once a day after expiry: cb({ daysRemaining, expiryMs, message })
+export const onLicenseExpired = (cb) => {
+  const p = listen('lf-license-expired', (e) => cb(e?.payload || {})).catch(() => null);
+  return () => p.then(fn => fn && fn());
+};
+// Background archival moved completed items to the archive: cb({ count }) → reload the agenda
+export const onAgendaArchived = (cb) => {
+  const p = listen('lf-agenda-archived', (e) => cb(e?.payload || {})).catch(() => null);
   return () => p.then(fn => fn && fn());
 };
 
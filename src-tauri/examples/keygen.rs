@@ -0,0 +1,157 @@
+//! LexFlow — LXFW license issuer (Rust counterpart of scripts/generate_license_v2.py).
+//!
+//! Lives under examples/ rather than src/bin/ on purpose: a second binary target made
+//! Tauri bundle the wrong executable (see CHANGELOG 2.6.0), examples are never bundled.
+//!
+//!   LEXFLOW_SIGNING_KEY=<base64> cargo run --example keygen -- --client "Studio Rossi" --days 365
+//!   cargo run --example keygen -- --key-file signing.key --client "Studio Rossi" --days 30 --nonce <hex>
+//!   cargo run --example keygen -- --key-file signing.key --verify LXFW.<payload>.<sig>
+//!
+//! The token format must stay byte-for-byte what verify_license() in lib.rs expects:
+//! LXFW.<b64url(payload json)>.<b64url(Ed25519 signature over the payload b64 STRING)>.
+
+use base64::{engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE_NO_PAD}, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY_MS: u64 = 86_400_000;
+
+/// Mirror of lib.rs LicensePayload (same field names, same optional fields).
+#[derive(Serialize, Deserialize)]
+struct LicensePayload {
+    c: String,
+    e: u64,
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    n: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    s: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    f: Option<Vec<String>>,
+}
+
+#[derive(Default)]
+struct Args {
+    key_file: Option<String>,
+    client: Option<String>,
+    days: Option<u64>,
+    nonce: Option<String>,
+    id: Option<String>,
+    verify: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut it = std::env::args().skip(1);
+    while let Some(flag) = it.next() {
+        let mut value = || it.next().ok_or(format!("Valore mancante per {}", flag));
+        match flag.as_str() {
+            "--key-file" => args.key_file = Some(value()?),
+            "--client" => args.client = Some(value()?),
+            "--days" => args.days = Some(value()?.parse().map_err(|_| "--days deve essere un intero")?),
+            "--nonce" => args.nonce = Some(value()?),
+            "--id" => args.id = Some(value()?),
+            "--verify" => args.verify = Some(value()?),
+            other => return Err(format!("Argomento sconosciuto: {}", other)),
+        }
+    }
+    Ok(args)
+}
+
+/// Accept standard or URL-safe base64, with or without padding (like normalize_b64 in the Python tool).
+fn decode_b64(raw: &str) -> Result<Vec<u8>, String> {
+    let normalized: String = raw.trim().trim_end_matches('=').chars()
+        .map(|c| match c { '-' => '+', '_' => '/', c => c })
+        .collect();
+    STANDARD_NO_PAD.decode(normalized).map_err(|e| format!("Base64 non valido: {}", e))
+}
+
+/// Private key from --key-file, else from LEXFLOW_SIGNING_KEY.
+fn load_signing_key(key_file: Option<&str>) -> Result<SigningKey, String> {
+    let raw = match key_file {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Lettura {} fallita: {}", path, e))?,
+        None => std::env::var("LEXFLOW_SIGNING_KEY")
+            .map_err(|_| "Chiave privata assente: usa --key-file o LEXFLOW_SIGNING_KEY".to_string())?,
+    };
+    let bytes: [u8; 32] = decode_b64(&raw)?.try_into()
+        .map_err(|b: Vec<u8>| format!("La chiave privata deve essere di 32 byte, ricevuti {}", b.len()))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+fn issue_token(key: &SigningKey, payload: &LicensePayload) -> Result<String, String> {
+    let json = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let payload_b64 = URL_SAFE_NO_PAD.encode(json);
+    let signature = key.sign(payload_b64.as_bytes());
+    Ok(format!("LXFW.{}.{}", payload_b64, URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+}
+
+/// Same checks, in the same order, as verify_license() — minus the burn registry.
+fn verify_token(public_key: &VerifyingKey, token: &str, now_ms: u64) -> Result<LicensePayload, String> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXFW" {
+        return Err("Formato chiave non valido.".into());
+    }
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).map_err(|_| "Errore decodifica payload.")?;
+    let signature_bytes = URL_SAFE_NO_PAD.decode(parts[2]).map_err(|_| "Errore decodifica firma.")?;
+    let signature = Signature::from_slice(&signature_bytes).map_err(|_| "Firma corrotta.")?;
+    public_key.verify(parts[1].as_bytes(), &signature).map_err(|_| "Firma non valida o licenza manomessa!")?;
+    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).map_err(|_| "Dati licenza corrotti.")?;
+    if now_ms > payload.e {
+        return Err("Licenza scaduta.".into());
+    }
+    Ok(payload)
+}
+
+/// Public key as base64 plus the fingerprint license_system_status() reports
+/// (first 8 bytes of SHA-256, hex), so it can be checked against PUBLIC_KEY_BYTES.
+fn describe_public_key(public_key: &VerifyingKey) -> String {
+    let bytes = public_key.to_bytes();
+    format!("{} (impronta {})", STANDARD.encode(bytes), hex::encode(&Sha256::digest(bytes)[..8]))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args()?;
+    let key = load_signing_key(args.key_file.as_deref())?;
+    let public_key = key.verifying_key();
+
+    if let Some(token) = args.verify {
+        let payload = verify_token(&public_key, &token, now_ms())?;
+        println!("✅ Firma verificata — cliente: {}, id: {}, scadenza (ms): {}", payload.c, payload.id, payload.e);
+        return Ok(());
+    }
+
+    let client = args.client.filter(|c| !c.trim().is_empty()).ok_or("--client è obbligatorio")?;
+    let days = args.days.unwrap_or(365);
+    if days == 0 {
+        return Err("--days deve essere maggiore di zero".into());
+    }
+    let nonce = args.nonce.unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+    let id = args.id.unwrap_or_else(|| hex::encode(rand::random::<[u8; 4]>()));
+    let payload = LicensePayload {
+        c: client.trim().to_string(),
+        e: now_ms() + days * DAY_MS,
+        id,
+        n: Some(nonce),
+        s: None,
+        f: None,
+    };
+    let token = issue_token(&key, &payload)?;
+    // Round-trip before printing: never hand out a token the app would reject
+    verify_token(&public_key, &token, now_ms())?;
+    eprintln!("Chiave pubblica: {}", describe_public_key(&public_key));
+    println!("{}", token);
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+}
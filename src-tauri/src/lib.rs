@@ -23,18 +23,44 @@ use ed25519_dalek::{Signature, Verifier, VerifyingKey};
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// eprintln! that also keeps the line in memory for export_diagnostics().
+macro_rules! lf_log {
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        eprintln!("{}", line);
+        record_diag_line(line);
+    }};
+}
+
 // ═══════════════════════════════════════════════════════════
 //  CONSTANTS — Security Parameters
 // ═══════════════════════════════════════════════════════════
 const VAULT_FILE: &str = "vault.lex";
 const VAULT_SALT_FILE: &str = "vault.salt";
 const VAULT_VERIFY_FILE: &str = "vault.verify";
+// Argon2 cost parameters of this vault (plaintext JSON). Missing = compiled defaults.
+const VAULT_KDF_FILE: &str = "vault.kdf";
 const SETTINGS_FILE: &str = "settings.json";
 const AUDIT_LOG_FILE: &str = "vault.audit";
+// Pending audit-log re-key after change_password: the OLD vault key, encrypted with the NEW one
+const AUDIT_REKEY_FILE: &str = ".audit-rekey";
 const NOTIF_SCHEDULE_FILE: &str = "notification-schedule.json";
+// Ledger of notifications actually fired by the desktop scheduler (newest last, capped)
+const NOTIF_HISTORY_FILE: &str = "notification-history.json";
+const MAX_NOTIF_HISTORY: usize = 200;
 const LICENSE_FILE: &str = "license.json";
+// Unlock timestamps, local-key encrypted so they are readable while the vault is locked
+const VAULT_META_FILE: &str = ".vault-meta";
+// Focus mode: "<until_unix_ms>" — not secret, only mutes non-critical notifications.
+const FOCUS_MODE_FILE: &str = ".focus-until";
+// Upper bound for a single focus session (a forgotten focus mode must not mute reminders forever)
+const MAX_FOCUS_MODE_MS: u64 = 24 * 60 * 60 * 1000;
+// Reminder lead time when neither the item, its practice area nor settings specify one
+const DEFAULT_REMIND_MINUTES: i64 = 30;
 // SECURITY: persisted brute-force state — survives app restart/kill (L7 fix #1)
 const LOCKOUT_FILE: &str = ".lockout";
+// License-key activation has its own counter: a mistyped key must not lock the vault
+const LICENSE_LOCKOUT_FILE: &str = ".lockout-license";
 // SECURITY: sentinel file — HMAC proof that a license was activated on this machine.
 // If license.json is deleted but sentinel exists, the user is warned about tampering.
 const LICENSE_SENTINEL_FILE: &str = ".license-sentinel";
@@ -42,6 +68,8 @@ const LICENSE_SENTINEL_FILE: &str = ".license-sentinel";
 // Once a key is burned it can NEVER be used again, even on the same machine.
 // The registry is AES-256-GCM encrypted with the device-bound key.
 const BURNED_KEYS_FILE: &str = ".burned-keys";
+// Last deactivation receipt, kept so the user can resend it to support.
+const LICENSE_DEACTIVATION_FILE: &str = "license-deactivation.json";
 // Biometric marker file — avoids keychain access (which triggers Touch ID popup)
 // just to check if bio credentials exist. Only actual bio_login reads the keychain.
 #[cfg(not(target_os = "android"))]
@@ -52,11 +80,30 @@ const BIO_MARKER_FILE: &str = ".bio-enabled";
 // burned-keys, license). A persistent random ID generated once is immune to this.
 #[cfg(not(target_os = "android"))]
 const MACHINE_ID_FILE: &str = ".machine-id";
+// Hash of the hardware identifiers present when .machine-id was created. A disk image
+// cloned onto another laptop carries the same .machine-id but a different context.
+#[cfg(not(target_os = "android"))]
+const MACHINE_ID_CTX_FILE: &str = ".machine-id.ctx";
 
 #[allow(dead_code)]
 const BIO_SERVICE: &str = "LexFlow_Bio";
 
+// Crypto container header: `LEXFLOW_V<n>_SECURE`, n = one ASCII digit (fixed 17-byte header).
+// v1 = header without AAD (pre-v3.6.0), v2 = single blob, header authenticated as AAD,
+// v3 = chunked frames (vault.lex only, see ChunkedWriter).
+// A new format gets the next version constant and a branch in decrypt_data.
 const VAULT_MAGIC: &[u8] = b"LEXFLOW_V2_SECURE";
+const VAULT_MAGIC_PREFIX: &[u8] = b"LEXFLOW_V";
+const VAULT_MAGIC_SUFFIX: &[u8] = b"_SECURE";
+const CRYPTO_FORMAT_VERSION: u8 = 2;
+const CHUNKED_FORMAT_VERSION: u8 = 3;
+// Plaintext bytes per v3 frame; the reader accepts any size up to the cap
+const CHUNK_FRAME_LEN: usize = 1024 * 1024;
+const MAX_CHUNK_FRAME_LEN: usize = 16 * 1024 * 1024;
+// Data schema of the vault JSON (independent of the crypto format above).
+// v1 = pre-versioning backups (no `schemaVersion` field, sections may be missing)
+// v2 = every top-level section present, `schemaVersion` stamped on export
+const VAULT_SCHEMA_VERSION: u64 = 2;
 const ARGON2_SALT_LEN: usize = 32;
 const AES_KEY_LEN: usize = 32; 
 const NONCE_LEN: usize = 12;
@@ -70,6 +117,35 @@ const NONCE_LEN: usize = 12;
 const ARGON2_M_COST: u32 = 16384; // 16 MB — works on all platforms, OWASP-compliant
 const ARGON2_T_COST: u32 = 3;
 const ARGON2_P_COST: u32 = 1;
+// Per-vault params (vault.kdf) may only be stronger than the defaults, and are capped so a
+// tampered file cannot make unlock allocate gigabytes.
+const MAX_KDF_M_COST: u32 = 1024 * 1024; // 1 GB
+const MAX_KDF_T_COST: u32 = 10;
+const MAX_KDF_P_COST: u32 = 8;
+
+// Debounced saves: rapid autosave calls (e.g. a notes field firing on every keystroke)
+// are coalesced per vault section and flushed at most once per window.
+const DEFAULT_SAVE_DEBOUNCE_MS: u64 = 1500;
+const MIN_SAVE_DEBOUNCE_MS: u64 = 100;
+const MAX_SAVE_DEBOUNCE_MS: u64 = 60_000;
+// Top-level vault sections that the frontend is allowed to write through queue_save.
+const VAULT_SECTIONS: &[&str] = &["practices", "agenda", "timeLogs", "invoices", "contacts"];
+
+// Re-authentication token: issued after a fresh password check, short-lived, cleared on lock.
+// Gates sensitive reads (confidential practices) without re-sending the password each call.
+const REAUTH_TOKEN_TTL_SECS: u64 = 120;
+
+// Leftover *.tmp files older than this are considered crash debris and swept at startup.
+// Younger ones may belong to a write in progress and are left alone.
+const STALE_TMP_AGE_SECS: u64 = 300;
+// Safety copy written by change_password (and restores) before its rename sequence. While it
+// exists a key change was interrupted and its .tmp files are needed to finish it.
+const VAULT_BACKUP_FILE: &str = ".vault.bak";
+// New salt, Argon2 params and verify tag, staged beside the live files until a key change
+// commits them. See commit_staged_key_files / resume_key_commit.
+const STAGED_SALT_FILE: &str = ".salt.tmp";
+const STAGED_KDF_FILE: &str = ".kdf.tmp";
+const STAGED_VERIFY_FILE: &str = ".verify.tmp";
 
 const MAX_FAILED_ATTEMPTS: u32 = 5;
 const LOCKOUT_SECS: u64 = 300;
@@ -87,10 +163,15 @@ const MAX_SETTINGS_FILE_SIZE: u64 = 10 * 1024 * 1024; // 10 MB
 // Generated once at first run, persisted in security_dir. Survives hostname changes,
 // network changes, and macOS Continuity renames. Uses 256-bit random + username hash.
 #[cfg(not(target_os = "android"))]
-fn get_or_create_machine_id() -> String {
-    let security_dir = dirs::data_dir()
+fn machine_id_dir() -> PathBuf {
+    dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("."))
-        .join("com.pietrolongo.lexflow");
+        .join("com.pietrolongo.lexflow")
+}
+
+#[cfg(not(target_os = "android"))]
+fn get_or_create_machine_id() -> String {
+    let security_dir = machine_id_dir();
     let _ = fs::create_dir_all(&security_dir);
     let id_path = security_dir.join(MACHINE_ID_FILE);
     if let Ok(existing) = fs::read_to_string(&id_path) {
@@ -104,9 +185,72 @@ fn get_or_create_machine_id() -> String {
     rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
     let machine_id = hex::encode(id_bytes);
     let _ = secure_write(&id_path, machine_id.as_bytes());
+    if let Some(ctx) = hardware_context_hash() {
+        let _ = secure_write(&security_dir.join(MACHINE_ID_CTX_FILE), ctx.as_bytes());
+    }
     machine_id
 }
 
+// ─── Cloned-disk detection ──────────────────────────────────────────────────
+/// SHA-256 of the platform hardware UUID (SMBIOS / IOPlatformUUID), None if unavailable.
+#[cfg(not(target_os = "android"))]
+fn hardware_context_hash() -> Option<String> {
+    #[cfg(target_os = "macos")]
+    let hw = {
+        let out = std::process::Command::new("/usr/sbin/ioreg").args(["-rd1", "-c", "IOPlatformExpertDevice"]).output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout).into_owned();
+        text.lines().find(|l| l.contains("IOPlatformUUID"))?.rsplit('"').nth(1)?.to_string()
+    };
+    #[cfg(target_os = "linux")]
+    let hw = ["/sys/class/dmi/id/product_uuid", "/etc/machine-id"].iter()
+        .find_map(|p| fs::read_to_string(p).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()))?;
+    #[cfg(target_os = "windows")]
+    let hw = {
+        let out = std::process::Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", "(Get-CimInstance Win32_ComputerSystemProduct).UUID"])
+            .output().ok()?;
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    let hw = String::new();
+    if hw.is_empty() { return None; }
+    let hash = <Sha256 as Digest>::digest(format!("LEXFLOW-HWCTX-V1:{}", hw).as_bytes());
+    Some(hex::encode(hash))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MachineIdCheck {
+    Match,
+    /// Installed before context tracking — adopt the current hardware as the baseline.
+    Unrecorded,
+    Mismatch,
+    /// Hardware identifiers unreadable right now: never treat as a clone.
+    Unknown,
+}
+
+fn check_machine_id_context(stored: Option<&str>, current: Option<&str>) -> MachineIdCheck {
+    match (stored.map(str::trim).filter(|s| !s.is_empty()), current) {
+        (_, None) => MachineIdCheck::Unknown,
+        (None, Some(_)) => MachineIdCheck::Unrecorded,
+        (Some(s), Some(c)) if s == c => MachineIdCheck::Match,
+        _ => MachineIdCheck::Mismatch,
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+fn current_machine_id_check() -> MachineIdCheck {
+    let dir = machine_id_dir();
+    let current = hardware_context_hash();
+    let stored = fs::read_to_string(dir.join(MACHINE_ID_CTX_FILE)).ok();
+    let check = check_machine_id_context(stored.as_deref(), current.as_deref());
+    if check == MachineIdCheck::Unrecorded && dir.join(MACHINE_ID_FILE).exists() {
+        if let Some(ctx) = current {
+            let _ = secure_write(&dir.join(MACHINE_ID_CTX_FILE), ctx.as_bytes());
+        }
+    }
+    check
+}
+
 // Legacy key computation (with hostname) for migration of existing encrypted files.
 // If the new key fails to decrypt, callers try this before giving up.
 #[cfg(not(target_os = "android"))]
@@ -133,7 +277,7 @@ fn get_local_encryption_key_legacy() -> Vec<u8> {
 /// On legacy success, re-encrypt with new key for silent migration.
 fn decrypt_local_with_migration(path: &std::path::Path) -> Option<Vec<u8>> {
     let enc = fs::read(path).ok()?;
-    let key = get_local_encryption_key();
+    let key = get_local_encryption_key().ok()?;
     if let Ok(dec) = decrypt_data(&key, &enc) {
         return Some(dec);
     }
@@ -152,83 +296,91 @@ fn decrypt_local_with_migration(path: &std::path::Path) -> Option<Vec<u8>> {
     None
 }
 
+// ─── Android device id ──────────────────────────────────────────────────────
+// The local key on Android is derived from a random id stored ONCE in the Tauri-resolved
+// app_data_dir(). setup() creates/adopts it before anything touches a local-key file; from
+// then on every caller reads that single canonical file and never regenerates — a fresh id
+// would make settings, license and burned-keys undecryptable.
+#[cfg(any(target_os = "android", test))]
+const DEVICE_ID_FILE: &str = ".device_id";
+
+#[cfg(target_os = "android")]
+static ANDROID_DATA_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+#[cfg(any(target_os = "android", test))]
+fn read_id_file(path: &std::path::Path) -> Option<String> {
+    let id = fs::read_to_string(path).ok()?;
+    let id = id.trim();
+    if id.is_empty() { None } else { Some(id.to_string()) }
+}
+
+/// setup()-only: return the canonical id, adopting one left by older builds in `legacy`
+/// locations, or generating it on first run. Written atomically before being returned.
+#[cfg(any(target_os = "android", test))]
+fn ensure_device_id(canonical_dir: &std::path::Path, legacy: &[PathBuf]) -> Result<String, String> {
+    let id_path = canonical_dir.join(DEVICE_ID_FILE);
+    if let Some(id) = read_id_file(&id_path) { return Ok(id); }
+    let id = legacy.iter().find_map(|p| read_id_file(&p.join(DEVICE_ID_FILE))).unwrap_or_else(|| {
+        let mut id_bytes = [0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
+        hex::encode(id_bytes)
+    });
+    fs::create_dir_all(canonical_dir).map_err(|e| e.to_string())?;
+    atomic_write_with_sync(&id_path, id.as_bytes())?;
+    Ok(id)
+}
+
+/// Every other caller: read the canonical id only. Never generates one.
+#[cfg(any(target_os = "android", test))]
+fn read_device_id(canonical_dir: Option<&std::path::Path>) -> Result<String, String> {
+    let dir = canonical_dir.ok_or("ID dispositivo non ancora inizializzato (setup non completato)")?;
+    read_id_file(&dir.join(DEVICE_ID_FILE))
+        .ok_or_else(|| format!("ID dispositivo mancante in {:?} — i dati locali cifrati non sono leggibili", dir))
+}
+
+#[cfg(target_os = "android")]
+fn android_device_id() -> Result<String, String> {
+    if let Ok(id) = std::env::var("LEXFLOW_DEVICE_ID") { return Ok(id); }
+    read_device_id(ANDROID_DATA_DIR.get().map(|p| p.as_path()))
+}
+
+#[cfg(not(target_os = "android"))]
+fn local_key_for_machine_id(machine_id: &str) -> Vec<u8> {
+    let user = whoami::username();
+    #[cfg(target_os = "windows")]
+    let uid = {
+        let domain = std::env::var("USERDOMAIN").unwrap_or_else(|_| "WORKGROUP".to_string());
+        let sid = std::env::var("USERPROFILE").unwrap_or_else(|_| std::env::var("LOCALAPPDATA").unwrap_or_else(|_| "0".to_string()));
+        format!("{}:{}", domain, sid)
+    };
+    #[cfg(not(target_os = "windows"))]
+    let uid = std::env::var("UID")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| format!("{}", std::process::id()));
+    // SECURITY FIX: machine_id replaces hostname — stable across renames/network changes
+    let seed = format!("LEXFLOW-LOCAL-KEY-V3:{}:{}:{}:FORTKNOX", user, machine_id, uid);
+    let h1 = <Sha256 as Digest>::digest(seed.as_bytes());
+    let h2 = <Sha256 as Digest>::digest(&h1);
+    h2.to_vec()
+}
+
 // Derivata dalla macchina/device, non dalla password utente — inaccessibile da remoto
 // SECURITY FIX (Gemini Audit v2): hostname removed from seed. Uses a persistent machine-id
 // file instead, so renaming the computer (or network changes on macOS) cannot corrupt
 // settings.json, .burned-keys, or license.json. Migration: if old key fails, try legacy.
-fn get_local_encryption_key() -> Vec<u8> {
+fn get_local_encryption_key() -> Result<Vec<u8>, String> {
     #[cfg(not(target_os = "android"))]
     {
-        let user = whoami::username();
-        let machine_id = get_or_create_machine_id();
-        #[cfg(target_os = "windows")]
-        let uid = {
-            let domain = std::env::var("USERDOMAIN").unwrap_or_else(|_| "WORKGROUP".to_string());
-            let sid = std::env::var("USERPROFILE").unwrap_or_else(|_| std::env::var("LOCALAPPDATA").unwrap_or_else(|_| "0".to_string()));
-            format!("{}:{}", domain, sid)
-        };
-        #[cfg(not(target_os = "windows"))]
-        let uid = std::env::var("UID")
-            .or_else(|_| std::env::var("USER"))
-            .unwrap_or_else(|_| format!("{}", std::process::id()));
-        // SECURITY FIX: machine_id replaces hostname — stable across renames/network changes
-        let seed = format!("LEXFLOW-LOCAL-KEY-V3:{}:{}:{}:FORTKNOX", user, machine_id, uid);
-        let h1 = <Sha256 as Digest>::digest(seed.as_bytes());
-        let h2 = <Sha256 as Digest>::digest(&h1);
-        h2.to_vec()
+        Ok(local_key_for_machine_id(&get_or_create_machine_id()))
     }
     #[cfg(target_os = "android")]
     {
-        // Su Android: preferisci LEXFLOW_DEVICE_ID (iniettato da Tauri mobile).
-        // Se non disponibile, genera un ID univoco persistente nel file system privato.
-        // Cerca .device_id in più percorsi possibili — il primo che esiste vince.
-        // setup() di Tauri risolve il path reale e crea la directory, ma questa
-        // funzione può essere chiamata prima che setup() finisca (e.g. settings migration).
-        let android_id = if let Ok(id) = std::env::var("LEXFLOW_DEVICE_ID") {
-            id
-        } else {
-            // Cerca .device_id nei possibili data dir dell'app
-            let candidate_dirs = [
-                // Path risolto da Tauri app_data_dir() (post-setup)
-                dirs::data_dir().map(|d| d.join("com.pietrolongo.lexflow")),
-                // NON usiamo path hardcoded /data/data/... — varia per utente/multi-user Android
-                // Fallback: directory temporanea che esiste sempre
-                std::env::temp_dir().parent().map(|p| p.join("com.pietrolongo.lexflow")),
-            ];
-            let mut found_id: Option<String> = None;
-            let mut first_writable: Option<std::path::PathBuf> = None;
-            for candidate in candidate_dirs.iter().flatten() {
-                let id_path = candidate.join(".device_id");
-                if id_path.exists() {
-                    if let Ok(id) = fs::read_to_string(&id_path) {
-                        let trimmed = id.trim().to_string();
-                        if !trimmed.is_empty() {
-                            found_id = Some(trimmed);
-                            break;
-                        }
-                    }
-                }
-                if first_writable.is_none() {
-                    first_writable = Some(id_path);
-                }
-            }
-            found_id.unwrap_or_else(|| {
-                // Prima esecuzione: genera ID casuale a 256-bit e persisti
-                let mut id_bytes = [0u8; 32];
-                rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
-                let id_hex = hex::encode(id_bytes);
-                if let Some(id_path) = first_writable {
-                    if let Some(parent) = id_path.parent() {
-                        let _ = fs::create_dir_all(parent);
-                    }
-                    let _ = fs::write(&id_path, &id_hex);
-                }
-                id_hex
-            })
-        };
+        // Su Android: LEXFLOW_DEVICE_ID (se iniettato) oppure il .device_id canonico
+        // creato da setup() — mai un ID nuovo generato qui.
+        let android_id = android_device_id()?;
         let seed = format!("LEXFLOW-ANDROID-KEY:{}:FORTKNOX", android_id);
         let hash = <Sha256 as Digest>::digest(seed.as_bytes());
-        hash.to_vec()
+        Ok(hash.to_vec())
     }
 }
 
@@ -258,18 +410,9 @@ fn compute_machine_fingerprint() -> String {
     }
     #[cfg(target_os = "android")]
     {
-        // On Android reuse LEXFLOW_DEVICE_ID / .device_id (same as get_local_encryption_key)
-        let android_id = std::env::var("LEXFLOW_DEVICE_ID").unwrap_or_else(|_| {
-            let candidates = [
-                dirs::data_dir().map(|d| d.join("com.pietrolongo.lexflow/.device_id")),
-                std::env::temp_dir().parent().map(|p| p.join("com.pietrolongo.lexflow/.device_id")),
-            ];
-            for c in candidates.iter().flatten() {
-                if let Ok(id) = fs::read_to_string(c) {
-                    let t = id.trim().to_string();
-                    if !t.is_empty() { return t; }
-                }
-            }
+        // On Android reuse the canonical device id (same as get_local_encryption_key)
+        let android_id = android_device_id().unwrap_or_else(|e| {
+            lf_log!("[LexFlow] {}", e);
             "unknown-android".to_string()
         });
         let seed = format!("LEXFLOW-ANDROID-FP:{}:IRONCLAD", android_id);
@@ -309,6 +452,15 @@ fn compute_burn_hash_legacy(token: &str, fingerprint: &str) -> String {
     hex::encode(hash)
 }
 
+/// Burn-hash derived from the key ID alone. Used only when the registry has to be rebuilt
+/// from a license record (which never holds the raw token), so re-entering that key is
+/// still rejected after a support repair.
+fn compute_keyid_burn_hash(key_id: &str) -> String {
+    let seed = format!("BURN-KEYID-V1:{}", key_id);
+    let hash = <Sha256 as Digest>::digest(seed.as_bytes());
+    hex::encode(hash)
+}
+
 /// Load burned hashes from disk. Returns empty vec if file missing/corrupt.
 /// SECURITY: if file is missing but sentinel exists, returns a special "TAMPERED" marker
 /// so callers can detect that the registry was deleted to bypass single-use enforcement.
@@ -329,17 +481,81 @@ fn load_burned_keys(dir: &std::path::Path) -> Vec<String> {
     text.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()).collect()
 }
 
+/// Write the full registry, encrypted with the device-bound key.
+fn write_burned_keys(dir: &std::path::Path, hashes: &[String]) -> Result<(), String> {
+    let content = hashes.join("\n");
+    let enc_key = get_local_encryption_key()?;
+    let encrypted = encrypt_data(&enc_key, content.as_bytes())?;
+    atomic_write_with_sync(&dir.join(BURNED_KEYS_FILE), &encrypted)
+}
+
 /// Append a burn-hash to the registry and write back encrypted.
 fn burn_key(dir: &std::path::Path, burn_hash: &str) {
     let mut hashes = load_burned_keys(dir);
     // Idempotent: don't add duplicates
     if hashes.contains(&burn_hash.to_string()) { return; }
     hashes.push(burn_hash.to_string());
-    let content = hashes.join("\n");
-    let enc_key = get_local_encryption_key();
-    if let Ok(encrypted) = encrypt_data(&enc_key, content.as_bytes()) {
-        let _ = atomic_write_with_sync(&dir.join(BURNED_KEYS_FILE), &encrypted);
+    let _ = write_burned_keys(dir, &hashes);
+}
+
+// ─── Registry transfer (machine migration) ──────────────────────────────────
+// The v2 burn-hash is machine-independent, so the hashes themselves move cleanly.
+// Only the envelope changes: on export the registry is sealed with a password-derived
+// key (Argon2id, fresh salt) instead of the device key; on import it is merged into the
+// local registry and re-encrypted with the new machine's key.
+const BURN_EXPORT_PREFIX: &str = "LXBR1.";
+
+/// Password envelope for data leaving the machine: <prefix><base64url(salt || AES-GCM blob)>,
+/// key = Argon2id(password, fresh salt).
+fn seal_for_transit(prefix: &str, plaintext: &[u8], password: &str) -> Result<String, String> {
+    let mut salt = vec![0u8; ARGON2_SALT_LEN];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut salt);
+    let key = Zeroizing::new(derive_secure_key(password, &salt)?);
+    let encrypted = encrypt_data(&key, plaintext)?;
+    let mut out = salt;
+    out.extend(encrypted);
+    Ok(format!("{}{}", prefix, URL_SAFE_NO_PAD.encode(out)))
+}
+
+/// The only open_from_transit error that means a wrong credential; malformed or truncated
+/// input fails before decryption with other messages and must not count toward the lockout.
+const TRANSIT_WRONG_PASSWORD_ERR: &str = "Password errata o contenuto corrotto";
+
+fn open_from_transit(prefix: &str, content: &str, password: &str) -> Result<Vec<u8>, String> {
+    let b64 = content.trim().strip_prefix(prefix).ok_or("Formato non valido")?;
+    let raw = URL_SAFE_NO_PAD.decode(b64).map_err(|_| "Formato non valido")?;
+    if raw.len() < ARGON2_SALT_LEN + VAULT_MAGIC.len() + NONCE_LEN + 16 {
+        return Err("Contenuto corrotto".into());
+    }
+    let key = Zeroizing::new(derive_secure_key(password, &raw[..ARGON2_SALT_LEN])?);
+    decrypt_data(&key, &raw[ARGON2_SALT_LEN..]).map_err(|_| TRANSIT_WRONG_PASSWORD_ERR.into())
+}
+
+fn seal_burn_registry(hashes: &[String], password: &str) -> Result<String, String> {
+    seal_for_transit(BURN_EXPORT_PREFIX, hashes.join("\n").as_bytes(), password)
+}
+
+fn open_burn_registry(content: &str, password: &str) -> Result<Vec<String>, String> {
+    if !content.trim().starts_with(BURN_EXPORT_PREFIX) {
+        return Err("Formato registro non valido".into());
+    }
+    let dec = open_from_transit(BURN_EXPORT_PREFIX, content, password)?;
+    let text = String::from_utf8(dec).map_err(|_| "Registro corrotto")?;
+    let hashes: Vec<String> = text.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    // Burn-hashes are hex SHA-256 — reject anything else rather than polluting the registry
+    if hashes.iter().any(|h| h.len() != 64 || !h.chars().all(|c| c.is_ascii_hexdigit())) {
+        return Err("Registro contiene voci non valide".into());
+    }
+    Ok(hashes)
+}
+
+/// Merge without duplicates, preserving local order. Returns the number of new hashes.
+fn merge_burn_hashes(local: &mut Vec<String>, imported: Vec<String>) -> usize {
+    let before = local.len();
+    for h in imported {
+        if !local.contains(&h) { local.push(h); }
     }
+    local.len() - before
 }
 
 /// Check if a token has been burned (checks both v2 global and v1 legacy hashes).
@@ -347,7 +563,10 @@ fn is_key_burned(dir: &std::path::Path, token: &str, fingerprint: &str) -> bool
     let burn_hash_v2 = compute_burn_hash(token, fingerprint);
     let burn_hash_legacy = compute_burn_hash_legacy(token, fingerprint);
     let hashes = load_burned_keys(dir);
-    hashes.contains(&burn_hash_v2) || hashes.contains(&burn_hash_legacy)
+    let burned_by_id = extract_key_id(token)
+        .map(|id| hashes.contains(&compute_keyid_burn_hash(&id)))
+        .unwrap_or(false);
+    hashes.contains(&burn_hash_v2) || hashes.contains(&burn_hash_legacy) || burned_by_id
 }
 
 // ═══════════════════════════════════════════════════════════
@@ -365,24 +584,177 @@ pub struct AppState {
     /// live OUTSIDE the vault so that deleting/resetting the vault cannot bypass them.
     pub security_dir: Mutex<PathBuf>,
     vault_key: Mutex<Option<SecureKey>>,
-    failed_attempts: Mutex<u32>,
-    locked_until: Mutex<Option<Instant>>,
+    vault_lockout: Mutex<LockoutCounter>,
+    license_lockout: Mutex<LockoutCounter>,
     last_activity: Mutex<Instant>,
     autolock_minutes: Mutex<u32>,
+    /// Deadline of the "prompt" autolock grace: the key stays in memory until then.
+    autolock_grace_until: Mutex<Option<Instant>>,
     // SECURITY FIX (Level-8 C1): serialise concurrent vault writes.
     // Tauri dispatches IPC commands on a thread pool; two simultaneous save_practices +
     // save_agenda calls both do read-modify-write on vault.lex, causing a data-loss race.
     // This mutex ensures only one write runs at a time without blocking reads.
     write_mutex: Mutex<()>,
+    /// Coalesced section updates waiting to be written by flush_pending_saves().
+    pending_saves: Mutex<PendingSaves>,
+    save_debounce_ms: Mutex<u64>,
+    reauth_token: Mutex<ReauthSlot>,
+    /// Serialises audit-log read-modify-write against the background re-key.
+    audit_mutex: Mutex<()>,
+    kdf_benchmark: Mutex<Option<KdfBenchmark>>,
+    /// Serialises the fired-notification ledger between the cron and clear_notification_history.
+    notif_history_mutex: Mutex<()>,
+    /// Serialises schedule-file read-modify-write: ad-hoc reminders vs the frontend sync.
+    schedule_mutex: Mutex<()>,
+    /// Decrypted read-only snapshot while an open_snapshot session is active.
+    snapshot: Mutex<Option<Value>>,
+    /// Outcome of the last mobile AOT sync (see ScheduleTally::report).
+    notification_capacity: Mutex<Option<Value>>,
+    /// Background license-expiry check: last run and last emitted nudge.
+    license_nudge: Mutex<LicenseNudge>,
+    /// UTC offset seen by the last utc_offset_pass.
+    utc_offset: Mutex<Option<i32>>,
+    /// Last run of agenda_archive_pass.
+    agenda_archived_at: Mutex<Option<Instant>>,
+    /// Bumped by every vault write — invalidates prepared conflict sessions.
+    vault_generation: std::sync::atomic::AtomicU64,
+    conflict_sessions: Mutex<std::collections::HashMap<String, ConflictIndex>>,
+    /// Serialises unlock_vault / bio_login (separate from write_mutex).
+    auth_gate: AuthGate,
+    /// Vault read/write timings since startup or the last reset_perf_stats.
+    perf_stats: Mutex<PerfStats>,
+    /// Slug of the vault profile data_dir points at (DEFAULT_PROFILE = lexflow-vault).
+    active_profile: Mutex<String>,
+}
+
+/// Last-value-wins buffer for queue_save. Only the newest value of each section is kept,
+/// so N keystrokes between two flushes cost a single vault re-encryption.
+#[derive(Default)]
+struct PendingSaves {
+    sections: serde_json::Map<String, Value>,
+    flush_scheduled: bool,
+}
+
+impl PendingSaves {
+    /// Queue a section update. Returns true if the caller must schedule a flush
+    /// (i.e. no flush is already pending for the current window).
+    fn queue(&mut self, section: &str, value: Value) -> bool {
+        self.sections.insert(section.to_string(), value);
+        let must_schedule = !self.flush_scheduled;
+        self.flush_scheduled = true;
+        must_schedule
+    }
+
+    /// Take every pending section, leaving the buffer empty.
+    fn take(&mut self) -> serde_json::Map<String, Value> {
+        self.flush_scheduled = false;
+        std::mem::take(&mut self.sections)
+    }
+
+    /// Put back sections of a failed flush. Values queued meanwhile are newer and win.
+    fn restore(&mut self, failed: serde_json::Map<String, Value>) {
+        for (section, value) in failed {
+            self.sections.entry(section).or_insert(value);
+        }
+    }
+
+    /// Drop a pending update — used when an immediate save_* supersedes it.
+    fn discard(&mut self, section: &str) {
+        self.sections.remove(section);
+    }
+}
+
+fn apply_pending_saves(vault: &mut Value, pending: serde_json::Map<String, Value>) {
+    let now = chrono::Utc::now().to_rfc3339();
+    for (section, value) in pending {
+        let value = match section.as_str() {
+            // Queued saves carry no token: the confidential practices are never in them
+            "practices" => {
+                let mut merged = merge_confidential_practices(value, &vault["practices"], false, false);
+                stamp_practice_status_changes(&mut merged, &vault["practices"], &now);
+                stamp_party_changes(&mut merged, &vault["practices"], &now);
+                merged
+            }
+            "timeLogs" => {
+                let mut logs = value;
+                canonicalize_time_logs(&mut logs);
+                logs
+            }
+            "agenda" => drop_archived_agenda_items(value, &vault["archivedAgenda"]),
+            "invoices" => {
+                let mut invoices = value;
+                canonicalize_invoices(&mut invoices);
+                invoices
+            }
+            _ => value,
+        };
+        vault[section.as_str()] = value;
+        if section == "practices" {
+            prune_orphaned_secrets(vault);
+        }
+    }
 }
 
 // ═══════════════════════════════════════════════════════════
 //  CORE CRYPTO ENGINE
 // ═══════════════════════════════════════════════════════════
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+const DEFAULT_KDF_PARAMS: KdfParams = KdfParams { m_cost: ARGON2_M_COST, t_cost: ARGON2_T_COST, p_cost: ARGON2_P_COST };
+
+impl KdfParams {
+    fn validate(self) -> Result<Self, String> {
+        let in_range = (ARGON2_M_COST..=MAX_KDF_M_COST).contains(&self.m_cost)
+            && (ARGON2_T_COST..=MAX_KDF_T_COST).contains(&self.t_cost)
+            && (ARGON2_P_COST..=MAX_KDF_P_COST).contains(&self.p_cost);
+        if in_range { Ok(self) } else {
+            Err(format!(
+                "Parametri KDF non validi (memoria {}–{} KB, iterazioni {}–{}, parallelismo {}–{})",
+                ARGON2_M_COST, MAX_KDF_M_COST, ARGON2_T_COST, MAX_KDF_T_COST, ARGON2_P_COST, MAX_KDF_P_COST
+            ))
+        }
+    }
+}
+
+/// Params for the vault in `dir`. A vault created before vault.kdf existed has no file and
+/// uses the defaults; a present but unreadable file is an error, never a silent fallback
+/// (that would derive a wrong key and look like a wrong password).
+fn read_vault_kdf(dir: &std::path::Path) -> Result<KdfParams, String> {
+    match fs::read(dir.join(VAULT_KDF_FILE)) {
+        Ok(bytes) => serde_json::from_slice::<KdfParams>(&bytes)
+            .map_err(|_| "vault.kdf corrotto".to_string())?
+            .validate(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DEFAULT_KDF_PARAMS),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn write_vault_kdf(path: &std::path::Path, params: &KdfParams) -> Result<(), String> {
+    let bytes = serde_json::to_vec(params).map_err(|e| e.to_string())?;
+    atomic_write_with_sync(path, &bytes).map_err(|e| e.to_string())
+}
+
+/// Derive the key of the vault in `dir` with that vault's own params.
+fn derive_vault_key(password: &str, dir: &std::path::Path, salt: &[u8]) -> Result<Vec<u8>, String> {
+    derive_key_with_params(password, salt, &read_vault_kdf(dir)?)
+}
+
+/// Default params: backups, archives and other password-wrapped files, which must open on
+/// any machine without knowing the source vault's settings.
 fn derive_secure_key(password: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
+    derive_key_with_params(password, salt, &DEFAULT_KDF_PARAMS)
+}
+
+fn derive_key_with_params(password: &str, salt: &[u8], kdf: &KdfParams) -> Result<Vec<u8>, String> {
     let mut key = vec![0u8; AES_KEY_LEN];
-    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, Some(AES_KEY_LEN))
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(AES_KEY_LEN))
         .map_err(|e| e.to_string())?;
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
     let pwd_bytes = Zeroizing::new(password.as_bytes().to_vec());
@@ -390,6 +762,37 @@ fn derive_secure_key(password: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
     Ok(key)
 }
 
+// ─── KDF timing estimate ────────────────────────────────────────────────────
+// One benchmark per parameter set (the vault's vault.kdf) describes every unlock on this machine. Re-measured when stale (thermal
+// state, power profile and background load drift over a long-running session).
+const KDF_BENCHMARK_TTL_SECS: u64 = 6 * 60 * 60;
+
+struct KdfBenchmark {
+    params: KdfParams,
+    ms: u64,
+    measured_at: Instant,
+}
+
+fn benchmark_kdf_ms(params: &KdfParams) -> Result<u64, String> {
+    let salt = [0u8; ARGON2_SALT_LEN];
+    let start = Instant::now();
+    let key = Zeroizing::new(derive_key_with_params("lexflow-kdf-benchmark", &salt, params)?);
+    drop(key);
+    Ok((start.elapsed().as_millis() as u64).max(1))
+}
+
+/// Cached estimate if fresh and for the current params, otherwise re-benchmark.
+fn cached_kdf_estimate(cache: &mut Option<KdfBenchmark>, params: &KdfParams) -> Result<u64, String> {
+    if let Some(b) = cache.as_ref() {
+        if b.params == *params && b.measured_at.elapsed() < Duration::from_secs(KDF_BENCHMARK_TTL_SECS) {
+            return Ok(b.ms);
+        }
+    }
+    let ms = benchmark_kdf_ms(params)?;
+    *cache = Some(KdfBenchmark { params: *params, ms, measured_at: Instant::now() });
+    Ok(ms)
+}
+
 fn encrypt_data(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
     let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
     let mut nonce_bytes = [0u8; NONCE_LEN];
@@ -398,36 +801,222 @@ fn encrypt_data(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
     // Previously, magic bytes were prepended in cleartext but NOT authenticated by AES-GCM's MAC.
     // An attacker could alter the magic bytes without detection. With AAD, any modification
     // to the header causes decryption to fail with "Auth failed".
-    let payload = Payload { msg: plaintext, aad: VAULT_MAGIC };
+    let header = vault_header(CRYPTO_FORMAT_VERSION);
+    let payload = Payload { msg: plaintext, aad: &header };
     let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce_bytes), payload).map_err(|_| "Encryption error")?;
-    let mut out = VAULT_MAGIC.to_vec();
+    let mut out = header;
     out.extend_from_slice(&nonce_bytes);
     out.extend_from_slice(&ciphertext);
     Ok(out)
 }
 
+fn vault_header(version: u8) -> Vec<u8> {
+    let mut h = VAULT_MAGIC_PREFIX.to_vec();
+    h.push(b'0' + version);
+    h.extend_from_slice(VAULT_MAGIC_SUFFIX);
+    h
+}
+
+/// Format version from a `LEXFLOW_V<n>_SECURE` header, None if `data` does not start with one.
+fn vault_header_version(data: &[u8]) -> Option<u8> {
+    if data.len() < VAULT_MAGIC.len()
+        || !data.starts_with(VAULT_MAGIC_PREFIX)
+        || !data[VAULT_MAGIC_PREFIX.len() + 1..].starts_with(VAULT_MAGIC_SUFFIX) {
+        return None;
+    }
+    let digit = data[VAULT_MAGIC_PREFIX.len()];
+    digit.is_ascii_digit().then(|| digit - b'0').filter(|v| *v > 0)
+}
+
 fn decrypt_data(key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
     if data.len() < VAULT_MAGIC.len() + NONCE_LEN + 16 { return Err("Corrupted".into()); }
     // SECURITY FIX (Gemini Audit v2): explicitly verify magic bytes BEFORE attempting decryption.
     // Previously the magic bytes were silently skipped without validation.
-    if !data.starts_with(VAULT_MAGIC) {
-        return Err("Invalid file format: magic bytes mismatch".into());
-    }
+    let version = vault_header_version(data).ok_or("Invalid file format: magic bytes mismatch")?;
+    let header = &data[..VAULT_MAGIC.len()];
     let nonce = Nonce::from_slice(&data[VAULT_MAGIC.len()..VAULT_MAGIC.len() + NONCE_LEN]);
     let ciphertext = &data[VAULT_MAGIC.len() + NONCE_LEN..];
     let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
-    // SECURITY FIX: VAULT_MAGIC passed as AAD — must match what was used during encryption.
-    let payload = Payload { msg: ciphertext, aad: VAULT_MAGIC };
-    cipher.decrypt(nonce, payload).map_err(|_| {
-        // MIGRATION: try decryption WITHOUT AAD for files encrypted before this fix.
-        // Old encrypt_data() did not pass VAULT_MAGIC as AAD, so old ciphertext was
-        // authenticated only with an empty AAD. We try the legacy path as fallback.
-        "Auth failed".into()
-    }).or_else(|_: String| {
-        // Legacy fallback: decrypt without AAD (pre-v3.6.0 files)
-        let legacy_payload = Payload { msg: ciphertext, aad: b"" };
-        cipher.decrypt(nonce, legacy_payload).map_err(|_| "Auth failed".into())
-    })
+    match version {
+        1 => cipher.decrypt(nonce, Payload { msg: ciphertext, aad: b"" }).map_err(|_| "Auth failed".into()),
+        // SECURITY FIX: the header is passed as AAD — must match what was used during encryption.
+        2 => cipher.decrypt(nonce, Payload { msg: ciphertext, aad: header }).or_else(|_| {
+            // MIGRATION: pre-v3.6.0 encrypt_data() wrote the V2 header but authenticated
+            // with an empty AAD. We try the legacy path as fallback.
+            cipher.decrypt(nonce, Payload { msg: ciphertext, aad: b"" })
+        }).map_err(|_| "Auth failed".into()),
+        3 => {
+            use std::io::Read;
+            let mut out = Vec::new();
+            ChunkedReader::new(key, data)?.read_to_end(&mut out).map_err(|e| e.to_string())?;
+            Ok(out)
+        }
+        v => Err(format!(
+            "Formato cifrato v{} non supportato (questa app legge fino a v{}). Aggiorna LexFlow.",
+            v, CHUNKED_FORMAT_VERSION
+        )),
+    }
+}
+
+// ─── Chunked vault encryption (v3) ──────────────────────────────────────────
+// A large vault is serialized straight into fixed-size frames, so the full plaintext and
+// the full ciphertext are never held in memory at the same time.
+// Layout: header || frame_len (u32 LE) || frames, frame = nonce || AES-GCM(chunk).
+// Every frame but the last carries exactly frame_len plaintext bytes (the last may be
+// full or empty too). AAD = header || frame_len || seq (u64 LE) || last flag: frames
+// cannot be reordered, dropped, or the stream cut at a frame boundary.
+fn chunk_aad(header: &[u8], frame_len: u32, seq: u64, last: bool) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.extend_from_slice(&frame_len.to_le_bytes());
+    aad.extend_from_slice(&seq.to_le_bytes());
+    aad.push(last as u8);
+    aad
+}
+
+struct ChunkedWriter<W: std::io::Write> {
+    cipher: Aes256Gcm,
+    out: W,
+    header: Vec<u8>,
+    frame_len: usize,
+    buf: Zeroizing<Vec<u8>>,
+    seq: u64,
+}
+
+impl<W: std::io::Write> ChunkedWriter<W> {
+    fn new(key: &[u8], mut out: W, frame_len: usize) -> Result<Self, String> {
+        let header = vault_header(CHUNKED_FORMAT_VERSION);
+        out.write_all(&header).map_err(|e| e.to_string())?;
+        out.write_all(&(frame_len as u32).to_le_bytes()).map_err(|e| e.to_string())?;
+        Ok(ChunkedWriter {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            out,
+            header,
+            frame_len,
+            buf: Zeroizing::new(Vec::with_capacity(frame_len)),
+            seq: 0,
+        })
+    }
+
+    fn emit(&mut self, last: bool) -> std::io::Result<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut nonce);
+        let aad = chunk_aad(&self.header, self.frame_len as u32, self.seq, last);
+        let ct = self.cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: &self.buf, aad: &aad })
+            .map_err(|_| std::io::Error::other("Encryption error"))?;
+        self.out.write_all(&nonce)?;
+        self.out.write_all(&ct)?;
+        self.buf.clear();
+        self.seq += 1;
+        Ok(())
+    }
+
+    /// Seals the final frame and hands back the underlying writer.
+    fn finish(mut self) -> Result<W, String> {
+        self.emit(true).map_err(|e| e.to_string())?;
+        Ok(self.out)
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for ChunkedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        // A full frame is sealed only once more data arrives: the last frame is
+        // known for certain only in finish()
+        if self.buf.len() == self.frame_len { self.emit(false)?; }
+        let n = data.len().min(self.frame_len - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> { self.out.flush() }
+}
+
+struct ChunkedReader<R: std::io::BufRead> {
+    cipher: Aes256Gcm,
+    inp: R,
+    header: Vec<u8>,
+    frame_len: usize,
+    seq: u64,
+    plain: Zeroizing<Vec<u8>>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: std::io::BufRead> ChunkedReader<R> {
+    fn new(key: &[u8], mut inp: R) -> Result<Self, String> {
+        const HEAD_LEN: usize = VAULT_MAGIC.len() + 4;
+        let mut head = [0u8; HEAD_LEN];
+        inp.read_exact(&mut head).map_err(|_| "Corrupted")?;
+        if vault_header_version(&head) != Some(CHUNKED_FORMAT_VERSION) {
+            return Err("Invalid file format: magic bytes mismatch".into());
+        }
+        let h = VAULT_MAGIC.len();
+        let frame_len = u32::from_le_bytes([head[h], head[h + 1], head[h + 2], head[h + 3]]) as usize;
+        if frame_len == 0 || frame_len > MAX_CHUNK_FRAME_LEN { return Err("Corrupted".into()); }
+        Ok(ChunkedReader {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            inp,
+            header: head[..h].to_vec(),
+            frame_len,
+            seq: 0,
+            plain: Zeroizing::new(Vec::new()),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    fn next_frame(&mut self) -> std::io::Result<()> {
+        let invalid = |m: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, m.to_string());
+        let mut frame = vec![0u8; NONCE_LEN + self.frame_len + 16];
+        let mut n = 0;
+        while n < frame.len() {
+            match self.inp.read(&mut frame[n..])? { 0 => break, k => n += k }
+        }
+        if n < NONCE_LEN + 16 { return Err(invalid("Corrupted")); }
+        let last = n < frame.len() || self.inp.fill_buf()?.is_empty();
+        let aad = chunk_aad(&self.header, self.frame_len as u32, self.seq, last);
+        let pt = self.cipher.decrypt(Nonce::from_slice(&frame[..NONCE_LEN]), Payload { msg: &frame[NONCE_LEN..n], aad: &aad })
+            .map_err(|_| invalid("Auth failed"))?;
+        self.plain = Zeroizing::new(pt);
+        self.pos = 0;
+        self.seq += 1;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: std::io::BufRead> std::io::Read for ChunkedReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.plain.len() {
+            if self.done { return Ok(0); }
+            self.next_frame()?;
+        }
+        let n = out.len().min(self.plain.len() - self.pos);
+        out[..n].copy_from_slice(&self.plain[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Streams `data` into `path` as a v3 chunked container (mode 0600, fsynced).
+fn write_vault_file(key: &[u8], path: &std::path::Path, data: &Value) -> Result<(), String> {
+    let file = secure_create(path).map_err(|e| e.to_string())?;
+    let mut writer = ChunkedWriter::new(key, std::io::BufWriter::new(file), CHUNK_FRAME_LEN)?;
+    serde_json::to_writer(&mut writer, data).map_err(|e| e.to_string())?;
+    let file = writer.finish()?.into_inner().map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())
+}
+
+/// Reads vault.lex: v3 frame by frame, older single-blob files in one piece.
+fn read_vault_file(key: &[u8], path: &std::path::Path) -> Result<Value, String> {
+    use std::io::{BufRead, Read};
+    let mut inp = std::io::BufReader::with_capacity(64 * 1024, fs::File::open(path).map_err(|e| e.to_string())?);
+    if vault_header_version(inp.fill_buf().map_err(|e| e.to_string())?) == Some(CHUNKED_FORMAT_VERSION) {
+        return serde_json::from_reader(ChunkedReader::new(key, inp)?).map_err(|e| e.to_string());
+    }
+    let mut raw = Vec::new();
+    inp.read_to_end(&mut raw).map_err(|e| e.to_string())?;
+    let decrypted = Zeroizing::new(decrypt_data(key, &raw)?);
+    serde_json::from_slice(&decrypted).map_err(|e| e.to_string())
 }
 
 fn verify_hash_matches(key: &[u8], stored: &[u8]) -> bool {
@@ -467,29 +1056,67 @@ fn get_vault_key(state: &State<AppState>) -> Result<Zeroizing<Vec<u8>>, String>
 // The lockout counters must survive app kills; otherwise an attacker can kill+restart
 // to reset failed_attempts to 0. We persist them in a plain file in the data dir.
 // Format: "<attempts>:<unix_lockout_end_secs>" — not secret, just anti-abuse.
+// One file + one in-memory counter per kind; every check reconciles the two (higher wins),
+// and a clear resets both, so neither side can lag behind the other.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockoutKind { Vault, License }
 
-fn lockout_load(data_dir: &PathBuf) -> (u32, Option<std::time::SystemTime>) {
-    let path = data_dir.join(LOCKOUT_FILE);
+impl LockoutKind {
+    fn file(self) -> &'static str {
+        match self {
+            LockoutKind::Vault => LOCKOUT_FILE,
+            LockoutKind::License => LICENSE_LOCKOUT_FILE,
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "vault" => Some(LockoutKind::Vault),
+            "license" => Some(LockoutKind::License),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct LockoutCounter {
+    attempts: u32,
+    locked_until: Option<SystemTime>,
+}
+
+fn lockout_load(data_dir: &std::path::Path, kind: LockoutKind) -> LockoutCounter {
+    let path = data_dir.join(kind.file());
     let text = fs::read_to_string(&path).unwrap_or_default();
     let parts: Vec<&str> = text.trim().split(':').collect();
-    if parts.len() != 2 { return (0, None); }
+    if parts.len() != 2 { return LockoutCounter::default(); }
     let attempts = parts[0].parse::<u32>().unwrap_or(0);
     let lockout_end_secs = parts[1].parse::<u64>().unwrap_or(0);
-    if lockout_end_secs == 0 { return (attempts, None); }
-    let end = std::time::UNIX_EPOCH + Duration::from_secs(lockout_end_secs);
-    (attempts, Some(end))
+    let locked_until = (lockout_end_secs != 0).then(|| UNIX_EPOCH + Duration::from_secs(lockout_end_secs));
+    LockoutCounter { attempts, locked_until }
 }
 
-fn lockout_save(data_dir: &PathBuf, attempts: u32, locked_until: Option<std::time::SystemTime>) {
-    let secs = locked_until
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+fn lockout_save(data_dir: &std::path::Path, kind: LockoutKind, counter: LockoutCounter) {
+    let secs = counter.locked_until
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
         .map(|d| d.as_secs())
         .unwrap_or(0);
-    let _ = fs::write(data_dir.join(LOCKOUT_FILE), format!("{}:{}", attempts, secs));
+    let _ = fs::write(data_dir.join(kind.file()), format!("{}:{}", counter.attempts, secs));
+}
+
+fn lockout_clear(data_dir: &std::path::Path, kind: LockoutKind) {
+    let _ = fs::remove_file(data_dir.join(kind.file()));
 }
 
-fn lockout_clear(data_dir: &PathBuf) {
-    let _ = fs::remove_file(data_dir.join(LOCKOUT_FILE));
+/// Merge disk into memory (another session or a restart may be ahead) and describe the result.
+fn reconcile_lockout(mem: &mut LockoutCounter, disk: LockoutCounter, now: SystemTime) -> Value {
+    mem.attempts = mem.attempts.max(disk.attempts);
+    mem.locked_until = mem.locked_until.max(disk.locked_until);
+    let remaining = mem.locked_until
+        .and_then(|end| end.duration_since(now).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    json!({"locked": remaining > 0, "remaining": remaining, "attempts": mem.attempts})
 }
 
 // ═══════════════════════════════════════════════════════════
@@ -505,49 +1132,63 @@ fn zeroize_password(password: String) {
     pwd_bytes.zeroize();
 }
 
-/// Centralized lockout check — replaces 3 duplicated lockout code blocks.
-/// Returns Ok(()) if not locked, or Err(json) with remaining time if locked.
-fn check_lockout(state: &State<AppState>, sec_dir: &std::path::Path) -> Result<(), Value> {
-    let (disk_attempts, disk_locked_until) = lockout_load(&sec_dir.to_path_buf());
-    // Sync in-memory from disk on first call after restart
-    {
-        let mut att = state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner());
-        if disk_attempts > *att { *att = disk_attempts; }
+fn lockout_counter<'a>(state: &'a State<AppState>, kind: LockoutKind) -> &'a Mutex<LockoutCounter> {
+    match kind {
+        LockoutKind::Vault => &state.vault_lockout,
+        LockoutKind::License => &state.license_lockout,
     }
-    // Check disk-based lockout
-    if let Some(end_time) = disk_locked_until {
-        if SystemTime::now() < end_time {
-            let remaining = end_time.duration_since(SystemTime::now())
-                .map(|d| d.as_secs()).unwrap_or(0);
-            return Err(json!({"success": false, "valid": false, "locked": true, "remaining": remaining}));
-        }
+}
+
+/// Where `kind`'s counter is persisted: the vault counter belongs to the active profile.
+fn lockout_dir(state: &State<AppState>, sec_dir: &std::path::Path, kind: LockoutKind) -> PathBuf {
+    match kind {
+        LockoutKind::Vault => profile_lockout_dir(sec_dir, &state.active_profile.lock().unwrap_or_else(|e| e.into_inner())),
+        LockoutKind::License => sec_dir.to_path_buf(),
     }
-    // Check in-memory lockout (Instant-based, within-session)
-    if let Some(until) = *state.locked_until.lock().unwrap_or_else(|e| e.into_inner()) {
-        if Instant::now() < until {
-            return Err(json!({"success": false, "valid": false, "locked": true, "remaining": (until - Instant::now()).as_secs()}));
-        }
+}
+
+/// Single source of truth for the displayed lockout: reads disk, reconciles memory.
+fn lockout_status_of(state: &State<AppState>, sec_dir: &std::path::Path, kind: LockoutKind) -> Value {
+    let disk = lockout_load(&lockout_dir(state, sec_dir, kind), kind);
+    let mut mem = lockout_counter(state, kind).lock().unwrap_or_else(|e| e.into_inner());
+    reconcile_lockout(&mut mem, disk, SystemTime::now())
+}
+
+/// Centralized lockout check — replaces 3 duplicated lockout code blocks.
+/// Returns Ok(()) if not locked, or Err(json) with remaining time if locked.
+fn check_lockout(state: &State<AppState>, sec_dir: &std::path::Path, kind: LockoutKind) -> Result<(), Value> {
+    let status = lockout_status_of(state, sec_dir, kind);
+    if status["locked"].as_bool() == Some(true) {
+        return Err(json!({"success": false, "valid": false, "locked": true, "remaining": status["remaining"]}));
     }
     Ok(())
 }
 
 /// Record a failed authentication attempt. Triggers lockout after MAX_FAILED_ATTEMPTS.
-fn record_failed_attempt(state: &State<AppState>, sec_dir: &std::path::Path) {
-    let mut att = state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner());
-    *att += 1;
-    let locked_sys = if *att >= MAX_FAILED_ATTEMPTS {
-        let t = SystemTime::now() + Duration::from_secs(LOCKOUT_SECS);
-        *state.locked_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + Duration::from_secs(LOCKOUT_SECS));
-        Some(t)
-    } else { None };
-    lockout_save(&sec_dir.to_path_buf(), *att, locked_sys);
+fn record_failed_attempt(state: &State<AppState>, sec_dir: &std::path::Path, kind: LockoutKind) {
+    let dir = lockout_dir(state, sec_dir, kind);
+    let disk = lockout_load(&dir, kind);
+    let mut mem = lockout_counter(state, kind).lock().unwrap_or_else(|e| e.into_inner());
+    reconcile_lockout(&mut mem, disk, SystemTime::now());
+    mem.attempts += 1;
+    if mem.attempts >= MAX_FAILED_ATTEMPTS {
+        mem.locked_until = Some(SystemTime::now() + Duration::from_secs(LOCKOUT_SECS));
+    }
+    lockout_save(&dir, kind, *mem);
 }
 
-/// Clear lockout state on successful authentication.
-fn clear_lockout(state: &State<AppState>, sec_dir: &std::path::Path) {
-    *state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner()) = 0;
-    *state.locked_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
-    lockout_clear(&sec_dir.to_path_buf());
+/// Clear lockout state on successful authentication — this kind only.
+fn clear_lockout(state: &State<AppState>, sec_dir: &std::path::Path, kind: LockoutKind) {
+    *lockout_counter(state, kind).lock().unwrap_or_else(|e| e.into_inner()) = LockoutCounter::default();
+    lockout_clear(&lockout_dir(state, sec_dir, kind), kind);
+}
+
+/// `kind`: "vault" (password) or "license" (key activation) → {locked, remaining, attempts}.
+#[tauri::command]
+fn lockout_status(state: State<AppState>, kind: String) -> Result<Value, String> {
+    let kind = LockoutKind::parse(&kind).ok_or("Tipo di blocco non valido")?;
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    Ok(lockout_status_of(&state, &sec_dir, kind))
 }
 
 /// Centralized atomic write with fsync — replaces 5+ duplicated patterns.
@@ -563,15 +1204,107 @@ fn atomic_write_with_sync(path: &std::path::Path, data: &[u8]) -> Result<(), Str
 }
 
 /// Centralized vault authentication — verifies password against salt+verify.
-/// Returns the derived AES key on success.
+/// Returns the derived AES key on success. During an interrupted key change the new
+/// password is accepted against the staged files, which are left as they are: only
+/// unlock_vault and change_password finish the commit.
 fn authenticate_vault_password(password: &str, dir: &std::path::Path) -> Result<Vec<u8>, String> {
     let salt = fs::read(dir.join(VAULT_SALT_FILE)).map_err(|e| e.to_string())?;
-    let key = derive_secure_key(password, &salt)?;
+    let key = derive_vault_key(password, dir, &salt)?;
+    match check_vault_key(dir, &key) {
+        Ok(_) => Ok(key),
+        Err(e) => staged_key_for(dir, password).ok_or(e),
+    }
+}
+
+/// authenticate_vault_password that also finishes an interrupted key change the password
+/// opens, for change_password, which rewrites the key files next.
+fn authenticate_and_resume_key_commit(password: &str, dir: &std::path::Path) -> Result<Vec<u8>, String> {
+    let salt = fs::read(dir.join(VAULT_SALT_FILE)).map_err(|e| e.to_string())?;
+    let key = derive_vault_key(password, dir, &salt)?;
+    match check_vault_key(dir, &key) {
+        Ok(_) => Ok(key),
+        Err(e) => resume_key_commit(dir, password).ok_or(e),
+    }
+}
+
+// ─── Key file commit ────────────────────────────────────────────────────────
+// Salt, Argon2 params and verify tag live in three files that must change together. A key
+// change stages all three as .tmp files, writes vault.lex under the new key, then renames
+// them salt → kdf → verify. No rename order makes the set atomic, so a crash in between
+// leaves a mix no password opens; the staged files are the journal that finishes the job.
+fn stage_key_files(dir: &std::path::Path, salt: &[u8], kdf: &KdfParams, verify_tag: &[u8]) -> Result<(), String> {
+    atomic_write_with_sync(&dir.join(STAGED_SALT_FILE), salt).map_err(|e| format!("tmp salt: {}", e))?;
+    write_vault_kdf(&dir.join(STAGED_KDF_FILE), kdf).map_err(|e| format!("tmp kdf: {}", e))?;
+    atomic_write_with_sync(&dir.join(STAGED_VERIFY_FILE), verify_tag).map_err(|e| format!("tmp verify: {}", e))
+}
+
+/// Rename whatever is still staged into place. Idempotent: a file already renamed by an
+/// interrupted run is skipped.
+fn commit_staged_key_files(dir: &std::path::Path) -> Result<(), String> {
+    for (staged, live) in [
+        (STAGED_SALT_FILE, VAULT_SALT_FILE),
+        (STAGED_KDF_FILE, VAULT_KDF_FILE),
+        (STAGED_VERIFY_FILE, VAULT_VERIFY_FILE),
+    ] {
+        let staged = dir.join(staged);
+        if staged.exists() {
+            fs::rename(&staged, dir.join(live)).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn discard_staged_key_files(dir: &std::path::Path) {
+    for staged in [STAGED_SALT_FILE, STAGED_KDF_FILE, STAGED_VERIFY_FILE] {
+        let _ = fs::remove_file(dir.join(staged));
+    }
+}
+
+/// The key of a key change that crashed after vault.lex was replaced, without touching any
+/// file. The password is tried against the staged salt and params where they have not been
+/// renamed yet; it is accepted only if it matches the staged verify tag AND opens vault.lex,
+/// so a change that crashed before the vault write (old vault still in place) never counts.
+fn staged_key_for(dir: &std::path::Path, password: &str) -> Option<Vec<u8>> {
+    let staged_tag = fs::read(dir.join(STAGED_VERIFY_FILE)).ok()?;
+    let salt = fs::read(dir.join(STAGED_SALT_FILE))
+        .or_else(|_| fs::read(dir.join(VAULT_SALT_FILE)))
+        .ok()?;
+    let kdf = match fs::read(dir.join(STAGED_KDF_FILE)) {
+        Ok(bytes) => serde_json::from_slice::<KdfParams>(&bytes).ok()?.validate().ok()?,
+        Err(_) => read_vault_kdf(dir).ok()?,
+    };
+    let key = derive_key_with_params(password, &salt, &kdf).ok()?;
+    if !verify_hash_matches(&key, &staged_tag) { return None; }
+    let enc = fs::read(dir.join(VAULT_FILE)).ok()?;
+    decrypt_data(&key, &enc).ok()?;
+    Some(key)
+}
+
+/// Finish a key change that crashed after vault.lex was replaced (see staged_key_for).
+/// Returns the key.
+fn resume_key_commit(dir: &std::path::Path, password: &str) -> Option<Vec<u8>> {
+    let key = staged_key_for(dir, password)?;
+    commit_staged_key_files(dir).ok()?;
+    let _ = fs::remove_file(dir.join(VAULT_BACKUP_FILE));
+    lf_log!("[LexFlow] Cambio chiave interrotto completato allo sblocco");
+    Some(key)
+}
+// ────────────────────────────────────────────────────────────────────────────
+
+/// Check a derived key against vault.verify. If the tag is missing or empty while vault.lex
+/// exists (partial copy, botched restore), a successful trial decryption of the vault proves
+/// the key — AES-GCM with the VAULT_MAGIC AAD cannot open under a wrong key — and the tag
+/// is regenerated. Returns Ok(true) when the tag was healed.
+fn check_vault_key(dir: &std::path::Path, key: &[u8]) -> Result<bool, String> {
     let stored = fs::read(dir.join(VAULT_VERIFY_FILE)).unwrap_or_default();
-    if !verify_hash_matches(&key, &stored) {
-        return Err("Password errata".into());
+    if !stored.is_empty() {
+        return if verify_hash_matches(key, &stored) { Ok(false) } else { Err("Password errata".into()) };
     }
-    Ok(key)
+    let enc = fs::read(dir.join(VAULT_FILE)).map_err(|_| "Password errata")?;
+    decrypt_data(key, &enc).map_err(|_| "Password errata")?;
+    secure_write(&dir.join(VAULT_VERIFY_FILE), &make_verify_tag(key)).map_err(|e| e.to_string())?;
+    lf_log!("[LexFlow] vault.verify mancante — rigenerato dopo decifratura di prova riuscita");
+    Ok(true)
 }
 // ────────────────────────────────────────────────────────────────────────────
 
@@ -582,7 +1315,7 @@ fn is_safe_write_path(path: &std::path::Path) -> bool {
     if path.exists() {
         if let Ok(meta) = path.symlink_metadata() {
             if meta.file_type().is_symlink() {
-                eprintln!("[LexFlow] SECURITY: refused to write to symlink at {:?}", path);
+                lf_log!("[LexFlow] SECURITY: refused to write to symlink at {:?}", path);
                 return false;
             }
         }
@@ -597,6 +1330,13 @@ fn is_safe_write_path(path: &std::path::Path) -> bool {
 // file correctly and the NTFS ACL on the data dir itself restricts access.
 fn secure_write(path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
     use std::io::Write;
+    let mut f = secure_create(path)?;
+    f.write_all(data)?;
+    f.sync_all()
+}
+
+/// Opens `path` for writing the way secure_write() does, for callers that stream.
+fn secure_create(path: &std::path::Path) -> std::io::Result<fs::File> {
     let mut opts = std::fs::OpenOptions::new();
     opts.write(true).create(true).truncate(true);
     #[cfg(unix)]
@@ -604,51 +1344,275 @@ fn secure_write(path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
         use std::os::unix::fs::OpenOptionsExt;
         opts.mode(0o600);
     }
-    let mut f = opts.open(path)?;
-    f.write_all(data)?;
-    f.sync_all()
+    opts.open(path)
+}
+
+/// Remove *.tmp files in `dir` whose mtime is older than `max_age`. Returns what was removed.
+/// Skips the whole directory if an interrupted change_password left its .vault.bak behind:
+/// those tmp files are part of the recovery state and must not be touched.
+fn sweep_stale_tmp_files(dir: &std::path::Path, max_age: Duration) -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    if dir.join(VAULT_BACKUP_FILE).exists() {
+        lf_log!("[LexFlow] {:?} contiene {} — sweep dei .tmp rimandato (recovery in corso)", dir, VAULT_BACKUP_FILE);
+        return removed;
+    }
+    let entries = match fs::read_dir(dir) { Ok(e) => e, Err(_) => return removed };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("tmp") { continue; }
+        // symlink_metadata: never follow a planted symlink
+        let meta = match path.symlink_metadata() { Ok(m) => m, Err(_) => continue };
+        if !meta.is_file() && !meta.file_type().is_symlink() { continue; }
+        let age = meta.modified().ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .unwrap_or(Duration::ZERO);
+        if age < max_age { continue; }
+        if fs::remove_file(&path).is_ok() {
+            removed.push(path);
+        }
+    }
+    removed
+}
+
+// ─── Minimum app version ────────────────────────────────────────────────────
+// Every write stamps `minAppVersion` = max(stored, running version). An older build
+// refuses to open the vault instead of saving it back without the sections it does
+// not know about.
+fn parse_app_version(v: &str) -> Option<(u64, u64, u64)> {
+    let core = v.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+fn vault_min_version_error(vault: &Value, running: &str) -> Option<String> {
+    let required = vault.get("minAppVersion").and_then(|v| v.as_str())?;
+    let (req, run) = (parse_app_version(required)?, parse_app_version(running)?);
+    (run < req).then(|| format!(
+        "Questo vault richiede LexFlow ≥ {} (versione in uso: {}). Aggiorna LexFlow prima di aprirlo.",
+        required, running
+    ))
+}
+
+fn min_app_version_outdated(vault: &Value, running: &str) -> bool {
+    let current = vault.get("minAppVersion").and_then(|v| v.as_str()).and_then(parse_app_version);
+    match parse_app_version(running) {
+        Some(run) => vault.is_object() && current.map(|c| c < run).unwrap_or(true),
+        None => false,
+    }
+}
+
+/// Raise `minAppVersion` to `running` — never lowers it.
+fn stamp_min_app_version(vault: &mut Value, running: &str) {
+    if min_app_version_outdated(vault, running) {
+        vault["minAppVersion"] = json!(running);
+    }
 }
 
 fn read_vault_internal(state: &State<AppState>) -> Result<Value, String> {
+    if let Some(snapshot) = state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Ok(snapshot.clone());
+    }
     let key = get_vault_key(state)?;
     let path = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(VAULT_FILE);
     if !path.exists() { return Ok(json!({"practices":[], "agenda":[]})); }
-    let decrypted = decrypt_data(&key, &fs::read(path).map_err(|e| e.to_string())?)?;
-    serde_json::from_slice(&decrypted).map_err(|e| e.to_string())
+    let started = Instant::now();
+    let vault = read_vault_file(&key, &path)?;
+    state.perf_stats.lock().unwrap_or_else(|e| e.into_inner()).read.record(started.elapsed());
+    if let Some(e) = vault_min_version_error(&vault, env!("CARGO_PKG_VERSION")) {
+        return Err(e);
+    }
+    Ok(vault)
 }
 
 fn write_vault_internal(state: &State<AppState>, data: &Value) -> Result<(), String> {
+    snapshot_write_guard(state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).as_ref())?;
     let key = get_vault_key(state)?;
     let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-    let plaintext = Zeroizing::new(serde_json::to_vec(data).map_err(|e| e.to_string())?);
-    let encrypted = encrypt_data(&key, &plaintext)?;
+    // Only the first write by a newer build pays for the copy
+    let stamped;
+    let data = if min_app_version_outdated(data, env!("CARGO_PKG_VERSION")) {
+        let mut copy = data.clone();
+        stamp_min_app_version(&mut copy, env!("CARGO_PKG_VERSION"));
+        stamped = copy;
+        &stamped
+    } else { data };
     let tmp = dir.join(".vault.tmp");
     // SECURITY FIX (Level-8 A5): refuse to write if tmp path is a symlink.
     if !is_safe_write_path(&tmp) {
         return Err("Security: .vault.tmp è un symlink — scrittura rifiutata".into());
     }
     // SECURITY FIX (Level-8 A3): write with mode 0600, then fsync before rename.
-    secure_write(&tmp, &encrypted).map_err(|e| e.to_string())?;
+    let started = Instant::now();
+    write_vault_file(&key, &tmp, data)?;
     fs::rename(tmp, dir.join(VAULT_FILE)).map_err(|e| e.to_string())?;
+    state.perf_stats.lock().unwrap_or_else(|e| e.into_inner()).write.record(started.elapsed());
+    state.vault_generation.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
     Ok(())
 }
 
+// ─── Vault timings ──────────────────────────────────────────────────────────
+// Averages hide the occasional pathological write (disk hiccup, antivirus scan), so each
+// operation also keeps a fixed-bucket histogram. Upper bounds in ms; the last is open.
+const PERF_BUCKET_BOUNDS_MS: [u64; 3] = [50, 200, 1000];
+const PERF_BUCKET_LABELS: [&str; 4] = ["<50ms", "50-200ms", "200ms-1s", ">1s"];
+
+#[derive(Default)]
+struct OpTimings {
+    count: u64,
+    total_ms: u64,
+    max_ms: u64,
+    buckets: [u64; 4],
+}
+
+impl OpTimings {
+    fn record(&mut self, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        let bucket = PERF_BUCKET_BOUNDS_MS.iter().position(|&bound| ms < bound).unwrap_or(PERF_BUCKET_BOUNDS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    fn to_json(&self) -> Value {
+        let histogram: serde_json::Map<String, Value> = PERF_BUCKET_LABELS.iter()
+            .zip(self.buckets)
+            .map(|(label, n)| (label.to_string(), json!(n)))
+            .collect();
+        json!({
+            "count": self.count,
+            "avgMs": self.total_ms.checked_div(self.count).unwrap_or(0),
+            "maxMs": self.max_ms,
+            "histogram": histogram,
+        })
+    }
+}
+
+#[derive(Default)]
+struct PerfStats {
+    read: OpTimings,
+    write: OpTimings,
+}
+
+#[tauri::command]
+fn get_perf_stats(state: State<AppState>) -> Value {
+    let stats = state.perf_stats.lock().unwrap_or_else(|e| e.into_inner());
+    json!({"read": stats.read.to_json(), "write": stats.write.to_json()})
+}
+
+#[tauri::command]
+fn reset_perf_stats(state: State<AppState>) {
+    *state.perf_stats.lock().unwrap_or_else(|e| e.into_inner()) = PerfStats::default();
+}
+
 // ═══════════════════════════════════════════════════════════
 //  VAULT COMMANDS
 // ═══════════════════════════════════════════════════════════
 
+/// Vault password policy: minimum 12 characters with upper, lower, digit and symbol.
+fn password_policy_error(password: &str) -> Option<&'static str> {
+    let strong = password.chars().count() >= 12
+        && password.chars().any(|c| c.is_uppercase())
+        && password.chars().any(|c| c.is_lowercase())
+        && password.chars().any(|c| c.is_ascii_digit())
+        && password.chars().any(|c| !c.is_alphanumeric());
+    if strong { None } else {
+        Some("Password troppo debole: minimo 12 caratteri, una maiuscola, una minuscola, un numero e un simbolo.")
+    }
+}
+
+/// Expected key-derivation time for an unlock on this machine, so the UI can size its
+/// spinner instead of looking hung. Uses the cached benchmark when fresh.
+#[tauri::command]
+fn estimated_unlock_ms(state: State<AppState>) -> Result<u64, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let params = read_vault_kdf(&dir).unwrap_or(DEFAULT_KDF_PARAMS);
+    let mut cache = state.kdf_benchmark.lock().unwrap_or_else(|e| e.into_inner());
+    cached_kdf_estimate(&mut cache, &params)
+}
+
 #[tauri::command]
 fn vault_exists(state: State<AppState>) -> bool {
     state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(VAULT_SALT_FILE).exists()
 }
 
+// ─── Authentication gate ────────────────────────────────────────────────────
+// A double-click or a UI retry can fire two unlocks at once: both would derive a key and
+// both would run the lockout bookkeeping. The gate runs one attempt at a time; a call that
+// had to wait reuses the result of the attempt that finished meanwhile, provided it carried
+// the same credential. Credentials are compared through an HMAC under a per-process random
+// key — the password itself is never retained.
+struct AuthOutcome {
+    seq: u64,
+    fingerprint: Vec<u8>,
+    result: Result<Value, String>,
+}
+
+struct AuthGate {
+    last: Mutex<Option<AuthOutcome>>,
+    completed: std::sync::atomic::AtomicU64,
+    fp_key: Zeroizing<[u8; 32]>,
+}
+
+impl AuthGate {
+    fn new() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut *key);
+        AuthGate { last: Mutex::new(None), completed: std::sync::atomic::AtomicU64::new(0), fp_key: key }
+    }
+
+    fn fingerprint(&self, kind: &str, secret: &str) -> Vec<u8> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&*self.fp_key).unwrap();
+        mac.update(kind.as_bytes());
+        mac.update(&[0]);
+        mac.update(secret.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn run(&self, fingerprint: Vec<u8>, attempt: impl FnOnce() -> Result<Value, String>) -> Result<Value, String> {
+        use std::sync::atomic::Ordering;
+        let seen = self.completed.load(Ordering::SeqCst);
+        let mut guard = match self.last.try_lock() {
+            Ok(g) => g,
+            Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => {
+                let g = self.last.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(outcome) = g.as_ref() {
+                    // Constant-time comparison of the two fingerprints
+                    let same = outcome.fingerprint.len() == fingerprint.len()
+                        && outcome.fingerprint.iter().zip(&fingerprint).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0;
+                    if outcome.seq > seen && same {
+                        return outcome.result.clone();
+                    }
+                }
+                g
+            }
+        };
+        let result = attempt();
+        let seq = self.completed.fetch_add(1, Ordering::SeqCst) + 1;
+        *guard = Some(AuthOutcome { seq, fingerprint, result: result.clone() });
+        result
+    }
+}
+
 #[tauri::command]
 fn unlock_vault(state: State<AppState>, password: String) -> Value {
+    // Zeroizing: wiped on return even when the gate hands back a concurrent attempt's result
+    let password = Zeroizing::new(password);
+    let fingerprint = state.auth_gate.fingerprint("password", &password);
+    state.auth_gate.run(fingerprint, || Ok(unlock_vault_attempt(&state, password.to_string())))
+        .unwrap_or_else(|e| json!({"success": false, "error": e}))
+}
+
+fn unlock_vault_attempt(state: &State<AppState>, password: String) -> Value {
     let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
     let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
 
     // Centralized lockout check (DRY — replaces 15+ lines of duplicated code)
-    if let Err(locked_json) = check_lockout(&state, &sec_dir) {
+    if let Err(locked_json) = check_lockout(state, &sec_dir, LockoutKind::Vault) {
         return locked_json;
     }
 
@@ -657,18 +1621,15 @@ fn unlock_vault(state: State<AppState>, password: String) -> Value {
 
     let salt = if is_new {
         // Backend password strength validation for new vaults
-        let pwd_strong = password.len() >= 12
-            && password.chars().any(|c| c.is_uppercase())
-            && password.chars().any(|c| c.is_lowercase())
-            && password.chars().any(|c| c.is_ascii_digit())
-            && password.chars().any(|c| !c.is_alphanumeric());
-        if !pwd_strong {
+        if let Some(err) = password_policy_error(&password) {
             zeroize_password(password);
-            return json!({"success": false, "error": "Password troppo debole: minimo 12 caratteri, una maiuscola, una minuscola, un numero e un simbolo."});
+            return json!({"success": false, "error": err});
         }
         let mut s = vec![0u8; 32];
         rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut s);
-        match secure_write(&salt_path, &s) {
+        let written = write_vault_kdf(&dir.join(VAULT_KDF_FILE), &DEFAULT_KDF_PARAMS)
+            .and_then(|_| secure_write(&salt_path, &s).map_err(|e| e.to_string()));
+        match written {
             Ok(_) => s,
             Err(e) => {
                 zeroize_password(password);
@@ -679,17 +1640,35 @@ fn unlock_vault(state: State<AppState>, password: String) -> Value {
         fs::read(&salt_path).unwrap_or_default()
     };
 
-    match derive_secure_key(&password, &salt) {
-        Ok(k) => {
+    match derive_vault_key(&password, &dir, &salt) {
+        Ok(mut k) => {
             let verify_path = dir.join(VAULT_VERIFY_FILE);
+            let mut verify_healed = false;
+            let mut key_change_resumed = false;
             if !is_new {
-                let stored = fs::read(&verify_path).unwrap_or_default();
-                if !verify_hash_matches(&k, &stored) {
-                    record_failed_attempt(&state, &sec_dir);
-                    // SECURITY FIX (Gemini Audit v2): safe zeroing — no more UB
-                    zeroize_password(password);
-                    return json!({"success": false, "error": "Password errata"});
+                match check_vault_key(&dir, &k) {
+                    Ok(healed) => verify_healed = healed,
+                    Err(_) => match resume_key_commit(&dir, &password) {
+                        Some(resumed) => {
+                            k = resumed;
+                            key_change_resumed = true;
+                        }
+                        None => {
+                            record_failed_attempt(state, &sec_dir, LockoutKind::Vault);
+                            // SECURITY FIX (Gemini Audit v2): safe zeroing — no more UB
+                            zeroize_password(password);
+                            return json!({"success": false, "error": "Password errata"});
+                        }
+                    },
+                }
+                // A password change interrupted before its audit re-key finished
+                {
+                    let _audit = state.audit_mutex.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Err(e) = rekey_audit_log(&dir, &k) {
+                        lf_log!("[LexFlow] Re-key audit log non completato: {}", e);
+                    }
                 }
+                *state.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = None;
                 *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = Some(SecureKey(k));
             } else {
                 let tag = make_verify_tag(&k);
@@ -700,14 +1679,28 @@ fn unlock_vault(state: State<AppState>, password: String) -> Value {
                         return json!({"success": false, "error": format!("Errore init vault: {}", e)});
                     }
                 }
+                *state.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = None;
                 *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = Some(SecureKey(k));
-                let _ = write_vault_internal(&state, &json!({"practices":[], "agenda":[]}));
+                let mut fresh = json!({"practices":[], "agenda":[]});
+                stamp_vault_created(&mut fresh, &chrono::Utc::now().to_rfc3339());
+                let _ = write_vault_internal(state, &fresh);
             }
-            clear_lockout(&state, &sec_dir);
+            clear_lockout(state, &sec_dir, LockoutKind::Vault);
             *state.last_activity.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
             // SECURITY FIX (Gemini Audit v2): safe zeroing replaces UB pointer cast
             zeroize_password(password);
-            let _ = append_audit_log(&state, "Sblocco Vault");
+            if verify_healed {
+                let _ = append_audit_log(state, AuditCategory::Security, None, "Recupero: tag di verifica vault rigenerato");
+            }
+            if key_change_resumed {
+                let _ = append_audit_log(state, AuditCategory::Security, None, "Recupero: cambio chiave interrotto completato");
+            }
+            let _ = append_audit_log(state, AuditCategory::Auth, None, "Sblocco Vault");
+            note_vault_unlock(state);
+            if !is_new {
+                purge_expired_trash(state);
+                archive_completed_agenda_pass(state);
+            }
             json!({"success": true, "isNew": is_new})
         },
         Err(e) => {
@@ -717,12 +1710,81 @@ fn unlock_vault(state: State<AppState>, password: String) -> Value {
     }
 }
 
+// ─── Vault creation / last-unlock timestamps ────────────────────────────────
+// createdAt lives inside the vault (set once, at initialization); unlock times live in
+// VAULT_META_FILE so "last opened" is known without decrypting the vault.
+fn stamp_vault_created(vault: &mut Value, now: &str) -> bool {
+    if vault.get("createdAt").and_then(|v| v.as_str()).is_some() { return false; }
+    vault["createdAt"] = json!(now);
+    true
+}
+
+fn read_vault_meta(data_dir: &std::path::Path) -> Value {
+    decrypt_local_with_migration(&data_dir.join(VAULT_META_FILE))
+        .and_then(|dec| serde_json::from_slice(&dec).ok())
+        .unwrap_or(json!({}))
+}
+
+/// Shifts the previous lastUnlockAt to previousUnlockAt, so callers still see when the
+/// vault was opened before the current session.
+fn record_vault_unlock(data_dir: &std::path::Path, now: &str) -> Result<(), String> {
+    let meta = read_vault_meta(data_dir);
+    let updated = json!({
+        "lastUnlockAt": now,
+        "previousUnlockAt": meta.get("lastUnlockAt").cloned().unwrap_or(Value::Null),
+        "unlockCount": meta.get("unlockCount").and_then(|v| v.as_u64()).unwrap_or(0) + 1,
+    });
+    let plaintext = serde_json::to_vec(&updated).map_err(|e| e.to_string())?;
+    let encrypted = encrypt_data(&get_local_encryption_key()?, &plaintext)?;
+    atomic_write_with_sync(&data_dir.join(VAULT_META_FILE), &encrypted)
+}
+
+fn note_vault_unlock(state: &State<AppState>) {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(e) = record_vault_unlock(&dir, &chrono::Utc::now().to_rfc3339()) {
+        lf_log!("[LexFlow] Registrazione ultimo sblocco non riuscita: {}", e);
+    }
+}
+
+/// Creation and unlock timestamps. createdAt needs the vault unlocked; for vaults created
+/// before it was recorded, the salt file's modification time is reported as an estimate.
+#[tauri::command]
+fn get_vault_info(state: State<AppState>) -> Value {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let meta = read_vault_meta(&dir);
+    let stored = read_vault_internal(&state).ok()
+        .and_then(|v| v.get("createdAt").and_then(|c| c.as_str()).map(String::from));
+    let estimated = stored.is_none();
+    let created_at = stored.or_else(|| {
+        fs::metadata(dir.join(VAULT_SALT_FILE)).and_then(|m| m.modified()).ok()
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+    });
+    json!({
+        "initialized": dir.join(VAULT_SALT_FILE).exists(),
+        "createdAt": created_at,
+        "createdAtEstimated": estimated && created_at.is_some(),
+        "lastUnlockAt": meta.get("lastUnlockAt").cloned().unwrap_or(Value::Null),
+        "previousUnlockAt": meta.get("previousUnlockAt").cloned().unwrap_or(Value::Null),
+        "unlockCount": meta.get("unlockCount").cloned().unwrap_or(json!(0)),
+    })
+}
+
 #[tauri::command]
 fn lock_vault(state: State<AppState>) -> bool {
-    *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    lock_vault_state(&state);
     true
 }
 
+/// Flush queued saves, then drop the key and everything derived from the unlocked session.
+fn lock_vault_state(state: &State<AppState>) {
+    let _ = flush_pending_saves(state);
+    state.conflict_sessions.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    *state.reauth_token.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    *state.autolock_grace_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    *state.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
 #[tauri::command]
 fn reset_vault(state: State<AppState>, password: String) -> Value {
     // SECURITY FIX (Gemini Audit v2): acquire write_mutex — prevents race with save_practices
@@ -739,7 +1801,7 @@ fn reset_vault(state: State<AppState>, password: String) -> Value {
         }
     }
     let _ = {
-        for sensitive_file in &[VAULT_FILE, VAULT_SALT_FILE, VAULT_VERIFY_FILE, AUDIT_LOG_FILE] {
+        for sensitive_file in &[VAULT_FILE, VAULT_SALT_FILE, VAULT_VERIFY_FILE, VAULT_KDF_FILE, AUDIT_LOG_FILE, AUDIT_REKEY_FILE] {
             let p = dir.join(sensitive_file);
             if p.exists() {
                 if let Ok(meta) = p.metadata() {
@@ -754,6 +1816,7 @@ fn reset_vault(state: State<AppState>, password: String) -> Value {
         let _ = fs::remove_dir_all(&dir);
         let _ = fs::create_dir_all(&dir);
     };
+    *state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()) = PendingSaves::default();
     *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
     // SECURITY FIX (Gemini Audit v2): safe zeroing — no more UB
     zeroize_password(password);
@@ -761,92 +1824,113 @@ fn reset_vault(state: State<AppState>, password: String) -> Value {
 }
 
 #[tauri::command]
-fn change_password(state: State<AppState>, current_password: String, new_password: String) -> Result<Value, String> {
+fn change_password(app: AppHandle, state: State<AppState>, current_password: String, new_password: String, kdf_params: Option<KdfParams>) -> Result<Value, String> {
+    // Wiped on every return path
+    let current_password = Zeroizing::new(current_password);
+    let new_password = Zeroizing::new(new_password);
     // SECURITY FIX (Gemini Audit v2): acquire write_mutex — prevents race with save_practices
     let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
     let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
 
-    // Authenticate with centralized helper
-    let current_key = match authenticate_vault_password(&current_password, &dir) {
+    // Authenticate with centralized helper; an interrupted key change is finished first
+    let current_key = match authenticate_and_resume_key_commit(&current_password, &dir) {
         Ok(k) => k,
-        Err(_) => {
-            zeroize_password(current_password);
-            zeroize_password(new_password);
-            return Ok(json!({"success": false, "error": "Password attuale errata"}));
-        }
+        Err(_) => return Ok(json!({"success": false, "error": "Password attuale errata"})),
     };
 
+    // Persist queued autosaves with the CURRENT key before the vault is re-encrypted
+    flush_pending_saves_locked(&state)?;
+
     // Read vault with current key
     let vault_path = dir.join(VAULT_FILE);
-    let vault_data = if vault_path.exists() {
-        let enc = fs::read(&vault_path).map_err(|e| e.to_string())?;
-        let dec = decrypt_data(&current_key, &enc)?;
-        serde_json::from_slice::<Value>(&dec).map_err(|e| e.to_string())?
+    let mut vault_data = if vault_path.exists() {
+        read_vault_file(&current_key, &vault_path)?
     } else {
         json!({"practices":[], "agenda":[]})
     };
+    if let Some(e) = vault_min_version_error(&vault_data, env!("CARGO_PKG_VERSION")) {
+        return Err(e);
+    }
+    stamp_min_app_version(&mut vault_data, env!("CARGO_PKG_VERSION"));
 
-    // New salt + key
+    // New salt + key; the Argon2 params carry over unless the caller raises them
+    let new_kdf = match kdf_params {
+        Some(p) => match p.validate() {
+            Ok(p) => p,
+            Err(e) => return Ok(json!({"success": false, "error": e})),
+        },
+        None => read_vault_kdf(&dir)?,
+    };
     let mut new_salt = vec![0u8; ARGON2_SALT_LEN];
     rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut new_salt);
-    let new_key = derive_secure_key(&new_password, &new_salt)?;
+    let new_key = derive_key_with_params(&new_password, &new_salt, &new_kdf)?;
+    reseal_secrets(&mut vault_data, Some(&current_key), Some(&new_key))?;
 
     // TRANSACTIONAL DATA-LOSS FIX (Gemini Audit v2):
     // The previous approach wrote salt and vault as separate files, creating a window
     // where a crash between the two renames would leave incompatible salt+vault pairs,
     // causing permanent data loss.
     //
-    // SOLUTION: We now write all three files (.tmp) FIRST, then rename in order:
+    // SOLUTION: We now write all four files (.tmp) FIRST, then rename in order:
     //   1. vault.lex (encrypted with NEW key)
-    //   2. vault.salt (NEW salt)
-    //   3. vault.verify (NEW verify tag)
+    //   2. vault.salt, vault.kdf, vault.verify (commit_staged_key_files)
     //
     // CRASH ANALYSIS:
     //   - Crash before step 1: old files intact → old password works → safe
-    //   - Crash after step 1, before step 2: new vault on disk but old salt →
-    //     old password derives old key → cannot decrypt new vault. BUT we keep
-    //     a backup of the old vault as .vault.bak BEFORE the rename, so recovery
-    //     is possible by restoring .vault.bak → vault.lex.
-    //   - Crash after step 2: new salt + new vault → new password works → safe
+    //   - Crash after step 1, anywhere inside step 2: new vault, live key files a mix of
+    //     old and new → neither password verifies against them. The staged .tmp files
+    //     are still there, so the next unlock with the NEW password derives from the
+    //     staged salt/params, matches the staged tag, opens the new vault and finishes
+    //     the renames (resume_key_commit). .vault.bak keeps the old vault meanwhile and
+    //     stops the startup sweep from deleting the staged files.
     //   - All steps complete: new password works → safe
 
-    let vault_plaintext = Zeroizing::new(serde_json::to_vec(&vault_data).map_err(|e| e.to_string())?);
-    let encrypted_vault = encrypt_data(&new_key, &vault_plaintext)?;
     let new_verify_tag = make_verify_tag(&new_key);
 
     // Write all tmp files first (crash here = safe, old files untouched)
     let tmp_vault  = dir.join(".vault.tmp");
-    let tmp_salt   = dir.join(".salt.tmp");
-    let tmp_verify = dir.join(".verify.tmp");
 
-    atomic_write_with_sync(&tmp_vault, &encrypted_vault).map_err(|e| format!("tmp vault: {}", e))?;
-    atomic_write_with_sync(&tmp_salt, &new_salt).map_err(|e| format!("tmp salt: {}", e))?;
-    atomic_write_with_sync(&tmp_verify, &new_verify_tag).map_err(|e| format!("tmp verify: {}", e))?;
+    if !is_safe_write_path(&tmp_vault) {
+        return Err("Security: .vault.tmp è un symlink — scrittura rifiutata".into());
+    }
+    write_vault_file(&new_key, &tmp_vault, &vault_data).map_err(|e| format!("tmp vault: {}", e))?;
+    stage_key_files(&dir, &new_salt, &new_kdf, &new_verify_tag)?;
+
+    // The audit log is re-keyed in the background after we return (a 10k-entry log would
+    // otherwise stretch the critical section). Until then the marker lets readers recover
+    // the old key. Written BEFORE the renames: if we crash first, the marker does not open
+    // with the old key and is simply discarded.
+    let audit_path = dir.join(AUDIT_LOG_FILE);
+    let rekey_pending = audit_path.exists();
+    if rekey_pending {
+        let sealed_old_key = encrypt_data(&new_key, &current_key)?;
+        atomic_write_with_sync(&dir.join(AUDIT_REKEY_FILE), &sealed_old_key).map_err(|e| format!("audit rekey: {}", e))?;
+    }
 
     // SAFETY NET: backup old vault before rename sequence
-    let vault_backup = dir.join(".vault.bak");
+    let vault_backup = dir.join(VAULT_BACKUP_FILE);
     if vault_path.exists() {
         let _ = fs::copy(&vault_path, &vault_backup);
     }
 
-    // Atomic rename sequence — vault FIRST (matches new key), then salt+verify
+    // Atomic rename sequence — vault FIRST (matches new key), then salt, kdf, verify
     fs::rename(&tmp_vault, &vault_path).map_err(|e| e.to_string())?;
-    fs::rename(&tmp_salt, dir.join(VAULT_SALT_FILE)).map_err(|e| e.to_string())?;
-    fs::rename(&tmp_verify, dir.join(VAULT_VERIFY_FILE)).map_err(|e| e.to_string())?;
+    commit_staged_key_files(&dir)?;
 
     // Success: remove backup
     let _ = fs::remove_file(&vault_backup);
 
-    // Re-encrypt audit log if exists
-    let audit_path = dir.join(AUDIT_LOG_FILE);
-    if audit_path.exists() {
-        if let Ok(enc) = fs::read(&audit_path) {
-            if let Ok(dec) = decrypt_data(&current_key, &enc) {
-                if let Ok(re_enc) = encrypt_data(&new_key, &dec) {
-                    let _ = atomic_write_with_sync(&audit_path, &re_enc);
-                }
+    if rekey_pending {
+        let rekey_key = Zeroizing::new(new_key.clone());
+        let rekey_dir = dir.clone();
+        let app_handle = app.clone();
+        std::thread::spawn(move || {
+            let state = app_handle.state::<AppState>();
+            let _audit = state.audit_mutex.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = rekey_audit_log(&rekey_dir, &rekey_key) {
+                lf_log!("[LexFlow] Re-key audit log fallito (riprovato al prossimo sblocco): {}", e);
             }
-        }
+        });
     }
 
     // Update in-memory key
@@ -864,11 +1948,93 @@ fn change_password(state: State<AppState>, current_password: String, new_passwor
         }
     }
 
-    let _ = append_audit_log(&state, "Password cambiata");
-    // SECURITY FIX (Gemini Audit v2): safe zeroing — no more UB
+    let _ = append_audit_log(&state, AuditCategory::Auth, None, "Password cambiata");
+    Ok(json!({"success": true}))
+}
+
+/// Free bytes on the volume holding `dir`, if the platform tool is available.
+fn available_disk_bytes(dir: &std::path::Path) -> Option<u64> {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        // Absolute path (no PATH lookup); POSIX output: last line, 4th column = available KiB
+        let df = ["/bin/df", "/usr/bin/df"].into_iter().find(|p| std::path::Path::new(p).exists())?;
+        let out = std::process::Command::new(df).arg("-Pk").arg(dir).output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let kib: u64 = text.lines().last()?.split_whitespace().nth(3)?.parse().ok()?;
+        Some(kib * 1024)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        // Path passed through the environment, never interpolated into the script
+        let out = std::process::Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", "(Get-Item -LiteralPath $env:LEXFLOW_DIR).PSDrive.Free"])
+            .env("LEXFLOW_DIR", dir)
+            .output().ok()?;
+        String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    {
+        let _ = dir;
+        None
+    }
+}
+
+/// Pre-flight for change_password: everything it checks, nothing it writes.
+/// Space needed: new vault tmp + .vault.bak copy + salt/verify tmp + audit-log rewrite.
+fn change_password_preflight(dir: &std::path::Path, current: &str, new: &str, available: Option<u64>) -> Value {
+    let mut reasons: Vec<String> = Vec::new();
+    let current_ok = authenticate_vault_password(current, dir).is_ok();
+    if !current_ok {
+        reasons.push("Password attuale errata".into());
+    }
+    if let Some(err) = password_policy_error(new) {
+        reasons.push(err.into());
+    }
+    if current_ok && current == new {
+        reasons.push("La nuova password coincide con quella attuale".into());
+    }
+    let size = |f: &str| fs::metadata(dir.join(f)).map(|m| m.len()).unwrap_or(0);
+    let vault_bytes = size(VAULT_FILE);
+    let audit_bytes = size(AUDIT_LOG_FILE);
+    let required = vault_bytes * 2 + audit_bytes + ARGON2_SALT_LEN as u64 + 4096;
+    match available {
+        Some(free) if free < required => reasons.push(format!(
+            "Spazio su disco insufficiente: servono {} KB, disponibili {} KB", required.div_ceil(1024), free / 1024
+        )),
+        _ => {}
+    }
+    json!({
+        "go": reasons.is_empty(),
+        "reasons": reasons,
+        "estimate": {
+            "vaultBytes": vault_bytes,
+            "auditBytes": audit_bytes,
+            "requiredBytes": required,
+            "availableBytes": available,
+        },
+    })
+}
+
+/// Dry run of change_password for the UI: go/no-go with reasons, writes nothing.
+/// Shares the brute-force lockout — otherwise it would be a free password oracle.
+#[tauri::command]
+fn dry_run_change_password(state: State<AppState>, current_password: String, new_password: String) -> Result<Value, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
+        zeroize_password(current_password);
+        zeroize_password(new_password);
+        return Ok(locked_json);
+    }
+    let report = change_password_preflight(&dir, &current_password, &new_password, available_disk_bytes(&dir));
+    if report["reasons"].as_array().map(|r| r.iter().any(|x| x == "Password attuale errata")).unwrap_or(false) {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+    } else {
+        clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    }
     zeroize_password(current_password);
     zeroize_password(new_password);
-    Ok(json!({"success": true}))
+    Ok(report)
 }
 
 #[tauri::command]
@@ -877,21 +2043,100 @@ fn verify_vault_password(state: State<AppState>, pwd: String) -> Result<Value, S
     let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
 
     // Centralized lockout check (DRY)
-    if let Err(locked_json) = check_lockout(&state, &sec_dir) {
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
         return Ok(locked_json);
     }
 
     // Centralized authentication
     let valid = authenticate_vault_password(&pwd, &dir).is_ok();
     if !valid {
-        record_failed_attempt(&state, &sec_dir);
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
     } else {
-        clear_lockout(&state, &sec_dir);
+        clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+        // A re-auth during the autolock grace keeps the session open
+        end_autolock_grace(&state.autolock_grace_until, &state.last_activity);
     }
     zeroize_password(pwd);
     Ok(json!({"valid": valid}))
 }
 
+/// Issue a short-lived re-authentication token after a fresh password check.
+/// Shares the brute-force lockout with unlock/verify.
+#[tauri::command]
+fn request_reauth_token(state: State<AppState>, pwd: String) -> Result<Value, String> {
+    get_vault_key(&state)?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
+        zeroize_password(pwd);
+        return Ok(locked_json);
+    }
+    let valid = authenticate_vault_password(&pwd, &dir).is_ok();
+    zeroize_password(pwd);
+    if !valid {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        return Ok(json!({"success": false, "error": "Password errata"}));
+    }
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let mut token_bytes = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut token_bytes);
+    let token = hex::encode(token_bytes);
+    *state.reauth_token.lock().unwrap_or_else(|e| e.into_inner()) =
+        Some((Zeroizing::new(token.clone()), Instant::now() + Duration::from_secs(REAUTH_TOKEN_TTL_SECS)));
+    Ok(json!({"success": true, "token": token, "expiresInSecs": REAUTH_TOKEN_TTL_SECS}))
+}
+
+/// Validate a re-authentication token (constant-time compare, TTL enforced).
+fn check_reauth_token(state: &State<AppState>, token: &str) -> Result<(), String> {
+    let mut guard = state.reauth_token.lock().unwrap_or_else(|e| e.into_inner());
+    verify_reauth_slot(&mut guard, token)
+}
+
+type ReauthSlot = Option<(Zeroizing<String>, Instant)>;
+
+fn verify_reauth_slot(guard: &mut ReauthSlot, token: &str) -> Result<(), String> {
+    match guard.as_ref() {
+        Some((stored, expires)) if Instant::now() < *expires => {
+            let a = stored.as_bytes();
+            let b = token.as_bytes();
+            let diff = a.len() ^ b.len()
+                | a.iter().zip(b.iter()).fold(0usize, |acc, (x, y)| acc | (x ^ y) as usize);
+            if diff == 0 { Ok(()) } else { Err("Riautenticazione richiesta".into()) }
+        }
+        Some(_) => {
+            *guard = None;
+            Err("Riautenticazione scaduta".into())
+        }
+        None => Err("Riautenticazione richiesta".into()),
+    }
+}
+
+/// Error returned by section commands when `protectedSections` demands a reauth token.
+/// Stable, untranslated code: the frontend matches on it to show the password prompt.
+const REAUTH_REQUIRED_ERR: &str = "ReauthRequired";
+
+fn section_is_protected(settings: &Value, section: &str) -> bool {
+    settings.get("protectedSections").and_then(|p| p.as_array())
+        .map(|list| list.iter().any(|s| s.as_str() == Some(section)))
+        .unwrap_or(false)
+}
+
+fn authorize_section(settings: &Value, section: &str, slot: &mut ReauthSlot, reauth_token: Option<&str>) -> Result<(), String> {
+    if !section_is_protected(settings, section) { return Ok(()); }
+    match reauth_token {
+        Some(token) if verify_reauth_slot(slot, token).is_ok() => Ok(()),
+        _ => Err(REAUTH_REQUIRED_ERR.into()),
+    }
+}
+
+/// Gate for reads AND writes of a section listed in the `protectedSections` setting.
+fn require_section_access(state: &State<AppState>, section: &str, reauth_token: Option<&str>) -> Result<(), String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let settings = read_settings(&dir);
+    let mut slot = state.reauth_token.lock().unwrap_or_else(|e| e.into_inner());
+    authorize_section(&settings, section, &mut slot, reauth_token)
+}
+
 // ═══════════════════════════════════════════════════════════
 //  SUMMARY — Server-side computation (Gemini L2-4)
 // ═══════════════════════════════════════════════════════════
@@ -928,246 +2173,3066 @@ fn get_summary(state: State<AppState>) -> Result<Value, String> {
     Ok(json!({"activePractices": active_practices, "urgentDeadlines": urgent_deadlines}))
 }
 
-// ═══════════════════════════════════════════════════════════
-//  PRACTICES & AGENDA
-// ═══════════════════════════════════════════════════════════
+// ─── Integrity check ────────────────────────────────────────────────────────
+// A vault can decrypt and parse yet be damaged (a truncated array from a bad disk).
+// These are the structural invariants the rest of the app relies on.
+fn check_vault_integrity(vault: &Value) -> Vec<Value> {
+    let mut errors: Vec<Value> = Vec::new();
+    let mut err = |path: String, message: &str| errors.push(json!({"path": path, "message": message}));
+    if !vault.is_object() {
+        err("$".into(), "Il vault non è un oggetto JSON");
+        return errors;
+    }
+    for section in ["practices", "agenda"] {
+        if !vault.get(section).map(|v| v.is_array()).unwrap_or(false) {
+            err(section.into(), "Sezione mancante o non è un array");
+        }
+    }
+    let contact_ids: std::collections::HashSet<&str> = vault.get("contacts").and_then(|c| c.as_array())
+        .map(|list| list.iter().filter_map(|c| c.get("id").and_then(|i| i.as_str())).collect())
+        .unwrap_or_default();
+    let practices = vault.get("practices").and_then(|p| p.as_array()).map(|a| a.as_slice()).unwrap_or(&[]);
+    let mut seen: std::collections::HashSet<&str> = std::collections::HashSet::new();
+    for (i, p) in practices.iter().enumerate() {
+        let at = format!("practices[{}]", i);
+        match p.get("id").and_then(|v| v.as_str()).filter(|id| !id.trim().is_empty()) {
+            None => err(format!("{}.id", at), "Id mancante o vuoto"),
+            Some(id) if !seen.insert(id) => err(format!("{}.id", at), "Id duplicato"),
+            Some(_) => {}
+        }
+        for (j, d) in p.get("deadlines").and_then(|d| d.as_array()).map(|a| a.as_slice()).unwrap_or(&[]).iter().enumerate() {
+            let ok = d.get("date").and_then(|v| v.as_str())
+                .map(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok())
+                .unwrap_or(false);
+            if !ok { err(format!("{}.deadlines[{}].date", at, j), "Data non valida (atteso AAAA-MM-GG)"); }
+        }
+        for (j, r) in p.get("roles").and_then(|r| r.as_array()).map(|a| a.as_slice()).unwrap_or(&[]).iter().enumerate() {
+            if let Some(cid) = r.get("contactId").and_then(|c| c.as_str()) {
+                if !contact_ids.contains(cid) {
+                    err(format!("{}.roles[{}].contactId", at, j), "Contatto inesistente");
+                }
+            }
+        }
+    }
+    errors
+}
 
+/// Read-only health check of vault.lex: decryption, JSON parsing and structural invariants.
 #[tauri::command]
-fn load_practices(state: State<AppState>) -> Result<Value, String> {
-    let vault = read_vault_internal(&state)?;
-    Ok(vault.get("practices").cloned().unwrap_or(json!([])))
+fn verify_vault_integrity(state: State<AppState>) -> Result<Value, String> {
+    let key = get_vault_key(&state)?;
+    let path = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(VAULT_FILE);
+    if !path.exists() { return Ok(json!({"ok": true, "errors": []})); }
+    let errors = match read_vault_file(&key, &path) {
+        Ok(vault) => check_vault_integrity(&vault),
+        Err(e) => vec![json!({"path": "$", "message": format!("Decifratura o lettura non riuscita: {}", e)})],
+    };
+    Ok(json!({"ok": errors.is_empty(), "errors": errors}))
 }
 
-#[tauri::command]
-fn save_practices(state: State<AppState>, list: Value) -> Result<bool, String> {
-    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
-    let mut vault = read_vault_internal(&state)?;
-    vault["practices"] = list;
-    write_vault_internal(&state, &vault)?;
-    Ok(true)
+// ─── Date check ─────────────────────────────────────────────────────────────
+// Every date the app parses is a "%Y-%m-%d" string; anything else is silently skipped by
+// the summary, the schedulers and the reports. validate_dates lists the malformed ones,
+// repair_dates rewrites those with an unambiguous reading (2024/1/5, 5.1.2024, an ISO
+// timestamp) and, with policy "clear", empties the rest. Missing or empty values are
+// optional fields, not errors.
+const DATE_FIELDS: &[(&str, &[&str])] = &[
+    ("agenda", &["date"]),
+    ("archivedAgenda", &["date"]),
+    ("timeLogs", &["date"]),
+    ("invoices", &["date", "issueDate", "dueDate"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateRepair {
+    /// Rewrite fixable dates, leave the others as they are.
+    Normalize,
+    /// Rewrite fixable dates, empty the others.
+    Clear,
+}
+
+fn date_repair(policy: &str) -> Result<DateRepair, String> {
+    match policy {
+        "normalize" => Ok(DateRepair::Normalize),
+        "clear" => Ok(DateRepair::Clear),
+        _ => Err("Politica non valida (attesa \"normalize\" o \"clear\")".into()),
+    }
+}
+
+/// Canonical "%Y-%m-%d" form of `raw`, if it can be read without guessing: Y-M-D or D-M-Y
+/// (Italian order) with '-', '/' or '.' separators, or the date part of an ISO timestamp.
+fn canonical_date(raw: &str) -> Option<String> {
+    let s = raw.trim();
+    if let Some(prefix) = s.get(..10).filter(|_| matches!(s.as_bytes().get(10), Some(b'T' | b' '))) {
+        return canonical_date(prefix);
+    }
+    let parts: Vec<&str> = s.split(['-', '/', '.']).collect();
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || p.len() > 4 || !p.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let (y, m, d) = match (parts[0].len(), parts[2].len()) {
+        (4, 1..=2) if parts[1].len() <= 2 => (parts[0], parts[1], parts[2]),
+        (1..=2, 4) if parts[1].len() <= 2 => (parts[2], parts[1], parts[0]),
+        _ => return None,
+    };
+    let date = chrono::NaiveDate::from_ymd_opt(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?)?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+struct DateIssue {
+    /// JSON pointer to the value, for repair.
+    pointer: String,
+    /// Human-readable path, e.g. "practices[2].deadlines[0].date".
+    path: String,
+    id: Value,
+    value: Value,
+    fixed: Option<String>,
+}
+
+fn date_issues(vault: &Value) -> Vec<DateIssue> {
+    let mut issues = Vec::new();
+    let mut check = |obj: &Value, pointer: String, path: String, id: &Value, field: &str| {
+        let value = match obj.get(field) {
+            None | Some(Value::Null) => return,
+            Some(Value::String(s)) if s.is_empty() => return,
+            Some(v) => v,
+        };
+        let fixed = value.as_str().and_then(canonical_date);
+        if fixed.as_deref() == value.as_str() { return; }
+        issues.push(DateIssue {
+            pointer: format!("{}/{}", pointer, field),
+            path: format!("{}.{}", path, field),
+            id: id.clone(),
+            value: value.clone(),
+            fixed,
+        });
+    };
+    for (section, fields) in DATE_FIELDS {
+        for (i, item) in vault.get(*section).and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+            let id = item.get("id").cloned().unwrap_or(Value::Null);
+            for field in *fields {
+                check(item, format!("/{}/{}", section, i), format!("{}[{}]", section, i), &id, field);
+            }
+        }
+    }
+    for (i, p) in vault.get("practices").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+        let id = p.get("id").cloned().unwrap_or(Value::Null);
+        for (j, d) in p.get("deadlines").and_then(|d| d.as_array()).into_iter().flatten().enumerate() {
+            check(d, format!("/practices/{}/deadlines/{}", i, j), format!("practices[{}].deadlines[{}]", i, j), &id, "date");
+        }
+    }
+    issues
+}
+
+fn date_issue_json(issue: &DateIssue) -> Value {
+    json!({"path": issue.path, "id": issue.id, "value": issue.value, "fixable": issue.fixed.is_some(), "fixed": issue.fixed})
+}
+
+/// Apply `policy` to every malformed date; returns (rewritten, cleared, left as is).
+fn repair_vault_dates(vault: &mut Value, policy: DateRepair) -> (Vec<DateIssue>, Vec<DateIssue>, Vec<DateIssue>) {
+    let (mut fixed, mut cleared, mut left) = (Vec::new(), Vec::new(), Vec::new());
+    for issue in date_issues(vault) {
+        let Some(slot) = vault.pointer_mut(&issue.pointer) else { continue };
+        match (&issue.fixed, policy) {
+            (Some(date), _) => { *slot = json!(date); fixed.push(issue); }
+            (None, DateRepair::Clear) => { *slot = json!(""); cleared.push(issue); }
+            (None, DateRepair::Normalize) => left.push(issue),
+        }
+    }
+    (fixed, cleared, left)
+}
+
+/// Sections the date scan reads (archivedAgenda belongs to agenda); each one's
+/// protectedSections gate applies.
+const DATE_SECTIONS: [&str; 4] = ["agenda", "timeLogs", "invoices", "practices"];
+
+fn require_date_sections_access(state: &State<AppState>, reauth_token: Option<&str>) -> Result<(), String> {
+    DATE_SECTIONS.iter().try_for_each(|section| require_section_access(state, section, reauth_token))
 }
 
+/// Read-only scan of every known date field → {ok, fixable, unfixable, issues: [{path, id, value, fixable, fixed}]}.
 #[tauri::command]
-fn load_agenda(state: State<AppState>) -> Result<Value, String> {
+fn validate_dates(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_date_sections_access(&state, reauth_token.as_deref())?;
     let vault = read_vault_internal(&state)?;
-    Ok(vault.get("agenda").cloned().unwrap_or(json!([])))
+    let issues = date_issues(&vault);
+    let fixable = issues.iter().filter(|i| i.fixed.is_some()).count();
+    Ok(json!({
+        "ok": issues.is_empty(),
+        "fixable": fixable,
+        "unfixable": issues.len() - fixable,
+        "issues": issues.iter().map(date_issue_json).collect::<Vec<_>>(),
+    }))
 }
 
+/// `policy`: "normalize" (rewrite fixable dates only) or "clear" (also empty unfixable ones).
 #[tauri::command]
-fn save_agenda(state: State<AppState>, agenda: Value) -> Result<bool, String> {
+fn repair_dates(state: State<AppState>, policy: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_date_sections_access(&state, reauth_token.as_deref())?;
+    let policy = date_repair(&policy)?;
     let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
     let mut vault = read_vault_internal(&state)?;
-    vault["agenda"] = agenda;
-    write_vault_internal(&state, &vault)?;
-    Ok(true)
+    let (fixed, cleared, left) = repair_vault_dates(&mut vault, policy);
+    if !fixed.is_empty() || !cleared.is_empty() {
+        write_vault_internal(&state, &vault)?;
+        let _ = append_audit_log(&state, AuditCategory::System, None,
+            &format!("Date corrette: {} normalizzate, {} svuotate", fixed.len(), cleared.len()));
+    }
+    Ok(json!({
+        "fixed": fixed.len(),
+        "cleared": cleared.iter().map(date_issue_json).collect::<Vec<_>>(),
+        "unfixable": left.iter().map(date_issue_json).collect::<Vec<_>>(),
+    }))
 }
 
 // ═══════════════════════════════════════════════════════════
-//  CONFLICT CHECK (v3.2.0)
+//  PRACTICES & AGENDA
 // ═══════════════════════════════════════════════════════════
 
-/// Searches ALL practices (active + archived) for a name match in client,
-/// counterparty, description, court, and roles[].contactName fields.
-/// Returns an array of matching practices with the matched field highlighted.
+fn is_confidential(practice: &Value) -> bool {
+    practice.get("confidential").and_then(|c| c.as_bool()).unwrap_or(false)
+}
+
+/// Split practices into the normally visible list and the count of hidden confidential ones.
+fn visible_practices(practices: &[Value]) -> (Vec<Value>, usize) {
+    let visible: Vec<Value> = practices.iter().filter(|p| !is_confidential(p)).cloned().collect();
+    let hidden = practices.len() - visible.len();
+    (visible, hidden)
+}
+
+/// A caller that loaded the confidential practices says so with `includes_confidential`;
+/// with a valid reauth token (`reauthorized`) its list is taken as is — a confidential
+/// practice it left out was deleted. A token alone is not enough: protectedSections makes
+/// every save carry one. Otherwise the caller only saw the normal list: the stored
+/// confidential practices are kept untouched, and an entry claiming one of their ids is ignored.
+fn merge_confidential_practices(incoming: Value, existing: &Value, includes_confidential: bool, reauthorized: bool) -> Value {
+    let list = match incoming {
+        Value::Array(a) if !(includes_confidential && reauthorized) => a,
+        other => return other,
+    };
+    let hidden: Vec<&Value> = existing.as_array()
+        .map(|stored| stored.iter().filter(|p| is_confidential(p)).collect())
+        .unwrap_or_default();
+    let hidden_id = |p: &Value| {
+        let id = p.get("id").and_then(|i| i.as_str());
+        id.is_some() && hidden.iter().any(|h| h.get("id").and_then(|i| i.as_str()) == id)
+    };
+    let mut merged: Vec<Value> = list.into_iter().filter(|p| !hidden_id(p)).collect();
+    merged.extend(hidden.into_iter().cloned());
+    Value::Array(merged)
+}
+
+/// The non-confidential practices. Confidential ones require load_confidential_practices
+/// with a re-authentication token; hidden_practices_count says how many there are.
 #[tauri::command]
-fn check_conflict(state: State<AppState>, name: String) -> Result<Value, String> {
-    if name.trim().is_empty() {
-        return Ok(json!({"practiceMatches": [], "contactMatches": []}));
-    }
+fn load_practices(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
     let vault = read_vault_internal(&state)?;
     let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
-    let contacts = vault.get("contacts").and_then(|c| c.as_array()).cloned().unwrap_or_default();
-    let query = name.trim().to_lowercase();
-    let mut results: Vec<Value> = Vec::new();
+    Ok(json!(visible_practices(&practices).0))
+}
 
-    for p in &practices {
-        let mut matched_fields: Vec<String> = Vec::new();
+/// How many confidential practices load_practices leaves out, for the "N riservate" badge.
+#[tauri::command]
+fn hidden_practices_count(state: State<AppState>, reauth_token: Option<String>) -> Result<usize, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+    Ok(visible_practices(&practices).1)
+}
 
-        // Check main text fields
-        for field in &["client", "counterparty", "description", "court", "object"] {
-            if let Some(val) = p.get(field).and_then(|v| v.as_str()) {
-                if val.to_lowercase().contains(&query) {
-                    matched_fields.push(field.to_string());
-                }
-            }
-        }
+#[tauri::command]
+fn load_confidential_practices(state: State<AppState>, reauth_token: String) -> Result<Value, String> {
+    check_reauth_token(&state, &reauth_token)?;
+    let vault = read_vault_internal(&state)?;
+    let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+    let _ = append_audit_log(&state, AuditCategory::Security, None, "Accesso pratiche riservate");
+    Ok(json!(practices.into_iter().filter(is_confidential).collect::<Vec<_>>()))
+}
 
-        // Check roles array (linked contacts)
-        if let Some(roles) = p.get("roles").and_then(|r| r.as_array()) {
-            for role in roles {
-                if let Some(cid) = role.get("contactId").and_then(|c| c.as_str()) {
-                    // Resolve contact name from contacts registry
-                    if let Some(contact) = contacts.iter().find(|c| c.get("id").and_then(|i| i.as_str()) == Some(cid)) {
-                        if let Some(cname) = contact.get("name").and_then(|n| n.as_str()) {
-                            if cname.to_lowercase().contains(&query) {
-                                let role_label = role.get("role").and_then(|r| r.as_str()).unwrap_or("contatto");
-                                matched_fields.push(format!("ruolo:{}", role_label));
-                            }
-                        }
-                    }
-                }
-            }
-        }
+#[tauri::command]
+fn save_practices(state: State<AppState>, list: Value, includes_confidential: bool, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).discard("practices");
+    let reauthorized = reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok());
+    let mut vault = read_vault_internal(&state)?;
+    let mut merged = merge_confidential_practices(list, &vault["practices"], includes_confidential, reauthorized);
+    let now = chrono::Utc::now().to_rfc3339();
+    stamp_practice_status_changes(&mut merged, &vault["practices"], &now);
+    stamp_party_changes(&mut merged, &vault["practices"], &now);
+    vault["practices"] = merged;
+    prune_orphaned_secrets(&mut vault);
+    write_vault_internal(&state, &vault)?;
+    append_data_audit_log(&state, AuditCategory::Practice, "Fascicoli salvati", &vault["practices"]);
+    Ok(true)
+}
 
-        if !matched_fields.is_empty() {
-            results.push(json!({
-                "practice": p,
-                "matchedFields": matched_fields,
-            }));
-        }
+/// Shallow merge of a JSON patch into one practice: `null` deletes the field, `id` is fixed.
+fn patch_practice(practice: &Value, patch: &Value) -> Result<Value, String> {
+    let changes = patch.as_object().ok_or("La modifica deve essere un oggetto JSON")?;
+    let mut patched = practice.clone();
+    let obj = patched.as_object_mut().ok_or("Fascicolo non valido")?;
+    for (k, v) in changes {
+        if k == "id" { continue; }
+        if v.is_null() { obj.remove(k); } else { obj.insert(k.clone(), v.clone()); }
     }
+    Ok(patched)
+}
 
-    // Also search contacts themselves (find the person even if not linked to a practice yet)
-    let mut contact_matches: Vec<Value> = Vec::new();
-    for c in &contacts {
-        let mut cmatch = false;
-        for field in &["name", "fiscalCode", "vatNumber", "email", "pec", "phone"] {
-            if let Some(val) = c.get(field).and_then(|v| v.as_str()) {
-                if val.to_lowercase().contains(&query) {
-                    cmatch = true;
-                    break;
-                }
-            }
-        }
-        if cmatch {
-            // Find all practices referencing this contact
-            let cid = c.get("id").and_then(|i| i.as_str()).unwrap_or("");
-            let linked: Vec<String> = practices.iter().filter_map(|p| {
-                let client_id = p.get("clientId").and_then(|i| i.as_str()).unwrap_or("");
-                let counter_id = p.get("counterpartyId").and_then(|i| i.as_str()).unwrap_or("");
-                let in_roles = p.get("roles").and_then(|r| r.as_array())
-                    .map(|roles| roles.iter().any(|r| r.get("contactId").and_then(|i| i.as_str()) == Some(cid)))
-                    .unwrap_or(false);
-                if client_id == cid || counter_id == cid || in_roles {
-                    Some(p.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string())
-                } else {
-                    None
-                }
-            }).collect();
-            contact_matches.push(json!({
-                "contact": c,
-                "linkedPracticeIds": linked,
-            }));
+fn practice_index(practices: &Value, id: &str) -> Option<usize> {
+    practices.as_array()?.iter().position(|p| p.get("id").and_then(|i| i.as_str()) == Some(id))
+}
+
+/// Incremental save of a single practice: only the diff crosses IPC. A practice that is
+/// currently confidential needs the reauth token, as in load_confidential_practices.
+#[tauri::command]
+fn update_practice(state: State<AppState>, id: String, patch: Value, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    // A queued full-list save predates this patch: persist it first so it cannot undo it
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &id).ok_or("Fascicolo non trovato")?;
+    let original = vault["practices"][idx].clone();
+    if is_confidential(&original) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let mut single = json!([patch_practice(&original, &patch)?]);
+    let previous = json!([original]);
+    let now = chrono::Utc::now().to_rfc3339();
+    stamp_practice_status_changes(&mut single, &previous, &now);
+    stamp_party_changes(&mut single, &previous, &now);
+    vault["practices"][idx] = single[0].take();
+    write_vault_internal(&state, &vault)?;
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, Some(&id), "Fascicolo aggiornato");
+    Ok(true)
+}
+
+// ─── Recycle bin ────────────────────────────────────────────────────────────
+// vault["trash"]: [{deletedAt, practice}]. soft_delete_practice moves a practice there
+// instead of dropping it, so it stays encrypted with the rest of the vault until
+// restore_practice or purge_trash. Its secrets are keyed by practice id and stay put;
+// purging drops them too. Every unlock purges entries older than
+// settings.trashRetentionDays (default TRASH_RETENTION_DAYS).
+const TRASH_RETENTION_DAYS: u64 = 30;
+
+fn trash_index(vault: &Value, id: &str) -> Option<usize> {
+    vault.get("trash")?.as_array()?.iter()
+        .position(|e| e.pointer("/practice/id").and_then(|i| i.as_str()) == Some(id))
+}
+
+fn trash_retention_days(settings: &Value) -> u64 {
+    settings.get("trashRetentionDays").and_then(|v| v.as_u64()).unwrap_or(TRASH_RETENTION_DAYS)
+}
+
+/// Drop trash entries deleted before `cutoff` together with their secrets; returns how many.
+/// An entry whose deletedAt cannot be read is kept — purging cannot be undone.
+fn purge_trash_entries(vault: &mut Value, cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    let Some(list) = vault.get_mut("trash").and_then(|t| t.as_array_mut()) else { return 0 };
+    let mut purged: Vec<String> = Vec::new();
+    list.retain(|e| {
+        let expired = e.get("deletedAt").and_then(|d| d.as_str())
+            .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d < cutoff)
+            .unwrap_or(false);
+        if expired {
+            purged.push(e.pointer("/practice/id").and_then(|i| i.as_str()).unwrap_or("").to_string());
         }
+        !expired
+    });
+    let live: Vec<String> = purged.iter().filter(|id| practice_index(&vault["practices"], id).is_some()).cloned().collect();
+    if let Some(secrets) = vault.get_mut("secrets").and_then(|s| s.as_array_mut()) {
+        secrets.retain(|e| {
+            let pid = e.get("practiceId").and_then(|v| v.as_str()).unwrap_or("");
+            !purged.iter().any(|id| id == pid) || live.iter().any(|id| id == pid)
+        });
     }
+    purged.len()
+}
 
-    Ok(json!({
-        "practiceMatches": results,
-        "contactMatches": contact_matches,
-    }))
+#[tauri::command]
+fn soft_delete_practice(state: State<AppState>, id: String, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &id).ok_or("Fascicolo non trovato")?;
+    if is_confidential(&vault["practices"][idx]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let practice = vault["practices"].as_array_mut().ok_or("Sezione fascicoli non valida")?.remove(idx);
+    if !vault.get("trash").map(|t| t.is_array()).unwrap_or(false) {
+        vault["trash"] = json!([]);
+    }
+    let trash = vault["trash"].as_array_mut().ok_or("Cestino non valido")?;
+    trash.push(json!({"deletedAt": chrono::Utc::now().to_rfc3339(), "practice": practice}));
+    write_vault_internal(&state, &vault)?;
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, Some(&id), "Fascicolo spostato nel cestino");
+    Ok(true)
 }
 
-// ═══════════════════════════════════════════════════════════
-//  TIME TRACKING (v3.3.0)
-// ═══════════════════════════════════════════════════════════
+#[tauri::command]
+fn restore_practice(state: State<AppState>, id: String, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let idx = trash_index(&vault, &id).ok_or("Fascicolo non presente nel cestino")?;
+    if is_confidential(&vault["trash"][idx]["practice"]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    if practice_index(&vault["practices"], &id).is_some() {
+        return Err("Esiste già un fascicolo con questo identificativo".into());
+    }
+    let mut entry = vault["trash"].as_array_mut().ok_or("Cestino non valido")?.remove(idx);
+    if !vault.get("practices").map(|p| p.is_array()).unwrap_or(false) {
+        vault["practices"] = json!([]);
+    }
+    vault["practices"].as_array_mut().ok_or("Sezione fascicoli non valida")?.push(entry["practice"].take());
+    write_vault_internal(&state, &vault)?;
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, Some(&id), "Fascicolo ripristinato dal cestino");
+    Ok(true)
+}
 
+/// Trash entries of non-confidential practices, newest first, plus the hidden count.
 #[tauri::command]
-fn load_time_logs(state: State<AppState>) -> Result<Value, String> {
+fn list_trash(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
     let vault = read_vault_internal(&state)?;
-    Ok(vault.get("timeLogs").cloned().unwrap_or(json!([])))
+    let entries = vault.get("trash").and_then(|t| t.as_array()).cloned().unwrap_or_default();
+    let mut items: Vec<Value> = entries.iter().filter(|e| !is_confidential(&e["practice"])).cloned().collect();
+    items.sort_by(|a, b| b["deletedAt"].as_str().unwrap_or("").cmp(a["deletedAt"].as_str().unwrap_or("")));
+    let hidden = entries.len() - items.len();
+    Ok(json!({"items": items, "hiddenConfidential": hidden}))
 }
 
+/// Permanently delete trash entries older than `older_than_days` (0 empties the bin).
 #[tauri::command]
-fn save_time_logs(state: State<AppState>, logs: Value) -> Result<bool, String> {
+fn purge_trash(state: State<AppState>, older_than_days: u32, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
     let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
     let mut vault = read_vault_internal(&state)?;
-    vault["timeLogs"] = logs;
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(older_than_days as i64);
+    let purged = purge_trash_entries(&mut vault, cutoff);
+    if purged > 0 {
+        write_vault_internal(&state, &vault)?;
+        let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, None, &format!("Cestino svuotato: {} fascicoli eliminati", purged));
+    }
+    Ok(json!({"purged": purged}))
+}
+
+/// Opportunistic retention purge run after a successful unlock; failures are only logged.
+fn purge_expired_trash(state: &State<AppState>) {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let days = trash_retention_days(&read_settings(&dir));
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let Ok(mut vault) = read_vault_internal(state) else { return };
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days.min(36_500) as i64);
+    let purged = purge_trash_entries(&mut vault, cutoff);
+    if purged == 0 { return; }
+    match write_vault_internal(state, &vault) {
+        Ok(_) => { let _ = append_audit_log(state, AuditCategory::System, None, &format!("Cestino: {} fascicoli scaduti eliminati", purged)); }
+        Err(e) => lf_log!("[LexFlow] Pulizia cestino non riuscita: {}", e),
+    }
+}
+
+// ─── Practice secrets ───────────────────────────────────────────────────────
+// Matter-specific credentials (court portal login, PEC password) in vault["secrets"]:
+// [{practiceId, label, sealed, updatedAt}]. `sealed` is AES-GCM under the vault key with
+// practice id + label as AAD, so a secret never travels in clear with bulk practice data
+// and cannot be moved to another label or practice. Not a queue_save section: only these
+// commands write it. Whatever re-keys the vault (password change, restore) must reseal,
+// and exports carry the opened `value` inside their own encryption. A practice dropped
+// from a saved list takes its secrets with it; one moved to the trash keeps them.
+const MAX_SECRET_LABEL_LEN: usize = 100;
+const MAX_SECRET_VALUE_LEN: usize = 4096;
+
+fn secret_aad(practice_id: &str, label: &str) -> Vec<u8> {
+    format!("lexflow-secret:{}:{}", practice_id, label).into_bytes()
+}
+
+fn seal_secret(key: &[u8], practice_id: &str, label: &str, value: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut nonce);
+    let aad = secret_aad(practice_id, label);
+    let ct = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: value.as_bytes(), aad: &aad })
+        .map_err(|_| "Encryption error")?;
+    let mut out = nonce.to_vec();
+    out.extend(ct);
+    Ok(URL_SAFE_NO_PAD.encode(out))
+}
+
+fn open_secret(key: &[u8], practice_id: &str, label: &str, sealed: &str) -> Result<Zeroizing<String>, String> {
+    let raw = URL_SAFE_NO_PAD.decode(sealed).map_err(|_| "Segreto corrotto")?;
+    if raw.len() < NONCE_LEN + 16 { return Err("Segreto corrotto".into()); }
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let aad = secret_aad(practice_id, label);
+    let pt = cipher.decrypt(Nonce::from_slice(&raw[..NONCE_LEN]), Payload { msg: &raw[NONCE_LEN..], aad: &aad })
+        .map_err(|_| "Segreto non decifrabile")?;
+    String::from_utf8(pt).map(Zeroizing::new).map_err(|_| "Segreto corrotto".into())
+}
+
+/// Drop secrets whose practice is neither in the list nor in the trash; returns how many.
+fn prune_orphaned_secrets(vault: &mut Value) -> usize {
+    let owned: Vec<String> = vault.get("practices").and_then(|p| p.as_array()).into_iter().flatten()
+        .chain(vault.get("trash").and_then(|t| t.as_array()).into_iter().flatten().map(|e| &e["practice"]))
+        .filter_map(|p| p.get("id").and_then(|i| i.as_str()).map(|s| s.to_string()))
+        .collect();
+    let Some(secrets) = vault.get_mut("secrets").and_then(|s| s.as_array_mut()) else { return 0 };
+    let before = secrets.len();
+    secrets.retain(|e| {
+        let pid = e.get("practiceId").and_then(|v| v.as_str()).unwrap_or("");
+        owned.iter().any(|id| id == pid)
+    });
+    before - secrets.len()
+}
+
+fn secret_index(vault: &Value, practice_id: &str, label: &str) -> Option<usize> {
+    vault.get("secrets")?.as_array()?.iter().position(|e| {
+        e.get("practiceId").and_then(|v| v.as_str()) == Some(practice_id)
+            && e.get("label").and_then(|v| v.as_str()) == Some(label)
+    })
+}
+
+/// Move every secret between the sealed and the opened form: `from` opens `sealed` into
+/// `value`, `to` seals `value` back. Both together re-key (change_password).
+fn reseal_secrets(vault: &mut Value, from: Option<&[u8]>, to: Option<&[u8]>) -> Result<(), String> {
+    let Some(list) = vault.get_mut("secrets").and_then(|s| s.as_array_mut()) else { return Ok(()) };
+    for entry in list.iter_mut() {
+        let pid = entry.get("practiceId").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let label = entry.get("label").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let Some(obj) = entry.as_object_mut() else { continue };
+        if let (Some(key), Some(sealed)) = (from, obj.get("sealed").and_then(|v| v.as_str())) {
+            let value = open_secret(key, &pid, &label, sealed)?;
+            obj.remove("sealed");
+            obj.insert("value".into(), json!(value.as_str()));
+        }
+        if let (Some(key), Some(value)) = (to, obj.get("value").and_then(|v| v.as_str())) {
+            let sealed = seal_secret(key, &pid, &label, value)?;
+            obj.remove("value");
+            obj.insert("sealed".into(), json!(sealed));
+        }
+    }
+    Ok(())
+}
+
+/// Secrets of a confidential practice, or all of them with settings.secretsRequireReauth,
+/// need a fresh reauth token to be read.
+fn secret_needs_reauth(practice: &Value, settings: &Value) -> bool {
+    is_confidential(practice) || settings.get("secretsRequireReauth").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// Store (or, with an empty value, delete) one labelled secret of a practice.
+#[tauri::command]
+fn set_practice_secret(state: State<AppState>, practice_id: String, label: String, value: String, reauth_token: Option<String>) -> Result<bool, String> {
+    let label = label.trim().to_string();
+    if label.is_empty() || label.chars().count() > MAX_SECRET_LABEL_LEN {
+        return Err("Etichetta non valida".into());
+    }
+    if value.len() > MAX_SECRET_VALUE_LEN {
+        return Err("Segreto troppo lungo".into());
+    }
+    let value = Zeroizing::new(value);
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Fascicolo non trovato")?;
+    if is_confidential(&vault["practices"][idx]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    if !vault.get("secrets").map(|s| s.is_array()).unwrap_or(false) {
+        vault["secrets"] = json!([]);
+    }
+    let existing = secret_index(&vault, &practice_id, &label);
+    let list = vault["secrets"].as_array_mut().ok_or("Sezione segreti non valida")?;
+    if value.is_empty() {
+        let Some(i) = existing else { return Ok(false) };
+        list.remove(i);
+    } else {
+        let key = get_vault_key(&state)?;
+        let entry = json!({
+            "practiceId": practice_id,
+            "label": label,
+            "sealed": seal_secret(&key, &practice_id, &label, &value)?,
+            "updatedAt": chrono::Utc::now().to_rfc3339(),
+        });
+        match existing {
+            Some(i) => list[i] = entry,
+            None => list.push(entry),
+        }
+    }
     write_vault_internal(&state, &vault)?;
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, Some(&practice_id), "Segreto del fascicolo salvato");
     Ok(true)
 }
 
-// ═══════════════════════════════════════════════════════════
-//  INVOICES / BILLING (v3.4.0)
-// ═══════════════════════════════════════════════════════════
+#[tauri::command]
+fn get_practice_secret(state: State<AppState>, practice_id: String, label: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Fascicolo non trovato")?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if secret_needs_reauth(&vault["practices"][idx], &read_settings(&dir)) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let label = label.trim();
+    let i = secret_index(&vault, &practice_id, label).ok_or("Segreto non trovato")?;
+    let sealed = vault["secrets"][i].get("sealed").and_then(|v| v.as_str()).ok_or("Segreto corrotto")?;
+    let value = open_secret(&get_vault_key(&state)?, &practice_id, label, sealed)?;
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, Some(&practice_id), "Segreto del fascicolo letto");
+    Ok(json!({"label": label, "value": value.as_str(), "updatedAt": vault["secrets"][i]["updatedAt"]}))
+}
 
+/// Labels only — enough for the UI to list what is stored without opening anything.
 #[tauri::command]
-fn load_invoices(state: State<AppState>) -> Result<Value, String> {
+fn list_practice_secrets(state: State<AppState>, practice_id: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
     let vault = read_vault_internal(&state)?;
-    Ok(vault.get("invoices").cloned().unwrap_or(json!([])))
+    let labels: Vec<Value> = vault.get("secrets").and_then(|s| s.as_array()).into_iter().flatten()
+        .filter(|e| e.get("practiceId").and_then(|v| v.as_str()) == Some(practice_id.as_str()))
+        .map(|e| json!({"label": e["label"], "updatedAt": e["updatedAt"]}))
+        .collect();
+    Ok(json!(labels))
+}
+
+/// Record when a practice changes status, for reporting: `statusChangedAt` on every change,
+/// `closedAt` while closed (cleared on reopen), `createdAt` if a new practice lacks it.
+fn stamp_practice_status_changes(list: &mut Value, previous: &Value, now: &str) {
+    let prev = previous.as_array().cloned().unwrap_or_default();
+    let Some(items) = list.as_array_mut() else { return };
+    for p in items.iter_mut() {
+        let id = p.get("id").cloned();
+        let status = p.get("status").and_then(|s| s.as_str()).unwrap_or("").to_string();
+        let Some(obj) = p.as_object_mut() else { continue };
+        match prev.iter().find(|o| o.get("id").is_some() && o.get("id").cloned() == id) {
+            Some(old) => {
+                if old.get("status").and_then(|s| s.as_str()).unwrap_or("") == status { continue; }
+                obj.insert("statusChangedAt".into(), json!(now));
+                if status == "closed" {
+                    obj.insert("closedAt".into(), json!(now));
+                } else {
+                    obj.remove("closedAt");
+                }
+            }
+            None => {
+                obj.entry("createdAt").or_insert_with(|| json!(now));
+                if status == "closed" { obj.entry("closedAt").or_insert_with(|| json!(now)); }
+            }
+        }
+    }
 }
 
+// ─── Conflict-check confirmation ────────────────────────────────────────────
+// `conflictCleared` (RFC 3339) records when the user confirmed the conflict review for a
+// practice; `partiesChangedAt` is stamped on save whenever client, counterparty or roles
+// change. A confirmation older than the last party change no longer counts.
+const PARTY_FIELDS: [&str; 5] = ["client", "clientId", "counterparty", "counterpartyId", "roles"];
+
+fn party_signature(p: &Value) -> Vec<Value> {
+    PARTY_FIELDS.iter().map(|f| p.get(*f).cloned().unwrap_or(Value::Null)).collect()
+}
+
+/// Stamp `partiesChangedAt` on new practices and on party edits, and keep a stored
+/// `conflictCleared` that the incoming list omitted.
+fn stamp_party_changes(list: &mut Value, previous: &Value, now: &str) {
+    let prev = previous.as_array().cloned().unwrap_or_default();
+    let Some(items) = list.as_array_mut() else { return };
+    for p in items.iter_mut() {
+        let id = p.get("id").cloned();
+        let signature = party_signature(p);
+        let Some(obj) = p.as_object_mut() else { continue };
+        match prev.iter().find(|o| o.get("id").is_some() && o.get("id").cloned() == id) {
+            Some(old) => {
+                if party_signature(old) != signature {
+                    obj.insert("partiesChangedAt".into(), json!(now));
+                } else if let Some(at) = old.get("partiesChangedAt") {
+                    obj.entry("partiesChangedAt").or_insert_with(|| at.clone());
+                }
+                if let Some(cleared) = old.get("conflictCleared") {
+                    obj.entry("conflictCleared").or_insert_with(|| cleared.clone());
+                }
+            }
+            None => { obj.entry("partiesChangedAt").or_insert_with(|| json!(now)); }
+        }
+    }
+}
+
+/// Active practices whose conflict check is missing ("missing") or predates the last
+/// change to the parties ("stale"). Confidential entries carry only id and reason.
+fn missing_conflict_checks(practices: &[Value], reveal_confidential: bool) -> Vec<Value> {
+    let mut out = Vec::new();
+    for p in practices {
+        if p.get("status").and_then(|s| s.as_str()) != Some("active") { continue; }
+        let cleared = p.get("conflictCleared").and_then(|c| c.as_str()).and_then(|c| chrono::DateTime::parse_from_rfc3339(c).ok());
+        let changed = p.get("partiesChangedAt").and_then(|c| c.as_str()).and_then(|c| chrono::DateTime::parse_from_rfc3339(c).ok());
+        let reason = match (cleared, changed) {
+            (None, _) => "missing",
+            (Some(c), Some(ch)) if c < ch => "stale",
+            _ => continue,
+        };
+        let mut entry = json!({
+            "id": p.get("id").cloned().unwrap_or(Value::Null),
+            "reason": reason,
+            "confidential": is_confidential(p),
+        });
+        if reveal_confidential || !is_confidential(p) {
+            entry["client"] = p.get("client").cloned().unwrap_or(Value::Null);
+            entry["counterparty"] = p.get("counterparty").cloned().unwrap_or(Value::Null);
+            entry["conflictCleared"] = p.get("conflictCleared").cloned().unwrap_or(Value::Null);
+            entry["partiesChangedAt"] = p.get("partiesChangedAt").cloned().unwrap_or(Value::Null);
+        }
+        out.push(entry);
+    }
+    out
+}
+
+/// Compliance to-do list: active practices without a valid conflict-check confirmation.
+#[tauri::command]
+fn practices_missing_conflict_check(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let reveal = reauth_token.as_deref().map(|t| check_reauth_token(&state, t).is_ok()).unwrap_or(false);
+    let vault = read_vault_internal(&state)?;
+    let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+    let missing = missing_conflict_checks(&practices, reveal);
+    Ok(json!({"count": missing.len(), "practices": missing}))
+}
+
+/// Record that the conflict review for a practice was run and cleared.
 #[tauri::command]
-fn save_invoices(state: State<AppState>, invoices: Value) -> Result<bool, String> {
+fn confirm_conflict_check(state: State<AppState>, practice_id: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
     let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    // Only this practice changes: queued edits to the list must land first, not be lost
+    flush_pending_saves_locked(&state)?;
     let mut vault = read_vault_internal(&state)?;
-    vault["invoices"] = invoices;
+    let now = chrono::Utc::now().to_rfc3339();
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Pratica non trovata")?;
+    if is_confidential(&vault["practices"][idx]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    vault["practices"][idx]["conflictCleared"] = json!(now);
     write_vault_internal(&state, &vault)?;
-    Ok(true)
+    let _ = append_audit_log(&state, AuditCategory::Practice, Some(&practice_id), &format!("Verifica conflitti confermata: {}", practice_id));
+    Ok(json!({"success": true, "conflictCleared": now}))
 }
 
-// ═══════════════════════════════════════════════════════════
-//  CONTACTS REGISTRY (v3.5.0)
-// ═══════════════════════════════════════════════════════════
-
 #[tauri::command]
-fn load_contacts(state: State<AppState>) -> Result<Value, String> {
+fn load_agenda(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
     let vault = read_vault_internal(&state)?;
-    Ok(vault.get("contacts").cloned().unwrap_or(json!([])))
+    Ok(vault.get("agenda").cloned().unwrap_or(json!([])))
 }
 
 #[tauri::command]
-fn save_contacts(state: State<AppState>, contacts: Value) -> Result<bool, String> {
+fn save_agenda(state: State<AppState>, agenda: Value, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
     let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).discard("agenda");
     let mut vault = read_vault_internal(&state)?;
-    vault["contacts"] = contacts;
+    vault["agenda"] = drop_archived_agenda_items(agenda, &vault["archivedAgenda"]);
     write_vault_internal(&state, &vault)?;
+    append_data_audit_log(&state, AuditCategory::Agenda, "Agenda salvata", &vault["agenda"]);
     Ok(true)
 }
 
-// ═══════════════════════════════════════════════════════════
-//  BIOMETRICS
-// ═══════════════════════════════════════════════════════════
+// ─── Agenda ↔ practice links ────────────────────────────────────────────────
+// Agenda items point at their matter through `practiceId`; hard-deleting or purging a
+// practice leaves that id dangling. A link to a practice in the recycle bin is not
+// dangling: restore_practice brings the practice back.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AgendaLinkRepair {
+    /// Keep the item, set practiceId to null.
+    Unlink,
+    /// Drop the item.
+    Remove,
+}
+
+fn agenda_link_repair(policy: &str) -> Result<AgendaLinkRepair, String> {
+    match policy {
+        "unlink" => Ok(AgendaLinkRepair::Unlink),
+        "remove" => Ok(AgendaLinkRepair::Remove),
+        _ => Err("Politica non valida (attesa \"unlink\" o \"remove\")".into()),
+    }
+}
+
+/// Indices of agenda items whose practiceId matches neither a live nor a trashed practice.
+fn dangling_agenda_links(vault: &Value) -> Vec<usize> {
+    let live = vault.get("practices").and_then(|p| p.as_array()).into_iter().flatten();
+    let trashed = vault.get("trash").and_then(|t| t.as_array()).into_iter().flatten().map(|e| &e["practice"]);
+    let known: std::collections::HashSet<&str> = live.chain(trashed)
+        .filter_map(|p| p.get("id").and_then(|i| i.as_str()))
+        .collect();
+    vault.get("agenda").and_then(|a| a.as_array()).into_iter().flatten().enumerate()
+        .filter(|(_, item)| item.get("practiceId").and_then(|p| p.as_str())
+            .map(|pid| !pid.is_empty() && !known.contains(pid))
+            .unwrap_or(false))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Apply `policy` to every dangling link; returns how many items were touched.
+fn repair_dangling_agenda_links(vault: &mut Value, policy: AgendaLinkRepair) -> usize {
+    let dangling = dangling_agenda_links(vault);
+    let Some(agenda) = vault.get_mut("agenda").and_then(|a| a.as_array_mut()) else { return 0 };
+    for &i in dangling.iter().rev() {
+        match policy {
+            AgendaLinkRepair::Unlink => agenda[i]["practiceId"] = Value::Null,
+            AgendaLinkRepair::Remove => { agenda.remove(i); }
+        }
+    }
+    dangling.len()
+}
 
 #[tauri::command]
-fn check_bio() -> bool {
-    // macOS/Windows: biometria nativa disponibile
-    // Android: fingerprint/face disponibile via Android Biometric API (gestita lato JS)
-    cfg!(any(target_os = "macos", target_os = "windows", target_os = "android"))
+fn validate_agenda_links(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let items: Vec<Value> = dangling_agenda_links(&vault).into_iter().map(|i| {
+        let item = &vault["agenda"][i];
+        json!({"id": item["id"], "title": item["title"], "date": item["date"], "practiceId": item["practiceId"]})
+    }).collect();
+    Ok(json!({"dangling": items.len(), "items": items}))
 }
 
+/// `policy`: "unlink" (keep the item, clear practiceId) or "remove" (drop the item).
 #[tauri::command]
-fn has_bio_saved(state: State<AppState>) -> bool {
-    // TOUCH ID FIX: Do NOT call keyring::get_password() here — on macOS that triggers
-    // the Touch ID / password popup just to check if credentials exist!
-    // Instead, use a lightweight marker file written by save_bio / cleared by clear_bio.
-    #[cfg(not(target_os = "android"))]
-    {
-        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-        dir.join(BIO_MARKER_FILE).exists()
+fn repair_agenda_links(state: State<AppState>, policy: String, reauth_token: Option<String>) -> Result<Value, String> {
+    let policy = agenda_link_repair(&policy)?;
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let repaired = repair_dangling_agenda_links(&mut vault, policy);
+    if repaired > 0 {
+        write_vault_internal(&state, &vault)?;
+        let action = if policy == AgendaLinkRepair::Remove { "rimossi" } else { "scollegati" };
+        let _ = append_audit_log(&state, AuditCategory::Agenda, None, &format!("Impegni con fascicolo inesistente {}: {}", action, repaired));
     }
-    #[cfg(target_os = "android")]
-    {
-        let _ = state;
-        false
+    Ok(json!({"repaired": repaired}))
+}
+
+// ─── ICS import ─────────────────────────────────────────────────────────────
+// Minimal RFC 5545 reader: enough for exports from Google/Apple/Outlook calendars.
+// Recurring events are kept as a single item carrying the raw RRULE in `recurrence`.
+// TZID-qualified times are kept as wall-clock time with the TZID in `timezone` (see
+// zoned_datetime); UTC times ("...Z") are converted to local time.
+
+/// Unfold continuation lines (CRLF followed by a space or tab).
+fn unfold_ics_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in content.lines() {
+        let line = raw.trim_end_matches('\r');
+        if let Some(cont) = line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')) {
+            if let Some(last) = lines.last_mut() { last.push_str(cont); continue; }
+        }
+        lines.push(line.to_string());
     }
+    lines
 }
 
-#[tauri::command]
-fn save_bio(state: State<AppState>, pwd: String) -> Result<bool, String> {
-    #[cfg(not(target_os = "android"))]
-    {
-        let user = whoami::username();
-        let entry = keyring::Entry::new(BIO_SERVICE, &user).map_err(|e| e.to_string())?;
-        entry.set_password(&pwd).map_err(|e| e.to_string())?;
-        // Write marker file so has_bio_saved() can check without triggering Touch ID
-        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-        let _ = fs::write(dir.join(BIO_MARKER_FILE), "1");
-        Ok(true)
+fn unescape_ics_text(v: &str) -> String {
+    v.replace("\\n", "\n").replace("\\N", "\n").replace("\\,", ",").replace("\\;", ";").replace("\\\\", "\\")
+}
+
+/// Parse a DATE / DATE-TIME value into (date, time) — time is None for all-day values.
+fn parse_ics_datetime(value: &str) -> Option<(String, Option<String>)> {
+    let value = value.trim();
+    if value.len() == 8 {
+        let d = chrono::NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((d.format("%Y-%m-%d").to_string(), None));
     }
-    #[cfg(target_os = "android")]
-    {
-        let _ = (state, pwd);
-        Ok(true)
+    let (naive, utc) = match value.strip_suffix('Z') {
+        Some(v) => (v, true),
+        None => (value, false),
+    };
+    let dt = chrono::NaiveDateTime::parse_from_str(naive, "%Y%m%dT%H%M%S").ok()?;
+    let local = if utc {
+        chrono::Utc.from_utc_datetime(&dt).with_timezone(&chrono::Local).naive_local()
+    } else { dt };
+    Some((local.format("%Y-%m-%d").to_string(), Some(local.format("%H:%M").to_string())))
+}
+
+/// VALARM TRIGGER duration ("-PT15M", "-P1D", "-P1DT2H") → minutes before the event.
+/// Triggers after the start or absolute triggers are not representable → None.
+fn parse_ics_trigger_minutes(value: &str) -> Option<i64> {
+    let rest = value.trim().strip_prefix("-P")?;
+    let (mut minutes, mut num, mut in_time) = (0i64, 0i64, false);
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => num = num * 10 + c.to_digit(10)? as i64,
+            'W' => { minutes += num * 7 * 24 * 60; num = 0; }
+            'D' => { minutes += num * 24 * 60; num = 0; }
+            'H' if in_time => { minutes += num * 60; num = 0; }
+            'M' if in_time => { minutes += num; num = 0; }
+            'S' if in_time => { num = 0; }
+            _ => return None,
+        }
     }
+    Some(minutes)
 }
 
-#[tauri::command]
-fn bio_login(_state: State<AppState>) -> Result<Value, String> {
-    #[cfg(target_os = "macos")]
-    {
-        // FORT KNOX: Swift code passed via stdin — NEVER written to disk
-        let swift_code = "import LocalAuthentication\nlet ctx = LAContext()\nvar err: NSError?\nif ctx.canEvaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, error: &err) {\n  let sema = DispatchSemaphore(value: 0)\n  var ok = false\n  ctx.evaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, localizedReason: \"LexFlow\") { s, _ in ok = s; sema.signal() }\n  sema.wait()\n  if ok { exit(0) } else { exit(1) }\n} else { exit(1) }";
-        
+/// Map every VEVENT to an agenda item. Returns (items, skipped[{uid, summary, reason}]).
+fn parse_ics_events(content: &str) -> (Vec<Value>, Vec<Value>) {
+    let mut items = Vec::new();
+    let mut skipped = Vec::new();
+    let mut event: Option<Vec<(String, String, String)>> = None; // (name, params, value)
+    let mut in_alarm = false;
+    let mut alarm_minutes: Option<i64> = None;
+    for line in unfold_ics_lines(content) {
+        match line.as_str() {
+            "BEGIN:VEVENT" => { event = Some(Vec::new()); alarm_minutes = None; continue; }
+            "BEGIN:VALARM" => { in_alarm = true; continue; }
+            "END:VALARM" => { in_alarm = false; continue; }
+            _ => {}
+        }
+        if line == "END:VEVENT" {
+            if let Some(props) = event.take() {
+                match ics_event_to_item(&props, alarm_minutes) {
+                    Ok(item) => items.push(item),
+                    Err(reason) => {
+                        let get = |k: &str| props.iter().find(|(n, _, _)| n == k).map(|(_, _, v)| unescape_ics_text(v));
+                        skipped.push(json!({"uid": get("UID"), "summary": get("SUMMARY"), "reason": reason}));
+                    }
+                }
+            }
+            continue;
+        }
+        let Some(props) = event.as_mut() else { continue };
+        let Some((head, value)) = line.split_once(':') else { continue };
+        let (name, params) = head.split_once(';').unwrap_or((head, ""));
+        let name = name.to_ascii_uppercase();
+        if in_alarm {
+            if name == "TRIGGER" && !params.to_ascii_uppercase().contains("RELATED=END") {
+                if let Some(m) = parse_ics_trigger_minutes(value) {
+                    alarm_minutes = Some(alarm_minutes.map_or(m, |prev: i64| prev.max(m)));
+                }
+            }
+        } else {
+            props.push((name, params.to_string(), value.to_string()));
+        }
+    }
+    (items, skipped)
+}
+
+fn ics_event_to_item(props: &[(String, String, String)], alarm_minutes: Option<i64>) -> Result<Value, String> {
+    let get = |k: &str| props.iter().find(|(n, _, _)| n == k);
+    let (_, start_params, dtstart) = get("DTSTART").ok_or("DTSTART mancante")?;
+    let (date, time_start) = parse_ics_datetime(dtstart).ok_or_else(|| format!("DTSTART non valido: {}", dtstart))?;
+    let time_end = get("DTEND").and_then(|(_, _, v)| parse_ics_datetime(v)).and_then(|(_, t)| t);
+    let title = get("SUMMARY").map(|(_, _, v)| unescape_ics_text(v)).filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| "Evento importato".to_string());
+    // Without a UID the id still has to be stable across re-imports of the same file
+    let uid = get("UID").map(|(_, _, v)| v.trim().to_string())
+        .unwrap_or_else(|| format!("{}|{}", dtstart, title));
+    let id = format!("ics-{}", &hex::encode(<Sha256 as Digest>::digest(uid.as_bytes()))[..16]);
+    let all_day = time_start.is_none();
+    let timezone = start_params.split(';').find_map(|p| p.strip_prefix("TZID="))
+        .map(|tz| tz.trim_matches('"')).filter(|tz| !all_day && !dtstart.ends_with('Z') && load_time_zone(tz).is_some());
+    Ok(json!({
+        "id": id,
+        "icsUid": uid,
+        "title": title,
+        "date": date,
+        "timeStart": time_start.unwrap_or_default(),
+        "timeEnd": time_end.unwrap_or_default(),
+        "allDay": all_day,
+        "category": "altro",
+        "notes": get("DESCRIPTION").map(|(_, _, v)| unescape_ics_text(v)).unwrap_or_default(),
+        "remindMinutes": alarm_minutes,
+        "customRemindTime": null,
+        "recurrence": get("RRULE").map(|(_, _, v)| v.clone()),
+        "timezone": timezone,
+        "completed": false,
+        "autoSync": false,
+        "practiceId": null,
+    }))
+}
+
+/// Append parsed items to the agenda, skipping UIDs already present.
+fn merge_ics_items(agenda: &mut Vec<Value>, items: Vec<Value>, skipped: &mut Vec<Value>) -> usize {
+    let mut imported = 0;
+    for item in items {
+        let dup = agenda.iter().any(|a| a.get("id") == item.get("id"));
+        if dup {
+            skipped.push(json!({"uid": item["icsUid"], "summary": item["title"], "reason": "Evento già presente in agenda"}));
+            continue;
+        }
+        agenda.push(item);
+        imported += 1;
+    }
+    imported
+}
+
+#[tauri::command]
+fn import_agenda_ics(state: State<AppState>, content: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
+    if !content.contains("BEGIN:VCALENDAR") {
+        return Err("File .ics non valido".into());
+    }
+    let (items, mut skipped) = parse_ics_events(&content);
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    // Imported events are merged into the agenda: queued edits must be in it first
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let mut agenda = vault.get("agenda").and_then(|a| a.as_array()).cloned().unwrap_or_default();
+    let imported = merge_ics_items(&mut agenda, items, &mut skipped);
+    if imported > 0 {
+        vault["agenda"] = json!(agenda);
+        write_vault_internal(&state, &vault)?;
+        let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Agenda, None, &format!("Importati {} impegni da file .ics", imported));
+    }
+    Ok(json!({"imported": imported, "skipped": skipped.len(), "skippedItems": skipped}))
+}
+
+// ─── Practice folders ───────────────────────────────────────────────────────
+// A practice can reference one folder on disk (`folderPath`, stored encrypted in the vault).
+// LexFlow only lists it; opening a listed file goes through the hardened open_path.
+
+/// Folder must be absolute, exist, be a real directory and NOT a symlink
+/// (a symlink could be retargeted later to expose an unrelated location).
+fn validate_practice_folder(folder: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(folder);
+    if !path.is_absolute() {
+        return Err("Il percorso della cartella deve essere assoluto".into());
+    }
+    let meta = path.symlink_metadata().map_err(|_| "Cartella non trovata")?;
+    if meta.file_type().is_symlink() {
+        return Err("Collegamenti simbolici non ammessi come cartella pratica".into());
+    }
+    if !meta.is_dir() {
+        return Err("Il percorso non è una cartella".into());
+    }
+    Ok(path)
+}
+
+/// Non-recursive listing of a practice folder: regular files only, hidden files skipped.
+fn list_folder_documents(folder: &std::path::Path) -> Value {
+    let entries = match fs::read_dir(folder) {
+        Ok(e) => e,
+        Err(_) => return json!({"folderPath": folder.to_string_lossy(), "exists": false, "files": []}),
+    };
+    let mut files: Vec<Value> = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') { continue; }
+        let meta = match entry.path().symlink_metadata() { Ok(m) => m, Err(_) => continue };
+        if !meta.is_file() { continue; }
+        let modified = meta.modified().ok()
+            .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339());
+        files.push(json!({
+            "name": name,
+            "path": entry.path().to_string_lossy(),
+            "size": meta.len(),
+            "modified": modified,
+        }));
+    }
+    files.sort_by(|a, b| a["name"].as_str().unwrap_or("").cmp(b["name"].as_str().unwrap_or("")));
+    json!({"folderPath": folder.to_string_lossy(), "exists": true, "files": files})
+}
+
+#[tauri::command]
+fn link_practice_folder(state: State<AppState>, practice_id: String, folder_path: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let folder = validate_practice_folder(&folder_path)?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    // A queued full-list save predates the link: persist it first so it cannot drop folderPath
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Pratica non trovata")?;
+    if is_confidential(&vault["practices"][idx]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    vault["practices"][idx]["folderPath"] = json!(folder.to_string_lossy());
+    write_vault_internal(&state, &vault)?;
+    Ok(json!({"success": true, "folderPath": folder.to_string_lossy()}))
+}
+
+#[tauri::command]
+fn list_practice_documents(state: State<AppState>, practice_id: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Pratica non trovata")?;
+    let practice = &vault["practices"][idx];
+    if is_confidential(practice) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let folder = match practice.get("folderPath").and_then(|f| f.as_str()).filter(|f| !f.is_empty()) {
+        Some(f) => f,
+        None => return Ok(json!({"folderPath": null, "exists": false, "files": []})),
+    };
+    // Re-validate on every listing: the folder may have been replaced by a symlink since linking
+    match validate_practice_folder(folder) {
+        Ok(path) => Ok(list_folder_documents(&path)),
+        Err(e) => Ok(json!({"folderPath": folder, "exists": false, "files": [], "error": e})),
+    }
+}
+
+// ─── Attachment audit ───────────────────────────────────────────────────────
+// Files referenced by practices get moved or deleted outside LexFlow. audit_attachments
+// only stats each stored path (never opens it) so the UI can flag dead links. Relative
+// paths are skipped, as open_path refuses them anyway.
+
+/// (practiceId, field, path) for attachments[].path, documents[].path and folderPath.
+fn collect_attachment_paths(practices: &[Value]) -> Vec<(String, &'static str, String)> {
+    let mut out = Vec::new();
+    for p in practices {
+        let pid = p.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string();
+        for field in ["attachments", "documents"] {
+            for item in p.get(field).and_then(|a| a.as_array()).into_iter().flatten() {
+                if let Some(path) = item.get("path").and_then(|v| v.as_str()) {
+                    out.push((pid.clone(), field, path.to_string()));
+                }
+            }
+        }
+        if let Some(folder) = p.get("folderPath").and_then(|f| f.as_str()) {
+            out.push((pid.clone(), "folderPath", folder.to_string()));
+        }
+    }
+    out.retain(|(_, _, path)| std::path::Path::new(path).is_absolute());
+    out
+}
+
+/// (exists, isSymlink): a symlink counts as existing only if its target does.
+fn stat_attachment(path: &std::path::Path) -> (bool, bool) {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.file_type().is_symlink() => (fs::metadata(path).is_ok(), true),
+        Ok(_) => (true, false),
+        Err(_) => (false, false),
+    }
+}
+
+/// Confidential practices are left out, as in load_practices.
+#[tauri::command]
+fn audit_attachments(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+    let (visible, _) = visible_practices(&practices);
+    let report: Vec<Value> = collect_attachment_paths(&visible).into_iter().map(|(pid, field, path)| {
+        let (exists, is_symlink) = stat_attachment(std::path::Path::new(&path));
+        json!({"practiceId": pid, "field": field, "path": path, "exists": exists, "isSymlink": is_symlink})
+    }).collect();
+    Ok(json!(report))
+}
+
+// ─── Attachment size impact ─────────────────────────────────────────────────
+// Attachments are path references today; storing a file inside the vault would add its
+// base64 form to vault.lex and to every later full rewrite. estimate_attachment_impact
+// stats the file only and recommends "inline" below settings.attachmentInlineMaxMb when
+// the vault stays under settings.vaultSoftLimitMb, otherwise an external reference.
+const DEFAULT_ATTACHMENT_INLINE_MAX_MB: u64 = 10;
+const DEFAULT_VAULT_SOFT_LIMIT_MB: u64 = 200;
+const MB: u64 = 1024 * 1024;
+
+/// Bytes a file would add to vault.lex once inlined: base64 (4/3) of the content.
+fn inlined_size(file_bytes: u64) -> u64 {
+    file_bytes.div_ceil(3) * 4
+}
+
+fn attachment_recommendation(file_bytes: u64, vault_bytes: u64, inline_max_mb: u64, vault_limit_mb: u64) -> Value {
+    let projected = vault_bytes + inlined_size(file_bytes);
+    let (recommendation, reason) = if file_bytes > inline_max_mb * MB {
+        ("external", format!("Il file supera {} MB: conviene collegarlo dalla cartella della pratica.", inline_max_mb))
+    } else if projected > vault_limit_mb * MB {
+        ("external", format!("Il vault supererebbe {} MB: ogni salvataggio diventerebbe più lento.", vault_limit_mb))
+    } else {
+        ("inline", "Dimensioni contenute: il file può essere conservato nel vault.".to_string())
+    };
+    json!({
+        "fileBytes": file_bytes,
+        "vaultBytes": vault_bytes,
+        "projectedVaultBytes": projected,
+        "recommendation": recommendation,
+        "reason": reason,
+    })
+}
+
+#[tauri::command]
+fn estimate_attachment_impact(state: State<AppState>, source_path: String) -> Result<Value, String> {
+    let source = std::path::Path::new(&source_path);
+    if !source.is_absolute() { return Err("Percorso non valido".into()); }
+    let meta = fs::metadata(source).map_err(|_| "File non trovato".to_string())?;
+    if !meta.is_file() { return Err("Il percorso indicato non è un file".into()); }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let vault_bytes = fs::metadata(dir.join(VAULT_FILE)).map(|m| m.len()).unwrap_or(0);
+    let settings = read_settings(&dir);
+    let setting = |k: &str, default: u64| settings.get(k).and_then(|v| v.as_u64()).filter(|v| *v > 0).unwrap_or(default);
+    Ok(attachment_recommendation(
+        meta.len(),
+        vault_bytes,
+        setting("attachmentInlineMaxMb", DEFAULT_ATTACHMENT_INLINE_MAX_MB),
+        setting("vaultSoftLimitMb", DEFAULT_VAULT_SOFT_LIMIT_MB),
+    ))
+}
+
+// ─── Orphaned attachment blobs ──────────────────────────────────────────────
+// A vault-level `attachments` object (id → blob) is not written by this build — practices
+// reference files by path — but vaults imported or restored from elsewhere may carry one.
+// A blob counts as orphaned when no practice, live or in the trash, lists its id under
+// attachments[].id or documents[].id. Trashed practices still own their blobs so that
+// restore_practice keeps working. The same rule covers encrypted documents: a key in
+// `documentKeys` or a file in blobs/ whose blobId no practice lists any more is orphaned.
+
+fn referenced_attachment_ids(vault: &Value) -> std::collections::HashSet<String> {
+    let live = vault.get("practices").and_then(|p| p.as_array()).into_iter().flatten();
+    let trashed = vault.get("trash").and_then(|t| t.as_array()).into_iter().flatten().map(|e| &e["practice"]);
+    live.chain(trashed)
+        .flat_map(|p| ["attachments", "documents"].into_iter()
+            .flat_map(move |field| p.get(field).and_then(|a| a.as_array()).into_iter().flatten()))
+        .filter_map(|item| item.get("id").and_then(|i| i.as_str()).map(str::to_string))
+        .collect()
+}
+
+/// (id, serialised bytes) of every unreferenced blob, sorted by id.
+fn orphaned_attachments(vault: &Value) -> Vec<(String, usize)> {
+    let Some(blobs) = vault.get("attachments").and_then(|a| a.as_object()) else { return Vec::new() };
+    let referenced = referenced_attachment_ids(vault);
+    let mut out: Vec<(String, usize)> = blobs.iter()
+        .filter(|(id, _)| !referenced.contains(id.as_str()))
+        .map(|(id, blob)| (id.clone(), serde_json::to_vec(blob).map(|b| b.len()).unwrap_or(0)))
+        .collect();
+    out.sort();
+    out
+}
+
+/// Remove orphaned blobs; returns the removed ids.
+fn purge_orphaned_blobs(vault: &mut Value) -> Vec<String> {
+    let ids: Vec<String> = orphaned_attachments(vault).into_iter().map(|(id, _)| id).collect();
+    if let Some(blobs) = vault.get_mut("attachments").and_then(|a| a.as_object_mut()) {
+        for id in &ids { blobs.remove(id); }
+    }
+    ids
+}
+
+fn referenced_document_blobs(vault: &Value) -> std::collections::HashSet<String> {
+    let live = vault.get("practices").and_then(|p| p.as_array()).into_iter().flatten();
+    let trashed = vault.get("trash").and_then(|t| t.as_array()).into_iter().flatten().map(|e| &e["practice"]);
+    live.chain(trashed)
+        .flat_map(|p| p.get("documents").and_then(|d| d.as_array()).into_iter().flatten())
+        .filter_map(|d| d.get("blobId").and_then(|b| b.as_str()).map(str::to_string))
+        .collect()
+}
+
+/// Ids in `documentKeys` that no document references, sorted.
+fn orphaned_document_keys(vault: &Value) -> Vec<String> {
+    let Some(keys) = vault.get("documentKeys").and_then(|k| k.as_object()) else { return Vec::new() };
+    let referenced = referenced_document_blobs(vault);
+    let mut out: Vec<String> = keys.keys().filter(|id| !referenced.contains(id.as_str())).cloned().collect();
+    out.sort();
+    out
+}
+
+/// (blobId, file bytes) of every file in blobs/ that no document references, sorted.
+fn orphaned_document_files(dir: &std::path::Path, vault: &Value) -> Vec<(String, u64)> {
+    let Ok(entries) = fs::read_dir(dir.join(DOCUMENT_BLOBS_DIR)) else { return Vec::new() };
+    let referenced = referenced_document_blobs(vault);
+    let mut out: Vec<(String, u64)> = entries.flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let id = name.strip_suffix(".blob")?.to_string();
+            let meta = entry.path().symlink_metadata().ok()?;
+            let ours = meta.is_file() && document_blob_path(dir, &id).is_ok();
+            (ours && !referenced.contains(&id)).then_some((id, meta.len()))
+        })
+        .collect();
+    out.sort();
+    out
+}
+
+#[tauri::command]
+fn find_orphaned_attachments(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let orphans = orphaned_attachments(&vault);
+    let files = orphaned_document_files(&dir, &vault);
+    let total = orphans.iter().map(|(_, size)| *size as u64).sum::<u64>() + files.iter().map(|(_, size)| size).sum::<u64>();
+    let items: Vec<Value> = orphans.into_iter().map(|(id, size)| json!({"id": id, "bytes": size})).collect();
+    let documents: Vec<Value> = files.into_iter().map(|(id, size)| json!({"id": id, "bytes": size})).collect();
+    Ok(json!({
+        "items": items,
+        "documentFiles": documents,
+        "documentKeys": orphaned_document_keys(&vault),
+        "totalBytes": total,
+    }))
+}
+
+/// Drop orphaned blobs and document keys, rewrite vault.lex, then delete orphaned document
+/// files; reclaimedBytes is measured on disk.
+#[tauri::command]
+fn purge_orphaned_attachments(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let purged = purge_orphaned_blobs(&mut vault);
+    let keys = orphaned_document_keys(&vault);
+    if let Some(stored) = vault.get_mut("documentKeys").and_then(|k| k.as_object_mut()) {
+        for id in &keys { stored.remove(id); }
+    }
+    let files = orphaned_document_files(&dir, &vault);
+    if purged.is_empty() && keys.is_empty() && files.is_empty() {
+        return Ok(json!({"purged": 0, "documentKeys": 0, "documentFiles": 0, "reclaimedBytes": 0}));
+    }
+    let vault_path = dir.join(VAULT_FILE);
+    let before = fs::metadata(&vault_path).map(|m| m.len()).unwrap_or(0);
+    if !purged.is_empty() || !keys.is_empty() {
+        write_vault_internal(&state, &vault)?;
+    }
+    let after = fs::metadata(&vault_path).map(|m| m.len()).unwrap_or(before);
+    // Keys first, files second, as in delete_document
+    let mut removed_bytes = 0;
+    let mut removed_files = 0;
+    for (id, size) in &files {
+        if document_blob_path(&dir, id).map(|p| fs::remove_file(p).is_ok()).unwrap_or(false) {
+            removed_bytes += size;
+            removed_files += 1;
+        }
+    }
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, None,
+        &format!("Allegati orfani eliminati: {} (chiavi documento {}, file documento {})", purged.len(), keys.len(), removed_files));
+    Ok(json!({
+        "purged": purged.len(),
+        "documentKeys": keys.len(),
+        "documentFiles": removed_files,
+        "reclaimedBytes": before.saturating_sub(after) + removed_bytes,
+    }))
+}
+
+// ─── Encrypted practice documents ───────────────────────────────────────────
+// attach_document copies a file into <data>/blobs/<blobId>.blob, AES-GCM encrypted under
+// a random per-document key. The practice lists {blobId, name, size, sha256, addedAt}
+// under documents[]; the keys live in the vault's top-level `documentKeys` (blobId → hex),
+// so they are re-encrypted with vault.lex on change_password and the blobs never need
+// rewriting. Backups carry the keys with the practices but not the blobs, which stay in
+// the data folder across a restore; read-only snapshots carry neither. Blobs and keys left
+// behind by purged practices are reclaimed by purge_orphaned_attachments.
+const DOCUMENT_BLOBS_DIR: &str = "blobs";
+const MAX_DOCUMENT_BYTES: u64 = 50 * MB;
+
+/// Regular, non-symlinked file within MAX_DOCUMENT_BYTES; returns its size.
+fn validate_document_source(path: &std::path::Path) -> Result<u64, String> {
+    if !path.is_absolute() { return Err("Percorso non valido".into()); }
+    let meta = path.symlink_metadata().map_err(|_| "File non trovato")?;
+    if meta.file_type().is_symlink() {
+        return Err("Collegamenti simbolici non ammessi come documento".into());
+    }
+    if !meta.is_file() { return Err("Il percorso indicato non è un file".into()); }
+    if meta.len() > MAX_DOCUMENT_BYTES {
+        return Err(format!("Il documento supera il limite di {} MB", MAX_DOCUMENT_BYTES / MB));
+    }
+    Ok(meta.len())
+}
+
+/// Blob ids are generated here (32 hex chars); anything else never reaches a path join.
+fn document_blob_path(dir: &std::path::Path, blob_id: &str) -> Result<PathBuf, String> {
+    if blob_id.len() != 32 || !blob_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("Documento non trovato".into());
+    }
+    Ok(dir.join(DOCUMENT_BLOBS_DIR).join(format!("{}.blob", blob_id)))
+}
+
+struct SealedDocument {
+    key: Zeroizing<Vec<u8>>,
+    blob: Vec<u8>,
+    /// Hex SHA-256 of the plaintext.
+    sha256: String,
+}
+
+fn seal_document(bytes: &[u8]) -> Result<SealedDocument, String> {
+    let mut key = Zeroizing::new(vec![0u8; 32]);
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut key);
+    let blob = encrypt_data(&key, bytes)?;
+    Ok(SealedDocument { key, blob, sha256: hex::encode(<Sha256 as Digest>::digest(bytes)) })
+}
+
+fn open_document(key: &[u8], sealed: &[u8], sha256: &str) -> Result<Vec<u8>, String> {
+    let bytes = decrypt_data(key, sealed).map_err(|_| "Documento corrotto")?;
+    if hex::encode(<Sha256 as Digest>::digest(&bytes)) != sha256 {
+        return Err("Documento corrotto: impronta non corrispondente".into());
+    }
+    Ok(bytes)
+}
+
+/// (practice index, document index) of a blob among live practices.
+fn document_location(vault: &Value, blob_id: &str) -> Option<(usize, usize)> {
+    vault.get("practices")?.as_array()?.iter().enumerate().find_map(|(pi, p)| {
+        p.get("documents")?.as_array()?.iter()
+            .position(|d| d.get("blobId").and_then(|b| b.as_str()) == Some(blob_id))
+            .map(|di| (pi, di))
+    })
+}
+
+#[tauri::command]
+fn attach_document(state: State<AppState>, practice_id: String, src_path: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let source = std::path::Path::new(&src_path);
+    validate_document_source(source)?;
+    let bytes = Zeroizing::new(fs::read(source).map_err(|e| format!("Lettura del file fallita: {}", e))?);
+    if bytes.len() as u64 > MAX_DOCUMENT_BYTES {
+        return Err(format!("Il documento supera il limite di {} MB", MAX_DOCUMENT_BYTES / MB));
+    }
+    let name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let sealed = seal_document(&bytes)?;
+    let mut id_bytes = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
+    let blob_id = hex::encode(id_bytes);
+
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Fascicolo non trovato")?;
+    if is_confidential(&vault["practices"][idx]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let blob_path = document_blob_path(&dir, &blob_id)?;
+    fs::create_dir_all(dir.join(DOCUMENT_BLOBS_DIR)).map_err(|e| e.to_string())?;
+    secure_write(&blob_path, &sealed.blob).map_err(|e| e.to_string())?;
+
+    let record = json!({
+        "blobId": blob_id,
+        "name": name,
+        "size": bytes.len(),
+        "sha256": sealed.sha256,
+        "addedAt": chrono::Utc::now().to_rfc3339(),
+    });
+    let practice = &mut vault["practices"][idx];
+    if !practice.get("documents").map(|d| d.is_array()).unwrap_or(false) {
+        practice["documents"] = json!([]);
+    }
+    practice["documents"].as_array_mut().ok_or("Fascicolo non valido")?.push(record.clone());
+    if !vault.get("documentKeys").map(|k| k.is_object()).unwrap_or(false) {
+        vault["documentKeys"] = json!({});
+    }
+    vault["documentKeys"][blob_id.as_str()] = json!(hex::encode(sealed.key.as_slice()));
+    if let Err(e) = write_vault_internal(&state, &vault) {
+        let _ = fs::remove_file(&blob_path);
+        return Err(e);
+    }
+    let _ = append_audit_log(&state, AuditCategory::Practice, Some(&practice_id), "Documento cifrato allegato");
+    Ok(record)
+}
+
+/// Decrypted content as raw bytes (an ArrayBuffer on the JS side).
+#[tauri::command]
+fn read_document(state: State<AppState>, blob_id: String, reauth_token: Option<String>) -> Result<tauri::ipc::Response, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let (pi, di) = document_location(&vault, &blob_id).ok_or("Documento non trovato")?;
+    let practice = &vault["practices"][pi];
+    if is_confidential(practice) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let key = vault.get("documentKeys").and_then(|k| k.get(&blob_id)).and_then(|k| k.as_str())
+        .and_then(|k| hex::decode(k).ok()).map(Zeroizing::new)
+        .ok_or("Chiave del documento mancante")?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sealed = fs::read(document_blob_path(&dir, &blob_id)?).map_err(|_| "File del documento mancante")?;
+    let sha256 = practice["documents"][di].get("sha256").and_then(|h| h.as_str()).unwrap_or("");
+    let bytes = open_document(&key, &sealed, sha256)?;
+    let pid = practice.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string();
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Practice, Some(&pid), "Documento cifrato letto");
+    Ok(tauri::ipc::Response::new(bytes))
+}
+
+#[tauri::command]
+fn delete_document(state: State<AppState>, blob_id: String, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(&state)?;
+    let mut vault = read_vault_internal(&state)?;
+    let Some((pi, di)) = document_location(&vault, &blob_id) else { return Ok(false) };
+    if is_confidential(&vault["practices"][pi]) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let pid = vault["practices"][pi].get("id").and_then(|i| i.as_str()).unwrap_or("").to_string();
+    if let Some(docs) = vault["practices"][pi]["documents"].as_array_mut() { docs.remove(di); }
+    if let Some(keys) = vault.get_mut("documentKeys").and_then(|k| k.as_object_mut()) { keys.remove(&blob_id); }
+    write_vault_internal(&state, &vault)?;
+    // Key first, file second: a crash in between leaves an undecryptable, unreferenced blob
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let _ = fs::remove_file(document_blob_path(&dir, &blob_id)?);
+    let _ = append_audit_log(&state, AuditCategory::Practice, Some(&pid), "Documento cifrato eliminato");
+    Ok(true)
+}
+
+// ═══════════════════════════════════════════════════════════
+//  TAGS — cross-section labels (`tags: [..]` on each object)
+// ═══════════════════════════════════════════════════════════
+
+const TAGGABLE_SECTIONS: [&str; 3] = ["practices", "contacts", "agenda"];
+
+/// Tags compare trimmed and case-insensitive ("Urgente " == "urgente").
+fn normalize_tag(tag: &str) -> String { tag.trim().to_lowercase() }
+
+fn item_tags(item: &Value) -> impl Iterator<Item = &str> {
+    item.get("tags").and_then(|t| t.as_array()).into_iter().flatten()
+        .filter_map(|t| t.as_str())
+        .filter(|t| !t.trim().is_empty())
+}
+
+/// Items of a section eligible for tag queries — confidential practices never surface here.
+fn taggable_items<'a>(vault: &'a Value, section: &str) -> impl Iterator<Item = &'a Value> {
+    let confidential_filter = section == "practices";
+    vault.get(section).and_then(|v| v.as_array()).into_iter().flatten()
+        .filter(move |item| !(confidential_filter && is_confidential(item)))
+}
+
+fn filter_vault_by_tag(vault: &Value, tag: &str, sections: &[&str]) -> Value {
+    let wanted = normalize_tag(tag);
+    let mut result = serde_json::Map::new();
+    for section in sections {
+        let matches: Vec<Value> = taggable_items(vault, section)
+            .filter(|item| item_tags(item).any(|t| normalize_tag(t) == wanted))
+            .cloned()
+            .collect();
+        result.insert(section.to_string(), json!(matches));
+    }
+    Value::Object(result)
+}
+
+/// [{tag, count}] by usage (desc) then name; `tag` keeps the first spelling encountered.
+fn count_tags(vault: &Value, sections: &[&str]) -> Vec<Value> {
+    let mut counts: std::collections::HashMap<String, (String, usize)> = std::collections::HashMap::new();
+    for section in sections {
+        for item in taggable_items(vault, section) {
+            let mut seen = std::collections::HashSet::new();
+            for t in item_tags(item) {
+                let key = normalize_tag(t);
+                if !seen.insert(key.clone()) { continue; }
+                counts.entry(key).or_insert_with(|| (t.trim().to_string(), 0)).1 += 1;
+            }
+        }
+    }
+    let mut list: Vec<(String, usize)> = counts.into_values().collect();
+    list.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase())));
+    list.into_iter().map(|(tag, count)| json!({"tag": tag, "count": count})).collect()
+}
+
+/// Splits the requested sections into those readable now and those behind `protectedSections`.
+fn accessible_tag_sections(state: &State<AppState>, sections: Option<Vec<String>>, reauth_token: Option<&str>) -> Result<(Vec<&'static str>, Vec<&'static str>), String> {
+    let requested: Vec<&'static str> = match sections {
+        None => TAGGABLE_SECTIONS.to_vec(),
+        Some(list) => list.iter().map(|s| TAGGABLE_SECTIONS.iter().copied().find(|t| *t == s.as_str())
+            .ok_or_else(|| format!("Sezione non supportata: {}", s))).collect::<Result<_, _>>()?,
+    };
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let settings = read_settings(&dir);
+    let mut slot = state.reauth_token.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(requested.into_iter().partition(|section| authorize_section(&settings, section, &mut slot, reauth_token).is_ok()))
+}
+
+/// Objects carrying `tag`, per section. Protected sections without a valid reauth token are
+/// skipped and listed under `locked` rather than failing the whole query.
+#[tauri::command]
+fn filter_by_tag(state: State<AppState>, tag: String, sections: Option<Vec<String>>, reauth_token: Option<String>) -> Result<Value, String> {
+    if normalize_tag(&tag).is_empty() { return Err("Tag vuoto".into()); }
+    let (allowed, locked) = accessible_tag_sections(&state, sections, reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let mut result = filter_vault_by_tag(&vault, &tag, &allowed);
+    result["locked"] = json!(locked);
+    Ok(result)
+}
+
+/// All tags with usage counts, for autocomplete.
+#[tauri::command]
+fn list_tags(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    let (allowed, _) = accessible_tag_sections(&state, None, reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    Ok(json!(count_tags(&vault, &allowed)))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  CONFLICT CHECK (v3.2.0)
+// ═══════════════════════════════════════════════════════════
+
+/// Searches ALL practices (active + archived) for a name match in client,
+/// counterparty, description, court, and roles[].contactName fields.
+/// Returns an array of matching practices with the matched field highlighted.
+/// Confidential practices are ALWAYS searched (conflict checks are mandatory), but without
+/// a valid reauth token their matches carry only the id and a placeholder.
+#[tauri::command]
+fn check_conflict(state: State<AppState>, name: String, reauth_token: Option<String>) -> Result<Value, String> {
+    if name.trim().is_empty() {
+        return Ok(json!({"practiceMatches": [], "contactMatches": []}));
+    }
+    let vault = read_vault_internal(&state)?;
+    let mut result = compute_conflicts(&vault, &name);
+    // Conflict checks are mandatory: a protected contacts section is still searched,
+    // but without reauth only the identity of each match is disclosed.
+    if require_section_access(&state, "contacts", reauth_token.as_deref()).is_err() {
+        mask_contact_matches(&mut result);
+    }
+    if !reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok()) {
+        mask_confidential_practice_matches(&mut result);
+    }
+    Ok(result)
+}
+
+// ─── Practice proof of existence ────────────────────────────────────────────
+// A signed statement that a practice had exactly this content at issuedAt: contentHash is
+// SHA-256 over the practice's canonical JSON (serde_json maps are sorted), so identical
+// content always hashes the same. The issue is also written to the audit log with the
+// hash in the event name; auditAnchor is that entry's chain link, which places the proof
+// among the other audited events. The signature is an HMAC under the vault key over every
+// other field — a password change re-keys the vault, after which only contentHash and the
+// audit entry remain checkable.
+const PRACTICE_PROOF_VERSION: u64 = 1;
+const PRACTICE_PROOF_DOMAIN: &[u8] = b"lexflow-practice-proof:";
+
+fn practice_content_hash(practice: &Value) -> String {
+    hex::encode(<Sha256 as Digest>::digest(serde_json::to_vec(practice).unwrap_or_default()))
+}
+
+fn practice_proof_mac(key: &[u8], unsigned: &Value) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(PRACTICE_PROOF_DOMAIN);
+    mac.update(&serde_json::to_vec(unsigned).unwrap_or_default());
+    mac
+}
+
+fn sign_practice_proof(key: &[u8], practice: &Value, issued_at: &str, audit_anchor: Value) -> Value {
+    let mut proof = json!({
+        "version": PRACTICE_PROOF_VERSION,
+        "practiceId": practice.get("id").cloned().unwrap_or(Value::Null),
+        "contentHash": practice_content_hash(practice),
+        "issuedAt": issued_at,
+        "auditAnchor": audit_anchor,
+    });
+    proof["signature"] = json!(hex::encode(practice_proof_mac(key, &proof).finalize().into_bytes()));
+    proof
+}
+
+fn verify_practice_proof_sig(key: &[u8], proof: &Value) -> bool {
+    let Some(sig) = proof.get("signature").and_then(|s| s.as_str()).and_then(|s| hex::decode(s).ok()) else { return false };
+    let mut unsigned = proof.clone();
+    if let Some(obj) = unsigned.as_object_mut() { obj.remove("signature"); }
+    practice_proof_mac(key, &unsigned).verify_slice(&sig).is_ok()
+}
+
+/// Issue a proof for the practice as currently stored →
+/// {version, practiceId, contentHash, issuedAt, auditAnchor: {index, hash} | null, signature}.
+#[tauri::command]
+fn practice_proof(state: State<AppState>, practice_id: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let idx = practice_index(&vault["practices"], &practice_id).ok_or("Fascicolo non trovato")?;
+    let practice = &vault["practices"][idx];
+    if is_confidential(practice) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let event = format!("Prova di esistenza fascicolo: {}", practice_content_hash(practice));
+    let _ = append_audit_log(&state, AuditCategory::Practice, Some(&practice_id), &event);
+    // Not anchored when the audit level is "off"
+    let vault_key = get_vault_key(&state)?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let entries = read_audit_entries(&state)?;
+    let anchor = entries.iter().rposition(|e| {
+        e.get("event").and_then(|v| v.as_str()) == Some(event.as_str())
+            && e.get("targetId").and_then(|v| v.as_str()) == Some(practice_id.as_str())
+    }).map(|i| json!({"index": i, "hash": audit_chain_link(&audit_log_key(&dir, &vault_key), Some(&entries[i]))}))
+        .unwrap_or(Value::Null);
+    Ok(sign_practice_proof(&vault_key, practice, &chrono::Utc::now().to_rfc3339(), anchor))
+}
+
+/// Check a proof issued by practice_proof → {valid, matchesCurrent}: `valid` when the
+/// signature holds under the current vault key, `matchesCurrent` when the practice still
+/// has the proven content.
+#[tauri::command]
+fn verify_practice_proof(state: State<AppState>, proof: Value, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let valid = verify_practice_proof_sig(&get_vault_key(&state)?, &proof);
+    let vault = read_vault_internal(&state)?;
+    let practice_id = proof.get("practiceId").and_then(|v| v.as_str()).unwrap_or("");
+    let current = practice_index(&vault["practices"], practice_id).map(|i| &vault["practices"][i]);
+    if current.map(is_confidential).unwrap_or(false) {
+        check_reauth_token(&state, reauth_token.as_deref().unwrap_or(""))?;
+    }
+    let matches_current = current.map(practice_content_hash).as_deref()
+        == proof.get("contentHash").and_then(|h| h.as_str());
+    Ok(json!({"valid": valid, "matchesCurrent": matches_current}))
+}
+
+// ─── Vault-wide search ──────────────────────────────────────────────────────
+// Generalizes check_conflict() to every section: one vault read, every string field
+// (nested ones reported with a dotted path). All whitespace-separated terms must occur
+// somewhere in the object, case-insensitively. Confidential practices never surface,
+// as in tag queries.
+const MAX_SEARCH_RESULTS: usize = 200;
+
+fn collect_search_fields(value: &Value, path: &str, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(s) => out.push((path.to_string(), s.to_lowercase())),
+        Value::Number(n) => out.push((path.to_string(), n.to_string())),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_search_fields(item, &format!("{}.{}", path, i), out);
+            }
+        }
+        Value::Object(map) => {
+            for (k, v) in map {
+                // Identifiers and references are not user text
+                if k == "id" || k.ends_with("Id") { continue; }
+                let child = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+                collect_search_fields(v, &child, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Fields matching at least one term, or None unless every term matched somewhere.
+fn match_search_terms(item: &Value, terms: &[String]) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    collect_search_fields(item, "", &mut fields);
+    if !terms.iter().all(|t| fields.iter().any(|(_, v)| v.contains(t.as_str()))) { return None; }
+    Some(fields.into_iter()
+        .filter(|(_, v)| terms.iter().any(|t| v.contains(t.as_str())))
+        .map(|(k, _)| k)
+        .collect())
+}
+
+fn search_label(item: &Value) -> String {
+    ["title", "client", "name", "description", "number"].iter()
+        .find_map(|k| item.get(*k).and_then(|v| v.as_str()).filter(|s| !s.is_empty()))
+        .unwrap_or("")
+        .to_string()
+}
+
+fn search_vault_sections(vault: &Value, query: &str, sections: &[&str]) -> Value {
+    let terms: Vec<String> = query.split_whitespace().map(|t| t.to_lowercase()).collect();
+    let mut results: Vec<Value> = Vec::new();
+    let mut truncated = false;
+    if !terms.is_empty() {
+        'sections: for section in sections {
+            let items = vault.get(*section).and_then(|v| v.as_array()).map(|a| a.as_slice()).unwrap_or(&[]);
+            for item in items {
+                if *section == "practices" && is_confidential(item) { continue; }
+                let Some(matched) = match_search_terms(item, &terms) else { continue };
+                if results.len() == MAX_SEARCH_RESULTS {
+                    truncated = true;
+                    break 'sections;
+                }
+                results.push(json!({
+                    "collection": section,
+                    "id": item.get("id").cloned().unwrap_or(Value::Null),
+                    "label": search_label(item),
+                    "matchedFields": matched,
+                }));
+            }
+        }
+    }
+    json!({"results": results, "truncated": truncated})
+}
+
+/// Full-text search across vault sections. Empty `scope` means all sections; sections
+/// protected by `protectedSections` are searched only with a valid reauth token and are
+/// otherwise listed in `skipped`.
+#[tauri::command]
+fn search_vault(state: State<AppState>, query: String, scope: Vec<String>, reauth_token: Option<String>) -> Result<Value, String> {
+    if let Some(bad) = scope.iter().find(|s| !VAULT_SECTIONS.contains(&s.as_str())) {
+        return Err(format!("Sezione sconosciuta: {}", bad));
+    }
+    let requested: Vec<&str> = if scope.is_empty() {
+        VAULT_SECTIONS.to_vec()
+    } else {
+        VAULT_SECTIONS.iter().copied().filter(|s| scope.iter().any(|x| x == s)).collect()
+    };
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let settings = read_settings(&dir);
+    let (allowed, skipped): (Vec<&str>, Vec<&str>) = {
+        let mut slot = state.reauth_token.lock().unwrap_or_else(|e| e.into_inner());
+        requested.into_iter()
+            .partition(|s| authorize_section(&settings, s, &mut slot, reauth_token.as_deref()).is_ok())
+    };
+    let vault = read_vault_internal(&state)?;
+    let mut result = search_vault_sections(&vault, &query, &allowed);
+    result["skipped"] = json!(skipped);
+    Ok(result)
+}
+
+// ─── Conflict sessions (as-you-type) ────────────────────────────────────────
+// A "new client" form queries on every keystroke. begin_conflict_session() decrypts the vault
+// once and keeps only practices + contacts in memory; conflict_query() runs against that copy.
+// Any vault write bumps AppState.vault_generation and the session answers
+// CONFLICT_SESSION_STALE_ERR — the frontend begins a new one. Cleared on lock.
+const CONFLICT_SESSION_STALE_ERR: &str = "ConflictSessionStale";
+const MAX_CONFLICT_SESSIONS: usize = 4;
+
+struct ConflictIndex {
+    generation: u64,
+    vault: Value,
+    masked: bool,
+    reveal_confidential: bool,
+    created: Instant,
+}
+
+fn build_conflict_index(vault: &Value, generation: u64, masked: bool, reveal_confidential: bool) -> ConflictIndex {
+    ConflictIndex {
+        generation,
+        vault: json!({
+            "practices": vault.get("practices").cloned().unwrap_or(json!([])),
+            "contacts": vault.get("contacts").cloned().unwrap_or(json!([])),
+        }),
+        masked,
+        reveal_confidential,
+        created: Instant::now(),
+    }
+}
+
+fn query_conflict_index(index: &ConflictIndex, current_generation: u64, name: &str) -> Result<Value, String> {
+    if index.generation != current_generation {
+        return Err(CONFLICT_SESSION_STALE_ERR.into());
+    }
+    if name.trim().is_empty() {
+        return Ok(json!({"practiceMatches": [], "contactMatches": []}));
+    }
+    let mut result = compute_conflicts(&index.vault, name);
+    if index.masked { mask_contact_matches(&mut result); }
+    if !index.reveal_confidential { mask_confidential_practice_matches(&mut result); }
+    Ok(result)
+}
+
+#[tauri::command]
+fn begin_conflict_session(state: State<AppState>, reauth_token: Option<String>) -> Result<String, String> {
+    let generation = state.vault_generation.load(std::sync::atomic::Ordering::SeqCst);
+    let vault = read_vault_internal(&state)?;
+    let masked = require_section_access(&state, "contacts", reauth_token.as_deref()).is_err();
+    let reveal_confidential = reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok());
+    let mut id_bytes = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
+    let session_id = hex::encode(id_bytes);
+    let mut sessions = state.conflict_sessions.lock().unwrap_or_else(|e| e.into_inner());
+    // Forms abandoned without end_conflict_session(): drop the oldest
+    while sessions.len() >= MAX_CONFLICT_SESSIONS {
+        let oldest = sessions.iter().min_by_key(|(_, s)| s.created).map(|(k, _)| k.clone());
+        match oldest { Some(k) => { sessions.remove(&k); } None => break }
+    }
+    sessions.insert(session_id.clone(), build_conflict_index(&vault, generation, masked, reveal_confidential));
+    Ok(session_id)
+}
+
+#[tauri::command]
+fn conflict_query(state: State<AppState>, session_id: String, name: String) -> Result<Value, String> {
+    let mut sessions = state.conflict_sessions.lock().unwrap_or_else(|e| e.into_inner());
+    if get_vault_key(&state).is_err() {
+        sessions.clear();
+        return Err("Vault bloccato".into());
+    }
+    let generation = state.vault_generation.load(std::sync::atomic::Ordering::SeqCst);
+    let index = sessions.get(&session_id).ok_or("Sessione di verifica conflitti non trovata")?;
+    let result = query_conflict_index(index, generation, &name);
+    if result.is_err() { sessions.remove(&session_id); }
+    result
+}
+
+#[tauri::command]
+fn end_conflict_session(state: State<AppState>, session_id: String) -> bool {
+    state.conflict_sessions.lock().unwrap_or_else(|e| e.into_inner()).remove(&session_id).is_some()
+}
+
+/// Reduce each contact match to id + name (enough to flag the conflict).
+fn mask_contact_matches(result: &mut Value) {
+    if let Some(matches) = result.get_mut("contactMatches").and_then(|m| m.as_array_mut()) {
+        for m in matches.iter_mut() {
+            let c = &m["contact"];
+            m["contact"] = json!({"id": c.get("id").cloned().unwrap_or(Value::Null), "name": c.get("name").cloned().unwrap_or(Value::Null)});
+            m["masked"] = json!(true);
+        }
+    }
+}
+
+/// Reduce each confidential practice match to its id and a placeholder: the conflict is
+/// flagged, the matter stays hidden until re-authentication.
+fn mask_confidential_practice_matches(result: &mut Value) {
+    if let Some(matches) = result.get_mut("practiceMatches").and_then(|m| m.as_array_mut()) {
+        for m in matches.iter_mut().filter(|m| m["confidential"] == json!(true)) {
+            let id = m["practice"].get("id").cloned().unwrap_or(Value::Null);
+            m["practice"] = json!({"id": id, "client": "Fascicolo riservato"});
+            m["masked"] = json!(true);
+        }
+    }
+}
+
+fn compute_conflicts(vault: &Value, name: &str) -> Value {
+    let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+    let contacts = vault.get("contacts").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    let query = name.trim().to_lowercase();
+    let mut results: Vec<Value> = Vec::new();
+
+    for p in &practices {
+        let mut matched_fields: Vec<String> = Vec::new();
+
+        // Check main text fields
+        for field in &["client", "counterparty", "description", "court", "object"] {
+            if let Some(val) = p.get(field).and_then(|v| v.as_str()) {
+                if val.to_lowercase().contains(&query) {
+                    matched_fields.push(field.to_string());
+                }
+            }
+        }
+
+        // Check roles array (linked contacts)
+        if let Some(roles) = p.get("roles").and_then(|r| r.as_array()) {
+            for role in roles {
+                if let Some(cid) = role.get("contactId").and_then(|c| c.as_str()) {
+                    // Resolve contact name from contacts registry
+                    if let Some(contact) = contacts.iter().find(|c| c.get("id").and_then(|i| i.as_str()) == Some(cid)) {
+                        if let Some(cname) = contact.get("name").and_then(|n| n.as_str()) {
+                            if cname.to_lowercase().contains(&query) {
+                                let role_label = role.get("role").and_then(|r| r.as_str()).unwrap_or("contatto");
+                                matched_fields.push(format!("ruolo:{}", role_label));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !matched_fields.is_empty() {
+            results.push(json!({
+                "practice": p,
+                "matchedFields": matched_fields,
+                "confidential": is_confidential(p),
+            }));
+        }
+    }
+
+    // Also search contacts themselves (find the person even if not linked to a practice yet)
+    let mut contact_matches: Vec<Value> = Vec::new();
+    for c in &contacts {
+        let mut cmatch = false;
+        for field in &["name", "fiscalCode", "vatNumber", "email", "pec", "phone"] {
+            if let Some(val) = c.get(field).and_then(|v| v.as_str()) {
+                if val.to_lowercase().contains(&query) {
+                    cmatch = true;
+                    break;
+                }
+            }
+        }
+        if cmatch {
+            // Find all practices referencing this contact
+            let cid = c.get("id").and_then(|i| i.as_str()).unwrap_or("");
+            let linked: Vec<String> = practices.iter()
+                .filter(|p| contact_refs(p, cid).is_linked())
+                .map(|p| p.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string())
+                .collect();
+            contact_matches.push(json!({
+                "contact": c,
+                "linkedPracticeIds": linked,
+            }));
+        }
+    }
+
+    json!({
+        "practiceMatches": results,
+        "contactMatches": contact_matches,
+    })
+}
+
+/// How a practice references a contact: as client, as counterparty, and in which roles.
+struct ContactRefs {
+    client: bool,
+    counterparty: bool,
+    roles: Vec<String>,
+}
+
+impl ContactRefs {
+    fn is_linked(&self) -> bool {
+        self.client || self.counterparty || !self.roles.is_empty()
+    }
+}
+
+fn contact_refs(practice: &Value, contact_id: &str) -> ContactRefs {
+    let refers = |v: &Value, field: &str| !contact_id.is_empty() && v.get(field).and_then(|i| i.as_str()) == Some(contact_id);
+    ContactRefs {
+        client: refers(practice, "clientId"),
+        counterparty: refers(practice, "counterpartyId"),
+        roles: practice.get("roles").and_then(|r| r.as_array()).into_iter().flatten()
+            .filter(|r| refers(r, "contactId"))
+            .map(|r| r.get("role").and_then(|x| x.as_str()).unwrap_or("contatto").to_string())
+            .collect(),
+    }
+}
+
+// ─── Adversity map ──────────────────────────────────────────────────────────
+// For ethics walls: where a contact is our client, where they are on the other side
+// (counterparty, or a role such as opposing counsel) and which clients that makes them
+// adverse to. Confidential practices are included but reduced to their id unless the caller
+// holds a reauth token, as for load_confidential_practices.
+const ADVERSE_ROLES: [&str; 2] = ["counterparty", "opposing_counsel"];
+
+fn is_adverse_role(role: &str) -> bool {
+    let role = role.trim().to_lowercase();
+    ADVERSE_ROLES.contains(&role.as_str()) || role.contains("controparte") || role.contains("avvers")
+}
+
+fn adversity_entry(p: &Value, role: Option<&str>, reveal_confidential: bool) -> Value {
+    let mut entry = json!({"id": p.get("id").cloned().unwrap_or(Value::Null), "confidential": is_confidential(p)});
+    if reveal_confidential || !is_confidential(p) {
+        entry["client"] = p.get("client").cloned().unwrap_or(Value::Null);
+        entry["counterparty"] = p.get("counterparty").cloned().unwrap_or(Value::Null);
+        entry["status"] = p.get("status").cloned().unwrap_or(Value::Null);
+    }
+    if let Some(r) = role { entry["role"] = json!(r); }
+    entry
+}
+
+fn build_adversity_map(vault: &Value, contact_id: &str, reveal_confidential: bool) -> Result<Value, String> {
+    let contact = vault.get("contacts").and_then(|c| c.as_array()).into_iter().flatten()
+        .find(|c| c.get("id").and_then(|i| i.as_str()) == Some(contact_id))
+        .ok_or("Contatto non trovato")?;
+    let (mut as_client, mut as_counterparty, mut adverse_roles, mut other_roles) = (vec![], vec![], vec![], vec![]);
+    let mut adverse_to: Vec<String> = Vec::new();
+    for p in vault.get("practices").and_then(|p| p.as_array()).into_iter().flatten() {
+        let refs = contact_refs(p, contact_id);
+        if refs.client { as_client.push(adversity_entry(p, None, reveal_confidential)); }
+        if refs.counterparty { as_counterparty.push(adversity_entry(p, None, reveal_confidential)); }
+        let mut adverse = refs.counterparty;
+        for role in &refs.roles {
+            if is_adverse_role(role) {
+                adverse = true;
+                adverse_roles.push(adversity_entry(p, Some(role), reveal_confidential));
+            } else {
+                other_roles.push(adversity_entry(p, Some(role), reveal_confidential));
+            }
+        }
+        if adverse && (reveal_confidential || !is_confidential(p)) {
+            if let Some(client) = p.get("client").and_then(|c| c.as_str()).filter(|c| !c.trim().is_empty()) {
+                if !adverse_to.iter().any(|a| a == client) { adverse_to.push(client.to_string()); }
+            }
+        }
+    }
+    Ok(json!({
+        "contact": {"id": contact_id, "name": contact.get("name").cloned().unwrap_or(Value::Null)},
+        "asClient": as_client,
+        "asCounterparty": as_counterparty,
+        "adverseRoles": adverse_roles,
+        "otherRoles": other_roles,
+        "adverseToClients": adverse_to,
+    }))
+}
+
+#[tauri::command]
+fn contact_adversity_map(state: State<AppState>, contact_id: String, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "contacts", reauth_token.as_deref())?;
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let reveal = reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok());
+    let vault = read_vault_internal(&state)?;
+    let map = build_adversity_map(&vault, &contact_id, reveal)?;
+    let confidential_linked = vault.get("practices").and_then(|p| p.as_array()).into_iter().flatten()
+        .any(|p| is_confidential(p) && contact_refs(p, &contact_id).is_linked());
+    if reveal && confidential_linked {
+        let _ = append_audit_log(&state, AuditCategory::Security, None, "Accesso pratiche riservate");
+    }
+    Ok(map)
+}
+
+// ─── Duplicate practices ────────────────────────────────────────────────────
+// Two matters for the same clientId about the same thing fragment the file. Practices of
+// one client are linked when their object or description is similar (trigram Dice
+// coefficient ≥ DUPLICATE_SIMILARITY); linked practices form a cluster the user can merge.
+// Closed practices are skipped unless asked for; confidential ones never appear.
+const DUPLICATE_SIMILARITY: f64 = 0.7;
+
+fn text_trigrams(text: &str) -> std::collections::HashSet<String> {
+    let cleaned: String = text.to_lowercase().chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    let words: Vec<&str> = cleaned.split_whitespace().collect();
+    if words.is_empty() { return Default::default(); }
+    let chars: Vec<char> = format!("  {} ", words.join(" ")).chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// Fuzzy similarity in [0, 1]: 1 for texts equal up to case, punctuation and spacing.
+fn text_similarity(a: &str, b: &str) -> f64 {
+    let (ta, tb) = (text_trigrams(a), text_trigrams(b));
+    if ta.is_empty() || tb.is_empty() { return 0.0; }
+    2.0 * ta.intersection(&tb).count() as f64 / (ta.len() + tb.len()) as f64
+}
+
+fn practice_similarity(a: &Value, b: &Value) -> f64 {
+    ["object", "description"].iter()
+        .filter_map(|f| Some(text_similarity(a.get(*f)?.as_str()?, b.get(*f)?.as_str()?)))
+        .fold(0.0, f64::max)
+}
+
+fn find_duplicate_clusters(practices: &[Value], include_closed: bool) -> Vec<Value> {
+    let candidates: Vec<&Value> = practices.iter()
+        .filter(|p| !is_confidential(p))
+        .filter(|p| include_closed || p.get("status").and_then(|s| s.as_str()) != Some("closed"))
+        .filter(|p| p.get("clientId").and_then(|c| c.as_str()).map(|c| !c.is_empty()).unwrap_or(false))
+        .collect();
+    // Union-find over the candidates, linking similar pairs of the same client
+    let mut parent: Vec<usize> = (0..candidates.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i { parent[i] = parent[parent[i]]; i = parent[i]; }
+        i
+    }
+    let mut best: std::collections::HashMap<usize, f64> = std::collections::HashMap::new();
+    for i in 0..candidates.len() {
+        for j in (i + 1)..candidates.len() {
+            if candidates[i]["clientId"] != candidates[j]["clientId"] { continue; }
+            let score = practice_similarity(candidates[i], candidates[j]);
+            if score < DUPLICATE_SIMILARITY { continue; }
+            let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+            let merged = best.remove(&ri).unwrap_or(0.0).max(best.remove(&rj).unwrap_or(0.0)).max(score);
+            parent[rj] = ri;
+            best.insert(ri, merged);
+        }
+    }
+    let mut clusters: Vec<Value> = best.into_iter().map(|(r, score)| {
+        let members: Vec<Value> = (0..candidates.len())
+            .filter(|&i| root(&mut parent, i) == r)
+            .map(|i| {
+                let p = candidates[i];
+                json!({
+                    "id": p.get("id").cloned().unwrap_or(Value::Null),
+                    "object": p.get("object").cloned().unwrap_or(Value::Null),
+                    "description": p.get("description").cloned().unwrap_or(Value::Null),
+                    "status": p.get("status").cloned().unwrap_or(Value::Null),
+                })
+            })
+            .collect();
+        json!({
+            "clientId": candidates[r]["clientId"],
+            "client": candidates[r].get("client").cloned().unwrap_or(Value::Null),
+            "score": (score * 100.0).round() / 100.0,
+            "practices": members,
+        })
+    }).collect();
+    clusters.sort_by(|a, b| b["score"].as_f64().unwrap_or(0.0).total_cmp(&a["score"].as_f64().unwrap_or(0.0)));
+    clusters
+}
+
+#[tauri::command]
+fn find_duplicate_practices(state: State<AppState>, include_closed: Option<bool>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "practices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+    Ok(json!({"clusters": find_duplicate_clusters(&practices, include_closed.unwrap_or(false))}))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  TIME TRACKING (v3.3.0)
+// ═══════════════════════════════════════════════════════════
+
+#[tauri::command]
+fn load_time_logs(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "timeLogs", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    Ok(vault.get("timeLogs").cloned().unwrap_or(json!([])))
+}
+
+#[tauri::command]
+fn save_time_logs(state: State<AppState>, mut logs: Value, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "timeLogs", reauth_token.as_deref())?;
+    let (normalized, warnings) = canonicalize_time_logs(&mut logs);
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).discard("timeLogs");
+    let mut vault = read_vault_internal(&state)?;
+    vault["timeLogs"] = logs;
+    write_vault_internal(&state, &vault)?;
+    append_data_audit_log(&state, AuditCategory::TimeLog, "Ore lavorate salvate", &vault["timeLogs"]);
+    Ok(json!({"success": true, "normalized": normalized, "warnings": warnings}))
+}
+
+// ─── Durations ──────────────────────────────────────────────────────────────
+// durationMin (integer minutes) is the only stored form. The time page may send decimal
+// hours ("1.5", "1,5", "2h"), HH:MM ("1:30") or minutes ("90m", or a JSON number, which
+// has always meant minutes). A bare string like "90" could be either and is refused.
+const MAX_LOG_MINUTES: i64 = 24 * 60;
+
+fn parse_duration_minutes(raw: &Value) -> Result<i64, String> {
+    let minutes = match raw {
+        Value::Number(n) => n.as_f64().map(|m| m.round() as i64).ok_or("numero non valido")?,
+        Value::String(text) => {
+            let t = text.trim().to_lowercase().replace(',', ".");
+            if let Some((h, m)) = t.split_once(':') {
+                let (h, m) = (h.trim().parse::<i64>(), m.trim().parse::<i64>());
+                match (h, m) {
+                    (Ok(h), Ok(m)) if h >= 0 && (0..60).contains(&m) => h * 60 + m,
+                    _ => return Err(format!("\"{}\" non è un orario HH:MM valido", text)),
+                }
+            } else if let Some(m) = t.strip_suffix("min").or_else(|| t.strip_suffix('m')) {
+                m.trim().parse::<u32>().map_err(|_| format!("\"{}\" non è un numero di minuti", text))? as i64
+            } else {
+                let hours = t.strip_suffix('h').unwrap_or(&t).trim();
+                if !t.ends_with('h') && !hours.contains('.') {
+                    return Err(format!("\"{}\" è ambiguo: indicare ore (1.5, 2h), HH:MM o minuti (90m)", text));
+                }
+                let h: f64 = hours.parse().map_err(|_| format!("\"{}\" non è una durata valida", text))?;
+                if !h.is_finite() || h < 0.0 {
+                    return Err(format!("\"{}\" non è una durata valida", text));
+                }
+                (h * 60.0).round() as i64
+            }
+        }
+        _ => return Err("durata non valida".into()),
+    };
+    if minutes < 0 {
+        return Err("durata negativa".into());
+    }
+    if minutes > MAX_LOG_MINUTES {
+        return Err("durata superiore a 24 ore".into());
+    }
+    Ok(minutes)
+}
+
+/// "hhmm" → 1:30, "decimal" → 1,50, anything else → 1 h 30 min.
+fn format_duration(minutes: i64, style: &str) -> String {
+    let sign = if minutes < 0 { "-" } else { "" };
+    let m = minutes.abs();
+    match style {
+        "hhmm" => format!("{}{}:{:02}", sign, m / 60, m % 60),
+        "decimal" => format!("{}{:.2}", sign, m as f64 / 60.0).replace('.', ","),
+        _ if m < 60 => format!("{}{} min", sign, m),
+        _ if m % 60 == 0 => format!("{}{} h", sign, m / 60),
+        _ => format!("{}{} h {} min", sign, m / 60, m % 60),
+    }
+}
+
+/// Rewrites each log's `durationMin` (or legacy `duration` input) as integer minutes.
+/// An unparseable value is left untouched and reported, never dropped.
+/// Returns (rewritten count, [{logId, messages}]).
+fn canonicalize_time_logs(logs: &mut Value) -> (usize, Vec<Value>) {
+    let mut normalized = 0;
+    let mut warnings = Vec::new();
+    let Some(list) = logs.as_array_mut() else { return (0, warnings) };
+    for log in list.iter_mut() {
+        let Some(obj) = log.as_object_mut() else { continue };
+        let key = if obj.contains_key("durationMin") { "durationMin" } else { "duration" };
+        let Some(raw) = obj.get(key).filter(|v| !v.is_null()) else { continue };
+        match parse_duration_minutes(raw) {
+            Ok(minutes) => {
+                if key != "durationMin" || raw.as_i64() != Some(minutes) {
+                    normalized += 1;
+                }
+                obj.remove("duration");
+                obj.insert("durationMin".into(), json!(minutes));
+            }
+            Err(e) => {
+                let id = obj.get("id").map(|v| match v.as_str() { Some(s) => s.to_string(), None => v.to_string() })
+                    .unwrap_or_default();
+                warnings.push(json!({"logId": id, "messages": [format!("Durata: {}", e)]}));
+            }
+        }
+    }
+    (normalized, warnings)
+}
+
+/// Parse any accepted duration input and render it; lets the UI preview what will be stored.
+#[tauri::command]
+fn convert_duration(value: Value, style: Option<String>) -> Result<Value, String> {
+    let minutes = parse_duration_minutes(&value)?;
+    Ok(json!({"minutes": minutes, "formatted": format_duration(minutes, style.as_deref().unwrap_or("hhmm"))}))
+}
+
+/// Find pairs of time logs whose [startedAt, endedAt) intervals overlap on the same
+/// (local) day. Logs with only a duration are skipped and counted. Touching intervals
+/// (one ends exactly when the next starts) are not an overlap.
+fn compute_timelog_overlaps(logs: &[Value], date: Option<&str>) -> Value {
+    type Interval<'a> = (chrono::DateTime<chrono::Local>, chrono::DateTime<chrono::Local>, &'a Value);
+    let parse = |v: Option<&Value>| v.and_then(|s| s.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|d| d.with_timezone(&chrono::Local));
+
+    let mut by_day: std::collections::BTreeMap<String, Vec<Interval>> = std::collections::BTreeMap::new();
+    let mut skipped = 0usize;
+    for log in logs {
+        let (start, end) = match (parse(log.get("startedAt")), parse(log.get("endedAt"))) {
+            (Some(s), Some(e)) if e > s => (s, e),
+            _ => { skipped += 1; continue; }
+        };
+        let day = start.format("%Y-%m-%d").to_string();
+        if date.map(|d| d != day).unwrap_or(false) { continue; }
+        by_day.entry(day).or_default().push((start, end, log));
+    }
+
+    let summary = |l: &Value| json!({
+        "id": l.get("id").cloned().unwrap_or(Value::Null),
+        "practiceId": l.get("practiceId").cloned().unwrap_or(Value::Null),
+        "description": l.get("description").cloned().unwrap_or(Value::Null),
+        "startedAt": l.get("startedAt").cloned().unwrap_or(Value::Null),
+        "endedAt": l.get("endedAt").cloned().unwrap_or(Value::Null),
+    });
+    let mut overlaps: Vec<Value> = Vec::new();
+    for (day, mut intervals) in by_day {
+        intervals.sort_by_key(|(s, _, _)| *s);
+        for i in 0..intervals.len() {
+            for j in (i + 1)..intervals.len() {
+                let (s1, e1, a) = &intervals[i];
+                let (s2, e2, b) = &intervals[j];
+                if s2 >= e1 { break; } // sorted by start: no later log can overlap `a`
+                let overlap = (*e1.min(e2) - *s1.max(s2)).num_minutes();
+                overlaps.push(json!({
+                    "date": day,
+                    "a": summary(a),
+                    "b": summary(b),
+                    "overlapMinutes": overlap,
+                }));
+            }
+        }
+    }
+    json!({"overlaps": overlaps, "skippedWithoutInterval": skipped})
+}
+
+#[tauri::command]
+fn find_timelog_overlaps(state: State<AppState>, date: Option<String>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "timeLogs", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    let logs = vault.get("timeLogs").and_then(|l| l.as_array()).cloned().unwrap_or_default();
+    Ok(compute_timelog_overlaps(&logs, date.as_deref()))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  INVOICES / BILLING (v3.4.0)
+// ═══════════════════════════════════════════════════════════
+
+#[tauri::command]
+fn load_invoices(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "invoices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    Ok(vault.get("invoices").cloned().unwrap_or(json!([])))
+}
+
+#[tauri::command]
+fn save_invoices(state: State<AppState>, mut invoices: Value, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "invoices", reauth_token.as_deref())?;
+    let (repaired, warnings) = canonicalize_invoices(&mut invoices);
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).discard("invoices");
+    let mut vault = read_vault_internal(&state)?;
+    vault["invoices"] = invoices;
+    write_vault_internal(&state, &vault)?;
+    append_data_audit_log(&state, AuditCategory::Invoice, "Fatture salvate", &vault["invoices"]);
+    Ok(json!({"success": true, "repaired": repaired, "warnings": warnings}))
+}
+
+/// Validates line items (`hours` or `quantity` × `rate` = `total`) and repairs what can be
+/// recomputed: line totals off by more than a cent, and stored subtotal/CPA/IVA/grand total
+/// once every line is valid. Anything not safely fixable (negative quantity, missing rate)
+/// is left as-is and reported. Returns (repaired line/total count, [{invoiceId, messages}]).
+fn canonicalize_invoices(invoices: &mut Value) -> (usize, Vec<Value>) {
+    let mut repaired = 0;
+    let mut warnings = Vec::new();
+    let list = match invoices.as_array_mut() { Some(l) => l, None => return (0, warnings) };
+    for inv in list.iter_mut() {
+        let id = inv.get("id").map(|v| match v.as_str() { Some(s) => s.to_string(), None => v.to_string() })
+            .unwrap_or_default();
+        let mut messages: Vec<String> = Vec::new();
+        let mut all_valid = true;
+        if let Some(items) = inv.get_mut("items").and_then(|i| i.as_array_mut()) {
+            for (n, item) in items.iter_mut().enumerate() {
+                let qty = item.get("hours").or_else(|| item.get("quantity")).and_then(|v| v.as_f64());
+                let rate = item.get("rate").and_then(|v| v.as_f64());
+                match (qty, rate) {
+                    (Some(q), _) if q < 0.0 => {
+                        messages.push(format!("Voce {}: quantità negativa", n + 1));
+                        all_valid = false;
+                    }
+                    (_, None) => {
+                        messages.push(format!("Voce {}: tariffa mancante", n + 1));
+                        all_valid = false;
+                    }
+                    (None, Some(_)) => {
+                        messages.push(format!("Voce {}: quantità mancante", n + 1));
+                        all_valid = false;
+                    }
+                    (Some(q), Some(r)) => {
+                        let expected = round_cents(q * r);
+                        let declared = item.get("total").and_then(|v| v.as_f64());
+                        if declared.map(|d| (d - expected).abs() > 0.01).unwrap_or(true) {
+                            item["total"] = json!(expected);
+                            repaired += 1;
+                        }
+                    }
+                }
+            }
+        }
+        if all_valid {
+            let subtotal = round_cents(inv.get("items").and_then(|i| i.as_array())
+                .map(|items| items.iter().filter_map(|i| i.get("total").and_then(|t| t.as_f64())).sum())
+                .unwrap_or(0.0));
+            let cpa = round_cents(subtotal * INVOICE_CPA_RATE);
+            let iva = round_cents((subtotal + cpa) * INVOICE_IVA_RATE);
+            let grand = round_cents(subtotal + cpa + iva);
+            let mut changed = false;
+            for (key, expected) in [("subtotal", subtotal), ("cpa", cpa), ("iva", iva), ("grandTotal", grand)] {
+                if let Some(declared) = inv.get(key).and_then(|v| v.as_f64()) {
+                    if (declared - expected).abs() > 0.01 {
+                        inv[key] = json!(expected);
+                        changed = true;
+                    }
+                }
+            }
+            if changed { repaired += 1; }
+        } else {
+            messages.push("Totali non ricalcolati: correggere le voci indicate".to_string());
+        }
+        if !messages.is_empty() {
+            warnings.push(json!({"invoiceId": id, "messages": messages}));
+        }
+    }
+    (repaired, warnings)
+}
+
+// Same rates as the billing page (CPA 4% Cassa Previdenza Avvocati + IVA 22%) —
+// used only when an invoice was saved without its computed totals.
+const INVOICE_CPA_RATE: f64 = 0.04;
+const INVOICE_IVA_RATE: f64 = 0.22;
+
+fn round_cents(v: f64) -> f64 { (v * 100.0).round() / 100.0 }
+
+/// Returns (taxable, vat, total) for an invoice. Taxable = onorario + CPA (IVA base).
+fn invoice_amounts(inv: &Value) -> (f64, f64, f64) {
+    let num = |k: &str| inv.get(k).and_then(|v| v.as_f64());
+    let subtotal = num("subtotal").unwrap_or_else(|| {
+        inv.get("items").and_then(|i| i.as_array())
+            .map(|items| items.iter().filter_map(|i| i.get("total").and_then(|t| t.as_f64())).sum())
+            .unwrap_or(0.0)
+    });
+    let cpa = num("cpa").unwrap_or(subtotal * INVOICE_CPA_RATE);
+    let taxable = subtotal + cpa;
+    let vat = num("iva").unwrap_or(taxable * INVOICE_IVA_RATE);
+    let total = num("grandTotal").unwrap_or(taxable + vat);
+    (taxable, vat, total)
+}
+
+fn in_year(date: &str, year: Option<i32>) -> bool {
+    match year {
+        Some(y) => date.get(..4).and_then(|d| d.parse::<i32>().ok()) == Some(y),
+        None => true,
+    }
+}
+
+/// Calendar date in `tz` of an RFC 3339 timestamp, or a plain YYYY-MM-DD string as is.
+fn local_date_of<Tz: chrono::TimeZone>(ts: &str, tz: &Tz) -> Option<chrono::NaiveDate> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(ts) {
+        return Some(dt.with_timezone(tz).date_naive());
+    }
+    chrono::NaiveDate::parse_from_str(ts.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Oversight report for [from, to] (both inclusive): audit events by type plus
+/// practices opened/closed, invoices issued and hours logged in the range. Timestamps are
+/// bucketed by their calendar date in `tz` (the local zone outside tests). Confidential
+/// practices are counted but listed only with `reveal_confidential`; otherwise
+/// `hiddenConfidential` says how many were left out of the lists.
+fn build_activity_report<Tz: chrono::TimeZone>(audit: &[Value], vault: &Value, from: chrono::NaiveDate, to: chrono::NaiveDate, tz: &Tz, reveal_confidential: bool) -> Value {
+    let in_range = |v: Option<&Value>| v.and_then(|t| t.as_str()).and_then(|t| local_date_of(t, tz))
+        .map(|d| d >= from && d <= to).unwrap_or(false);
+    let list = |k: &str| vault.get(k).and_then(|v| v.as_array()).cloned().unwrap_or_default();
+
+    // Audit: group by event label, dropping the "(N elementi)" suffix of data events
+    let mut by_event: std::collections::BTreeMap<String, u64> = std::collections::BTreeMap::new();
+    for e in audit.iter().filter(|e| in_range(e.get("time"))) {
+        let name = e.get("event").and_then(|n| n.as_str()).unwrap_or("");
+        let label = name.split(" (").next().unwrap_or(name).to_string();
+        *by_event.entry(label).or_insert(0) += 1;
+    }
+    let count_prefix = |prefix: &str| by_event.iter().filter(|(k, _)| k.starts_with(prefix)).map(|(_, v)| v).sum::<u64>();
+
+    let practices = list("practices");
+    let summary = |p: &Value, date_key: &str| json!({
+        "id": p.get("id").cloned().unwrap_or(Value::Null),
+        "client": p.get("client").cloned().unwrap_or(Value::Null),
+        "date": p.get(date_key).cloned().unwrap_or(Value::Null),
+    });
+    let opened_all: Vec<&Value> = practices.iter().filter(|p| in_range(p.get("createdAt"))).collect();
+    let closed_all: Vec<&Value> = practices.iter()
+        .filter(|p| p.get("status").and_then(|s| s.as_str()) == Some("closed") && in_range(p.get("closedAt")))
+        .collect();
+    let listed = |p: &Value| reveal_confidential || !is_confidential(p);
+    let opened: Vec<Value> = opened_all.iter().filter(|p| listed(p)).map(|p| summary(p, "createdAt")).collect();
+    let closed: Vec<Value> = closed_all.iter().filter(|p| listed(p)).map(|p| summary(p, "closedAt")).collect();
+    let hidden_confidential = opened_all.len() + closed_all.len() - opened.len() - closed.len();
+
+    let issued: Vec<Value> = list("invoices").into_iter()
+        .filter(|i| matches!(i.get("status").and_then(|s| s.as_str()), Some("sent" | "issued" | "paid")))
+        .filter(|i| in_range(i.get("date")))
+        .collect();
+    let issued_total = round_cents(issued.iter().map(|i| invoice_amounts(i).2).sum());
+
+    let (mut minutes, mut billable_minutes) = (0i64, 0i64);
+    for log in list("timeLogs").iter().filter(|l| in_range(l.get("startedAt"))) {
+        let m = log.get("durationMin").and_then(|d| d.as_i64()).unwrap_or(0);
+        minutes += m;
+        if log.get("billable").and_then(|b| b.as_bool()).unwrap_or(false) { billable_minutes += m; }
+    }
+
+    json!({
+        "from": from.format("%Y-%m-%d").to_string(),
+        "to": to.format("%Y-%m-%d").to_string(),
+        "audit": {
+            "logins": count_prefix("Sblocco Vault"),
+            "passwordChanges": count_prefix("Password cambiata"),
+            "events": by_event,
+        },
+        "practices": {
+            "opened": opened_all.len(),
+            "closed": closed_all.len(),
+            "openedList": opened,
+            "closedList": closed,
+            "hiddenConfidential": hidden_confidential,
+        },
+        "invoices": {"issued": issued.len(), "issuedTotal": issued_total},
+        "hours": {
+            "total": round_cents(minutes as f64 / 60.0),
+            "billable": round_cents(billable_minutes as f64 / 60.0),
+            "totalFormatted": format_duration(minutes, "hhmm"),
+            "billableFormatted": format_duration(billable_minutes, "hhmm"),
+        },
+    })
+}
+
+/// Read-only firm activity report for a date range (YYYY-MM-DD, inclusive).
+#[tauri::command]
+fn firm_activity_report(state: State<AppState>, from: String, to: String, reauth_token: Option<String>) -> Result<Value, String> {
+    for section in ["practices", "invoices", "timeLogs"] {
+        require_section_access(&state, section, reauth_token.as_deref())?;
+    }
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| "Data non valida (atteso AAAA-MM-GG)".to_string());
+    let (from, to) = (parse(&from)?, parse(&to)?);
+    if from > to {
+        return Err("La data di inizio è successiva alla data di fine".into());
+    }
+    let vault = read_vault_internal(&state)?;
+    let audit = get_audit_log(state.clone())?.as_array().cloned().unwrap_or_default();
+    let reveal_confidential = reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok());
+    Ok(build_activity_report(&audit, &vault, from, to, &chrono::Local, reveal_confidential))
+}
+
+/// Aggregate billing for one client across ALL their practices.
+/// Invoices match by their own `clientId` or by a `practiceId` whose practice has that client.
+/// Unbilled = billable time logs on those practices not yet linked to an invoice.
+fn compute_client_billing(vault: &Value, contact_id: &str, year: Option<i32>) -> Value {
+    let practice_ids: Vec<&str> = vault.get("practices").and_then(|p| p.as_array())
+        .map(|ps| ps.iter()
+            .filter(|p| p.get("clientId").and_then(|c| c.as_str()) == Some(contact_id))
+            .filter_map(|p| p.get("id").and_then(|i| i.as_str()))
+            .collect())
+        .unwrap_or_default();
+
+    let mut buckets = serde_json::Map::new();
+    for status in &["draft", "issued", "paid"] {
+        buckets.insert(status.to_string(), json!({"count": 0, "taxable": 0.0, "vat": 0.0, "total": 0.0}));
+    }
+    let invoices = vault.get("invoices").and_then(|i| i.as_array()).cloned().unwrap_or_default();
+    for inv in &invoices {
+        let by_client = inv.get("clientId").and_then(|c| c.as_str()) == Some(contact_id);
+        let by_practice = inv.get("practiceId").and_then(|p| p.as_str())
+            .map(|pid| practice_ids.contains(&pid)).unwrap_or(false);
+        if !by_client && !by_practice { continue; }
+        if !in_year(inv.get("date").and_then(|d| d.as_str()).unwrap_or(""), year) { continue; }
+        // The billing page calls an issued invoice "sent"
+        let status = match inv.get("status").and_then(|s| s.as_str()).unwrap_or("draft") {
+            "paid" => "paid",
+            "sent" | "issued" => "issued",
+            _ => "draft",
+        };
+        let (taxable, vat, total) = invoice_amounts(inv);
+        let b = &mut buckets[status];
+        b["count"] = json!(b["count"].as_u64().unwrap_or(0) + 1);
+        b["taxable"] = json!(round_cents(b["taxable"].as_f64().unwrap_or(0.0) + taxable));
+        b["vat"] = json!(round_cents(b["vat"].as_f64().unwrap_or(0.0) + vat));
+        b["total"] = json!(round_cents(b["total"].as_f64().unwrap_or(0.0) + total));
+    }
+
+    let mut unbilled_minutes: f64 = 0.0;
+    let mut unbilled_value: f64 = 0.0;
+    let logs = vault.get("timeLogs").and_then(|l| l.as_array()).cloned().unwrap_or_default();
+    for log in &logs {
+        let on_client = log.get("practiceId").and_then(|p| p.as_str())
+            .map(|pid| practice_ids.contains(&pid)).unwrap_or(false);
+        let billable = log.get("billable").and_then(|b| b.as_bool()).unwrap_or(false);
+        let billed = log.get("invoiceId").map(|i| !i.is_null()).unwrap_or(false)
+            || log.get("billed").and_then(|b| b.as_bool()).unwrap_or(false);
+        if !on_client || !billable || billed { continue; }
+        if !in_year(log.get("startedAt").and_then(|d| d.as_str()).unwrap_or(""), year) { continue; }
+        let minutes = log.get("durationMin").and_then(|m| m.as_f64()).unwrap_or(0.0);
+        let rate = log.get("hourlyRate").and_then(|r| r.as_f64()).unwrap_or(0.0);
+        unbilled_minutes += minutes;
+        unbilled_value += minutes / 60.0 * rate;
+    }
+
+    let billed_total = ["issued", "paid"].iter()
+        .map(|s| buckets[*s]["total"].as_f64().unwrap_or(0.0)).sum::<f64>();
+    json!({
+        "contactId": contact_id,
+        "year": year,
+        "practiceCount": practice_ids.len(),
+        "byStatus": buckets,
+        "billedTotal": round_cents(billed_total),
+        "collectedTotal": buckets["paid"]["total"],
+        "unbilled": {
+            "minutes": unbilled_minutes,
+            "formatted": format_duration(unbilled_minutes.round() as i64, "hhmm"),
+            "value": round_cents(unbilled_value),
+        },
+    })
+}
+
+const AGING_BUCKETS: &[&str] = &["0-30", "31-60", "61-90", "90+"];
+
+/// Due date of an invoice: explicit `dueDate`, else issue date + `paymentTermsDays`,
+/// else the issue date itself (no terms = due on issue).
+fn invoice_due_date(inv: &Value) -> Option<chrono::NaiveDate> {
+    let parse = |k: &str| inv.get(k).and_then(|d| d.as_str())
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d.get(..10).unwrap_or(d), "%Y-%m-%d").ok());
+    if let Some(due) = parse("dueDate") { return Some(due); }
+    let issued = parse("issueDate").or_else(|| parse("date"))?;
+    let terms = inv.get("paymentTermsDays").and_then(|t| t.as_i64()).unwrap_or(0);
+    Some(issued + chrono::Duration::days(terms))
+}
+
+/// Accounts-receivable aging of issued, unpaid invoices as of `as_of`.
+fn compute_invoice_aging(invoices: &[Value], as_of: chrono::NaiveDate) -> Value {
+    let mut buckets = serde_json::Map::new();
+    for b in AGING_BUCKETS.iter().chain(["notDue"].iter()) {
+        buckets.insert(b.to_string(), json!({"total": 0.0, "invoices": []}));
+    }
+    let mut outstanding_total = 0.0;
+    for inv in invoices {
+        // The billing page calls an issued invoice "sent"
+        let status = inv.get("status").and_then(|s| s.as_str()).unwrap_or("draft");
+        if !matches!(status, "sent" | "issued" | "unpaid") { continue; }
+        let (_, _, total) = invoice_amounts(inv);
+        let paid = inv.get("paidAmount").and_then(|p| p.as_f64()).unwrap_or(0.0);
+        let outstanding = round_cents(total - paid);
+        if outstanding <= 0.0 { continue; }
+        let due = match invoice_due_date(inv) { Some(d) => d, None => continue };
+        let days = (as_of - due).num_days();
+        let bucket = match days {
+            d if d < 0 => "notDue",
+            0..=30 => "0-30",
+            31..=60 => "31-60",
+            61..=90 => "61-90",
+            _ => "90+",
+        };
+        let b = &mut buckets[bucket];
+        b["total"] = json!(round_cents(b["total"].as_f64().unwrap_or(0.0) + outstanding));
+        if let Some(list) = b["invoices"].as_array_mut() {
+            list.push(json!({
+                "id": inv.get("id").cloned().unwrap_or(Value::Null),
+                "number": inv.get("number").cloned().unwrap_or(Value::Null),
+                "clientName": inv.get("clientName").cloned().unwrap_or(Value::Null),
+                "dueDate": due.format("%Y-%m-%d").to_string(),
+                "daysOverdue": days.max(0),
+                "outstanding": outstanding,
+            }));
+        }
+        outstanding_total += outstanding;
+    }
+    json!({
+        "asOf": as_of.format("%Y-%m-%d").to_string(),
+        "buckets": buckets,
+        "outstandingTotal": round_cents(outstanding_total),
+    })
+}
+
+#[tauri::command]
+fn invoice_aging(state: State<AppState>, as_of: Option<String>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_feature(&state, FEATURE_BILLING)?;
+    require_section_access(&state, "invoices", reauth_token.as_deref())?;
+    let as_of = match as_of {
+        Some(d) => chrono::NaiveDate::parse_from_str(&d, "%Y-%m-%d").map_err(|_| "Data non valida (atteso AAAA-MM-GG)")?,
+        None => chrono::Local::now().date_naive(),
+    };
+    let vault = read_vault_internal(&state)?;
+    let invoices = vault.get("invoices").and_then(|i| i.as_array()).cloned().unwrap_or_default();
+    Ok(compute_invoice_aging(&invoices, as_of))
+}
+
+#[tauri::command]
+fn client_billing_summary(state: State<AppState>, contact_id: String, year: Option<i32>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_feature(&state, FEATURE_BILLING)?;
+    require_section_access(&state, "invoices", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    Ok(compute_client_billing(&vault, &contact_id, year))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  CONTACTS REGISTRY (v3.5.0)
+// ═══════════════════════════════════════════════════════════
+
+#[tauri::command]
+fn load_contacts(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "contacts", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    Ok(vault.get("contacts").cloned().unwrap_or(json!([])))
+}
+
+#[tauri::command]
+fn save_contacts(state: State<AppState>, contacts: Value, reauth_token: Option<String>) -> Result<bool, String> {
+    require_section_access(&state, "contacts", reauth_token.as_deref())?;
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).discard("contacts");
+    let mut vault = read_vault_internal(&state)?;
+    vault["contacts"] = contacts;
+    write_vault_internal(&state, &vault)?;
+    append_data_audit_log(&state, AuditCategory::Contact, "Contatti salvati", &vault["contacts"]);
+    Ok(true)
+}
+
+// ─── Contact sharing (vCard) ────────────────────────────────────────────────
+// vCard 3.0 with Italian identifiers as extended fields. Internal `notes` are never
+// exported — they are the firm's own annotations, not part of the referral.
+const VCARD_EXPORT_PREFIX: &str = "LXVC1.";
+
+fn escape_vcard_text(v: &str) -> String {
+    v.replace('\\', "\\\\").replace('\n', "\\n").replace(',', "\\,").replace(';', "\\;")
+}
+
+fn contact_to_vcard(contact: &Value) -> String {
+    let field = |k: &str| contact.get(k).and_then(|v| v.as_str()).map(str::trim).filter(|v| !v.is_empty());
+    let name = escape_vcard_text(field("name").unwrap_or(""));
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:3.0".to_string(),
+        format!("FN:{}", name),
+        format!("N:{};;;;", name),
+    ];
+    let optional = [
+        ("email", "EMAIL;TYPE=INTERNET"),
+        ("phone", "TEL;TYPE=VOICE"),
+        ("pec", "X-PEC"),
+        ("fiscalCode", "X-CODICE-FISCALE"),
+        ("vatNumber", "X-PARTITA-IVA"),
+        ("barAssociation", "X-ORDINE-AVVOCATI"),
+    ];
+    for (key, prop) in optional {
+        if let Some(v) = field(key) { lines.push(format!("{}:{}", prop, escape_vcard_text(v))); }
+    }
+    if let Some(addr) = field("address") {
+        lines.push(format!("ADR;TYPE=WORK:;;{};;;;", escape_vcard_text(addr)));
+    }
+    lines.push("END:VCARD".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+/// Export one contact as a vCard. With a password the card is sealed for transit;
+/// without one the caller must pass `confirm_plaintext` because the card exposes PII.
+#[tauri::command]
+fn export_contact(state: State<AppState>, contact_id: String, password: Option<String>, confirm_plaintext: Option<bool>, reauth_token: Option<String>) -> Result<String, String> {
+    if let Err(e) = require_section_access(&state, "contacts", reauth_token.as_deref()) {
+        if let Some(pwd) = password { zeroize_password(pwd); }
+        return Err(e);
+    }
+    let vault = read_vault_internal(&state)?;
+    let contact = vault.get("contacts").and_then(|c| c.as_array())
+        .and_then(|list| list.iter().find(|c| c.get("id").and_then(|i| i.as_str()) == Some(contact_id.as_str())))
+        .cloned()
+        .ok_or("Contatto non trovato")?;
+    let vcard = Zeroizing::new(contact_to_vcard(&contact));
+    let result = match password {
+        Some(pwd) => {
+            let sealed = if pwd.is_empty() {
+                Err("La password di cifratura non può essere vuota".to_string())
+            } else {
+                seal_for_transit(VCARD_EXPORT_PREFIX, vcard.as_bytes(), &pwd)
+            };
+            zeroize_password(pwd);
+            sealed?
+        }
+        None if confirm_plaintext == Some(true) => vcard.to_string(),
+        None => return Err("Esportazione in chiaro: conferma richiesta (contiene dati personali)".into()),
+    };
+    let _ = append_audit_log(&state, AuditCategory::Contact, Some(&contact_id), "Contatto esportato (vCard)");
+    Ok(result)
+}
+
+// ─── Data-subject access report (GDPR art. 15) ──────────────────────────────
+// Everything held about one contact, as labelled fields a recipient can read without the
+// app. Only explicit field lists are copied: practices where the contact is not the client
+// disclose just the reference and the contact's role, never the other client's matter.
+const SAR_CONTACT_FIELDS: &[(&str, &str)] = &[
+    ("id", "ID"), ("type", "Tipo"), ("name", "Nome"), ("fiscalCode", "Codice fiscale"),
+    ("vatNumber", "Partita IVA"), ("phone", "Telefono"), ("email", "Email"), ("pec", "PEC"),
+    ("address", "Indirizzo"), ("barAssociation", "Ordine professionale"), ("court", "Foro"),
+    ("notes", "Note"),
+];
+const SAR_PRACTICE_FIELDS: &[(&str, &str)] = &[
+    ("object", "Oggetto"), ("description", "Descrizione"), ("court", "Autorità giudiziaria"),
+    ("type", "Materia"), ("createdAt", "Aperto il"), ("closedAt", "Chiuso il"),
+];
+const SAR_INVOICE_FIELDS: &[(&str, &str)] = &[
+    ("number", "Numero"), ("date", "Data"), ("status", "Stato"), ("clientName", "Intestatario"),
+    ("clientFiscalCode", "Codice fiscale intestatario"), ("practiceObject", "Oggetto"),
+    ("subtotal", "Imponibile"), ("iva", "IVA"), ("grandTotal", "Totale"),
+];
+const SAR_TIME_LOG_FIELDS: &[(&str, &str)] = &[
+    ("description", "Attività"), ("startedAt", "Inizio"), ("endedAt", "Fine"),
+    ("durationMin", "Durata (minuti)"), ("billable", "Fatturabile"),
+];
+const SAR_AGENDA_FIELDS: &[(&str, &str)] = &[
+    ("title", "Titolo"), ("date", "Data"), ("time", "Ora"), ("type", "Tipo"), ("notes", "Note"),
+];
+
+fn labelled(obj: &Value, fields: &[(&str, &str)]) -> Value {
+    let mut out = serde_json::Map::new();
+    for (key, label) in fields {
+        match obj.get(*key) {
+            Some(v) if !v.is_null() && v.as_str() != Some("") => { out.insert(label.to_string(), v.clone()); }
+            _ => {}
+        }
+    }
+    Value::Object(out)
+}
+
+fn build_subject_access_report(vault: &Value, contact_id: &str, generated_at: &str) -> Result<Value, String> {
+    let list = |section: &str| vault.get(section).and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let id_of = |v: &Value| v.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string();
+    let contact = list("contacts").into_iter()
+        .find(|c| c.get("id").and_then(|i| i.as_str()) == Some(contact_id))
+        .ok_or("Contatto non trovato")?;
+
+    let mut practices = Vec::new();
+    let mut client_practice_ids: Vec<String> = Vec::new();
+    for p in list("practices") {
+        let mut roles: Vec<String> = Vec::new();
+        let is_client = p.get("clientId").and_then(|c| c.as_str()) == Some(contact_id);
+        if is_client { roles.push("Cliente".into()); }
+        if p.get("counterpartyId").and_then(|c| c.as_str()) == Some(contact_id) { roles.push("Controparte".into()); }
+        for r in p.get("roles").and_then(|r| r.as_array()).cloned().unwrap_or_default() {
+            if r.get("contactId").and_then(|c| c.as_str()) == Some(contact_id) {
+                roles.push(r.get("role").and_then(|x| x.as_str()).unwrap_or("contatto").to_string());
+            }
+        }
+        if roles.is_empty() { continue; }
+        let mut entry = if is_client { labelled(&p, SAR_PRACTICE_FIELDS) } else { json!({}) };
+        entry["Riferimento fascicolo"] = json!(id_of(&p));
+        entry["Ruolo dell'interessato"] = json!(roles);
+        if let Some(st) = p.get("status").and_then(|s| s.as_str()) {
+            entry["Stato"] = json!(if st == "closed" { "Chiuso" } else { "Aperto" });
+        }
+        if is_client { client_practice_ids.push(id_of(&p)); }
+        practices.push(entry);
+    }
+    let on_client_practice = |v: &Value| v.get("practiceId").and_then(|p| p.as_str())
+        .map(|pid| client_practice_ids.iter().any(|c| c == pid)).unwrap_or(false);
+
+    let invoices: Vec<Value> = list("invoices").into_iter()
+        .filter(|inv| match inv.get("clientId").and_then(|c| c.as_str()) {
+            Some(cid) => cid == contact_id,
+            None => on_client_practice(inv),
+        })
+        .map(|inv| {
+            let mut e = labelled(&inv, SAR_INVOICE_FIELDS);
+            if let Some(pid) = inv.get("practiceId").and_then(|p| p.as_str()) { e["Riferimento fascicolo"] = json!(pid); }
+            e
+        })
+        .collect();
+    let time_logs: Vec<Value> = list("timeLogs").into_iter()
+        .filter(|l| on_client_practice(l))
+        .map(|l| {
+            let mut e = labelled(&l, SAR_TIME_LOG_FIELDS);
+            e["Riferimento fascicolo"] = l.get("practiceId").cloned().unwrap_or(Value::Null);
+            e
+        })
+        .collect();
+    let agenda: Vec<Value> = list("agenda").into_iter()
+        .filter(|a| a.get("contactId").and_then(|c| c.as_str()) == Some(contact_id) || on_client_practice(a))
+        .map(|a| {
+            let mut e = labelled(&a, SAR_AGENDA_FIELDS);
+            if let Some(pid) = a.get("practiceId").and_then(|p| p.as_str()) { e["Riferimento fascicolo"] = json!(pid); }
+            e
+        })
+        .collect();
+
+    Ok(json!({
+        "Documento": "Copia dei dati personali (art. 15 GDPR)",
+        "Generato il": generated_at,
+        "Interessato": labelled(&contact, SAR_CONTACT_FIELDS),
+        "Fascicoli": practices,
+        "Fatture": invoices,
+        "Attività registrate": time_logs,
+        "Appuntamenti e scadenze": agenda,
+        "Riepilogo": {
+            "Fascicoli": practices.len(),
+            "Fatture": invoices.len(),
+            "Attività registrate": time_logs.len(),
+            "Appuntamenti e scadenze": agenda.len(),
+        },
+    }))
+}
+
+/// Data-subject access request: after password verification, write the contact's report
+/// as readable JSON to a user-chosen path. The report is also returned so the frontend
+/// can render the optional PDF with its existing PDF tooling.
+#[tauri::command]
+async fn client_data_export(state: State<'_, AppState>, contact_id: String, password: String, app: AppHandle) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let password = Zeroizing::new(password);
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    // Same brute-force lockout as unlock/verify
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
+        return Ok(locked_json);
+    }
+    if authenticate_vault_password(&password, &dir).is_err() {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        return Ok(json!({"success": false, "error": "Password errata"}));
+    }
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    drop(password);
+    let vault = read_vault_internal(&state)?;
+    let report = build_subject_access_report(&vault, &contact_id, &chrono::Local::now().to_rfc3339())?;
+    let name: String = report.pointer("/Interessato/Nome").and_then(|n| n.as_str()).unwrap_or("interessato")
+        .chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file()
+        .add_filter("JSON", &["json"])
+        .set_file_name(format!("LexFlow_Dati_{}.json", name))
+        .save_file(move |file_path| { let _ = tx.send(file_path); });
+    let Some(p) = rx.await.map_err(|e| format!("Dialog error: {}", e))? else {
+        return Ok(json!({"success": false, "cancelled": true}));
+    };
+    let path = p.into_path().map_err(|e| format!("Path error: {:?}", e))?;
+    let pretty = Zeroizing::new(serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?);
+    secure_write(&path, &pretty).map_err(|e| e.to_string())?;
+    let _ = append_audit_log(&state, AuditCategory::Contact, Some(&contact_id), &format!("Esportazione dati interessato (GDPR): {}", contact_id));
+    Ok(json!({"success": true, "path": path.to_string_lossy(), "report": report}))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  DEBOUNCED SAVES
+// ═══════════════════════════════════════════════════════════
+// save_* write immediately; queue_save coalesces rapid updates to the same section
+// and writes them in one atomic pass under write_mutex. Lock/quit paths call
+// flush_pending_saves() BEFORE dropping the key so nothing queued is lost.
+
+/// Write every pending section in a single read-modify-write. Caller must hold write_mutex.
+fn flush_pending_saves_locked(state: &State<AppState>) -> Result<(), String> {
+    let pending = state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if pending.is_empty() { return Ok(()); }
+    let sections = pending.keys().cloned().collect::<Vec<_>>().join(", ");
+    let result = read_vault_internal(state).and_then(|mut vault| {
+        apply_pending_saves(&mut vault, pending.clone());
+        write_vault_internal(state, &vault)
+    });
+    if result.is_ok() {
+        let _ = append_audit_event(state, AuditKind::Data, AuditCategory::System, None, &format!("Salvataggio differito: {}", sections));
+    }
+    if let Err(e) = &result {
+        lf_log!("[LexFlow] Debounced save failed: {} — pending sections kept", e);
+        state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).restore(pending);
+    }
+    result
+}
+
+fn flush_pending_saves(state: &State<AppState>) -> Result<(), String> {
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    flush_pending_saves_locked(state)
+}
+
+#[tauri::command]
+fn queue_save(app: AppHandle, state: State<AppState>, section: String, value: Value, reauth_token: Option<String>) -> Result<bool, String> {
+    if !VAULT_SECTIONS.contains(&section.as_str()) {
+        return Err(format!("Sezione non valida: {}", section));
+    }
+    require_section_access(&state, &section, reauth_token.as_deref())?;
+    snapshot_write_guard(state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).as_ref())?;
+    get_vault_key(&state)?;
+    let must_schedule = state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).queue(&section, value);
+    if must_schedule {
+        let delay = *state.save_debounce_ms.lock().unwrap_or_else(|e| e.into_inner());
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            let state = app.state::<AppState>();
+            let _ = flush_pending_saves(&state);
+        });
+    }
+    Ok(true)
+}
+
+#[tauri::command]
+fn flush_saves(state: State<AppState>) -> Result<bool, String> {
+    flush_pending_saves(&state)?;
+    Ok(true)
+}
+
+#[tauri::command]
+fn set_save_debounce_ms(state: State<AppState>, ms: u64) {
+    *state.save_debounce_ms.lock().unwrap_or_else(|e| e.into_inner()) = ms.clamp(MIN_SAVE_DEBOUNCE_MS, MAX_SAVE_DEBOUNCE_MS);
+}
+
+#[tauri::command]
+fn get_save_debounce_ms(state: State<AppState>) -> u64 {
+    *state.save_debounce_ms.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// ═══════════════════════════════════════════════════════════
+//  BIOMETRICS
+// ═══════════════════════════════════════════════════════════
+
+#[tauri::command]
+fn check_bio() -> bool {
+    // macOS/Windows: biometria nativa disponibile
+    // Android: fingerprint/face disponibile via Android Biometric API (gestita lato JS)
+    cfg!(any(target_os = "macos", target_os = "windows", target_os = "android"))
+}
+
+#[tauri::command]
+fn has_bio_saved(state: State<AppState>) -> bool {
+    // TOUCH ID FIX: Do NOT call keyring::get_password() here — on macOS that triggers
+    // the Touch ID / password popup just to check if credentials exist!
+    // Instead, use a lightweight marker file written by save_bio / cleared by clear_bio.
+    #[cfg(not(target_os = "android"))]
+    {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        dir.join(BIO_MARKER_FILE).exists()
+    }
+    #[cfg(target_os = "android")]
+    {
+        let _ = state;
+        false
+    }
+}
+
+#[tauri::command]
+fn save_bio(state: State<AppState>, pwd: String) -> Result<bool, String> {
+    #[cfg(not(target_os = "android"))]
+    {
+        let user = whoami::username();
+        let entry = keyring::Entry::new(BIO_SERVICE, &user).map_err(|e| e.to_string())?;
+        entry.set_password(&pwd).map_err(|e| e.to_string())?;
+        // Write marker file so has_bio_saved() can check without triggering Touch ID
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let _ = fs::write(dir.join(BIO_MARKER_FILE), "1");
+        Ok(true)
+    }
+    #[cfg(target_os = "android")]
+    {
+        let _ = (state, pwd);
+        Ok(true)
+    }
+}
+
+#[tauri::command]
+fn bio_login(state: State<AppState>) -> Result<Value, String> {
+    // One biometric prompt at a time; a concurrent second call gets the first one's outcome
+    let fingerprint = state.auth_gate.fingerprint("bio", "");
+    state.auth_gate.run(fingerprint, || bio_login_attempt(&state))
+}
+
+fn bio_login_attempt(_state: &State<AppState>) -> Result<Value, String> {
+    #[cfg(target_os = "macos")]
+    {
+        // FORT KNOX: Swift code passed via stdin — NEVER written to disk
+        let swift_code = "import LocalAuthentication\nlet ctx = LAContext()\nvar err: NSError?\nif ctx.canEvaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, error: &err) {\n  let sema = DispatchSemaphore(value: 0)\n  var ok = false\n  ctx.evaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, localizedReason: \"LexFlow\") { s, _ in ok = s; sema.signal() }\n  sema.wait()\n  if ok { exit(0) } else { exit(1) }\n} else { exit(1) }";
+        
         use std::io::Write;
         // SECURITY FIX (Gemini L1-2): use absolute path to prevent PATH hijacking.
         // /usr/bin/swift is the canonical location on macOS; never rely on $PATH for security-critical executables.
@@ -1182,1037 +5247,7795 @@ fn bio_login(_state: State<AppState>) -> Result<Value, String> {
         if let Some(ref mut stdin) = child.stdin {
             stdin.write_all(swift_code.as_bytes()).map_err(|e| e.to_string())?;
         }
-        drop(child.stdin.take());
-        let status = child.wait().map_err(|e| e.to_string())?;
-        if !status.success() { return Ok(json!({"success": false, "error": "Autenticazione biometrica fallita"})); }
+        drop(child.stdin.take());
+        let status = child.wait().map_err(|e| e.to_string())?;
+        if !status.success() { return Ok(json!({"success": false, "error": "Autenticazione biometrica fallita"})); }
+
+        // Recupera la password salvata dal keyring (non la ritorniamo al JS)
+        let user = whoami::username();
+        let saved_pwd = keyring::Entry::new(BIO_SERVICE, &user)
+            .and_then(|e| e.get_password()).map_err(|e| e.to_string())?;
+
+        // Esegui internamente lo sblocco del vault esattamente come unlock_vault
+    let dir = _state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = _state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let salt_path = dir.join(VAULT_SALT_FILE);
+        if !salt_path.exists() { return Ok(json!({"success": false, "error": "Vault non inizializzato"})); }
+        let salt = fs::read(&salt_path).unwrap_or_default();
+        match derive_vault_key(&saved_pwd, &dir, &salt) {
+            Ok(k) => {
+                // SECURITY FIX: verify the derived key against vault.verify BEFORE accepting.
+                // If the user changed their password after saving biometrics, the old keyring
+                // password would derive a wrong key. Without this check, the vault would appear
+                // "unlocked" but all data reads would fail with AES decryption errors.
+                let verify_path = dir.join(VAULT_VERIFY_FILE);
+                let stored = fs::read(&verify_path).unwrap_or_default();
+                if !stored.is_empty() && !verify_hash_matches(&k, &stored) {
+                    // Keyring password is stale (user changed password).
+                    // Clear the stale bio credentials so the user isn't stuck in a loop.
+                    let _ = keyring::Entry::new(BIO_SERVICE, &user)
+                        .and_then(|e| e.delete_credential());
+                    let _ = fs::remove_file(dir.join(BIO_MARKER_FILE));
+                    return Ok(json!({
+                        "success": false,
+                        "error": "Password biometrica non più valida. Accedi con la password e riconfigura la biometria."
+                    }));
+                }
+                *(_state.vault_key.lock().unwrap_or_else(|e| e.into_inner())) = Some(SecureKey(k));
+                clear_lockout(_state, &sec_dir, LockoutKind::Vault);
+                *(_state.last_activity.lock().unwrap_or_else(|e| e.into_inner())) = Instant::now();
+                let _ = append_audit_log(_state, AuditCategory::Auth, None, "Sblocco Vault (biometria)");
+                note_vault_unlock(_state);
+                Ok(json!({"success": true}))
+            },
+            Err(e) => Ok(json!({"success": false, "error": e}))
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        // Windows Hello: verifica biometrica reale tramite UserConsentVerifier WinRT API.
+        // Usa PowerShell per invocare Windows.Security.Credentials.UI.UserConsentVerifier
+        // — più affidabile che controllare solo il keyring senza autenticazione.
+        use std::process::Command;
+        let ps_script = r#"
+Add-Type -AssemblyName System.Runtime.WindowsRuntime
+$asTaskGeneric = ([System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object { $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1' })[0]
+function Await($WinRtTask, $ResultType) {
+    $asTaskSpecific = $asTaskGeneric.MakeGenericMethod($ResultType)
+    $netTask = $asTaskSpecific.Invoke($null, @($WinRtTask))
+    $netTask.Wait(-1) | Out-Null
+    $netTask.Result
+}
+[Windows.Security.Credentials.UI.UserConsentVerifier,Windows.Security.Credentials.UI,ContentType=WindowsRuntime] | Out-Null
+$result = Await ([Windows.Security.Credentials.UI.UserConsentVerifier]::RequestVerificationAsync("LexFlow — Verifica identità")) ([Windows.Security.Credentials.UI.UserConsentVerificationResult])
+if ($result -eq [Windows.Security.Credentials.UI.UserConsentVerificationResult]::Verified) { exit 0 } else { exit 1 }
+"#;
+        // SECURITY FIX (Gemini L1-2): use absolute path to prevent PATH hijacking.
+        // C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe is the canonical location.
+        let status = Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", ps_script])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_err(|e| e.to_string())?;
+        if !status.success() { return Ok(json!({"success": false, "error": "Windows Hello fallito o non disponibile"})); }
+
+        // Recupera la password salvata dal keyring e sblocca internamente il vault
+        let user = whoami::username();
+        let saved_pwd = keyring::Entry::new(BIO_SERVICE, &user)
+            .and_then(|e| e.get_password()).map_err(|e| e.to_string())?;
+
+    let dir = _state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = _state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let salt_path = dir.join(VAULT_SALT_FILE);
+        if !salt_path.exists() { return Ok(json!({"success": false, "error": "Vault non inizializzato"})); }
+        let salt = fs::read(&salt_path).unwrap_or_default();
+        match derive_vault_key(&saved_pwd, &dir, &salt) {
+            Ok(k) => {
+                // SECURITY FIX: verify the derived key against vault.verify BEFORE accepting.
+                let verify_path = dir.join(VAULT_VERIFY_FILE);
+                let stored = fs::read(&verify_path).unwrap_or_default();
+                if !stored.is_empty() && !verify_hash_matches(&k, &stored) {
+                    let _ = keyring::Entry::new(BIO_SERVICE, &user)
+                        .and_then(|e| e.delete_credential());
+                    let _ = fs::remove_file(dir.join(BIO_MARKER_FILE));
+                    return Ok(json!({
+                        "success": false,
+                        "error": "Password biometrica non più valida. Accedi con la password e riconfigura la biometria."
+                    }));
+                }
+                *(_state.vault_key.lock().unwrap_or_else(|e| e.into_inner())) = Some(SecureKey(k));
+                clear_lockout(_state, &sec_dir, LockoutKind::Vault);
+                *(_state.last_activity.lock().unwrap_or_else(|e| e.into_inner())) = Instant::now();
+                let _ = append_audit_log(_state, AuditCategory::Auth, None, "Sblocco Vault (biometria)");
+                note_vault_unlock(_state);
+                Ok(json!({"success": true}))
+            },
+            Err(e) => Ok(json!({"success": false, "error": e}))
+        }
+    }
+    #[cfg(target_os = "android")]
+    {
+        // Su Android il biometric prompt è gestito interamente dal frontend JS
+        // tramite l'Android BiometricPrompt API via tauri-plugin-biometric (futuro)
+        // Per ora restituisce errore che il frontend gestisce con fallback a password
+        Err("android-bio-use-frontend".into())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "android")))]
+    {
+        Err("Non supportato su questa piattaforma".into())
+    }
+}
+
+/// Result of reading the biometric credential from the OS keyring.
+#[cfg_attr(target_os = "android", allow(dead_code))]
+enum KeyringRead { Missing, Error(String), Password(Zeroizing<String>) }
+
+/// Decide whether the stored biometric password still opens the vault. `read_keyring` is only
+/// called when the vault is unlocked — a locked vault has no key to compare against.
+#[cfg_attr(target_os = "android", allow(dead_code))]
+fn assess_bio_credential(marker: bool, read_keyring: impl FnOnce() -> KeyringRead, salt: &[u8], kdf: &KdfParams, current_key: Option<&[u8]>) -> Value {
+    if !marker {
+        return json!({"valid": false, "reason": "not-enrolled", "reenroll": false});
+    }
+    let Some(current_key) = current_key else {
+        return json!({"valid": Value::Null, "reason": "vault-locked", "reenroll": false});
+    };
+    let pwd = match read_keyring() {
+        KeyringRead::Password(p) => p,
+        KeyringRead::Missing => return json!({
+            "valid": false, "reason": "keyring-missing", "reenroll": true,
+            "message": "Credenziale biometrica assente dal portachiavi. Riconfigura la biometria."
+        }),
+        KeyringRead::Error(e) => return json!({
+            "valid": false, "reason": "keyring-error", "reenroll": true,
+            "message": format!("Portachiavi non leggibile ({}). Riconfigura la biometria.", e)
+        }),
+    };
+    let derived = derive_key_with_params(&pwd, salt, kdf).map(Zeroizing::new);
+    match derived {
+        Ok(k) if verify_hash_matches(&k, &make_verify_tag(current_key)) =>
+            json!({"valid": true, "reason": "ok", "reenroll": false}),
+        _ => json!({
+            "valid": false, "reason": "stale", "reenroll": true,
+            "message": "La password biometrica non corrisponde più a quella del vault. Riconfigura la biometria."
+        }),
+    }
+}
+
+/// Check, without a biometric prompt, that the keyring credential still maps to the
+/// current password — e.g. after change_password failed to update the keyring.
+#[tauri::command]
+fn verify_bio_credential(state: State<AppState>) -> Value {
+    #[cfg(not(target_os = "android"))]
+    {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let current_key = get_vault_key(&state).ok();
+        let salt = fs::read(dir.join(VAULT_SALT_FILE)).unwrap_or_default();
+        let read_keyring = || {
+            let user = whoami::username();
+            match keyring::Entry::new(BIO_SERVICE, &user).and_then(|e| e.get_password()) {
+                Ok(p) => KeyringRead::Password(Zeroizing::new(p)),
+                Err(keyring::Error::NoEntry) => KeyringRead::Missing,
+                Err(e) => KeyringRead::Error(e.to_string()),
+            }
+        };
+        let kdf = read_vault_kdf(&dir).unwrap_or(DEFAULT_KDF_PARAMS);
+        assess_bio_credential(dir.join(BIO_MARKER_FILE).exists(), read_keyring, &salt, &kdf, current_key.as_deref().map(|k| k.as_slice()))
+    }
+    #[cfg(target_os = "android")]
+    {
+        let _ = state;
+        json!({"valid": Value::Null, "reason": "unsupported", "reenroll": false})
+    }
+}
+
+/// Store `pwd` as the biometric credential: keyring first, marker only once that succeeded.
+#[cfg_attr(target_os = "android", allow(dead_code))]
+fn refresh_bio_credential(dir: &std::path::Path, pwd: &str, write_keyring: impl FnOnce(&str) -> Result<(), String>) -> Result<(), String> {
+    write_keyring(pwd)?;
+    fs::write(dir.join(BIO_MARKER_FILE), "1").map_err(|e| e.to_string())
+}
+
+/// Recovery path when change_password could not update the keyring (biometrics off at the
+/// time, or the keyring write failed): re-enrol with the verified current password.
+#[tauri::command]
+fn refresh_bio_after_password_change(state: State<AppState>, current_password: String) -> Result<Value, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
+        zeroize_password(current_password);
+        return Ok(locked_json);
+    }
+    if authenticate_vault_password(&current_password, &dir).is_err() {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        zeroize_password(current_password);
+        return Ok(json!({"success": false, "error": "Password errata"}));
+    }
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    #[cfg(not(target_os = "android"))]
+    let result = refresh_bio_credential(&dir, &current_password, |pwd| {
+        let user = whoami::username();
+        keyring::Entry::new(BIO_SERVICE, &user)
+            .and_then(|e| e.set_password(pwd))
+            .map_err(|e| e.to_string())
+    });
+    #[cfg(target_os = "android")]
+    let result: Result<(), String> = Err("Non supportato su questa piattaforma".into());
+    zeroize_password(current_password);
+    match result {
+        Ok(()) => {
+            let _ = append_audit_log(&state, AuditCategory::Auth, None, "Credenziale biometrica aggiornata");
+            Ok(json!({"success": true}))
+        }
+        Err(e) => Ok(json!({"success": false, "error": format!("Portachiavi non aggiornato: {}", e)})),
+    }
+}
+
+#[tauri::command]
+fn clear_bio(state: State<AppState>) -> bool {
+    #[cfg(not(target_os = "android"))]
+    {
+        let user = whoami::username();
+        if let Ok(e) = keyring::Entry::new(BIO_SERVICE, &user) { let _ = e.delete_credential(); }
+        // Remove marker file
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let _ = fs::remove_file(dir.join(BIO_MARKER_FILE));
+        true
+    }
+    #[cfg(target_os = "android")]
+    {
+        let _ = state;
+        true
+    }
+}
+
+// ═══════════════════════════════════════════════════════════
+//  AUDIT & LOGS
+// ═══════════════════════════════════════════════════════════
+
+/// Key the audit log is currently encrypted with: the vault key, or — while a re-key
+/// after change_password is pending — the old key recovered from AUDIT_REKEY_FILE.
+/// A marker that does not open with the vault key is stale (change_password crashed
+/// before its renames) and is ignored.
+fn audit_log_key(dir: &std::path::Path, vault_key: &[u8]) -> Zeroizing<Vec<u8>> {
+    fs::read(dir.join(AUDIT_REKEY_FILE)).ok()
+        .and_then(|sealed| decrypt_data(vault_key, &sealed).ok())
+        .map(Zeroizing::new)
+        .unwrap_or_else(|| Zeroizing::new(vault_key.to_vec()))
+}
+
+/// Finish a pending audit-log re-key. Idempotent: if a previous run already rewrote the
+/// log but crashed before removing the marker, the log opens with the vault key and only
+/// the marker is dropped. Callers hold `audit_mutex`.
+fn rekey_audit_log(dir: &std::path::Path, vault_key: &[u8]) -> Result<(), String> {
+    let marker = dir.join(AUDIT_REKEY_FILE);
+    if !marker.exists() { return Ok(()); }
+    let old_key = audit_log_key(dir, vault_key);
+    let audit_path = dir.join(AUDIT_LOG_FILE);
+    if audit_path.exists() && old_key.as_slice() != vault_key {
+        let enc = fs::read(&audit_path).map_err(|e| e.to_string())?;
+        if decrypt_data(vault_key, &enc).is_err() {
+            let mut plaintext = Zeroizing::new(decrypt_data(&old_key, &enc)?);
+            if let Ok(mut entries) = serde_json::from_slice::<Vec<Value>>(&plaintext) {
+                rechain_audit_entries(&mut entries, &old_key, vault_key);
+                plaintext = Zeroizing::new(serde_json::to_vec(&entries).map_err(|e| e.to_string())?);
+            }
+            atomic_write_with_sync(&audit_path, &encrypt_data(vault_key, &plaintext)?)?;
+        }
+    }
+    fs::remove_file(&marker).map_err(|e| e.to_string())
+}
+
+/// `auditLevel` setting: "off" | "security" (default, historical behaviour) | "verbose".
+/// Tamper detection is written at every level, including "off".
+#[derive(Clone, Copy, PartialEq)]
+enum AuditKind {
+    /// Authentication, license, import/export
+    Security,
+    /// Data mutations (saves), logged only at "verbose"
+    Data,
+}
+
+fn audit_level_allows(level: &str, kind: AuditKind) -> bool {
+    match level {
+        "off" => false,
+        "verbose" => true,
+        _ => kind == AuditKind::Security,
+    }
+}
+
+// ─── Audit hash chain ───────────────────────────────────────────────────────
+// Each entry stores prevHash = HMAC(audit key, canonical JSON of the previous entry), so
+// removing or editing a middle entry breaks the next link even for whoever can decrypt
+// the file. serde_json maps are sorted, which makes to_vec canonical. Entry 0 is the
+// anchor: once the 10k cap rotates the oldest entry out, its predecessor is gone.
+// Entries written before the chain existed carry no prevHash and are not covered.
+const AUDIT_CHAIN_GENESIS: &[u8] = b"lexflow-audit-genesis";
+
+fn audit_chain_link(key: &[u8], prev: Option<&Value>) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+    match prev {
+        Some(entry) => mac.update(&serde_json::to_vec(entry).unwrap_or_default()),
+        None => mac.update(AUDIT_CHAIN_GENESIS),
+    }
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Append an entry linked to the current last one.
+fn push_chained_audit_entry(logs: &mut Vec<Value>, key: &[u8], mut entry: Value) {
+    entry["prevHash"] = json!(audit_chain_link(key, logs.last()));
+    logs.push(entry);
+}
+
+/// First index whose prevHash does not match its predecessor, or -1 if the chain holds.
+/// Checking starts at the first chained entry (legacy entries precede it).
+fn verify_audit_chain_entries(entries: &[Value], key: &[u8]) -> i64 {
+    let Some(start) = entries.iter().position(|e| e.get("prevHash").is_some()) else { return -1 };
+    for i in start.max(1)..entries.len() {
+        let stored = entries[i].get("prevHash").and_then(|h| h.as_str());
+        if stored != Some(audit_chain_link(key, Some(&entries[i - 1])).as_str()) {
+            return i as i64;
+        }
+    }
+    -1
+}
+
+/// Re-link under a new key (password change). Links that did not verify under the old
+/// key are left as they are, so a break stays at the same index.
+fn rechain_audit_entries(entries: &mut [Value], old_key: &[u8], new_key: &[u8]) {
+    let valid: Vec<bool> = (0..entries.len()).map(|i| {
+        let stored = entries[i].get("prevHash").and_then(|h| h.as_str());
+        stored.is_some() && stored == Some(audit_chain_link(old_key, i.checked_sub(1).map(|p| &entries[p])).as_str())
+    }).collect();
+    // Forward: each new link hashes the predecessor as it will be stored (already re-linked)
+    for i in 0..entries.len() {
+        if valid[i] {
+            let link = audit_chain_link(new_key, i.checked_sub(1).map(|p| &entries[p]));
+            entries[i]["prevHash"] = json!(link);
+        }
+    }
+}
+
+/// Area an audit entry belongs to; stored as `category` so the UI can filter the log.
+/// Background jobs (retention purge, deferred saves) log as System.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum AuditCategory {
+    Auth,
+    Security,
+    License,
+    Practice,
+    Agenda,
+    TimeLog,
+    Invoice,
+    Contact,
+    Exchange,
+    Audit,
+    System,
+}
+
+impl AuditCategory {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuditCategory::Auth => "auth",
+            AuditCategory::Security => "security",
+            AuditCategory::License => "license",
+            AuditCategory::Practice => "practice",
+            AuditCategory::Agenda => "agenda",
+            AuditCategory::TimeLog => "timeLog",
+            AuditCategory::Invoice => "invoice",
+            AuditCategory::Contact => "contact",
+            AuditCategory::Exchange => "exchange",
+            AuditCategory::Audit => "audit",
+            AuditCategory::System => "system",
+        }
+    }
+}
+
+/// Category of entries written before the structured schema.
+const AUDIT_LEGACY_CATEGORY: &str = "general";
+
+/// Entry as shown to the UI: pre-schema entries get category "general" and a null targetId.
+/// The stored entry is left alone — prevHash links cover its exact bytes.
+fn audit_entry_view(entry: &Value) -> Value {
+    let mut view = entry.clone();
+    if let Some(obj) = view.as_object_mut() {
+        obj.entry("category").or_insert(json!(AUDIT_LEGACY_CATEGORY));
+        obj.entry("targetId").or_insert(Value::Null);
+    }
+    view
+}
+
+fn audit_entry_matches(entry: &Value, category: Option<&str>, target_id: Option<&str>) -> bool {
+    let field = |k: &str| entry.get(k).and_then(|v| v.as_str());
+    category.map(|c| field("category").unwrap_or(AUDIT_LEGACY_CATEGORY) == c).unwrap_or(true)
+        && target_id.map(|t| field("targetId") == Some(t)).unwrap_or(true)
+}
+
+fn audit_entry(category: AuditCategory, target_id: Option<&str>, event_name: &str) -> Value {
+    json!({
+        "event": event_name,
+        "category": category.as_str(),
+        "targetId": target_id,
+        "time": chrono::Local::now().to_rfc3339(),
+    })
+}
+
+fn write_audit_event(dir: &std::path::Path, key: &[u8], level: &str, kind: AuditKind, category: AuditCategory, target_id: Option<&str>, event_name: &str) -> Result<(), String> {
+    let path = dir.join(AUDIT_LOG_FILE);
+    let mut tampered = false;
+    let mut logs: Vec<Value> = if path.exists() {
+        let enc = fs::read(&path).unwrap_or_default();
+        match decrypt_data(key, &enc) {
+            Ok(dec) => serde_json::from_slice(&dec).unwrap_or_default(),
+            Err(_) => {
+                // SECURITY FIX (Gemini Audit v2): if audit log decryption fails, the file
+                // has been tampered with. DO NOT silently overwrite it — that would destroy
+                // the entire forensic history. Instead, preserve the corrupted file as evidence
+                // and start a NEW log with a tamper-detection event.
+                let corrupt_backup = path.with_extension("audit.corrupt");
+                let _ = fs::copy(&path, &corrupt_backup);
+                lf_log!("[LexFlow] SECURITY: Audit log decryption failed — tampered? Backup saved to {:?}", corrupt_backup);
+                tampered = true;
+                let mut fresh = Vec::new();
+                push_chained_audit_entry(&mut fresh, key, audit_entry(AuditCategory::Security, None, AUDIT_TAMPER_EVENT));
+                fresh
+            }
+        }
+    } else { vec![] };
+
+    let allowed = audit_level_allows(level, kind);
+    if !allowed && !tampered { return Ok(()); }
+    if allowed {
+        push_chained_audit_entry(&mut logs, key, audit_entry(category, target_id, event_name));
+    }
+    if logs.len() > 10000 { logs.remove(0); }
+    let plaintext = Zeroizing::new(serde_json::to_vec(&logs).unwrap_or_default());
+    let enc = encrypt_data(key, &plaintext)?;
+    atomic_write_with_sync(&path, &enc)?;
+    Ok(())
+}
+
+fn append_audit_event(state: &State<AppState>, kind: AuditKind, category: AuditCategory, target_id: Option<&str>, event_name: &str) -> Result<(), String> {
+    let vault_key = match get_vault_key(state) { Ok(k) => k, Err(_) => return Ok(()) };
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let settings = read_settings(&dir);
+    let level = settings.get("auditLevel").and_then(|l| l.as_str()).unwrap_or("security");
+    let _audit = state.audit_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let key = audit_log_key(&dir, &vault_key);
+    write_audit_event(&dir, &key, level, kind, category, target_id, event_name)
+}
+
+fn append_audit_log(state: &State<AppState>, category: AuditCategory, target_id: Option<&str>, event_name: &str) -> Result<(), String> {
+    append_audit_event(state, AuditKind::Security, category, target_id, event_name)
+}
+
+/// Data mutation entry ("verbose" level only), with the number of affected records.
+fn append_data_audit_log(state: &State<AppState>, category: AuditCategory, label: &str, data: &Value) {
+    let count = data.as_array().map(|a| a.len()).unwrap_or(0);
+    let _ = append_audit_event(state, AuditKind::Data, category, None, &format!("{} ({} elementi)", label, count));
+}
+
+#[tauri::command]
+fn get_audit_log(state: State<AppState>) -> Result<Value, String> {
+    Ok(Value::Array(read_audit_entries(&state)?.iter().map(audit_entry_view).collect()))
+}
+
+/// Entries of one category and/or about one record (e.g. every change to a practice).
+#[tauri::command]
+fn get_audit_log_filtered(state: State<AppState>, category: Option<String>, target_id: Option<String>) -> Result<Value, String> {
+    let entries = read_audit_entries(&state)?;
+    Ok(Value::Array(entries.iter()
+        .filter(|e| audit_entry_matches(e, category.as_deref(), target_id.as_deref()))
+        .map(audit_entry_view)
+        .collect()))
+}
+
+fn read_audit_entries(state: &State<AppState>) -> Result<Vec<Value>, String> {
+    let vault_key = get_vault_key(state)?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let _audit = state.audit_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let key = audit_log_key(&dir, &vault_key);
+    let path = dir.join(AUDIT_LOG_FILE);
+    if !path.exists() { return Ok(Vec::new()); }
+    let dec = Zeroizing::new(decrypt_data(&key, &fs::read(path).map_err(|e| e.to_string())?)?);
+    serde_json::from_slice(&dec).map_err(|e| e.to_string())
+}
+
+/// Index of the first broken link in the audit hash chain, -1 if intact.
+#[tauri::command]
+fn verify_audit_chain(state: State<AppState>) -> Result<i64, String> {
+    let entries = read_audit_entries(&state)?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let key = audit_log_key(&dir, &get_vault_key(&state)?);
+    Ok(verify_audit_chain_entries(&entries, &key))
+}
+
+/// Largest window a single get_audit_log_page call returns.
+const MAX_AUDIT_PAGE: usize = 500;
+
+/// Newest-first window over the log (stored oldest-first). An offset past the end yields
+/// an empty page with the real total, so the UI can fall back to the last page.
+fn audit_log_page(entries: Vec<Value>, offset: usize, limit: usize) -> Value {
+    let total = entries.len();
+    let limit = limit.clamp(1, MAX_AUDIT_PAGE);
+    let page: Vec<Value> = entries.iter().rev().skip(offset).take(limit).map(audit_entry_view).collect();
+    json!({
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+        "hasMore": offset.saturating_add(page.len()) < total,
+        "entries": page,
+    })
+}
+
+/// Paged audit view: the log is decrypted once per call, but only `limit` entries cross
+/// the IPC bridge instead of the whole (up to 10k-entry) array.
+#[tauri::command]
+fn get_audit_log_page(state: State<AppState>, offset: Option<usize>, limit: Option<usize>) -> Result<Value, String> {
+    let entries = read_audit_entries(&state)?;
+    Ok(audit_log_page(entries, offset.unwrap_or(0), limit.unwrap_or(100)))
+}
+
+// ─── Signed audit CSV ───────────────────────────────────────────────────────
+// For oversight bodies: the audit log as CSV (time, event, category, target, metadata,
+// chain) plus a companion .sig. `chain` is a running SHA-256 over the rows, so a dropped
+// or reordered row breaks every later link; the .sig holds HMAC-SHA256 over the exact CSV bytes keyed
+// by the vault key, so whoever can unlock the vault can confirm the file is untouched.
+// The same HMAC is also appended as the CSV's last line, so the file verifies on its own
+// (verify_audit_csv) once the .sig has been lost; both cover the body without the trailer.
+// A password change makes older exports unverifiable.
+const AUDIT_SIG_HEADER: &str = "LEXFLOW-AUDIT-CSV-SIG v1";
+const AUDIT_CSV_HEADER: &str = "time,event,category,target,metadata,chain";
+const AUDIT_CSV_TRAILER: &str = "# hmac-sha256: ";
+const AUDIT_TAMPER_EVENT: &str = "AUDIT_LOG_TAMPERING_DETECTED";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// CSV text of the audit entries and the last chain link.
+fn audit_csv(entries: &[Value]) -> (String, String) {
+    let mut out = format!("{}\n", AUDIT_CSV_HEADER);
+    let mut link = hex::encode(<Sha256 as Digest>::digest(b"lexflow-audit-chain"));
+    for e in entries {
+        let view = audit_entry_view(e);
+        let text = |k: &str| view.get(k).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let meta: serde_json::Map<String, Value> = e.as_object()
+            .map(|o| o.iter().filter(|(k, _)| !matches!(k.as_str(), "time" | "event" | "category" | "targetId"))
+                .map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        let metadata = if meta.is_empty() { String::new() } else { Value::Object(meta).to_string() };
+        let row = [text("time"), text("event"), text("category"), text("targetId"), metadata]
+            .iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+        let mut h = <Sha256 as Digest>::new();
+        h.update(link.as_bytes());
+        h.update(row.as_bytes());
+        link = hex::encode(h.finalize());
+        out.push_str(&format!("{},{}\n", row, link));
+    }
+    (out, link)
+}
+
+fn audit_csv_mac(key: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(AUDIT_SIG_HEADER.as_bytes());
+    mac
+}
+
+fn audit_csv_signature(key: &[u8], csv: &str, chain_head: &str, entries: usize) -> String {
+    let mut mac = audit_csv_mac(key);
+    mac.update(csv.as_bytes());
+    format!(
+        "{}\nalg: HMAC-SHA256\nsha256: {}\nhmac: {}\nchainHead: {}\nentries: {}\n",
+        AUDIT_SIG_HEADER,
+        hex::encode(<Sha256 as Digest>::digest(csv.as_bytes())),
+        hex::encode(mac.finalize().into_bytes()),
+        chain_head,
+        entries,
+    )
+}
+
+fn audit_csv_trailer(key: &[u8], csv: &str) -> String {
+    let mut mac = audit_csv_mac(key);
+    mac.update(csv.as_bytes());
+    format!("{}{}\n", AUDIT_CSV_TRAILER, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Body and trailer hex of an exported file; None when the last line is not a trailer.
+fn split_audit_csv_trailer(text: &str) -> Option<(&str, &str)> {
+    let trimmed = text.trim_end_matches(['\r', '\n']);
+    let start = trimmed.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let hex = trimmed[start..].strip_prefix(AUDIT_CSV_TRAILER)?;
+    Some((&text[..start], hex.trim()))
+}
+
+/// The HMAC must match the CSV bytes and the last row must end on the signed chain head.
+/// A trailer line, if present, is not part of the signed body.
+fn verify_audit_csv_sig(key: &[u8], csv: &str, sig: &str) -> bool {
+    let csv = split_audit_csv_trailer(csv).map(|(body, _)| body).unwrap_or(csv);
+    let field = |name: &str| sig.lines().find_map(|l| l.strip_prefix(name)?.strip_prefix(": "));
+    if sig.lines().next() != Some(AUDIT_SIG_HEADER) { return false; }
+    let Some(expected) = field("hmac").and_then(|h| hex::decode(h).ok()) else { return false };
+    let mut mac = audit_csv_mac(key);
+    mac.update(csv.as_bytes());
+    if mac.verify_slice(&expected).is_err() { return false; }
+    match (field("chainHead"), csv.lines().last()) {
+        (Some(head), Some(last)) => last == AUDIT_CSV_HEADER || last.ends_with(&format!(",{}", head)),
+        _ => false,
+    }
+}
+
+/// Self-contained check of an export: the trailer HMAC must match the body above it.
+fn verify_audit_csv_trailer(key: &[u8], text: &str) -> bool {
+    let Some((body, hex_mac)) = split_audit_csv_trailer(text) else { return false };
+    let Ok(expected) = hex::decode(hex_mac) else { return false };
+    let mut mac = audit_csv_mac(key);
+    mac.update(body.as_bytes());
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Hash-chain status reported with the export: the tamper events the log itself recorded
+/// and whether a quarantined corrupt log exists beside it.
+fn audit_chain_status(entries: &[Value], chain_head: &str, corrupt_backup: bool, broken_at: i64) -> Value {
+    let tamper_events = entries.iter()
+        .filter(|e| e.get("event").and_then(|v| v.as_str()) == Some(AUDIT_TAMPER_EVENT))
+        .count();
+    json!({
+        "head": chain_head,
+        "tamperEvents": tamper_events,
+        "corruptBackupPresent": corrupt_backup,
+        "brokenAt": broken_at,
+        "intact": tamper_events == 0 && !corrupt_backup && broken_at < 0,
+    })
+}
+
+#[tauri::command]
+async fn export_audit_csv(state: State<'_, AppState>, pwd: String, app: AppHandle) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
+        zeroize_password(pwd);
+        return Ok(locked_json);
+    }
+    if authenticate_vault_password(&pwd, &dir).is_err() {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        zeroize_password(pwd);
+        return Ok(json!({"success": false, "error": "Password errata"}));
+    }
+    zeroize_password(pwd);
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let entries = read_audit_entries(&state)?;
+    let (csv, head) = audit_csv(&entries);
+    let vault_key = get_vault_key(&state)?;
+    let sig = audit_csv_signature(&vault_key, &csv, &head, entries.len());
+    let signed = format!("{}{}", csv, audit_csv_trailer(&vault_key, &csv));
+    let corrupt = dir.join(AUDIT_LOG_FILE).with_extension("audit.corrupt").exists();
+    let broken_at = verify_audit_chain_entries(&entries, &audit_log_key(&dir, &get_vault_key(&state)?));
+    let chain = audit_chain_status(&entries, &head, corrupt, broken_at);
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file()
+        .add_filter("CSV", &["csv"])
+        .set_file_name(format!("LexFlow_Registro_{}.csv", chrono::Local::now().format("%Y%m%d_%H%M")))
+        .save_file(move |file_path| { let _ = tx.send(file_path); });
+    let Some(p) = rx.await.map_err(|e| format!("Dialog error: {}", e))? else {
+        return Ok(json!({"success": false, "cancelled": true}));
+    };
+    let path = p.into_path().map_err(|e| format!("Path error: {:?}", e))?;
+    let mut sig_name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    sig_name.push(".sig");
+    let sig_path = path.with_file_name(sig_name);
+    secure_write(&path, signed.as_bytes()).map_err(|e| e.to_string())?;
+    secure_write(&sig_path, sig.as_bytes()).map_err(|e| e.to_string())?;
+    let _ = append_audit_log(&state, AuditCategory::Audit, None, "Registro attività esportato (CSV firmato)");
+    Ok(json!({
+        "success": true,
+        "path": path.to_string_lossy(),
+        "sigPath": sig_path.to_string_lossy(),
+        "entries": entries.len(),
+        "hashChain": chain,
+    }))
+}
+
+/// Check an exported audit CSV against its .sig (contents, not paths). Needs the vault
+/// unlocked with the same password as at export time.
+#[tauri::command]
+fn verify_audit_csv_export(state: State<AppState>, csv: String, sig: String) -> Result<Value, String> {
+    let key = get_vault_key(&state)?;
+    Ok(json!({"valid": verify_audit_csv_sig(&key, &csv, &sig)}))
+}
+
+/// Check an exported audit CSV on disk by its own trailer line (no .sig needed).
+/// `signed: false` marks exports made before the trailer existed.
+#[tauri::command]
+fn verify_audit_csv(state: State<AppState>, path: String) -> Result<Value, String> {
+    let key = get_vault_key(&state)?;
+    let text = fs::read_to_string(&path).map_err(|e| format!("File non leggibile: {}", e))?;
+    let signed = split_audit_csv_trailer(&text).is_some();
+    Ok(json!({"valid": signed && verify_audit_csv_trailer(&key, &text), "signed": signed}))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  SETTINGS & LICENSE
+// ═══════════════════════════════════════════════════════════
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_license_verification_full_cycle() {
+        // Questa è la tua licenza di prova (ho aggiunto il prefisso LXFW. richiesto dal codice)
+        // Nota: i campi devono essere "c" (client) ed "e" (expiry) come definito nella tua struct LicensePayload
+    let valid_token = "LXFW.eyJjIjoicGlldHJvX3Rlc3QiLCJlIjoyMDg3NzAzMjcyNTg4LCJpZCI6IjVhOTFiYzNlLWQ0ZjctNGExMi05YzhiLTNmMWU3ZDJhMGI0NSJ9.gCTXtrcIcHatN-GPOQaXhYgyXn9Wn9XtJAArEbaOGNAJX0CP2z0tYJ7EV1DttWRn6MUxdyPZsgkgWcoIoK3aDA";
+
+        // 1. Test verifica positiva
+        let result = verify_license(valid_token.to_string());
+        assert!(result.valid, "La licenza valida è stata respinta! Errore: {}", result.message);
+        assert_eq!(result.client.unwrap(), "pietro_test");
+
+        // 2. Test Anti-Manomissione (cambiamo un solo carattere nella firma)
+        let mut tampered_token = valid_token.to_string();
+        tampered_token.replace_range(tampered_token.len()-5..tampered_token.len()-4, "Z");
+        let tamper_result = verify_license(tampered_token);
+        assert!(!tamper_result.valid, "Sicurezza fallita: la licenza manomessa è stata accettata!");
+        assert_eq!(tamper_result.message, "Firma non valida o licenza manomessa!");
+
+        // 3. Test Formato errato
+        let invalid_format = "TOKEN_SENZA_PUNTI";
+        let format_result = verify_license(invalid_format.to_string());
+        assert!(!format_result.valid);
+        assert_eq!(format_result.message, "Formato chiave non valido.");
+    }
+
+    #[test]
+    fn test_public_key_self_check() {
+        let real = public_key_status(&PUBLIC_KEY_BYTES);
+        assert_eq!(real["valid"], true, "la chiave pubblica compilata deve essere valida");
+        assert_eq!(real["fingerprint"].as_str().unwrap().len(), 16);
+
+        let zero = public_key_status(&[0u8; 32]);
+        assert_eq!(zero["valid"], false, "una chiave tutta a zero deve essere segnalata come non valida");
+    }
+
+    #[test]
+    fn test_confidential_practices_hidden_but_conflict_checked() {
+        let vault = json!({
+            "practices": [
+                {"id": "p1", "client": "Rossi Srl", "object": "Recupero crediti"},
+                {"id": "p2", "client": "Rossi Mario", "object": "Vertenza interna", "confidential": true},
+            ],
+            "contacts": [],
+        });
+        let practices = vault["practices"].as_array().unwrap().clone();
+        let (visible, hidden) = visible_practices(&practices);
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0]["id"], "p1");
+        assert_eq!(hidden, 1);
+
+        let conflicts = compute_conflicts(&vault, "rossi");
+        let matches = conflicts["practiceMatches"].as_array().unwrap();
+        assert_eq!(matches.len(), 2, "le pratiche riservate devono comparire nel conflict check");
+        let conf = matches.iter().find(|m| m["practice"]["id"] == "p2").unwrap();
+        assert_eq!(conf["confidential"], true);
+        // Without reauth only the id and a placeholder leave the backend
+        let mut masked = conflicts.clone();
+        mask_confidential_practice_matches(&mut masked);
+        let conf = masked["practiceMatches"].as_array().unwrap().iter().find(|m| m["practice"]["id"] == "p2").unwrap();
+        assert_eq!(conf["practice"], json!({"id": "p2", "client": "Fascicolo riservato"}));
+        assert_eq!(conf["masked"], true);
+        let open = masked["practiceMatches"].as_array().unwrap().iter().find(|m| m["practice"]["id"] == "p1").unwrap();
+        assert!(open.get("masked").is_none(), "le pratiche normali restano intere");
+
+        // A plain save of the visible list must not drop the confidential practice
+        let merged = merge_confidential_practices(json!(visible), &vault["practices"], false, false);
+        assert_eq!(merged.as_array().unwrap().len(), 2);
+        // …even with a reauth token, as every save has one when practices are protected
+        let merged = merge_confidential_practices(json!(visible), &vault["practices"], false, true);
+        assert_eq!(merged.as_array().unwrap().len(), 2, "un token senza lista completa non elimina le riservate");
+        let merged = merge_confidential_practices(json!(visible), &vault["practices"], true, false);
+        assert_eq!(merged.as_array().unwrap().len(), 2, "lista completa dichiarata ma senza token valido");
+        // …nor let a caller that never saw it overwrite it
+        let forged = json!([{"id": "p1"}, {"id": "p2", "client": "Altro"}]);
+        let merged = merge_confidential_practices(forged, &vault["practices"], false, false);
+        assert_eq!(merged.as_array().unwrap().len(), 2);
+        assert_eq!(merged[1]["client"], "Rossi Mario", "la versione salvata resta");
+        // A full list after re-authentication is authoritative: leaving p2 out deletes it
+        let merged = merge_confidential_practices(json!([{"id": "p1"}]), &vault["practices"], true, true);
+        assert_eq!(merged, json!([{"id": "p1"}]), "pratica riservata eliminata");
+    }
+
+    #[test]
+    fn test_client_billing_summary_mixed_statuses() {
+        let vault = json!({
+            "practices": [
+                {"id": "p1", "clientId": "c1"},
+                {"id": "p2", "clientId": "c1"},
+                {"id": "p3", "clientId": "c2"},
+            ],
+            "invoices": [
+                {"id": "i1", "practiceId": "p1", "date": "2026-02-01", "status": "draft",
+                 "items": [{"total": 100.0}]},
+                {"id": "i2", "practiceId": "p2", "date": "2026-03-01", "status": "sent",
+                 "subtotal": 1000.0, "cpa": 40.0, "iva": 228.8, "grandTotal": 1268.8},
+                {"id": "i3", "practiceId": "p1", "date": "2026-04-01", "status": "paid",
+                 "subtotal": 500.0, "cpa": 20.0, "iva": 114.4, "grandTotal": 634.4},
+                {"id": "i4", "practiceId": "p1", "date": "2025-12-01", "status": "paid",
+                 "subtotal": 999.0, "cpa": 0.0, "iva": 0.0, "grandTotal": 999.0},
+                {"id": "i5", "practiceId": "p3", "date": "2026-01-01", "status": "paid",
+                 "subtotal": 50.0, "cpa": 2.0, "iva": 11.44, "grandTotal": 63.44},
+            ],
+            "timeLogs": [
+                {"practiceId": "p1", "startedAt": "2026-05-01T09:00:00Z", "durationMin": 90, "billable": true, "hourlyRate": 200},
+                {"practiceId": "p2", "startedAt": "2026-05-02T09:00:00Z", "durationMin": 60, "billable": false, "hourlyRate": 200},
+                {"practiceId": "p2", "startedAt": "2026-05-03T09:00:00Z", "durationMin": 60, "billable": true, "hourlyRate": 150, "invoiceId": "i2"},
+                {"practiceId": "p3", "startedAt": "2026-05-03T09:00:00Z", "durationMin": 60, "billable": true, "hourlyRate": 150},
+            ],
+        });
+        let summary = compute_client_billing(&vault, "c1", Some(2026));
+        assert_eq!(summary["byStatus"]["draft"]["count"], 1);
+        assert_eq!(summary["byStatus"]["draft"]["taxable"], 104.0);
+        assert_eq!(summary["byStatus"]["draft"]["vat"], 22.88);
+        assert_eq!(summary["byStatus"]["issued"]["total"], 1268.8);
+        assert_eq!(summary["byStatus"]["paid"]["count"], 1, "fattura 2025 esclusa dal filtro anno");
+        assert_eq!(summary["collectedTotal"], 634.4);
+        assert_eq!(summary["billedTotal"], 1903.2);
+        assert_eq!(summary["unbilled"]["minutes"], 90.0);
+        assert_eq!(summary["unbilled"]["value"], 300.0);
+
+        let empty = compute_client_billing(&vault, "nessuno", None);
+        assert_eq!(empty["byStatus"]["paid"]["total"], 0.0);
+        assert_eq!(empty["unbilled"]["value"], 0.0);
+    }
+
+    #[test]
+    fn test_sweep_stale_tmp_files() {
+        let dir = std::env::temp_dir().join(format!("lexflow-sweep-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let stale = dir.join(".salt.tmp");
+        let fresh = dir.join(".vault.tmp");
+        let other = dir.join("vault.lex");
+        for f in [&stale, &fresh, &other] { fs::write(f, b"x").unwrap(); }
+        let old = SystemTime::now() - Duration::from_secs(STALE_TMP_AGE_SECS + 60);
+        fs::File::options().write(true).open(&stale).unwrap().set_modified(old).unwrap();
+        fs::File::options().write(true).open(&other).unwrap().set_modified(old).unwrap();
+
+        let removed = sweep_stale_tmp_files(&dir, Duration::from_secs(STALE_TMP_AGE_SECS));
+        assert_eq!(removed, vec![stale.clone()]);
+        assert!(!stale.exists());
+        assert!(fresh.exists(), "un .tmp recente può appartenere a una scrittura in corso");
+        assert!(other.exists());
+
+        // Interrupted change_password: nothing is swept
+        fs::write(dir.join(VAULT_BACKUP_FILE), b"bak").unwrap();
+        fs::File::options().write(true).open(&fresh).unwrap().set_modified(old).unwrap();
+        assert!(sweep_stale_tmp_files(&dir, Duration::from_secs(STALE_TMP_AGE_SECS)).is_empty());
+        assert!(fresh.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_burn_registry_export_import_round_trip() {
+        let h1 = compute_burn_hash("LXFW.a.b", "");
+        let h2 = compute_burn_hash("LXFW.c.d", "");
+        let h3 = compute_burn_hash("LXFW.e.f", "");
+        let sealed = seal_burn_registry(&[h1.clone(), h2.clone()], "Trasferimento!2026").unwrap();
+        assert!(sealed.starts_with(BURN_EXPORT_PREFIX));
+        assert!(!sealed.contains(&h1), "l'export non deve contenere hash in chiaro");
+
+        let opened = open_burn_registry(&sealed, "Trasferimento!2026").unwrap();
+        assert_eq!(opened, vec![h1.clone(), h2.clone()]);
+        assert_eq!(open_burn_registry(&sealed, "password-sbagliata"), Err(TRANSIT_WRONG_PASSWORD_ERR.to_string()));
+        // Malformed input is refused before decryption: not a wrong password, no lockout
+        let truncated = &sealed[..BURN_EXPORT_PREFIX.len() + 20];
+        for bad in ["testo qualsiasi", "LXBR1.!!!", truncated] {
+            let err = open_burn_registry(bad, "Trasferimento!2026").unwrap_err();
+            assert_ne!(err, TRANSIT_WRONG_PASSWORD_ERR, "{:?}", bad);
+        }
+
+        // Merge into a registry that already knows h2: no duplicates
+        let mut local = vec![h2.clone(), h3.clone()];
+        let added = merge_burn_hashes(&mut local, opened);
+        assert_eq!(added, 1);
+        assert_eq!(local, vec![h2, h3, h1]);
+    }
+
+    #[test]
+    fn test_focus_mode_only_critical_reminders_fire() {
+        let normal = json!({"id": "a", "title": "Chiamata cliente", "date": "2026-10-17", "time": "10:00"});
+        let critical = json!({"id": "b", "title": "Termine appello", "date": "2026-10-17", "time": "10:00", "urgency": "critical"});
+        assert!(!reminder_allowed(&normal, true), "promemoria normale soppresso in focus");
+        assert!(reminder_allowed(&critical, true), "promemoria critico sempre consegnato");
+        assert!(reminder_allowed(&normal, false));
+
+        let dir = std::env::temp_dir().join(format!("lexflow-focus-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(focus_until_ms(&dir), None);
+        let until = now_ms() + 60_000;
+        fs::write(dir.join(FOCUS_MODE_FILE), until.to_string()).unwrap();
+        assert_eq!(focus_until_ms(&dir), Some(until));
+        fs::write(dir.join(FOCUS_MODE_FILE), (now_ms() - 1).to_string()).unwrap();
+        assert_eq!(focus_until_ms(&dir), None, "focus scaduto = non attivo");
+        let _ = fs::remove_dir_all(&dir);
+
+        // Held-back reminders live in the schedule, once per item and event time
+        let at = local_datetime("2026-10-17", "10:00").unwrap();
+        let mut schedule = json!({"items": [normal.clone()]});
+        push_focus_deferred(&mut schedule, at, normal.clone());
+        push_focus_deferred(&mut schedule, at, normal.clone());
+        push_focus_deferred(&mut schedule, at + chrono::Duration::days(1), normal.clone());
+        let stored: Value = serde_json::from_slice(&serde_json::to_vec(&schedule).unwrap()).unwrap();
+        let deferred = focus_deferred_entries(&stored);
+        assert_eq!(deferred.len(), 2, "sopravvivono al riavvio, senza duplicati");
+        assert_eq!(deferred[0], (at, normal));
+        schedule["focusDeferred"].as_array_mut().unwrap().push(json!({"eventAtMs": "x"}));
+        assert_eq!(focus_deferred_entries(&schedule).len(), 2, "voci illeggibili ignorate");
+    }
+
+    #[test]
+    fn test_backup_schema_migration_and_rejection() {
+        // v1 backup (no schemaVersion, only practices/agenda) → migrated
+        let old = json!({"practices": [{"id": "p1"}], "agenda": []});
+        let migrated = migrate_backup_schema(old).unwrap();
+        assert_eq!(migrated["schemaVersion"], VAULT_SCHEMA_VERSION);
+        assert_eq!(migrated["practices"][0]["id"], "p1");
+        for section in VAULT_SECTIONS {
+            assert!(migrated[*section].is_array(), "sezione {} mancante dopo la migrazione", section);
+        }
+
+        // Same version → unchanged
+        let same = json!({"schemaVersion": VAULT_SCHEMA_VERSION, "practices": [], "agenda": [], "contacts": [{"id": "c"}]});
+        assert_eq!(migrate_backup_schema(same.clone()).unwrap()["contacts"], same["contacts"]);
+
+        // Newer → refused with both versions in the message
+        let newer = json!({"schemaVersion": VAULT_SCHEMA_VERSION + 1, "practices": []});
+        let err = migrate_backup_schema(newer).unwrap_err();
+        assert!(err.contains(&(VAULT_SCHEMA_VERSION + 1).to_string()));
+        assert!(err.contains(&VAULT_SCHEMA_VERSION.to_string()));
+    }
+
+    #[test]
+    fn test_partial_export_and_import_keeps_omitted_sections() {
+        let vault = json!({
+            "practices": [{"id": "p1", "client": "Bianchi", "attachments": [{"name": "atto.pdf", "path": "/doc/atto.pdf"}]}],
+            "agenda": [{"id": "a1"}],
+            "timeLogs": [{"id": "t1"}],
+            "invoices": [{"id": "i1"}],
+            "contacts": [{"id": "c1"}],
+        });
+        let opts = ExportOptions {
+            include_attachments: false,
+            include_audit: false,
+            sections: vec!["practices".into(), "contacts".into()],
+        };
+        let backup = build_export_payload(&vault, Some(json!([{"event": "x"}])), &opts);
+        assert!(backup.get("agenda").is_none());
+        assert!(backup.get("invoices").is_none());
+        assert!(backup.get("auditLog").is_none());
+        assert!(backup["practices"][0].get("attachments").is_none());
+        assert_eq!(backup["manifest"]["partial"], true);
+
+        // Import onto a vault that has since changed: omitted sections are preserved
+        let current = json!({
+            "practices": [{"id": "p1", "client": "Bianchi", "attachments": [{"name": "atto.pdf", "path": "/doc/atto.pdf"}]}],
+            "agenda": [{"id": "a1"}, {"id": "a2"}],
+            "timeLogs": [],
+            "invoices": [{"id": "i1"}, {"id": "i2"}],
+            "contacts": [],
+            "documentKeys": {"b1": "00ff"},
+        });
+        let restored = merge_partial_backup(&current, migrate_backup_schema(backup).unwrap());
+        assert_eq!(restored["agenda"].as_array().unwrap().len(), 2);
+        assert_eq!(restored["invoices"].as_array().unwrap().len(), 2);
+        assert_eq!(restored["contacts"][0]["id"], "c1");
+        assert_eq!(restored["practices"][0]["attachments"][0]["name"], "atto.pdf");
+        assert_eq!(restored["documentKeys"]["b1"], "00ff", "le chiavi dei documenti allegati dopo il backup vanno conservate");
+        let mut written = restored.clone();
+        strip_backup_envelope(&mut written);
+        assert!(written.get("manifest").is_none() && written.get("schemaVersion").is_none(), "l'involucro del backup non va scritto nel vault");
+        assert_eq!(written["contacts"], restored["contacts"]);
+
+        // Full export is not partial
+        let full = ExportOptions { include_attachments: true, include_audit: true,
+            sections: VAULT_SECTIONS.iter().map(|s| s.to_string()).collect() };
+        let full_backup = build_export_payload(&vault, Some(json!([])), &full);
+        assert_eq!(full_backup["manifest"]["partial"], false);
+        assert_eq!(full_backup["manifest"]["includesAudit"], true);
+    }
+
+    #[test]
+    fn test_timelog_overlaps() {
+        let logs = vec![
+            // Clear overlap: 10:00-11:00 vs 10:30-12:00 → 30 min
+            json!({"id": "a", "practiceId": "p1", "startedAt": "2026-03-10T10:00:00Z", "endedAt": "2026-03-10T11:00:00Z", "durationMin": 60}),
+            json!({"id": "b", "practiceId": "p2", "startedAt": "2026-03-10T10:30:00Z", "endedAt": "2026-03-10T12:00:00Z", "durationMin": 90}),
+            // Adjacent to b: starts exactly when b ends → no overlap
+            json!({"id": "c", "practiceId": "p3", "startedAt": "2026-03-10T12:00:00Z", "endedAt": "2026-03-10T12:30:00Z", "durationMin": 30}),
+            // Duration only → skipped
+            json!({"id": "d", "practiceId": "p1", "durationMin": 45}),
+        ];
+        let result = compute_timelog_overlaps(&logs, None);
+        let overlaps = result["overlaps"].as_array().unwrap();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0]["a"]["id"], "a");
+        assert_eq!(overlaps[0]["b"]["id"], "b");
+        assert_eq!(overlaps[0]["overlapMinutes"], 30);
+        assert_eq!(result["skippedWithoutInterval"], 1);
+
+        // Day filter that matches nothing
+        let none = compute_timelog_overlaps(&logs, Some("2020-01-01"));
+        assert!(none["overlaps"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_practice_folder_listing() {
+        let dir = std::env::temp_dir().join(format!("lexflow-docs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sottocartella")).unwrap();
+        fs::write(dir.join("citazione.pdf"), b"%PDF-1.4").unwrap();
+        fs::write(dir.join("atto.docx"), b"1234567890").unwrap();
+        fs::write(dir.join(".DS_Store"), b"x").unwrap();
+
+        let folder = validate_practice_folder(dir.to_str().unwrap()).unwrap();
+        let listing = list_folder_documents(&folder);
+        assert_eq!(listing["exists"], true);
+        let files = listing["files"].as_array().unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["atto.docx", "citazione.pdf"]);
+        assert_eq!(files[0]["size"], 10);
+        assert!(files[0]["modified"].is_string());
+
+        assert!(validate_practice_folder("relativa/cartella").is_err());
+        assert!(validate_practice_folder(dir.join("citazione.pdf").to_str().unwrap()).is_err());
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(list_folder_documents(&dir)["exists"], false);
+    }
+
+    #[test]
+    fn test_encrypted_document_blobs() {
+        let dir = std::env::temp_dir().join(format!("lexflow-blobs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("atto.pdf");
+        fs::write(&src, b"%PDF-1.4 ricorso").unwrap();
+        assert_eq!(validate_document_source(&src), Ok(16));
+        assert!(validate_document_source(std::path::Path::new("relativo.pdf")).is_err());
+        assert!(validate_document_source(&dir).is_err(), "le cartelle non sono documenti");
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.pdf");
+            std::os::unix::fs::symlink(&src, &link).unwrap();
+            assert!(validate_document_source(&link).is_err(), "symlink rifiutato");
+        }
+        let big = dir.join("grande.bin");
+        fs::File::create(&big).unwrap().set_len(MAX_DOCUMENT_BYTES + 1).unwrap();
+        assert!(validate_document_source(&big).is_err(), "oltre il limite");
+
+        let doc = seal_document(b"%PDF-1.4 ricorso").unwrap();
+        assert!(!doc.blob.windows(7).any(|w| w == b"ricorso"), "contenuto cifrato");
+        assert_eq!(open_document(&doc.key, &doc.blob, &doc.sha256).unwrap(), b"%PDF-1.4 ricorso");
+        assert!(open_document(&[1u8; 32], &doc.blob, &doc.sha256).is_err(), "chiave errata");
+        assert!(open_document(&doc.key, &doc.blob, &"0".repeat(64)).is_err(), "impronta diversa");
+
+        assert!(document_blob_path(&dir, "../../etc/passwd").is_err());
+        assert!(document_blob_path(&dir, &"a".repeat(32)).unwrap().starts_with(dir.join(DOCUMENT_BLOBS_DIR)));
+        let vault = json!({"practices": [
+            {"id": "p1", "documents": [{"path": "/tmp/x.pdf"}]},
+            {"id": "p2", "documents": [{"blobId": "b0"}, {"blobId": "b1"}]},
+        ]});
+        assert_eq!(document_location(&vault, "b1"), Some((1, 1)));
+        assert_eq!(document_location(&vault, "b9"), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_invoice_aging_buckets_and_boundaries() {
+        let inv = |id: &str, status: &str, extra: Value| {
+            let mut v = json!({"id": id, "status": status, "grandTotal": 100.0});
+            for (k, val) in extra.as_object().unwrap() { v[k] = val.clone(); }
+            v
+        };
+        let invoices = vec![
+            inv("due-today", "sent", json!({"dueDate": "2026-06-30"})),            // 0 → 0-30
+            inv("d30", "sent", json!({"dueDate": "2026-05-31"})),                  // 30 → 0-30
+            inv("d31", "sent", json!({"dueDate": "2026-05-30"})),                  // 31 → 31-60
+            inv("d60", "issued", json!({"dueDate": "2026-05-01"})),                // 60 → 31-60
+            inv("d61", "sent", json!({"date": "2026-04-01", "paymentTermsDays": 29})), // due 04-30 → 61
+            inv("d91", "sent", json!({"date": "2026-03-31"})),                     // no terms: due on issue → 91
+            inv("future", "sent", json!({"dueDate": "2026-07-15"})),               // not due yet
+            inv("paid", "paid", json!({"dueDate": "2026-01-01"})),                 // ignored
+            inv("draft", "draft", json!({"dueDate": "2026-01-01"})),               // ignored
+            inv("partial", "sent", json!({"dueDate": "2026-06-01", "paidAmount": 40.0})), // 29 → 0-30, 60 left
+        ];
+        let as_of = chrono::NaiveDate::from_ymd_opt(2026, 6, 30).unwrap();
+        let aging = compute_invoice_aging(&invoices, as_of);
+        let ids = |b: &str| aging["buckets"][b]["invoices"].as_array().unwrap()
+            .iter().map(|i| i["id"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(ids("0-30"), vec!["due-today", "d30", "partial"]);
+        assert_eq!(ids("31-60"), vec!["d31", "d60"]);
+        assert_eq!(ids("61-90"), vec!["d61"]);
+        assert_eq!(ids("90+"), vec!["d91"]);
+        assert_eq!(ids("notDue"), vec!["future"]);
+        assert_eq!(aging["buckets"]["0-30"]["total"], 260.0);
+        assert_eq!(aging["outstandingTotal"], 760.0);
+    }
+
+    #[test]
+    fn test_license_receipt_is_reproducible() {
+        let record = json!({
+            "tokenHmac": "ab".repeat(32),
+            "activatedAt": "2026-01-15T09:00:00+00:00",
+            "client": "Studio Rossi",
+            "keyVersion": "ed25519-burned",
+            "machineFingerprint": "f".repeat(64),
+            "keyId": "KEY-0042",
+            "expiryMs": 1_800_000_000_000u64,
+        });
+        let key = [7u8; 32];
+        let a = build_license_receipt(&record, &key).unwrap();
+        let b = build_license_receipt(&record, &key).unwrap();
+        assert_eq!(a["keyId"], "KEY-0042");
+        assert_eq!(a, b, "la ricevuta deve essere riproducibile");
+        assert!(a.get("tokenHmac").is_none(), "nessun dato segreto nella ricevuta");
+        let mut edited = record.clone();
+        edited["client"] = json!("Altro Studio");
+        assert_ne!(build_license_receipt(&edited, &key).unwrap()["deviceTag"], a["deviceTag"]);
+        assert!(build_license_receipt(&json!({"keyVersion": "ed25519-burned"}), &key).is_err());
+    }
+
+    #[test]
+    fn test_remind_minutes_resolution_precedence() {
+        let settings = json!({"reminderDefaultsByArea": {"penale": 1440, "civile": 10080}});
+        // 1. explicit item offset wins over everything
+        let explicit = json!({"remindMinutes": 15, "practiceArea": "penale"});
+        assert_eq!(resolve_remind_minutes(&explicit, &settings), 15);
+        let offsets = json!({"reminderOffsets": [60, 120], "practiceArea": "penale"});
+        assert_eq!(resolve_remind_minutes(&offsets, &settings), 120);
+        // 2. area default when the item has none
+        let by_area = json!({"remindMinutes": null, "practiceArea": "civile"});
+        assert_eq!(resolve_remind_minutes(&by_area, &settings), 10080);
+        // 3. global fallback: unknown area, no area, then the user's preavviso
+        let unknown = json!({"practiceArea": "tributario"});
+        assert_eq!(resolve_remind_minutes(&unknown, &settings), 30);
+        assert_eq!(resolve_remind_minutes(&json!({}), &json!({})), 30);
+        assert_eq!(resolve_remind_minutes(&json!({}), &json!({"preavviso": 45})), 45);
+
+        let mut schedule = json!({"items": [
+            {"id": "a", "practiceId": "p1"},
+            {"id": "b", "practiceId": "p2", "practiceArea": "penale"},
+            {"id": "c"},
+        ]});
+        let practices = vec![json!({"id": "p1", "type": "civile"}), json!({"id": "p2", "area": "civile"})];
+        stamp_practice_areas(&mut schedule, &practices);
+        assert_eq!(schedule["items"][0]["practiceArea"], "civile");
+        assert_eq!(schedule["items"][1]["practiceArea"], "penale", "l'area esplicita non va sovrascritta");
+        assert!(schedule["items"][2].get("practiceArea").is_none());
+    }
+
+    fn write_test_license(dir: &std::path::Path, fingerprint: &str) {
+        let record = json!({
+            "tokenHmac": "ab".repeat(32),
+            "activatedAt": "2026-01-15T09:00:00+00:00",
+            "client": "Studio Rossi",
+            "keyVersion": "ed25519-burned",
+            "machineFingerprint": fingerprint,
+            "keyId": "KEY-0042",
+            "expiryMs": now_ms() + 86_400_000,
+        });
+        let enc = encrypt_data(&get_local_encryption_key().unwrap(), &serde_json::to_vec(&record).unwrap()).unwrap();
+        fs::write(dir.join(LICENSE_FILE), enc).unwrap();
+    }
+
+    #[test]
+    fn test_repair_license_missing_sentinel() {
+        let dir = std::env::temp_dir().join(format!("lexflow-repair-s-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fp = "f".repeat(64);
+        write_test_license(&dir, &fp);
+        write_burned_keys(&dir, &["0".repeat(64)]).unwrap();
+
+        let changes = reconcile_license_files(&dir, &fp, now_ms()).unwrap();
+        assert_eq!(changes.len(), 1);
+        let sentinel = fs::read_to_string(dir.join(LICENSE_SENTINEL_FILE)).unwrap();
+        let enc_id = hex::decode(sentinel.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(decrypt_data(&get_local_encryption_key().unwrap(), &enc_id).unwrap(), b"KEY-0042");
+        // Already consistent: second run changes nothing
+        assert!(reconcile_license_files(&dir, &fp, now_ms()).unwrap().is_empty());
+        // License bound to another machine is never repaired
+        fs::remove_file(dir.join(LICENSE_SENTINEL_FILE)).unwrap();
+        assert!(reconcile_license_files(&dir, &"e".repeat(64), now_ms()).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn verify_deactivation_receipt(receipt: &Value, device_key: &[u8]) -> bool {
+        let field = |k: &str| receipt.get(k).and_then(|v| v.as_str()).unwrap_or("");
+        let Ok(tag) = hex::decode(field("deviceTag")) else { return false };
+        !field("keyId").is_empty()
+            && deactivation_mac(device_key, field("keyId"), field("machineFingerprint"), field("deactivatedAt"))
+                .verify_slice(&tag).is_ok()
+    }
+
+    #[test]
+    fn test_deactivate_license_keeps_burn_entry() {
+        let dir = std::env::temp_dir().join(format!("lexflow-deactivate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fp = "f".repeat(64);
+        write_test_license(&dir, &fp);
+        assert!(deactivate_license_files(&dir, &"e".repeat(64), "2026-10-18T10:00:00+00:00").is_err(), "mai su un altro dispositivo");
+        assert!(dir.join(LICENSE_FILE).exists());
+
+        write_license_sentinel(&dir.join(LICENSE_SENTINEL_FILE), &get_local_encryption_key().unwrap(), &fp, "KEY-0042", "x").unwrap();
+        let receipt = deactivate_license_files(&dir, &fp, "2026-10-18T10:00:00+00:00").unwrap();
+        assert_eq!(receipt["keyId"], "KEY-0042");
+        let key = get_local_encryption_key().unwrap();
+        assert!(verify_deactivation_receipt(&receipt, &key));
+        let mut edited = receipt.clone();
+        edited["deactivatedAt"] = json!("2026-10-19T10:00:00+00:00");
+        assert!(!verify_deactivation_receipt(&edited, &key), "ricevuta modificata: firma non valida");
+        assert!(!dir.join(LICENSE_FILE).exists() && !dir.join(LICENSE_SENTINEL_FILE).exists());
+        assert!(dir.join(LICENSE_DEACTIVATION_FILE).exists());
+        assert!(load_burned_keys(&dir).contains(&compute_keyid_burn_hash("KEY-0042")), "la chiave resta bruciata");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_repair_license_missing_registry() {
+        let dir = std::env::temp_dir().join(format!("lexflow-repair-r-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fp = "f".repeat(64);
+        write_test_license(&dir, &fp);
+        write_license_sentinel(&dir.join(LICENSE_SENTINEL_FILE), &get_local_encryption_key().unwrap(), &fp, "KEY-0042", "x").unwrap();
+
+        let changes = reconcile_license_files(&dir, &fp, now_ms()).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(load_burned_keys(&dir), vec![compute_keyid_burn_hash("KEY-0042")]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_support_code_bound_to_machine_and_expiry() {
+        use ed25519_dalek::{Signer, SigningKey};
+        let signing = SigningKey::from_bytes(&[9u8; 32]);
+        let public = signing.verifying_key().to_bytes();
+        let make = |fp: &str, e: u64| {
+            let payload = URL_SAFE_NO_PAD.encode(json!({"a": "repair-license", "fp": fp, "e": e}).to_string());
+            let sig = URL_SAFE_NO_PAD.encode(signing.sign(payload.as_bytes()).to_bytes());
+            format!("LXRP.{}.{}", payload, sig)
+        };
+        let now = 1_000_000;
+        assert!(verify_support_code(&make("fp-a", now + 1), &public, "fp-a", now).is_ok());
+        assert!(verify_support_code(&make("fp-b", now + 1), &public, "fp-a", now).is_err(), "altro dispositivo");
+        assert!(verify_support_code(&make("fp-a", now - 1), &public, "fp-a", now).is_err(), "scaduto");
+        assert!(verify_support_code(&make("fp-a", now + 1), &PUBLIC_KEY_BYTES, "fp-a", now).is_err(), "firma estranea");
+    }
+
+    #[test]
+    fn test_capabilities_include_core_features() {
+        let caps = capabilities();
+        assert_eq!(caps["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(caps["schemaVersion"], VAULT_SCHEMA_VERSION);
+        let features: Vec<&str> = caps["features"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+        for core in ["merge_import", "attachments", "export_options", "reauth_token", "focus_mode"] {
+            assert!(features.contains(&core), "manca la capability {}", core);
+        }
+        assert!(!features.contains(&"recovery_key"), "non pubblicizzare funzioni non compilate");
+    }
+
+    #[test]
+    fn test_audit_log_readable_across_password_change() {
+        let dir = std::env::temp_dir().join(format!("lexflow-audit-rekey-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let old_key = vec![1u8; 32];
+        let new_key = vec![2u8; 32];
+        let read = |key: &[u8]| -> Vec<Value> {
+            let k = audit_log_key(&dir, key);
+            serde_json::from_slice(&decrypt_data(&k, &fs::read(dir.join(AUDIT_LOG_FILE)).unwrap()).unwrap()).unwrap()
+        };
+        let entries = json!([{"event": "Sblocco Vault"}]);
+        fs::write(dir.join(AUDIT_LOG_FILE), encrypt_data(&old_key, entries.to_string().as_bytes()).unwrap()).unwrap();
+
+        // Stale marker (crash before renames): old key still in use, marker ignored
+        fs::write(dir.join(AUDIT_REKEY_FILE), encrypt_data(&new_key, &old_key).unwrap()).unwrap();
+        assert_eq!(read(&old_key).len(), 1);
+
+        // Renames done, re-key pending: readable with the NEW vault key via the marker
+        assert_eq!(read(&new_key).len(), 1, "log illeggibile durante il re-key");
+
+        rekey_audit_log(&dir, &new_key).unwrap();
+        assert!(!dir.join(AUDIT_REKEY_FILE).exists());
+        let raw = fs::read(dir.join(AUDIT_LOG_FILE)).unwrap();
+        assert!(decrypt_data(&new_key, &raw).is_ok(), "log non ricifrato con la nuova chiave");
+        assert_eq!(read(&new_key).len(), 1);
+
+        // Crash after rewrite but before marker removal: re-run only drops the marker
+        fs::write(dir.join(AUDIT_REKEY_FILE), encrypt_data(&new_key, &old_key).unwrap()).unwrap();
+        rekey_audit_log(&dir, &new_key).unwrap();
+        assert_eq!(read(&new_key).len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_agenda_ics_events() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+BEGIN:VEVENT\r\nUID:udienza-1@cal\r\nDTSTART;TZID=Europe/Rome:20261105T093000\r\nDTEND;TZID=Europe/Rome:20261105T103000\r\n\
+SUMMARY:Udienza Rossi\\, Trib. Milano\r\nRRULE:FREQ=WEEKLY;COUNT=3\r\n\
+BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT1H30M\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+BEGIN:VEVENT\r\nUID:deposito-2@cal\r\nDTSTART;VALUE=DATE:20261110\r\nSUMMARY:Deposito memoria\r\n integrativa\r\nEND:VEVENT\r\n\
+BEGIN:VEVENT\r\nUID:rotto-3@cal\r\nDTSTART:domani\r\nSUMMARY:Rotto\r\nEND:VEVENT\r\n\
+END:VCALENDAR\r\n";
+        let (items, skipped) = parse_ics_events(ics);
+        assert_eq!(items.len(), 2);
+        let timed = &items[0];
+        assert_eq!(timed["date"], "2026-11-05");
+        assert_eq!(timed["timeStart"], "09:30");
+        assert_eq!(timed["timeEnd"], "10:30");
+        assert_eq!(timed["title"], "Udienza Rossi, Trib. Milano");
+        assert_eq!(timed["remindMinutes"], 90);
+        assert_eq!(timed["recurrence"], "FREQ=WEEKLY;COUNT=3");
+        assert_eq!(timed["timezone"], "Europe/Rome");
+
+        let all_day = &items[1];
+        assert_eq!(all_day["date"], "2026-11-10");
+        assert_eq!(all_day["allDay"], true);
+        assert_eq!(all_day["timeStart"], "");
+        assert_eq!(all_day["title"], "Deposito memoriaintegrativa", "righe ripiegate RFC 5545");
+
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0]["uid"], "rotto-3@cal");
+        assert!(skipped[0]["reason"].as_str().unwrap().contains("DTSTART"));
+
+        // Re-importing the same file is deduplicated by UID
+        let mut agenda = items.clone();
+        let mut skipped2 = Vec::new();
+        assert_eq!(merge_ics_items(&mut agenda, items, &mut skipped2), 0);
+        assert_eq!(skipped2.len(), 2);
+    }
+
+    #[test]
+    fn test_audit_level_filters_events() {
+        let key = vec![3u8; 32];
+        let script = [
+            (AuditKind::Security, "Sblocco Vault"),
+            (AuditKind::Data, "Fascicoli salvati (3 elementi)"),
+            (AuditKind::Security, "Registro chiavi esportato"),
+            (AuditKind::Data, "Agenda salvata (12 elementi)"),
+        ];
+        let run = |level: &str| -> Vec<String> {
+            let dir = std::env::temp_dir().join(format!("lexflow-audit-level-{}-{}", level, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            for (kind, event) in script {
+                write_audit_event(&dir, &key, level, kind, AuditCategory::Auth, None, event).unwrap();
+            }
+            let events = match fs::read(dir.join(AUDIT_LOG_FILE)) {
+                Ok(enc) => serde_json::from_slice::<Vec<Value>>(&decrypt_data(&key, &enc).unwrap()).unwrap()
+                    .iter().map(|e| e["event"].as_str().unwrap().to_string()).collect(),
+                Err(_) => vec![],
+            };
+            let _ = fs::remove_dir_all(&dir);
+            events
+        };
+        assert_eq!(run("security"), vec!["Sblocco Vault", "Registro chiavi esportato"]);
+        assert_eq!(run("verbose").len(), 4);
+        assert!(run("off").is_empty());
+
+        // "off" still records tampering
+        let dir = std::env::temp_dir().join(format!("lexflow-audit-tamper-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(AUDIT_LOG_FILE), b"garbage").unwrap();
+        write_audit_event(&dir, &key, "off", AuditKind::Security, AuditCategory::Auth, None, "Sblocco Vault").unwrap();
+        let logs: Vec<Value> = serde_json::from_slice(&decrypt_data(&key, &fs::read(dir.join(AUDIT_LOG_FILE)).unwrap()).unwrap()).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["event"], "AUDIT_LOG_TAMPERING_DETECTED");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_contact_vcard() {
+        let contact = json!({
+            "id": "c1", "name": "Mario Rossi", "email": "mario@example.it",
+            "pec": "mario.rossi@pec.it", "fiscalCode": "RSSMRA80A01F205X",
+            "address": "Via Roma 1, Milano", "notes": "cliente difficile",
+        });
+        let vcard = contact_to_vcard(&contact);
+        assert!(vcard.starts_with("BEGIN:VCARD\r\nVERSION:3.0\r\n"));
+        assert!(vcard.contains("FN:Mario Rossi"));
+        assert!(vcard.contains("EMAIL;TYPE=INTERNET:mario@example.it"));
+        assert!(vcard.contains("X-CODICE-FISCALE:RSSMRA80A01F205X"));
+        assert!(vcard.contains("ADR;TYPE=WORK:;;Via Roma 1\\, Milano;;;;"));
+        assert!(!vcard.contains("difficile"), "le note interne non vanno esportate");
+
+        let sealed = seal_for_transit(VCARD_EXPORT_PREFIX, vcard.as_bytes(), "transito-123").unwrap();
+        assert!(sealed.starts_with(VCARD_EXPORT_PREFIX));
+        assert!(!sealed.contains("Mario") && !sealed.contains("mario@example.it"));
+        let opened = open_from_transit(VCARD_EXPORT_PREFIX, &sealed, "transito-123").unwrap();
+        assert_eq!(opened, vcard.as_bytes());
+        assert!(open_from_transit(VCARD_EXPORT_PREFIX, &sealed, "sbagliata").is_err());
+    }
+
+    #[test]
+    fn test_missing_verify_tag_self_heals() {
+        let dir = std::env::temp_dir().join(format!("lexflow-verify-heal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let salt = vec![5u8; ARGON2_SALT_LEN];
+        let key = derive_secure_key("Password-Giusta-1", &salt).unwrap();
+        fs::write(dir.join(VAULT_SALT_FILE), &salt).unwrap();
+        fs::write(dir.join(VAULT_FILE), encrypt_data(&key, br#"{"practices":[]}"#).unwrap()).unwrap();
+        // vault.verify lost
+
+        assert!(authenticate_vault_password("Password-Sbagliata-1", &dir).is_err());
+        assert!(!dir.join(VAULT_VERIFY_FILE).exists(), "una password errata non deve creare il tag");
+
+        assert_eq!(authenticate_vault_password("Password-Giusta-1", &dir).unwrap(), key);
+        let tag = fs::read(dir.join(VAULT_VERIFY_FILE)).unwrap();
+        assert!(verify_hash_matches(&key, &tag));
+        // Healed: subsequent checks go through the normal tag path
+        assert_eq!(check_vault_key(&dir, &key), Ok(false));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_firm_activity_report_aggregates_range() {
+        let audit = vec![
+            json!({"event": "Sblocco Vault", "time": "2026-03-01T12:00:00+01:00"}),
+            json!({"event": "Sblocco Vault (biometria)", "time": "2026-03-31T12:00:00+02:00"}),
+            json!({"event": "Password cambiata", "time": "2026-03-15T12:00:00+01:00"}),
+            json!({"event": "Fascicoli salvati (4 elementi)", "time": "2026-03-02T12:00:00+01:00"}),
+            json!({"event": "Sblocco Vault", "time": "2026-04-01T12:00:00+02:00"}), // out of range
+        ];
+        let vault = json!({
+            "practices": [
+                {"id": "p1", "client": "Rossi", "status": "active", "createdAt": "2026-03-01"},
+                {"id": "p2", "client": "Bianchi", "status": "closed", "createdAt": "2025-12-01", "closedAt": "2026-03-31"},
+                {"id": "p3", "client": "Verdi", "status": "active", "createdAt": "2026-04-02"},
+                {"id": "p4", "client": "Neri", "status": "active", "createdAt": "2026-03-20", "confidential": true},
+            ],
+            "invoices": [
+                {"status": "sent", "date": "2026-03-10", "grandTotal": 100.0},
+                {"status": "paid", "date": "2026-03-31", "grandTotal": 50.5},
+                {"status": "draft", "date": "2026-03-10", "grandTotal": 999.0},
+                {"status": "sent", "date": "2026-02-28", "grandTotal": 10.0},
+            ],
+            "timeLogs": [
+                {"startedAt": "2026-03-05", "durationMin": 90, "billable": true},
+                {"startedAt": "2026-03-06", "durationMin": 30, "billable": false},
+                {"startedAt": "2026-05-01", "durationMin": 600, "billable": true},
+            ],
+        });
+        let from = chrono::NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let to = chrono::NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        // Pinned to Rome winter time: the range edges must not depend on the host zone
+        let rome = chrono::FixedOffset::east_opt(3600).unwrap();
+        let r = build_activity_report(&audit, &vault, from, to, &rome, false);
+        assert_eq!(r["audit"]["logins"], 2);
+        assert_eq!(r["audit"]["passwordChanges"], 1);
+        assert_eq!(r["audit"]["events"]["Fascicoli salvati"], 1);
+        assert_eq!(r["practices"]["opened"], 2, "estremi inclusi, riservate contate");
+        assert_eq!(r["practices"]["openedList"], json!([{"id": "p1", "client": "Rossi", "date": "2026-03-01"}]));
+        assert_eq!(r["practices"]["hiddenConfidential"], 1);
+        let revealed = build_activity_report(&audit, &vault, from, to, &rome, true);
+        assert_eq!(revealed["practices"]["openedList"][1]["client"], "Neri", "dopo la riautenticazione la riservata è elencata");
+        assert_eq!(revealed["practices"]["hiddenConfidential"], 0);
+        assert_eq!(r["practices"]["closed"], 1);
+        assert_eq!(r["invoices"]["issued"], 2);
+        assert_eq!(r["invoices"]["issuedTotal"], 150.5);
+        assert_eq!(r["hours"]["total"], 2.0);
+        assert_eq!(r["hours"]["billable"], 1.5);
+
+        let mut list = json!([{"id": "p1", "status": "closed"}, {"id": "p9", "status": "active"}]);
+        stamp_practice_status_changes(&mut list, &json!([{"id": "p1", "status": "active"}]), "2026-03-20T10:00:00Z");
+        assert_eq!(list[0]["closedAt"], "2026-03-20T10:00:00Z");
+        assert_eq!(list[1]["createdAt"], "2026-03-20T10:00:00Z");
+
+        // Queued practice saves are stamped when flushed
+        let mut queued = json!({"practices": [{"id": "p1", "status": "active"}]});
+        let mut pending = serde_json::Map::new();
+        pending.insert("practices".into(), json!([{"id": "p1", "status": "closed"}]));
+        apply_pending_saves(&mut queued, pending);
+        assert!(queued["practices"][0]["closedAt"].is_string(), "anche i salvataggi in coda registrano la chiusura");
+        assert_eq!(queued["practices"][0]["closedAt"], queued["practices"][0]["statusChangedAt"]);
+    }
+
+    #[test]
+    fn test_change_password_dry_run() {
+        let dir = std::env::temp_dir().join(format!("lexflow-dry-run-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let salt = vec![6u8; ARGON2_SALT_LEN];
+        let key = derive_secure_key("Attuale-Passw0rd!", &salt).unwrap();
+        fs::write(dir.join(VAULT_SALT_FILE), &salt).unwrap();
+        fs::write(dir.join(VAULT_VERIFY_FILE), make_verify_tag(&key)).unwrap();
+        fs::write(dir.join(VAULT_FILE), encrypt_data(&key, b"{}").unwrap()).unwrap();
+        let snapshot = || {
+            let mut names: Vec<String> = fs::read_dir(&dir).unwrap().flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string()).collect();
+            names.sort();
+            names
+        };
+        let before = snapshot();
+
+        let wrong = change_password_preflight(&dir, "Sbagliata-Passw0rd!", "Nuova-Passw0rd!!", Some(1 << 30));
+        assert_eq!(wrong["go"], false);
+        assert_eq!(wrong["reasons"][0], "Password attuale errata");
+
+        let weak = change_password_preflight(&dir, "Attuale-Passw0rd!", "corta", Some(1 << 30));
+        assert_eq!(weak["go"], false);
+        assert!(weak["reasons"][0].as_str().unwrap().contains("troppo debole"));
+
+        let full = change_password_preflight(&dir, "Attuale-Passw0rd!", "Nuova-Passw0rd!!", Some(10));
+        assert_eq!(full["go"], false, "disco pieno");
+
+        let go = change_password_preflight(&dir, "Attuale-Passw0rd!", "Nuova-Passw0rd!!", Some(1 << 30));
+        assert_eq!(go["go"], true, "{:?}", go["reasons"]);
+        assert!(go["estimate"]["requiredBytes"].as_u64().unwrap() > go["estimate"]["vaultBytes"].as_u64().unwrap());
+
+        assert_eq!(snapshot(), before, "il dry run non deve scrivere nulla");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_protected_section_requires_reauth() {
+        let settings = json!({"protectedSections": ["invoices", "contacts"]});
+        let mut slot: ReauthSlot = None;
+        assert_eq!(authorize_section(&settings, "invoices", &mut slot, None), Err(REAUTH_REQUIRED_ERR.to_string()));
+        assert!(authorize_section(&settings, "agenda", &mut slot, None).is_ok(), "sezione non protetta");
+
+        // After reauth the same token opens reads and writes until it expires
+        slot = Some((Zeroizing::new("tok-123".to_string()), Instant::now() + Duration::from_secs(60)));
+        assert!(authorize_section(&settings, "invoices", &mut slot, Some("tok-123")).is_ok());
+        assert!(authorize_section(&settings, "contacts", &mut slot, Some("tok-123")).is_ok());
+        assert!(authorize_section(&settings, "invoices", &mut slot, Some("tok-999")).is_err());
+        slot = Some((Zeroizing::new("tok-123".to_string()), Instant::now() - Duration::from_secs(1)));
+        assert!(authorize_section(&settings, "invoices", &mut slot, Some("tok-123")).is_err(), "token scaduto");
+
+        // Conflict check on protected contacts: match reported, details masked
+        let vault = json!({"contacts": [{"id": "c1", "name": "Mario Rossi", "fiscalCode": "RSSMRA80A01F205X"}], "practices": []});
+        let mut result = compute_conflicts(&vault, "rossi");
+        mask_contact_matches(&mut result);
+        let m = &result["contactMatches"][0];
+        assert_eq!(m["contact"]["name"], "Mario Rossi");
+        assert!(m["contact"].get("fiscalCode").is_none());
+        assert_eq!(m["masked"], true);
+    }
+
+    #[test]
+    fn test_estimated_unlock_ms_matches_measured_derivation() {
+        let mut cache = None;
+        let estimate = cached_kdf_estimate(&mut cache, &DEFAULT_KDF_PARAMS).unwrap();
+        assert!(estimate > 0);
+        let start = Instant::now();
+        derive_secure_key("Password-Reale-1!", &[9u8; ARGON2_SALT_LEN]).unwrap();
+        let measured = start.elapsed().as_millis() as u64;
+        // Same params, same machine: same order of magnitude (generous bounds for CI noise)
+        assert!(estimate <= measured * 10 + 50 && measured <= estimate * 10 + 50,
+            "stima {} ms vs misurato {} ms", estimate, measured);
+        // Fresh cache is reused, a stale one is re-measured
+        cache.as_mut().unwrap().ms = 123_456;
+        assert_eq!(cached_kdf_estimate(&mut cache, &DEFAULT_KDF_PARAMS).unwrap(), 123_456);
+        cache.as_mut().unwrap().measured_at = Instant::now() - Duration::from_secs(KDF_BENCHMARK_TTL_SECS + 1);
+        assert_ne!(cached_kdf_estimate(&mut cache, &DEFAULT_KDF_PARAMS).unwrap(), 123_456);
+    }
+
+    #[test]
+    fn test_resolve_close_behavior_per_platform() {
+        // Defaults: tray where it is reliable, quit on Linux
+        assert_eq!(resolve_close_behavior(None, "macos"), CloseAction::Tray);
+        assert_eq!(resolve_close_behavior(None, "windows"), CloseAction::Tray);
+        assert_eq!(resolve_close_behavior(None, "linux"), CloseAction::Quit);
+        assert_eq!(resolve_close_behavior(Some("boh"), "linux"), CloseAction::Quit);
+        // Explicit setting wins on every platform
+        for os in ["macos", "windows", "linux"] {
+            assert_eq!(resolve_close_behavior(Some("tray"), os), CloseAction::Tray);
+            assert_eq!(resolve_close_behavior(Some("quit"), os), CloseAction::Quit);
+            assert_eq!(resolve_close_behavior(Some("ask"), os), CloseAction::Ask);
+        }
+    }
+
+    #[test]
+    fn test_notification_history_records_and_clears() {
+        let dir = std::env::temp_dir().join(format!("lexflow-notif-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert!(read_notification_history(&dir).is_empty());
+
+        record_fired_notification(&dir, "reminder", "evt-1", "LexFlow — Promemoria", "Udienza — 10:00 (tra 30 minuti)").unwrap();
+        record_fired_notification(&dir, "briefing", "2026-10-17 08:30", "LexFlow — 1 impegno oggi", "• 10:00 — Udienza").unwrap();
+        let history = read_notification_history(&dir);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0]["id"], "evt-1");
+        assert_eq!(history[0]["kind"], "reminder");
+        assert_eq!(history[1]["kind"], "briefing");
+        assert!(history[1]["firedAt"].as_str().is_some());
+        // Stored encrypted, not as readable JSON
+        assert!(!String::from_utf8_lossy(&fs::read(dir.join(NOTIF_HISTORY_FILE)).unwrap()).contains("Udienza"));
+
+        for i in 0..MAX_NOTIF_HISTORY {
+            record_fired_notification(&dir, "reminder", &format!("evt-{}", i + 2), "t", "b").unwrap();
+        }
+        assert_eq!(read_notification_history(&dir).len(), MAX_NOTIF_HISTORY, "storico deve restare limitato");
+
+        clear_notification_history_file(&dir).unwrap();
+        assert!(read_notification_history(&dir).is_empty(), "storico deve essere vuoto dopo la cancellazione");
+        clear_notification_history_file(&dir).unwrap();
+        record_fired_notification(&dir, "reminder", "evt-new", "t", "b").unwrap();
+        assert_eq!(read_notification_history(&dir).len(), 1, "dopo la cancellazione lo storico riparte da zero");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_canonicalize_invoices_repairs_and_warns() {
+        let mut invoices = json!([
+            {"id": "inv-1", "items": [
+                {"description": "Udienza", "hours": 2, "rate": 150, "total": 250},
+                {"description": "Studio", "hours": 1.5, "rate": 100, "total": 150}
+            ], "subtotal": 400, "grandTotal": 507.52},
+            {"id": "inv-2", "items": [
+                {"description": "Parere", "hours": 1, "total": 200}
+            ], "subtotal": 999}
+        ]);
+        let (repaired, warnings) = canonicalize_invoices(&mut invoices);
+        // inv-1: both line totals recomputed, invoice totals follow
+        assert_eq!(invoices[0]["items"][0]["total"], json!(300.0));
+        assert_eq!(invoices[0]["items"][1]["total"], json!(150));
+        assert_eq!(invoices[0]["subtotal"], json!(450.0));
+        assert_eq!(invoices[0]["grandTotal"], json!(570.96));
+        assert_eq!(repaired, 2);
+        // inv-2: missing rate → warned, nothing rewritten
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["invoiceId"], "inv-2");
+        assert!(warnings[0]["messages"][0].as_str().unwrap().contains("tariffa mancante"));
+        assert_eq!(invoices[1]["items"][0]["total"], json!(200));
+        assert_eq!(invoices[1]["subtotal"], json!(999), "totali non devono essere toccati se una voce non è valida");
+
+        // A queued save is repaired the same way when flushed
+        let mut vault = json!({"invoices": []});
+        let mut pending = serde_json::Map::new();
+        pending.insert("invoices".into(), json!([{"id": "inv-3", "items": [{"hours": 2, "rate": 150, "total": 250}], "subtotal": 250}]));
+        apply_pending_saves(&mut vault, pending);
+        assert_eq!(vault["invoices"][0]["items"][0]["total"], json!(300.0));
+        assert_eq!(vault["invoices"][0]["subtotal"], json!(300.0));
+    }
+
+    #[test]
+    fn test_tags_across_sections() {
+        let vault = json!({
+            "practices": [
+                {"id": "p1", "client": "Rossi", "tags": ["Urgente", "pro-bono"]},
+                {"id": "p2", "client": "Bianchi", "tags": ["2024-review"]},
+                {"id": "p3", "client": "Riservato", "confidential": true, "tags": ["urgente"]}
+            ],
+            "contacts": [
+                {"id": "c1", "name": "Verdi", "tags": [" urgente ", "URGENTE"]},
+                {"id": "c2", "name": "Neri"}
+            ],
+            "agenda": [{"id": "a1", "title": "Udienza", "tags": ["pro-bono"]}]
+        });
+        let found = filter_vault_by_tag(&vault, "  URGENTE", &TAGGABLE_SECTIONS);
+        assert_eq!(found["practices"].as_array().unwrap().len(), 1, "pratica riservata esclusa");
+        assert_eq!(found["practices"][0]["id"], "p1");
+        assert_eq!(found["contacts"][0]["id"], "c1");
+        assert!(found["agenda"].as_array().unwrap().is_empty());
+
+        let only_agenda = filter_vault_by_tag(&vault, "Pro-Bono", &["agenda"]);
+        assert_eq!(only_agenda["agenda"][0]["id"], "a1");
+        assert!(only_agenda.get("practices").is_none());
+
+        let tags = count_tags(&vault, &TAGGABLE_SECTIONS);
+        assert_eq!(tags[0], json!({"tag": "pro-bono", "count": 2}));
+        assert_eq!(tags[1], json!({"tag": "Urgente", "count": 2}), "duplicati nello stesso oggetto contati una volta");
+        assert_eq!(tags[2], json!({"tag": "2024-review", "count": 1}));
+        assert_eq!(tags.len(), 3);
+    }
+
+    #[test]
+    fn test_device_id_created_once_then_read_only() {
+        let base = std::env::temp_dir().join(format!("lexflow-device-id-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let canonical = base.join("app-data");
+        let legacy = base.join("legacy");
+        // Before setup: no canonical dir → clear error, nothing generated
+        assert!(read_device_id(None).is_err());
+        assert!(read_device_id(Some(&canonical)).is_err());
+        assert!(!canonical.join(DEVICE_ID_FILE).exists(), "la lettura non deve mai creare un ID");
+
+        // An id left by an older build is adopted, not replaced
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join(DEVICE_ID_FILE), "legacy-id\n").unwrap();
+        let id = ensure_device_id(&canonical, std::slice::from_ref(&legacy)).unwrap();
+        assert_eq!(id, "legacy-id");
+        assert_eq!(read_device_id(Some(&canonical)).unwrap(), "legacy-id");
+
+        // setup() again (next launch) keeps the canonical id even if the legacy one changes
+        fs::write(legacy.join(DEVICE_ID_FILE), "altro-id").unwrap();
+        assert_eq!(ensure_device_id(&canonical, std::slice::from_ref(&legacy)).unwrap(), "legacy-id");
+
+        // Canonical file unexpectedly missing after setup → error, never a fresh id
+        fs::remove_file(canonical.join(DEVICE_ID_FILE)).unwrap();
+        assert!(read_device_id(Some(&canonical)).is_err());
+        assert!(!canonical.join(DEVICE_ID_FILE).exists());
+
+        // Fresh install with no legacy id → random 256-bit id
+        let fresh = base.join("fresh");
+        let id = ensure_device_id(&fresh, &[]).unwrap();
+        assert_eq!(id.len(), 64);
+        assert_eq!(read_device_id(Some(&fresh)).unwrap(), id);
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_conflict_session_matches_full_check_and_goes_stale() {
+        let vault = json!({
+            "practices": [
+                {"id": "p1", "client": "Mario Rossi", "counterparty": "Beta Srl", "roles": [{"contactId": "c2", "role": "teste"}]},
+                {"id": "p2", "client": "Anna Bianchi", "confidential": true, "counterparty": "Rossi Immobiliare"}
+            ],
+            "contacts": [{"id": "c1", "name": "Luca Rossi", "email": "l@x.it"}, {"id": "c2", "name": "Giulia Verdi"}],
+            "agenda": [{"id": "a1", "title": "Rossi"}]
+        });
+        let index = build_conflict_index(&vault, 7, false, true);
+        for name in ["rossi", "  VERDI ", "beta", "nessuno", ""] {
+            let expected = if name.trim().is_empty() {
+                json!({"practiceMatches": [], "contactMatches": []})
+            } else { compute_conflicts(&vault, name) };
+            assert_eq!(query_conflict_index(&index, 7, name).unwrap(), expected, "query {:?}", name);
+        }
+        // Protected contacts: same masking as check_conflict without reauth
+        let masked = build_conflict_index(&vault, 7, true, true);
+        let mut expected = compute_conflicts(&vault, "rossi");
+        mask_contact_matches(&mut expected);
+        assert_eq!(query_conflict_index(&masked, 7, "rossi").unwrap(), expected);
+        // No reauth: confidential practices masked as in check_conflict
+        let hidden = build_conflict_index(&vault, 7, false, false);
+        let mut expected = compute_conflicts(&vault, "rossi");
+        mask_confidential_practice_matches(&mut expected);
+        assert_eq!(query_conflict_index(&hidden, 7, "rossi").unwrap(), expected);
+        assert!(!expected.to_string().contains("Anna Bianchi"));
+        // A vault write in between (generation bumped) invalidates the session
+        assert_eq!(query_conflict_index(&index, 8, "rossi").unwrap_err(), CONFLICT_SESSION_STALE_ERR);
+    }
+
+    #[test]
+    fn test_seat_bundle_claim_and_reclaim() {
+        let seats: Vec<String> = ["S1", "S2", "S3"].iter().map(|s| s.to_string()).collect();
+        let mut registry: Vec<String> = Vec::new();
+
+        // Machine A claims the first seat
+        let seat = claim_bundle_seat(&registry, "bundle-1", &seats, "fp-A").unwrap();
+        assert_eq!(seat, "S1");
+        registry.push(seat_burn_hash("bundle-1", &seat));
+        registry.push(bundle_machine_hash("bundle-1", "fp-A"));
+
+        // Re-claim on the same machine is rejected even though seats remain
+        let err = claim_bundle_seat(&registry, "bundle-1", &seats, "fp-A").unwrap_err();
+        assert!(err.contains("già un posto"), "{}", err);
+
+        // Another machine with the shared registry gets the next seat
+        assert_eq!(claim_bundle_seat(&registry, "bundle-1", &seats, "fp-B").unwrap(), "S2");
+        // Seats of other bundles are independent
+        assert_eq!(claim_bundle_seat(&registry, "bundle-2", &seats, "fp-A").unwrap(), "S1");
+
+        // All seats burned → rejected
+        for s in &seats { registry.push(seat_burn_hash("bundle-1", s)); }
+        assert!(claim_bundle_seat(&registry, "bundle-1", &seats, "fp-C").unwrap_err().contains("Tutti i posti"));
+
+        // Payload: seat list optional (old tokens still parse), malformed lists rejected
+        let old: LicensePayload = serde_json::from_str(r#"{"c":"x","e":1,"id":"k"}"#).unwrap();
+        assert!(old.s.is_none());
+        let bundle: LicensePayload = serde_json::from_str(r#"{"c":"x","e":1,"id":"k","s":["A","B"]}"#).unwrap();
+        assert_eq!(bundle.s.as_deref().and_then(seat_list_error), None);
+        assert!(seat_list_error(&["A".to_string(), "A".to_string()]).is_some());
+        assert!(seat_list_error(&[]).is_some());
+    }
+
+    #[test]
+    fn test_diagnose_backup_files() {
+        let dir = std::env::temp_dir().join(format!("lexflow-diagnose-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key = derive_secure_key("Password-Backup-1!", &[3u8; 32]).unwrap();
+        let mut good = vec![3u8; 32];
+        good.extend(encrypt_data(&key, br#"{"schemaVersion":2,"practices":[]}"#).unwrap());
+
+        let write = |name: &str, bytes: &[u8]| {
+            let p = dir.join(name);
+            fs::write(&p, bytes).unwrap();
+            diagnose_backup(p.to_string_lossy().into_owned()).unwrap()
+        };
+
+        let ok = write("ok.lex", &good);
+        assert_eq!(ok["status"], "ok");
+        assert_eq!(ok["magicPresent"], true);
+        assert_eq!(ok["lengthPlausible"], true);
+        assert_eq!(ok["manifestReadable"], false, "senza password il manifest resta cifrato");
+
+        let truncated = write("truncated.lex", &good[..BACKUP_MIN_LEN - 1]);
+        assert_eq!(truncated["status"], "truncated");
+        assert_eq!(truncated["magicPresent"], true);
+        assert_eq!(truncated["lengthPlausible"], false);
+
+        let mut wrong_magic = good.clone();
+        wrong_magic[BACKUP_SALT_LEN] ^= 0xFF;
+        let wrong = write("wrong.lex", &wrong_magic);
+        assert_eq!(wrong["status"], "wrong-format");
+        assert_eq!(wrong["magicPresent"], false);
+
+        // vault.lex copied instead of an exported backup
+        assert_eq!(write("vault.lex", &good[32..])["status"], "vault-file");
+        assert_eq!(write("tiny.lex", b"abc")["status"], "truncated");
+        assert!(diagnose_backup(dir.join("missing.lex").to_string_lossy().into_owned()).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_machine_id_clone_detection() {
+        assert_eq!(check_machine_id_context(Some("abc"), Some("abc")), MachineIdCheck::Match);
+        assert_eq!(check_machine_id_context(Some("abc\n"), Some("abc")), MachineIdCheck::Match);
+        // Same .machine-id, different hardware → cloned image
+        assert_eq!(check_machine_id_context(Some("abc"), Some("def")), MachineIdCheck::Mismatch);
+        // Pre-existing install without a recorded context: adopt, don't flag
+        assert_eq!(check_machine_id_context(None, Some("def")), MachineIdCheck::Unrecorded);
+        assert_eq!(check_machine_id_context(Some("  "), Some("def")), MachineIdCheck::Unrecorded);
+        // Hardware unreadable (sandbox, missing tool): never a false positive
+        assert_eq!(check_machine_id_context(Some("abc"), None), MachineIdCheck::Unknown);
+        assert_eq!(check_machine_id_context(None, None), MachineIdCheck::Unknown);
+        // A regenerated id yields a different local key
+        assert_ne!(local_key_for_machine_id("id-originale"), local_key_for_machine_id("id-nuovo"));
+    }
+
+    #[test]
+    fn test_lockout_status_reconciles_disk_and_kinds() {
+        let dir = std::env::temp_dir().join(format!("lexflow-lockout-kinds-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+
+        // Another session locked the vault on disk; this session's memory knows nothing
+        lockout_save(&dir, LockoutKind::Vault, LockoutCounter { attempts: 5, locked_until: Some(now + Duration::from_secs(120)) });
+        let mut mem = LockoutCounter::default();
+        let status = reconcile_lockout(&mut mem, lockout_load(&dir, LockoutKind::Vault), now);
+        assert_eq!(status["locked"], true);
+        assert_eq!(status["attempts"], 5);
+        assert!(status["remaining"].as_u64().unwrap() > 100);
+        assert_eq!(mem.attempts, 5, "memoria allineata al disco");
+
+        // Memory ahead of disk (file removed) keeps the higher count
+        let mut ahead = LockoutCounter { attempts: 3, locked_until: None };
+        assert_eq!(reconcile_lockout(&mut ahead, LockoutCounter::default(), now)["attempts"], 3);
+
+        // Clearing the license counter leaves the vault lockout in place
+        lockout_save(&dir, LockoutKind::License, LockoutCounter { attempts: 2, locked_until: None });
+        lockout_clear(&dir, LockoutKind::License);
+        assert_eq!(lockout_load(&dir, LockoutKind::License), LockoutCounter::default());
+        assert_eq!(lockout_load(&dir, LockoutKind::Vault).attempts, 5);
+
+        // Expired lockout: not locked, attempts kept (next failure relocks immediately)
+        let mut expired = LockoutCounter::default();
+        let status = reconcile_lockout(&mut expired, LockoutCounter { attempts: 5, locked_until: Some(now - Duration::from_secs(1)) }, now);
+        assert_eq!(status["locked"], false);
+        assert_eq!(status["remaining"], 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_blur_debounce_and_lock_decision() {
+        // Rapid toggles: blur(1) → focus(2) → blur(3). Only the last blur's check is current.
+        let current = 3;
+        assert_eq!(decide_blur_action(true, true, 1, current, true), BlurAction::None, "blur superato da un focus successivo");
+        assert_eq!(decide_blur_action(true, true, 3, current, true), BlurAction::Lock);
+        // Blur settled but only unfocused (dialog, other app) → flush, never lock
+        assert_eq!(decide_blur_action(true, true, 3, current, false), BlurAction::Flush);
+        assert_eq!(decide_blur_action(true, false, 3, current, false), BlurAction::None);
+        // Minimized without lockOnMinimize → autosave only if enabled
+        assert_eq!(decide_blur_action(false, true, 3, current, true), BlurAction::Flush);
+        assert_eq!(decide_blur_action(false, false, 3, current, true), BlurAction::None);
+    }
+
+    #[test]
+    fn test_archive_round_trip_and_future_version() {
+        let vault = json!({
+            "schemaVersion": VAULT_SCHEMA_VERSION,
+            "practices": [{"id": "p1", "client": "Rossi", "attachments": [{"name": "atto.pdf", "path": "/doc/atto.pdf"}]}],
+            "contacts": [{"id": "c1"}],
+        });
+        let settings = json!({"lockOnMinimize": true, "autolockMinutes": 10});
+        let payload = build_archive_payload(&vault, &settings, Some(json!([{"event": "login"}])));
+        let raw = seal_archive("archivio-pwd", &payload).unwrap();
+        assert!(raw.starts_with(ARCHIVE_MAGIC));
+
+        let opened = open_archive(&raw, "archivio-pwd").unwrap();
+        assert_eq!(opened["vault"]["practices"][0]["attachments"][0]["name"], "atto.pdf");
+        assert_eq!(opened["settings"], settings);
+        assert_eq!(opened["auditLog"][0]["event"], "login");
+        assert_eq!(opened["manifest"]["appVersion"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(opened["vault"]["schemaVersion"], VAULT_SCHEMA_VERSION);
+        assert!(open_archive(&raw, "sbagliata").unwrap_err().contains("Password errata"));
+
+        // Future format in the clear header → refused before the password is even tried
+        let mut newer = raw.clone();
+        newer[ARCHIVE_MAGIC.len()..ARCHIVE_HEADER_LEN].copy_from_slice(&(ARCHIVE_FORMAT_VERSION + 1).to_be_bytes());
+        assert!(open_archive(&newer, "qualsiasi").unwrap_err().contains("più recente"));
+
+        // Future manifest version inside the ciphertext → refused
+        let mut future = payload.clone();
+        future["manifest"]["formatVersion"] = json!(ARCHIVE_FORMAT_VERSION + 1);
+        let raw_future = seal_archive("archivio-pwd", &future).unwrap();
+        assert!(open_archive(&raw_future, "archivio-pwd").unwrap_err().contains("manifest non supportata"));
+
+        // Content altered after the fingerprint was computed → refused
+        let mut tampered = payload.clone();
+        tampered["settings"]["autolockMinutes"] = json!(0);
+        let raw_tampered = seal_archive("archivio-pwd", &tampered).unwrap();
+        assert!(open_archive(&raw_tampered, "archivio-pwd").unwrap_err().contains("alterato"));
+    }
+
+    #[test]
+    fn test_practices_missing_conflict_check() {
+        let practices = vec![
+            json!({"id": "p1", "status": "active", "client": "Rossi"}),
+            json!({"id": "p2", "status": "active", "client": "Bianchi",
+                   "partiesChangedAt": "2026-03-01T10:00:00+00:00", "conflictCleared": "2026-03-02T10:00:00+00:00"}),
+            json!({"id": "p3", "status": "active", "client": "Verdi",
+                   "partiesChangedAt": "2026-03-05T10:00:00+00:00", "conflictCleared": "2026-03-02T10:00:00+00:00"}),
+            json!({"id": "p4", "status": "closed", "client": "Neri"}),
+            json!({"id": "p5", "status": "active", "client": "Segreto", "confidential": true}),
+        ];
+        let missing = missing_conflict_checks(&practices, false);
+        let ids: Vec<&str> = missing.iter().map(|m| m["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["p1", "p3", "p5"], "p2 confermata, p4 chiusa");
+        assert_eq!(missing[0]["reason"], "missing");
+        assert_eq!(missing[1]["reason"], "stale");
+        assert!(missing[2].get("client").is_none(), "pratica riservata senza dettagli");
+        assert_eq!(missing_conflict_checks(&practices, true)[2]["client"], "Segreto");
+
+        // Saving: party edits restamp partiesChangedAt, other edits keep it and the confirmation
+        let previous = json!([
+            {"id": "p2", "client": "Bianchi", "partiesChangedAt": "2026-03-01T10:00:00+00:00", "conflictCleared": "2026-03-02T10:00:00+00:00"},
+            {"id": "p3", "client": "Verdi", "conflictCleared": "2026-03-02T10:00:00+00:00"},
+        ]);
+        let mut list = json!([
+            {"id": "p2", "client": "Bianchi", "description": "nuova nota"},
+            {"id": "p3", "client": "Verdi", "counterparty": "Gamma Spa"},
+            {"id": "p6", "client": "Nuovo"},
+        ]);
+        // A queued save keeps the stored confirmation too
+        let mut queued = json!({"practices": previous.clone()});
+        let mut pending = serde_json::Map::new();
+        pending.insert("practices".into(), list.clone());
+        apply_pending_saves(&mut queued, pending);
+        assert_eq!(queued["practices"][0]["conflictCleared"], "2026-03-02T10:00:00+00:00");
+        assert_eq!(queued["practices"][0]["partiesChangedAt"], "2026-03-01T10:00:00+00:00");
+        assert!(queued["practices"][1]["partiesChangedAt"].is_string(), "modifica delle parti in coda");
+        stamp_party_changes(&mut list, &previous, "2026-04-01T09:00:00+00:00");
+        assert_eq!(list[0]["partiesChangedAt"], "2026-03-01T10:00:00+00:00");
+        assert_eq!(list[0]["conflictCleared"], "2026-03-02T10:00:00+00:00");
+        assert_eq!(list[1]["partiesChangedAt"], "2026-04-01T09:00:00+00:00");
+        assert_eq!(list[2]["partiesChangedAt"], "2026-04-01T09:00:00+00:00");
+        list[1]["status"] = json!("active");
+        assert_eq!(missing_conflict_checks(list.as_array().unwrap(), false)[0]["reason"], "stale");
+    }
+
+    #[test]
+    fn test_audit_log_page_windows_newest_first() {
+        let entries: Vec<Value> = (0..25).map(|i| json!({"event": format!("e{}", i)})).collect();
+        let first = audit_log_page(entries.clone(), 0, 10);
+        assert_eq!(first["total"], 25);
+        assert_eq!(first["entries"][0]["event"], "e24", "il più recente per primo");
+        assert_eq!(first["entries"][9]["event"], "e15");
+        assert_eq!(first["hasMore"], true);
+
+        let last = audit_log_page(entries.clone(), 20, 10);
+        assert_eq!(last["entries"].as_array().unwrap().len(), 5);
+        assert_eq!(last["entries"][4]["event"], "e0");
+        assert_eq!(last["hasMore"], false);
+
+        // Offset out of range → empty page, total still reported
+        let beyond = audit_log_page(entries.clone(), 100, 10);
+        assert!(beyond["entries"].as_array().unwrap().is_empty());
+        assert_eq!(beyond["total"], 25);
+        assert_eq!(beyond["hasMore"], false);
+
+        // Limit clamped to [1, MAX_AUDIT_PAGE]
+        assert_eq!(audit_log_page(entries.clone(), 0, 0)["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(audit_log_page(entries, 0, usize::MAX)["limit"], MAX_AUDIT_PAGE);
+    }
+
+    #[test]
+    fn test_crypto_header_versions() {
+        let key = [7u8; 32];
+        assert_eq!(vault_header(CRYPTO_FORMAT_VERSION), VAULT_MAGIC);
+
+        // v2 (current): header authenticated as AAD
+        let v2 = encrypt_data(&key, b"corrente").unwrap();
+        assert_eq!(vault_header_version(&v2), Some(2));
+        assert_eq!(decrypt_data(&key, &v2).unwrap(), b"corrente");
+        let mut forged = v2.clone();
+        forged[VAULT_MAGIC_PREFIX.len()] = b'1';
+        assert!(decrypt_data(&key, &forged).is_err(), "header v2 riscritto come v1 deve fallire");
+
+        // v1: no AAD, explicit header and the legacy V2-header-without-AAD layout
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        let nonce = [3u8; NONCE_LEN];
+        let ct = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: b"legacy", aad: b"" }).unwrap();
+        for header in [vault_header(1), VAULT_MAGIC.to_vec()] {
+            let mut blob = header;
+            blob.extend_from_slice(&nonce);
+            blob.extend_from_slice(&ct);
+            assert_eq!(decrypt_data(&key, &blob).unwrap(), b"legacy");
+        }
+
+        // v4 (future): recognized as a LexFlow header, refused with a version message
+        let mut v4 = vault_header(4);
+        v4.extend_from_slice(&v2[VAULT_MAGIC.len()..]);
+        assert_eq!(vault_header_version(&v4), Some(4));
+        let err = decrypt_data(&key, &v4).unwrap_err();
+        assert!(err.contains("v4") && err.contains("v3"), "messaggio: {}", err);
+
+        assert_eq!(vault_header_version(b"LEXFLOW_VX_SECURE..."), None);
+        assert_eq!(vault_header_version(b"LEXFLOW_V0_SECURE..."), None);
+        assert!(decrypt_data(&key, &[b'x'; 64]).unwrap_err().contains("magic"));
+    }
+
+    #[test]
+    fn test_verify_bio_credential_detects_stale_password() {
+        let salt = [9u8; ARGON2_SALT_LEN];
+        let current = derive_secure_key("nuova-password", &salt).unwrap();
+        // Simulated keyring: change_password failed to update it, so it still holds the old one
+        let stale = assess_bio_credential(true, || KeyringRead::Password(Zeroizing::new("vecchia-password".into())), &salt, &DEFAULT_KDF_PARAMS, Some(&current));
+        assert_eq!(stale["valid"], false);
+        assert_eq!(stale["reason"], "stale");
+        assert_eq!(stale["reenroll"], true);
+
+        let ok = assess_bio_credential(true, || KeyringRead::Password(Zeroizing::new("nuova-password".into())), &salt, &DEFAULT_KDF_PARAMS, Some(&current));
+        assert_eq!(ok["valid"], true);
+
+        assert_eq!(assess_bio_credential(true, || KeyringRead::Missing, &salt, &DEFAULT_KDF_PARAMS, Some(&current))["reason"], "keyring-missing");
+        assert_eq!(assess_bio_credential(true, || KeyringRead::Error("locked".into()), &salt, &DEFAULT_KDF_PARAMS, Some(&current))["reason"], "keyring-error");
+        assert_eq!(assess_bio_credential(false, || panic!("non deve leggere il portachiavi"), &salt, &DEFAULT_KDF_PARAMS, Some(&current))["reason"], "not-enrolled");
+        let locked = assess_bio_credential(true, || panic!("vault bloccato: nessuna lettura"), &salt, &DEFAULT_KDF_PARAMS, None);
+        assert!(locked["valid"].is_null());
+    }
+
+    #[test]
+    fn test_refresh_bio_credential_rewrites_keyring() {
+        let dir = std::env::temp_dir().join(format!("lexflow-bio-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let salt = [9u8; ARGON2_SALT_LEN];
+        let current = derive_secure_key("nuova-password", &salt).unwrap();
+        // Simulated keyring still holding the password from before change_password
+        let keyring = std::cell::RefCell::new(Some("vecchia-password".to_string()));
+        let read = || KeyringRead::Password(Zeroizing::new(keyring.borrow().clone().unwrap()));
+        assert_eq!(assess_bio_credential(true, read, &salt, &DEFAULT_KDF_PARAMS, Some(&current))["reason"], "stale");
+
+        let failed = refresh_bio_credential(&dir, "nuova-password", |_| Err("portachiavi bloccato".into()));
+        assert!(failed.is_err());
+        assert!(!dir.join(BIO_MARKER_FILE).exists(), "nessun marker se il portachiavi non è stato scritto");
+
+        refresh_bio_credential(&dir, "nuova-password", |pwd| {
+            *keyring.borrow_mut() = Some(pwd.to_string());
+            Ok(())
+        }).unwrap();
+        assert_eq!(keyring.borrow().as_deref(), Some("nuova-password"));
+        assert!(dir.join(BIO_MARKER_FILE).exists());
+        let read = || KeyringRead::Password(Zeroizing::new(keyring.borrow().clone().unwrap()));
+        assert_eq!(assess_bio_credential(true, read, &salt, &DEFAULT_KDF_PARAMS, Some(&current))["valid"], true);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recurring_items_expand_per_occurrence() {
+        let date = |s: &str| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(parse_recurrence("FREQ=WEEKLY;INTERVAL=2;UNTIL=20260630T235959Z"),
+            Some(Recurrence { freq: RecurrenceFreq::Weekly, interval: 2, until: Some(date("2026-06-30")) }));
+        assert_eq!(parse_recurrence("RRULE:freq=daily").map(|r| r.interval), Some(1));
+        for invalid in ["FREQ=YEARLY", "FREQ=WEEKLY;COUNT=3", "INTERVAL=2", "FREQ=DAILY;INTERVAL=0", "FREQ=DAILY;UNTIL=giugno", "boh"] {
+            assert_eq!(parse_recurrence(invalid), None, "regola non valida: {}", invalid);
+        }
+
+        let monthly = parse_recurrence("FREQ=MONTHLY").unwrap();
+        assert_eq!(recurrence_dates(date("2026-01-31"), &monthly, date("2026-01-01"), date("2026-05-31")),
+            vec![date("2026-01-31"), date("2026-03-31"), date("2026-05-31")], "mesi senza il 31 saltati");
+        let weekly = parse_recurrence("FREQ=WEEKLY;INTERVAL=2;UNTIL=2026-03-20").unwrap();
+        assert_eq!(recurrence_dates(date("2026-01-05"), &weekly, date("2026-02-20"), date("2026-12-31")),
+            vec![date("2026-03-02"), date("2026-03-16")]);
+
+        let items = vec![
+            json!({"id": "u1", "title": "Udienza di verifica", "date": "2026-05-04", "time": "10:00",
+                   "recurrence": "FREQ=WEEKLY", "completed": true, "completedOccurrences": ["2026-05-11"]}),
+            json!({"id": "u2", "title": "Regola rotta", "date": "2026-05-05", "time": "09:00", "recurrence": "FREQ=HOURLY"}),
+        ];
+        let expanded = expand_recurring_items(&items, date("2026-05-01"), date("2026-05-20"));
+        let ids: Vec<&str> = expanded.iter().map(|i| i["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["u1@2026-05-04", "u1@2026-05-11", "u1@2026-05-18", "u2"], "regola non valida: evento singolo");
+        let done: Vec<bool> = expanded.iter().map(|i| i["completed"].as_bool().unwrap_or(false)).collect();
+        assert_eq!(done, vec![true, true, false, false], "completamento per singola occorrenza");
+        assert_eq!(expanded[2]["occurrenceOf"], "u1");
+        assert!(expanded[2].get("recurrence").is_none());
+
+        // The cron fires the next week's occurrence although earlier ones are done
+        let settings = json!({"preavviso": 30});
+        let fired = due_notifications(&[], &items, &settings, local_datetime("2026-05-18", "09:30").unwrap());
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].id, "u1@2026-05-18");
+        assert!(due_notifications(&[], &items, &settings, local_datetime("2026-05-11", "09:30").unwrap()).is_empty());
+
+        let mut item = items[0].clone();
+        set_occurrence_done(&mut item, "2026-05-04", true);
+        set_occurrence_done(&mut item, "2026-05-11", false);
+        assert_eq!(item["completedOccurrences"], json!(["2026-05-04"]));
+    }
+
+    #[test]
+    fn test_snoozed_reminder_moves_to_snooze_end() {
+        let now = local_datetime("2026-05-04", "09:30").unwrap();
+        let until = local_datetime("2026-05-04", "09:40").unwrap();
+        let schedule = json!({
+            "items": [
+                {"id": "u1", "title": "Udienza", "date": "2026-05-04", "time": "10:00", "remindMinutes": 30},
+                {"id": "u2", "title": "Call", "date": "2026-05-04", "time": "11:00", "remindMinutes": 30},
+            ],
+            "snoozes": {"u1": until.timestamp_millis(), "sparito": until.timestamp_millis(), "rotto": "x"},
+        });
+        let entries = snooze_entries(&schedule);
+        assert_eq!(entries.len(), 2, "voci illeggibili ignorate");
+
+        let items = schedule["items"].as_array().unwrap().clone();
+        let n = snoozed_notification(&items, &json!({}), "u1", until).unwrap();
+        assert_eq!(n.at, until);
+        assert!(n.body.contains("tra 20 minuti"), "testo relativo alla fine del posticipo: {}", n.body);
+        assert!(snoozed_notification(&items, &json!({}), "sparito", until).is_none());
+
+        let upcoming = compute_upcoming_notifications(&schedule, &json!({}), now - chrono::Duration::minutes(1), None, 5);
+        let plan: Vec<(String, i64)> = upcoming.iter()
+            .map(|u| (u["id"].as_str().unwrap().to_string(), u["atMs"].as_i64().unwrap())).collect();
+        assert_eq!(plan, vec![
+            ("u1".to_string(), until.timestamp_millis()),
+            ("u2".to_string(), local_datetime("2026-05-04", "10:30").unwrap().timestamp_millis()),
+        ], "il promemoria posticipato non scatta all'orario originale");
+    }
+
+    #[test]
+    fn test_archive_completed_agenda() {
+        let mut vault = json!({"agenda": [
+            {"id": "old", "title": "Udienza", "date": "2026-01-10", "time": "09:00", "completed": true},
+            {"id": "recent", "date": "2026-02-20", "time": "09:00", "completed": true},
+            {"id": "open", "date": "2026-01-10", "time": "10:00", "completed": false},
+            {"id": "weekly", "date": "2026-01-05", "time": "11:00", "completed": true, "recurrence": "FREQ=WEEKLY"},
+            {"id": "undated", "completed": true},
+        ], "archivedAgenda": [{"id": "old", "title": "copia precedente"}]});
+        let cutoff = chrono::NaiveDate::from_ymd_opt(2026, 2, 1).unwrap();
+        let archived = archive_completed_agenda(&mut vault, cutoff, "2026-03-01T00:00:00Z");
+        assert_eq!(archived, vec!["old".to_string()]);
+        let left: Vec<&str> = vault["agenda"].as_array().unwrap().iter().map(|i| i["id"].as_str().unwrap()).collect();
+        assert_eq!(left, vec!["recent", "open", "weekly", "undated"]);
+        let archive = vault["archivedAgenda"].as_array().unwrap();
+        assert_eq!(archive.len(), 1, "la copia archiviata con lo stesso id va sostituita");
+        assert_eq!(archive[0]["title"], "Udienza");
+        assert_eq!(archive[0]["archivedAt"], "2026-03-01T00:00:00Z");
+        assert!(archive_completed_agenda(&mut vault, cutoff, "x").is_empty());
+
+        // The schedule loses the archived item and the scan never sees it again
+        let mut schedule = json!({"items": [{"id": "old", "date": "2026-01-10", "time": "09:00", "completed": true},
+                                            {"id": "open", "date": "2026-01-10", "time": "10:00"}]});
+        assert!(drop_schedule_items(&mut schedule, &archived));
+        assert!(!drop_schedule_items(&mut schedule, &archived));
+        let now = chrono::Local.with_ymd_and_hms(2026, 1, 10, 8, 0, 0).unwrap();
+        let scanned = schedule_items_in_horizon(schedule["items"].as_array().unwrap(), now);
+        assert!(scanned.iter().all(|i| i["id"] != "old"), "un impegno archiviato non va più considerato dallo scheduler");
+        assert_eq!(archive_completed_after_days(&json!({})), 0);
+        assert_eq!(archive_completed_after_days(&json!({"archiveCompletedAfterDays": 30})), 30);
+
+        // A save from a list loaded before the pass does not bring the archived item back
+        let stale = json!([{"id": "old", "completed": true}, {"id": "recent"}, {"id": "nuovo"}]);
+        let saved = drop_archived_agenda_items(stale.clone(), &vault["archivedAgenda"]);
+        assert_eq!(saved, json!([{"id": "recent"}, {"id": "nuovo"}]));
+        let mut queued = vault.clone();
+        let mut pending = serde_json::Map::new();
+        pending.insert("agenda".into(), stale);
+        apply_pending_saves(&mut queued, pending);
+        assert_eq!(queued["agenda"], saved, "anche i salvataggi in coda escludono gli archiviati");
+    }
+
+    #[test]
+    fn test_canonical_date_formats() {
+        assert_eq!(canonical_date("2024/1/5").as_deref(), Some("2024-01-05"));
+        assert_eq!(canonical_date("2024-1-5").as_deref(), Some("2024-01-05"));
+        assert_eq!(canonical_date("05.01.2024").as_deref(), Some("2024-01-05"));
+        assert_eq!(canonical_date("2024-01-05T09:30:00Z").as_deref(), Some("2024-01-05"));
+        assert_eq!(canonical_date("2024-01-05").as_deref(), Some("2024-01-05"));
+        assert_eq!(canonical_date("2024-02-30"), None, "il 30 febbraio non esiste");
+        assert_eq!(canonical_date("24/1/5"), None, "anno a due cifre ambiguo");
+        assert_eq!(canonical_date("domani"), None);
+    }
+
+    #[test]
+    fn test_repair_vault_dates() {
+        let mut vault = json!({
+            "practices": [{"id": "p1", "deadlines": [{"date": "2024/1/5"}, {"date": "2024-03-01"}]}],
+            "agenda": [{"id": "a1", "date": "2024-13-40"}, {"id": "a2", "date": ""}, {"id": "a3"}],
+            "invoices": [{"id": "i1", "date": "2024-02-01", "dueDate": "01/03/2024"}],
+        });
+        let issues = date_issues(&vault);
+        let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, vec!["agenda[0].date", "invoices[0].dueDate", "practices[0].deadlines[0].date"]);
+        assert_eq!(issues[0].id, "a1");
+        assert!(issues[0].fixed.is_none());
+
+        let mut normalized = vault.clone();
+        let (fixed, cleared, left) = repair_vault_dates(&mut normalized, DateRepair::Normalize);
+        assert_eq!((fixed.len(), cleared.len(), left.len()), (2, 0, 1));
+        assert_eq!(normalized["practices"][0]["deadlines"][0]["date"], "2024-01-05");
+        assert_eq!(normalized["invoices"][0]["dueDate"], "2024-03-01");
+        assert_eq!(normalized["agenda"][0]["date"], "2024-13-40", "una data non recuperabile resta invariata");
+
+        let (_, cleared, left) = repair_vault_dates(&mut vault, DateRepair::Clear);
+        assert_eq!((cleared.len(), left.len()), (1, 0));
+        assert_eq!(vault["agenda"][0]["date"], "");
+        assert!(date_issues(&vault).is_empty());
+        assert!(date_repair("guess").is_err());
+
+        // Every scanned section sits behind its own protectedSections gate
+        for (section, _) in DATE_FIELDS {
+            let gate = if *section == "archivedAgenda" { "agenda" } else { section };
+            assert!(DATE_SECTIONS.contains(&gate), "{} non è coperta dal controllo di accesso", section);
+        }
+        let settings = json!({"protectedSections": ["invoices"]});
+        let mut slot: ReauthSlot = None;
+        assert!(DATE_SECTIONS.iter().any(|s| authorize_section(&settings, s, &mut slot, None).is_err()));
+    }
+
+    #[test]
+    fn test_item_time_zone_reminders() {
+        let utc = |y, m, d, h, min| chrono::Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+        let at = zoned_datetime("2026-07-01", "09:00", Some("Europe/Rome")).unwrap();
+        assert_eq!(at.with_timezone(&chrono::Utc), utc(2026, 7, 1, 7, 0));
+        let winter = zoned_datetime("2026-01-15", "09:00", Some("Europe/Rome")).unwrap();
+        assert_eq!(winter.with_timezone(&chrono::Utc), utc(2026, 1, 15, 8, 0));
+        let sydney = zoned_datetime("2026-01-15", "09:00", Some("Australia/Sydney")).unwrap();
+        assert_eq!(sydney.with_timezone(&chrono::Utc), utc(2026, 1, 14, 22, 0), "ora legale a cavallo dell'anno");
+        // 29 March 2026, 02:30 does not exist in Rome; 25 October 02:30 happens twice
+        let skipped = zoned_datetime("2026-03-29", "02:30", Some("Europe/Rome")).unwrap();
+        assert_eq!(skipped.with_timezone(&chrono::Utc), utc(2026, 3, 29, 1, 30));
+        let repeated = zoned_datetime("2026-10-25", "02:30", Some("Europe/Rome")).unwrap();
+        assert_eq!(repeated.with_timezone(&chrono::Utc), utc(2026, 10, 25, 0, 30), "prima occorrenza");
+        assert_eq!(zoned_datetime("2026-07-01", "09:00", Some("Nessuna/Zona")), local_datetime("2026-07-01", "09:00"));
+        assert!(load_time_zone("../../etc/passwd").is_none());
+
+        let item = json!({"id": "u", "date": "2026-07-01", "time": "09:00", "timezone": "Europe/Rome", "remindMinutes": 30});
+        let (event, remind) = reminder_times(&item, &json!({})).unwrap();
+        assert_eq!(event, at);
+        assert_eq!(event - remind, chrono::Duration::minutes(30));
+
+        assert!(utc_offset_changed(Some(3600), -14_400));
+        assert!(!utc_offset_changed(Some(3600), 3600));
+        assert!(!utc_offset_changed(None, 3600), "senza una sincronizzazione registrata non c'è nulla da rifare");
+    }
+
+    #[test]
+    fn test_cron_catches_up_after_sleep() {
+        let at = |h, m| local_datetime("2026-05-04", &format!("{:02}:{:02}", h, m)).unwrap();
+        let now = at(12, 0) + chrono::Duration::seconds(42);
+        assert_eq!(cron_minutes_to_process(None, now), vec![at(12, 0)]);
+        assert!(cron_minutes_to_process(Some(at(12, 0)), now).is_empty(), "mai due volte lo stesso minuto");
+        assert_eq!(cron_minutes_to_process(Some(at(11, 58)), now), vec![at(11, 59), at(12, 0)]);
+        assert_eq!(cron_minutes_to_process(Some(at(12, 30)), now), vec![at(12, 0)], "orologio spostato indietro");
+        let long_sleep = cron_minutes_to_process(Some(at(12, 0) - chrono::Duration::days(3)), now);
+        assert_eq!(long_sleep.len() as i64, CRON_MAX_CATCHUP_MINUTES);
+        assert_eq!(long_sleep.last(), Some(&at(12, 0)));
+
+        // Laptop asleep from 08:00 to 12:00: the 09:30 and 11:00 reminders still fire
+        let items = vec![
+            json!({"id": "u1", "title": "Udienza", "date": "2026-05-04", "time": "10:00", "remindMinutes": 30}),
+            json!({"id": "u2", "title": "Call", "date": "2026-05-04", "time": "12:30", "remindMinutes": 90}),
+            json!({"id": "u3", "title": "Pranzo", "date": "2026-05-04", "time": "13:00", "remindMinutes": 30}),
+        ];
+        let briefings = vec![json!("08:30"), json!("11:30")];
+        let minutes = cron_minutes_to_process(Some(at(8, 0)), now);
+        let fired = catch_up_notifications(&briefings, &items, &json!({}), &minutes, now);
+        let ids: Vec<&str> = fired.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["2026-05-04 11:30", "u1", "u2"], "solo l'ultimo briefing perso");
+        assert!(fired[1].body.contains("già iniziato"));
+        assert!(fired[2].body.contains("tra 29 minuti"), "testo riferito al momento della consegna");
+    }
+
+    #[test]
+    fn test_practice_proof_deterministic() {
+        let key = [7u8; 32];
+        let practice = json!({"id": "p1", "client": "Rossi", "deadlines": [{"date": "2026-05-04", "label": "Deposito"}]});
+        // Same content in a different key order serializes identically
+        let reordered = json!({"deadlines": [{"label": "Deposito", "date": "2026-05-04"}], "client": "Rossi", "id": "p1"});
+        let at = "2026-05-04T10:00:00+00:00";
+        let proof = sign_practice_proof(&key, &practice, at, Value::Null);
+        assert_eq!(proof, sign_practice_proof(&key, &reordered, at, Value::Null), "stesso contenuto, stessa prova");
+        assert!(verify_practice_proof_sig(&key, &proof));
+
+        let mut changed = practice.clone();
+        changed["deadlines"][0]["date"] = json!("2026-05-05");
+        let other = sign_practice_proof(&key, &changed, at, Value::Null);
+        assert_ne!(other["contentHash"], proof["contentHash"]);
+        assert_ne!(other["signature"], proof["signature"]);
+
+        let mut forged = proof.clone();
+        forged["issuedAt"] = json!("2020-01-01T00:00:00+00:00");
+        assert!(!verify_practice_proof_sig(&key, &forged), "la data firmata non si può retrodatare");
+        assert!(!verify_practice_proof_sig(&[8u8; 32], &proof));
+    }
+
+    #[test]
+    fn test_restore_refused_while_snapshot_open() {
+        let dir = std::env::temp_dir().join(format!("lexflow-restore-snap-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let snapshot = json!({"manifest": {"readOnly": true}, "practices": []});
+        let err = stage_restored_key_files(&dir, Some(&snapshot), "Password-Forte-1!", &DEFAULT_KDF_PARAMS).unwrap_err();
+        assert_eq!(err, SNAPSHOT_READ_ONLY_ERR);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0, "nessun file scritto con lo snapshot aperto");
+        let key = stage_restored_key_files(&dir, None, "Password-Forte-1!", &DEFAULT_KDF_PARAMS).unwrap();
+        assert_eq!(fs::read(dir.join(STAGED_VERIFY_FILE)).unwrap(), make_verify_tag(&key));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_interrupted_key_change_resumes_on_unlock() {
+        let dir = std::env::temp_dir().join(format!("lexflow-key-commit-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let (old_pwd, new_pwd) = ("Vecchia-Password-1!", "Nuova-Password-22!");
+        let old_salt = [3u8; ARGON2_SALT_LEN];
+        let old_key = derive_key_with_params(old_pwd, &old_salt, &DEFAULT_KDF_PARAMS).unwrap();
+        fs::write(dir.join(VAULT_SALT_FILE), old_salt).unwrap();
+        fs::write(dir.join(VAULT_VERIFY_FILE), make_verify_tag(&old_key)).unwrap();
+        write_vault_file(&old_key, &dir.join(VAULT_FILE), &json!({"practices": []})).unwrap();
+
+        let new_salt = [4u8; ARGON2_SALT_LEN];
+        let new_kdf = KdfParams { m_cost: 32768, ..DEFAULT_KDF_PARAMS };
+        let new_key = derive_key_with_params(new_pwd, &new_salt, &new_kdf).unwrap();
+        stage_key_files(&dir, &new_salt, &new_kdf, &make_verify_tag(&new_key)).unwrap();
+        // Crashed before the vault write: the staged files are never committed
+        assert!(resume_key_commit(&dir, new_pwd).is_none(), "il vault vecchio non si apre con la chiave nuova");
+        assert_eq!(authenticate_vault_password(old_pwd, &dir).unwrap(), old_key);
+
+        // Crashed after the vault write and the salt rename, before kdf and verify
+        write_vault_file(&new_key, &dir.join(VAULT_FILE), &json!({"practices": []})).unwrap();
+        fs::write(dir.join(VAULT_BACKUP_FILE), b"bak").unwrap();
+        fs::rename(dir.join(STAGED_SALT_FILE), dir.join(VAULT_SALT_FILE)).unwrap();
+        assert!(authenticate_vault_password(old_pwd, &dir).is_err());
+        assert!(authenticate_and_resume_key_commit(old_pwd, &dir).is_err());
+        assert!(dir.join(STAGED_KDF_FILE).exists(), "password errata: nulla viene completato");
+        // A plain check (verify, dry run, preflight) accepts the new password and writes nothing
+        assert_eq!(authenticate_vault_password(new_pwd, &dir).unwrap(), new_key);
+        for file in [STAGED_KDF_FILE, STAGED_VERIFY_FILE, VAULT_BACKUP_FILE] {
+            assert!(dir.join(file).exists(), "{} lasciato dalla sola verifica", file);
+        }
+        assert_eq!(authenticate_and_resume_key_commit(new_pwd, &dir).unwrap(), new_key);
+        assert_eq!(read_vault_kdf(&dir).unwrap(), new_kdf);
+        assert_eq!(fs::read(dir.join(VAULT_VERIFY_FILE)).unwrap(), make_verify_tag(&new_key));
+        for file in [STAGED_SALT_FILE, STAGED_KDF_FILE, STAGED_VERIFY_FILE, VAULT_BACKUP_FILE] {
+            assert!(!dir.join(file).exists(), "{} rimosso a cambio completato", file);
+        }
+        assert!(check_vault_key(&dir, &new_key).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_settings_keeps_keys_and_gates_protected_sections() {
+        let current = json!({"autolockMinutes": 5, "protectedSections": ["invoices"]});
+        let merged = merge_settings(&current, &json!({"lockOnMinimize": true}), false).unwrap();
+        assert_eq!(merged["autolockMinutes"], 5, "le chiavi non inviate restano");
+        assert_eq!(merged["protectedSections"], json!(["invoices"]));
+        assert_eq!(merged["lockOnMinimize"], true);
+        let merged = merge_settings(&merged, &json!({"lockOnMinimize": null}), false).unwrap();
+        assert!(merged.get("lockOnMinimize").is_none(), "null elimina la chiave");
+
+        for patch in [json!({"protectedSections": []}), json!({"protectedSections": null}), json!({"protectedSections": ["invoices", "contacts"]})] {
+            assert_eq!(merge_settings(&current, &patch, false).unwrap_err(), REAUTH_REQUIRED_ERR, "{}", patch);
+            assert!(merge_settings(&current, &patch, true).is_ok());
+        }
+        assert!(merge_settings(&current, &json!({"protectedSections": ["invoices"], "autolockMinutes": 10}), false).is_ok(),
+            "reinviare la stessa lista non è una modifica");
+        assert!(merge_settings(&current, &json!([]), true).is_err());
+    }
+
+    #[test]
+    fn test_upcoming_notifications_match_cron_order() {
+        let schedule = json!({
+            "briefingTimes": ["08:00", "14:00", "19:00"],
+            "items": [
+                {"id": "u1", "title": "Udienza", "date": "2026-05-04", "time": "10:00", "remindMinutes": 60},
+                {"id": "u2", "title": "Call cliente", "date": "2026-05-04", "time": "08:30", "remindMinutes": 30},
+                {"id": "u3", "title": "Deposito", "date": "2026-05-05", "time": "09:00", "customRemindTime": "07:15"},
+                {"id": "u4", "title": "Fatto", "date": "2026-05-04", "time": "11:00", "completed": true},
+                {"id": "u5", "title": "Passato", "date": "2026-05-04", "time": "06:00"},
+            ],
+        });
+        let settings = json!({"preavviso": 15});
+        let now = local_datetime("2026-05-04", "07:00").unwrap();
+        let upcoming = compute_upcoming_notifications(&schedule, &settings, now, None, 8);
+
+        // Drive the cron's per-minute decision over the same clock
+        let briefing_times = schedule["briefingTimes"].as_array().unwrap().clone();
+        let items = schedule["items"].as_array().unwrap().clone();
+        let mut fired: Vec<(String, String, String)> = Vec::new();
+        let mut minute = now + chrono::Duration::minutes(1);
+        while fired.len() < upcoming.len() && minute < now + chrono::Duration::days(3) {
+            for n in due_notifications(&briefing_times, &items, &settings, minute) {
+                fired.push((n.kind.to_string(), n.id, n.body));
+            }
+            minute += chrono::Duration::minutes(1);
+        }
+        let predicted: Vec<(String, String, String)> = upcoming.iter().map(|u| (
+            u["kind"].as_str().unwrap().to_string(),
+            u["id"].as_str().unwrap().to_string(),
+            u["body"].as_str().unwrap().to_string(),
+        )).collect();
+        assert_eq!(predicted, fired, "anteprima e cron devono coincidere");
+        let ids: Vec<&str> = upcoming.iter().map(|u| u["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["2026-05-04 08:00", "u2", "u1", "2026-05-04 14:00", "2026-05-04 19:00", "u3", "2026-05-05 08:00", "2026-05-05 14:00"]);
+
+        // Focus until 09:30: the 08:00 briefing is muted, u2 (event 08:30) is dropped,
+        // u1 (remind 09:00, event 10:00) moves to the focus end
+        let focus_end = local_datetime("2026-05-04", "09:30");
+        let focused = compute_upcoming_notifications(&schedule, &settings, now, focus_end, 3);
+        assert_eq!(focused[0]["id"], "u1");
+        assert_eq!(focused[0]["deferredByFocus"], true);
+        assert_eq!(focused[0]["body"], "Udienza — 10:00 (tra 30 minuti)");
+        assert_eq!(focused[1]["id"], "2026-05-04 14:00");
+    }
+
+    #[test]
+    fn test_concurrent_unlocks_share_one_attempt() {
+        use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+        let gate = Arc::new(AuthGate::new());
+        let salt = [4u8; ARGON2_SALT_LEN];
+        let tag = make_verify_tag(&derive_secure_key("corretta", &salt).unwrap());
+        let lockout = Arc::new(Mutex::new(LockoutCounter::default()));
+        let derivations = Arc::new(AtomicUsize::new(0));
+        let (started_tx, started_rx) = std::sync::mpsc::channel::<()>();
+
+        let attempt = |gate: Arc<AuthGate>, lockout: Arc<Mutex<LockoutCounter>>, derivations: Arc<AtomicUsize>, tag: Vec<u8>, started: Option<std::sync::mpsc::Sender<()>>| {
+            std::thread::spawn(move || {
+                let fp = gate.fingerprint("password", "corretta");
+                gate.run(fp, || {
+                    if let Some(tx) = started { let _ = tx.send(()); }
+                    derivations.fetch_add(1, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(200));
+                    let key = derive_secure_key("corretta", &salt)?;
+                    let mut counter = lockout.lock().unwrap();
+                    if verify_hash_matches(&key, &tag) {
+                        *counter = LockoutCounter::default();
+                        Ok(json!({"success": true}))
+                    } else {
+                        counter.attempts += 1;
+                        Ok(json!({"success": false}))
+                    }
+                })
+            })
+        };
+        let first = attempt(gate.clone(), lockout.clone(), derivations.clone(), tag.clone(), Some(started_tx));
+        started_rx.recv().unwrap(); // the first attempt holds the gate
+        let second = attempt(gate.clone(), lockout.clone(), derivations.clone(), tag.clone(), None);
+        assert_eq!(first.join().unwrap().unwrap()["success"], true);
+        assert_eq!(second.join().unwrap().unwrap()["success"], true);
+        assert_eq!(derivations.load(Ordering::SeqCst), 1, "il secondo sblocco riusa il risultato del primo");
+        assert_eq!(lockout.lock().unwrap().attempts, 0, "contatore tentativi intatto");
+
+        // A later, non-concurrent call runs its own attempt; a different password never reuses
+        let fp_other = gate.fingerprint("password", "diversa");
+        assert_ne!(fp_other, gate.fingerprint("password", "corretta"));
+        assert_ne!(gate.fingerprint("bio", ""), gate.fingerprint("password", ""));
+        let mut ran = false;
+        let _ = gate.run(fp_other, || { ran = true; Ok(json!({"success": false})) });
+        assert!(ran);
+    }
+
+    #[test]
+    fn test_subject_access_report_only_covers_the_contact() {
+        let vault = json!({
+            "contacts": [
+                {"id": "c1", "name": "Mario Rossi", "fiscalCode": "RSSMRA80A01H501U", "email": "mario@example.it"},
+                {"id": "c2", "name": "Luigi Verdi", "fiscalCode": "VRDLGU75B02F205X"},
+            ],
+            "practices": [
+                {"id": "p1", "clientId": "c1", "client": "Mario Rossi", "object": "Sfratto", "status": "active",
+                 "counterparty": "Luigi Verdi", "counterpartyId": "c2"},
+                {"id": "p2", "clientId": "c2", "client": "Luigi Verdi", "object": "Recupero crediti", "status": "closed",
+                 "roles": [{"contactId": "c1", "role": "teste"}]},
+                {"id": "p3", "clientId": "c2", "client": "Luigi Verdi", "object": "Divorzio"},
+            ],
+            "invoices": [
+                {"id": "i1", "practiceId": "p1", "number": "2026/001", "grandTotal": 500.0, "clientName": "Mario Rossi"},
+                {"id": "i2", "practiceId": "p2", "number": "2026/002", "clientName": "Luigi Verdi"},
+                {"id": "i3", "clientId": "c1", "number": "2026/003"},
+            ],
+            "timeLogs": [
+                {"id": "t1", "practiceId": "p1", "description": "Studio atti", "durationMin": 90},
+                {"id": "t2", "practiceId": "p2", "description": "Udienza Verdi", "durationMin": 60},
+            ],
+            "agenda": [
+                {"id": "a1", "practiceId": "p1", "title": "Udienza sfratto", "date": "2026-06-01"},
+                {"id": "a2", "contactId": "c1", "title": "Telefonata", "date": "2026-06-02"},
+                {"id": "a3", "practiceId": "p3", "title": "Mediazione Verdi", "date": "2026-06-03"},
+            ],
+        });
+        let r = build_subject_access_report(&vault, "c1", "2026-06-10T09:00:00+02:00").unwrap();
+        assert_eq!(r["Interessato"]["Nome"], "Mario Rossi");
+        assert_eq!(r["Interessato"]["Codice fiscale"], "RSSMRA80A01H501U");
+
+        let practices = r["Fascicoli"].as_array().unwrap();
+        assert_eq!(practices.len(), 2);
+        assert_eq!(practices[0]["Riferimento fascicolo"], "p1");
+        assert_eq!(practices[0]["Oggetto"], "Sfratto");
+        // Witness in someone else's matter: reference and role only
+        assert_eq!(practices[1]["Riferimento fascicolo"], "p2");
+        assert_eq!(practices[1]["Ruolo dell'interessato"], json!(["teste"]));
+        assert!(practices[1].get("Oggetto").is_none());
+
+        let numbers: Vec<&str> = r["Fatture"].as_array().unwrap().iter().map(|i| i["Numero"].as_str().unwrap()).collect();
+        assert_eq!(numbers, vec!["2026/001", "2026/003"]);
+        assert_eq!(r["Attività registrate"].as_array().unwrap().len(), 1);
+        let agenda: Vec<&str> = r["Appuntamenti e scadenze"].as_array().unwrap().iter().map(|a| a["Titolo"].as_str().unwrap()).collect();
+        assert_eq!(agenda, vec!["Udienza sfratto", "Telefonata"]);
+
+        // Nothing identifying the other contact
+        let text = serde_json::to_string(&r).unwrap();
+        for other in ["Luigi Verdi", "VRDLGU75B02F205X", "c2", "p3", "Recupero crediti", "Divorzio", "Mediazione"] {
+            assert!(!text.contains(other), "dato di terzi nel report: {}", other);
+        }
+        assert!(build_subject_access_report(&vault, "c9", "x").is_err());
+    }
+
+    #[test]
+    fn test_vault_min_app_version_blocks_downgrade() {
+        assert_eq!(parse_app_version("3.6.0"), Some((3, 6, 0)));
+        assert_eq!(parse_app_version("v3.10"), Some((3, 10, 0)));
+        assert_eq!(parse_app_version("4.0.1-beta.2"), Some((4, 0, 1)));
+        assert_eq!(parse_app_version("abc"), None);
+
+        let mut vault = json!({"practices": []});
+        stamp_min_app_version(&mut vault, "3.7.0");
+        assert_eq!(vault["minAppVersion"], "3.7.0");
+        // Older app: refused with the required version in the message
+        let err = vault_min_version_error(&vault, "3.6.9").unwrap();
+        assert!(err.contains("≥ 3.7.0") && err.contains("3.6.9"), "{}", err);
+        assert!(vault_min_version_error(&vault, "3.10.0").is_none(), "3.10 > 3.7 (numerico, non lessicografico)");
+        // Equal and newer apps open it; a newer write raises, an older one never lowers
+        assert!(vault_min_version_error(&vault, "3.7.0").is_none());
+        stamp_min_app_version(&mut vault, "3.6.0");
+        assert_eq!(vault["minAppVersion"], "3.7.0");
+        stamp_min_app_version(&mut vault, "3.8.1");
+        assert_eq!(vault["minAppVersion"], "3.8.1");
+        // Legacy vaults without the field open everywhere
+        assert!(vault_min_version_error(&json!({"practices": []}), "1.0.0").is_none());
+
+        // Backups carry it, so an import preserves it
+        let opts = ExportOptions { include_attachments: true, include_audit: false,
+            sections: VAULT_SECTIONS.iter().map(|s| s.to_string()).collect() };
+        let backup = build_export_payload(&vault, None, &opts);
+        assert_eq!(backup["minAppVersion"], "3.8.1");
+        assert!(vault_min_version_error(&backup, "3.8.0").is_some());
+    }
+
+    #[test]
+    fn test_notification_strategy_selection() {
+        use NotificationStrategy::*;
+        use PowerSource::*;
+        assert_eq!(select_notification_strategy("auto", Ac), Poll);
+        assert_eq!(select_notification_strategy("auto", Unknown), Poll);
+        assert_eq!(select_notification_strategy("auto", Battery), NextWake, "auto su batteria deve risparmiare risvegli");
+        assert_eq!(select_notification_strategy("poll", Battery), Poll, "l'override utente vince sull'alimentazione");
+        assert_eq!(select_notification_strategy("next-wake", Ac), NextWake);
+        assert_eq!(notification_strategy_setting(&json!({"notificationStrategy": "bogus"})), "auto");
+        assert_eq!(notification_strategy_setting(&json!({})), "auto");
+
+        let now = chrono::Local.with_ymd_and_hms(2026, 3, 2, 9, 0, 30).unwrap();
+        let secs = |s, next| cron_wait(s, CRON_POLL_SECS, now, next).as_secs();
+        assert_eq!(secs(Poll, Some(now + chrono::Duration::hours(2))), CRON_POLL_SECS);
+        assert_eq!(cron_wait(Poll, 30, now, None).as_secs(), 30);
+        assert_eq!(cron_poll_secs(&json!({})), CRON_POLL_SECS);
+        assert_eq!(cron_poll_secs(&json!({"cronIntervalSecs": 1})), MIN_CRON_POLL_SECS);
+        assert_eq!(cron_poll_secs(&json!({"cronIntervalSecs": 120})), 120);
+        assert_eq!(secs(NextWake, None), CRON_MAX_WAKE_SECS);
+        assert_eq!(secs(NextWake, Some(now + chrono::Duration::seconds(150))), 151, "risveglio un secondo dopo lo scatto");
+        assert_eq!(secs(NextWake, Some(now + chrono::Duration::hours(3))), CRON_MAX_WAKE_SECS, "il sonno è limitato");
+        assert_eq!(secs(NextWake, Some(now - chrono::Duration::seconds(5))), 1);
+    }
+
+    #[test]
+    fn test_license_warning_tiers() {
+        for (days, tier) in [(365, "none"), (31, "none"), (30, "info"), (8, "info"), (7, "warning"), (1, "warning"), (0, "expired"), (-3, "expired")] {
+            assert_eq!(license_warning_tier(days), tier, "giorni rimanenti: {}", days);
+        }
+        let now = 1_800_000_000_000u64;
+        assert_eq!(license_days_remaining(now + 1, now), 1, "l'ultimo giorno parziale conta come uno");
+        assert_eq!(license_days_remaining(now + 7 * DAY_MS as u64, now), 7);
+        assert_eq!(license_days_remaining(now, now), 0);
+        assert_eq!(license_days_remaining(now - 2 * DAY_MS as u64, now), -2);
+
+        let burned = json!({"keyVersion": "ed25519-burned", "expiryMs": now + 20 * DAY_MS as u64});
+        let w = license_warning(&burned, now);
+        assert_eq!(w["tier"], "info");
+        assert_eq!(w["daysRemaining"], 20);
+        assert_eq!(license_warning(&burned, now + 15 * DAY_MS as u64)["tier"], "warning");
+        assert_eq!(license_warning(&burned, now + 21 * DAY_MS as u64)["tier"], "expired");
+
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&json!({"c": "Studio Rossi", "e": now + 3 * DAY_MS as u64, "id": "KEY-0042"})).unwrap());
+        let legacy = json!({"key": format!("LXFW.{}.sig", payload)});
+        assert_eq!(license_warning(&legacy, now)["tier"], "warning", "formato legacy letto dal token");
+        assert_eq!(license_warning(&json!({"keyVersion": "ed25519-burned"}), now)["tier"], "unknown");
+    }
+
+    #[test]
+    fn test_migrate_legacy_file_from_custom_path() {
+        let base = std::env::temp_dir().join(format!("lexflow-legacy-{}", std::process::id()));
+        let data_dir = base.join("data");
+        fs::create_dir_all(&data_dir).unwrap();
+        let source = base.join("vecchio-pc").join("settings.json");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, br#"{"preavviso": 45, "theme": "dark"}"#).unwrap();
+        assert!(write_settings(&data_dir, &json!({"preavviso": 30, "protectedSections": ["invoices"]})));
+
+        let res = import_legacy_plaintext(&data_dir, "settings", &source, false).unwrap();
+        assert_eq!(res["success"], true);
+        let stored = fs::read(data_dir.join(SETTINGS_FILE)).unwrap();
+        assert!(serde_json::from_slice::<Value>(&stored).is_err(), "il file importato deve essere cifrato");
+        let settings = read_settings(&data_dir);
+        assert_eq!(settings["preavviso"], 45, "il file legacy vince sulle chiavi che contiene");
+        assert_eq!(settings["protectedSections"], json!(["invoices"]), "le chiavi assenti dal file restano");
+        fs::write(&source, br#"{"protectedSections": []}"#).unwrap();
+        assert_eq!(import_legacy_plaintext(&data_dir, "settings", &source, false).unwrap_err(), REAUTH_REQUIRED_ERR);
+        assert_eq!(read_settings(&data_dir)["protectedSections"], json!(["invoices"]));
+
+        assert!(import_legacy_plaintext(&data_dir, "vault", &source, false).is_err(), "tipo non valido");
+        fs::write(&source, b"non json").unwrap();
+        assert!(import_legacy_plaintext(&data_dir, "schedule", &source, false).is_err());
+        assert!(!data_dir.join(NOTIF_SCHEDULE_FILE).exists(), "nessuna scrittura su input non valido");
+        assert!(import_legacy_plaintext(&data_dir, "schedule", &base.join("assente.json"), false).is_err());
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_vault_created_once_and_unlock_times_advance() {
+        let mut vault = json!({"practices": [], "agenda": []});
+        assert!(stamp_vault_created(&mut vault, "2026-01-10T09:00:00+00:00"));
+        assert!(!stamp_vault_created(&mut vault, "2026-02-01T09:00:00+00:00"), "createdAt si imposta una sola volta");
+        assert_eq!(vault["createdAt"], "2026-01-10T09:00:00+00:00");
+
+        let dir = std::env::temp_dir().join(format!("lexflow-vault-meta-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        record_vault_unlock(&dir, "2026-01-10T09:00:00+00:00").unwrap();
+        record_vault_unlock(&dir, "2026-01-11T08:30:00+00:00").unwrap();
+        let meta = read_vault_meta(&dir);
+        assert_eq!(meta["lastUnlockAt"], "2026-01-11T08:30:00+00:00", "l'ultimo sblocco deve aggiornarsi");
+        assert_eq!(meta["previousUnlockAt"], "2026-01-10T09:00:00+00:00");
+        assert_eq!(meta["unlockCount"], 2);
+        let raw = fs::read(dir.join(VAULT_META_FILE)).unwrap();
+        assert!(serde_json::from_slice::<Value>(&raw).is_err(), "metadati cifrati con la chiave locale");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_search_vault_matches_all_terms_across_sections() {
+        let vault = json!({
+            "practices": [
+                {"id": "p1", "client": "Mario Rossi", "court": "Tribunale di Milano"},
+                {"id": "p2", "client": "Rossi Srl", "court": "Tribunale di Roma"},
+                {"id": "p3", "client": "Rossi Milano", "confidential": true},
+            ],
+            "contacts": [{"id": "c1", "name": "Anna Rossi", "addresses": [{"city": "MILANO"}]}],
+            "agenda": [{"id": "a1", "title": "Udienza Rossi", "practiceId": "milano"}],
+        });
+        let res = search_vault_sections(&vault, "rossi  milano", VAULT_SECTIONS);
+        let hits: Vec<(String, String)> = res["results"].as_array().unwrap().iter()
+            .map(|r| (r["collection"].as_str().unwrap().into(), r["id"].as_str().unwrap().into())).collect();
+        assert_eq!(hits, vec![("practices".into(), "p1".into()), ("contacts".into(), "c1".into())],
+            "entrambi i termini, in qualsiasi campo; fascicoli riservati e riferimenti esclusi");
+        assert_eq!(res["results"][0]["matchedFields"], json!(["client", "court"]));
+        assert_eq!(res["results"][1]["matchedFields"], json!(["addresses.0.city", "name"]));
+        assert_eq!(res["truncated"], false);
+
+        let only_agenda = search_vault_sections(&vault, "ROSSI", &["agenda"]);
+        assert_eq!(only_agenda["results"].as_array().unwrap().len(), 1);
+        assert!(search_vault_sections(&vault, "   ", VAULT_SECTIONS)["results"].as_array().unwrap().is_empty());
+
+        let many = json!({"practices": (0..MAX_SEARCH_RESULTS + 5).map(|i| json!({"id": i.to_string(), "client": "Rossi"})).collect::<Vec<_>>()});
+        let capped = search_vault_sections(&many, "rossi", VAULT_SECTIONS);
+        assert_eq!(capped["results"].as_array().unwrap().len(), MAX_SEARCH_RESULTS);
+        assert_eq!(capped["truncated"], true);
+    }
+
+    #[test]
+    fn test_diagnostics_bundle_is_redacted() {
+        let data_dir = std::path::Path::new("/home/avv.mario/Library/LexFlow/lexflow-vault");
+        let sec_dir = std::path::Path::new("/home/avv.mario/Library/LexFlow");
+        let redactions = vec![
+            (data_dir.to_string_lossy().into_owned(), "<DATA_DIR>"),
+            (sec_dir.to_string_lossy().into_owned(), "<SECURITY_DIR>"),
+            ("/home/avv.mario".to_string(), "~"),
+            ("avv.mario".to_string(), "<USER>"),
+        ];
+        let log = vec![
+            "[LexFlow] Rimosso file temporaneo orfano: \"/home/avv.mario/Library/LexFlow/lexflow-vault/vault.lex.tmp\"".to_string(),
+            "[LexFlow] Sessione di avv.mario".to_string(),
+        ];
+        let storage = json!({"files": {"vault.lex": {"exists": true, "bytes": 2048}}, "path": "/home/avv.mario/Documenti"});
+        let bundle = build_diagnostics_bundle("2026-03-02T09:00:00+01:00", json!({"version": "1.0.0"}), storage, Some(log), &redactions);
+        let text = bundle.to_string();
+        assert!(!text.contains("avv.mario"), "nome utente e percorsi devono essere oscurati");
+        assert!(text.contains("<DATA_DIR>/vault.lex.tmp"));
+        assert!(text.contains("~/Documenti"));
+        for section in ["capabilities", "support", "storage", "log"] {
+            assert!(!bundle[section].is_null(), "manca la sezione {}", section);
+        }
+        let no_log = build_diagnostics_bundle("", json!({}), json!({}), None, &redactions);
+        assert!(no_log["log"].is_null());
+
+        let real = storage_health(&std::env::temp_dir());
+        assert!(real["files"].get(VAULT_FILE).is_some());
+
+        // The exported file is sealed: nothing readable without the passphrase
+        let sealed = seal_diagnostics_bundle(&bundle, "Supporto-Diagn0stica!").unwrap();
+        assert!(sealed.starts_with(DIAGNOSTICS_MAGIC));
+        assert!(!String::from_utf8_lossy(&sealed).contains("lexflow-diagnostics"));
+        assert_eq!(open_diagnostics_bundle(&sealed, "Supporto-Diagn0stica!").unwrap(), bundle);
+        assert!(open_diagnostics_bundle(&sealed, "Sbagliata-Passw0rd!").is_err());
+        assert!(open_diagnostics_bundle(b"{}", "Supporto-Diagn0stica!").is_err());
+    }
+
+    #[test]
+    fn test_old_identifier_migration_copies_only_missing() {
+        let base = std::env::temp_dir().join(format!("lexflow-oldid-{}", std::process::id()));
+        let old = base.join("com.technojaw.lexflow");
+        let sec = base.join("com.pietrolongo.lexflow");
+        let data = sec.join("lexflow-vault");
+        fs::create_dir_all(old.join("lexflow-vault")).unwrap();
+        fs::create_dir_all(&data).unwrap();
+        for (name, body) in [(VAULT_FILE, "old-vault"), (VAULT_SALT_FILE, "old-salt"), (SETTINGS_FILE, "old-settings"), (NOTIF_SCHEDULE_FILE, "old-schedule")] {
+            fs::write(old.join("lexflow-vault").join(name), body).unwrap();
+        }
+        fs::write(old.join(LICENSE_FILE), "old-license").unwrap();
+        fs::write(old.join(BURNED_KEYS_FILE), "old-burned").unwrap();
+        // Already present in the new location
+        fs::write(data.join(SETTINGS_FILE), "new-settings").unwrap();
+        fs::write(sec.join(LICENSE_FILE), "new-license").unwrap();
+
+        let report = migrate_old_identifier_dirs(&old, &data, &sec);
+        assert_eq!(report["found"], true);
+        assert_eq!(fs::read_to_string(data.join(SETTINGS_FILE)).unwrap(), "new-settings", "mai sovrascrivere");
+        assert_eq!(fs::read_to_string(sec.join(LICENSE_FILE)).unwrap(), "new-license");
+        assert_eq!(fs::read_to_string(data.join(VAULT_FILE)).unwrap(), "old-vault");
+        assert_eq!(fs::read_to_string(sec.join(BURNED_KEYS_FILE)).unwrap(), "old-burned");
+        let copied: Vec<&str> = report["copied"].as_array().unwrap().iter().filter_map(|v| v.as_str()).collect();
+        assert!(copied.contains(&"lexflow-vault/notification-schedule.json") && copied.contains(&".burned-keys"));
+        assert!(report["skipped"].as_array().unwrap().contains(&json!("license.json")));
+        assert_eq!(report["vaultConflict"], false);
+
+        // A second vault in the new location: the old vault set must not be mixed in
+        fs::remove_file(data.join(VAULT_FILE)).unwrap();
+        fs::write(data.join(VAULT_SALT_FILE), "new-salt").unwrap();
+        let again = migrate_old_identifier_dirs(&old, &data, &sec);
+        assert_eq!(again["vaultConflict"], true);
+        assert!(!data.join(VAULT_FILE).exists(), "vault di un altro sale non va copiato");
+        assert!(!migrate_old_identifier_dirs(&base.join("assente"), &data, &sec)["found"].as_bool().unwrap());
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_chunked_vault_frames_round_trip_and_tamper() {
+        use std::io::Write;
+        let key = [9u8; 32];
+        let seal = |plain: &[u8], frame: usize| {
+            let mut w = ChunkedWriter::new(&key, Vec::new(), frame).unwrap();
+            w.write_all(plain).unwrap();
+            w.finish().unwrap()
+        };
+        let plain: Vec<u8> = (0..100u8).collect();
+        // several frames, an exact multiple of the frame size, and an empty stream
+        for (data, frame) in [(&plain[..], 16), (&plain[..96], 16), (&plain[..0], 16), (&plain[..], CHUNK_FRAME_LEN)] {
+            let sealed = seal(data, frame);
+            assert_eq!(vault_header_version(&sealed), Some(CHUNKED_FORMAT_VERSION));
+            assert_eq!(decrypt_data(&key, &sealed).unwrap(), data);
+        }
+
+        let sealed = seal(&plain, 16);
+        let frame = NONCE_LEN + 16 + 16;
+        let body = 17 + 4;
+        assert!(decrypt_data(&[1u8; 32], &sealed).is_err(), "chiave errata");
+        let mut swapped = sealed.clone();
+        let (a, b) = (body, body + frame);
+        let first = sealed[a..a + frame].to_vec();
+        swapped[a..a + frame].copy_from_slice(&sealed[b..b + frame]);
+        swapped[b..b + frame].copy_from_slice(&first);
+        assert!(decrypt_data(&key, &swapped).is_err(), "frame riordinati devono fallire");
+        assert!(decrypt_data(&key, &sealed[..body + 2 * frame]).is_err(), "troncamento a confine di frame");
+        let mut bad_len = sealed.clone();
+        bad_len[17] = 32;
+        assert!(decrypt_data(&key, &bad_len).is_err(), "frame_len autenticato");
+
+        // File path used by read/write_vault_internal, and the v2 fallback
+        let dir = std::env::temp_dir().join(format!("lexflow-chunked-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vault = json!({"practices": [{"id": "p1", "client": "Rossi"}], "agenda": []});
+        write_vault_file(&key, &dir.join(VAULT_FILE), &vault).unwrap();
+        assert_eq!(vault_header_version(&fs::read(dir.join(VAULT_FILE)).unwrap()), Some(3));
+        assert_eq!(read_vault_file(&key, &dir.join(VAULT_FILE)).unwrap(), vault);
+        fs::write(dir.join(VAULT_FILE), encrypt_data(&key, vault.to_string().as_bytes()).unwrap()).unwrap();
+        assert_eq!(read_vault_file(&key, &dir.join(VAULT_FILE)).unwrap(), vault, "vault v2 ancora leggibile");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_patch_practice_shallow_merge() {
+        let practices = json!([
+            {"id": "p1", "client": "Rossi", "court": "Milano", "notes": "da rivedere"},
+            {"id": "p2", "client": "Bianchi"},
+        ]);
+        assert_eq!(practice_index(&practices, "p2"), Some(1));
+        assert_eq!(practice_index(&practices, "p9"), None, "id inesistente");
+
+        let patched = patch_practice(&practices[0], &json!({"court": "Roma", "notes": null, "status": "closed", "id": "altro"})).unwrap();
+        assert_eq!(patched, json!({"id": "p1", "client": "Rossi", "court": "Roma", "status": "closed"}),
+            "merge superficiale: null elimina, id invariato");
+        let nested = patch_practice(&json!({"id": "p1", "fees": {"a": 1, "b": 2}}), &json!({"fees": {"a": 3}})).unwrap();
+        assert_eq!(nested["fees"], json!({"a": 3}), "gli oggetti annidati si sostituiscono");
+        assert!(patch_practice(&practices[0], &json!(["x"])).is_err());
+    }
+
+    #[test]
+    fn test_idle_lock_hide_decision() {
+        let hide = json!({"onIdleHideWindow": true});
+        assert_eq!(decide_idle_lock_action(&hide, true), IdleLockAction::LockAndHide);
+        assert_eq!(decide_idle_lock_action(&hide, false), IdleLockAction::Lock, "mobile: solo blocco");
+        assert_eq!(decide_idle_lock_action(&json!({"onIdleHideWindow": false}), true), IdleLockAction::Lock);
+        assert_eq!(decide_idle_lock_action(&json!({}), true), IdleLockAction::Lock, "predefinito: solo blocco");
+    }
+
+    #[test]
+    fn test_vault_integrity_invariants() {
+        let healthy = json!({
+            "practices": [{"id": "p1", "deadlines": [{"date": "2026-03-02"}], "roles": [{"contactId": "c1"}]}],
+            "agenda": [],
+            "contacts": [{"id": "c1"}],
+        });
+        assert!(check_vault_integrity(&healthy).is_empty());
+
+        let broken = json!({
+            "practices": [
+                {"id": "p1", "deadlines": [{"date": "02/03/2026"}, {}]},
+                {"id": "p1"},
+                {"id": " "},
+                {"id": "p4", "roles": [{"contactId": "c9"}]},
+            ],
+            "contacts": [{"id": "c1"}],
+        });
+        let paths: Vec<String> = check_vault_integrity(&broken).iter()
+            .map(|e| e["path"].as_str().unwrap().to_string()).collect();
+        assert_eq!(paths, vec![
+            "agenda", "practices[0].deadlines[0].date", "practices[0].deadlines[1].date",
+            "practices[1].id", "practices[2].id", "practices[3].roles[0].contactId",
+        ]);
+        assert_eq!(check_vault_integrity(&json!([]))[0]["path"], "$");
+    }
+
+    #[test]
+    fn test_dangling_agenda_links_detected_and_repaired() {
+        let vault = json!({
+            "practices": [{"id": "p1"}],
+            "trash": [{"deletedAt": "2026-01-01T00:00:00Z", "practice": {"id": "p2"}}],
+            "agenda": [
+                {"id": "a1", "practiceId": "p1"},
+                {"id": "a2", "practiceId": "p9"},
+                {"id": "a3", "practiceId": "p2"},
+                {"id": "a4", "practiceId": null},
+                {"id": "a5"},
+                {"id": "a6", "practiceId": "p8"},
+            ],
+        });
+        assert_eq!(dangling_agenda_links(&vault), vec![1, 5], "fascicoli nel cestino non contano");
+
+        let mut unlinked = vault.clone();
+        assert_eq!(repair_dangling_agenda_links(&mut unlinked, AgendaLinkRepair::Unlink), 2);
+        assert_eq!(unlinked["agenda"].as_array().unwrap().len(), 6);
+        assert!(unlinked["agenda"][1]["practiceId"].is_null() && unlinked["agenda"][5]["practiceId"].is_null());
+        assert!(dangling_agenda_links(&unlinked).is_empty());
+
+        let mut removed = vault.clone();
+        assert_eq!(repair_dangling_agenda_links(&mut removed, AgendaLinkRepair::Remove), 2);
+        let ids: Vec<&str> = removed["agenda"].as_array().unwrap().iter().map(|a| a["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["a1", "a3", "a4", "a5"]);
+        assert_eq!(repair_dangling_agenda_links(&mut removed, AgendaLinkRepair::Remove), 0);
+        assert!(agenda_link_repair("cancella").is_err());
+    }
+
+    #[test]
+    fn test_signed_artifact_rejects_tampered_signature() {
+        use ed25519_dalek::{Signer, SigningKey};
+        let signing = SigningKey::from_bytes(&[7u8; 32]);
+        let public = signing.verifying_key().to_bytes();
+        let sign = |prefix: &str, payload: Value| {
+            let payload = URL_SAFE_NO_PAD.encode(payload.to_string());
+            let sig = URL_SAFE_NO_PAD.encode(signing.sign(payload.as_bytes()).to_bytes());
+            format!("{}.{}.{}", prefix, payload, sig)
+        };
+        let bundle = sign("LXFW", json!({"c": "Studio", "e": 1u64, "id": "B-1", "s": ["a", "b", "c"]}));
+        assert_eq!(check_signed_artifact(&bundle, "seat-bundle", &public).unwrap()["seats"], 3);
+        let revocations = sign("LXRV", json!({"r": ["K-1", "K-2"], "i": 5u64}));
+        assert_eq!(check_signed_artifact(&revocations, "revocation-list", &public).unwrap()["revoked"], 2);
+        assert!(check_signed_artifact(&revocations, "seat-bundle", &public).is_err(), "tipo errato");
+
+        let mut tampered = revocations.clone();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == 'A' { 'B' } else { 'A' });
+        assert!(check_signed_artifact(&tampered, "revocation-list", &public).is_err(), "firma manomessa");
+        assert!(check_signed_artifact(&bundle, "seat-bundle", &PUBLIC_KEY_BYTES).is_err(), "firma estranea");
+    }
+
+    #[test]
+    fn test_plaintext_export_starts_with_warning() {
+        let vault = json!({"practices": [{"id": "p1", "client": "Rossi"}], "agenda": []});
+        let doc = plaintext_export_document(&vault, "2026-01-01T00:00:00+01:00");
+        let text = String::from_utf8(serde_json::to_vec_pretty(&doc).unwrap()).unwrap();
+        assert!(text.starts_with("{\n  \"_warning\": \"UNENCRYPTED\""), "l'avviso deve aprire il file");
+        let back: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(back["vault"], vault);
+    }
+
+    #[test]
+    fn test_perf_samples_land_in_fixed_buckets() {
+        let mut t = OpTimings::default();
+        for ms in [0, 49, 50, 199, 200, 999, 1000, 5000] {
+            t.record(Duration::from_millis(ms));
+        }
+        assert_eq!(t.buckets, [2, 2, 2, 2], "limiti dei bucket: 50, 200, 1000 ms esclusi");
+        let report = t.to_json();
+        assert_eq!(report["count"], 8);
+        assert_eq!(report["maxMs"], 5000);
+        assert_eq!(report["histogram"][">1s"], 2);
+        assert_eq!(OpTimings::default().to_json()["avgMs"], 0);
+    }
+
+    #[test]
+    fn test_dir_layout_flags_security_inside_data() {
+        let base = std::env::temp_dir().join(format!("lexflow-layout-{}", rand::random::<u64>()));
+        let data = base.join("lexflow-vault");
+        fs::create_dir_all(&data).unwrap();
+        assert!(dir_layout_issues(&data, &base).is_empty(), "layout corretto");
+
+        let nested = data.join("sec");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(dir_layout_issues(&data, &nested).len(), 1, "sicurezza dentro il vault");
+        assert_eq!(dir_layout_issues(&data, &data).len(), 1, "cartelle coincidenti");
+
+        fs::write(data.join(LICENSE_FILE), b"x").unwrap();
+        fs::write(base.join(VAULT_FILE), b"x").unwrap();
+        assert_eq!(dir_layout_issues(&data, &base).len(), 2, "file fuori posto");
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_vault_kdf_params_drive_derivation() {
+        let dir = std::env::temp_dir().join(format!("lexflow-kdf-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let salt = [2u8; ARGON2_SALT_LEN];
+        assert_eq!(read_vault_kdf(&dir).unwrap(), DEFAULT_KDF_PARAMS, "vault senza vault.kdf: parametri di default");
+        let legacy = derive_vault_key("Password-Forte-1!", &dir, &salt).unwrap();
+        assert_eq!(legacy, derive_secure_key("Password-Forte-1!", &salt).unwrap());
+
+        let stronger = KdfParams { m_cost: 32768, ..DEFAULT_KDF_PARAMS };
+        write_vault_kdf(&dir.join(VAULT_KDF_FILE), &stronger).unwrap();
+        let key = derive_vault_key("Password-Forte-1!", &dir, &salt).unwrap();
+        assert_ne!(key, legacy, "i parametri salvati cambiano la chiave");
+        assert_eq!(key, derive_key_with_params("Password-Forte-1!", &salt, &stronger).unwrap());
+        assert_eq!(manifest_kdf_params(&json!({"kdf": stronger})).unwrap(), stronger);
+        assert_eq!(manifest_kdf_params(&json!({})).unwrap(), DEFAULT_KDF_PARAMS);
+
+        assert!(KdfParams { m_cost: 1024, ..DEFAULT_KDF_PARAMS }.validate().is_err(), "mai più deboli dei default");
+        assert!(KdfParams { m_cost: MAX_KDF_M_COST + 1, ..DEFAULT_KDF_PARAMS }.validate().is_err());
+        fs::write(dir.join(VAULT_KDF_FILE), b"{").unwrap();
+        assert!(derive_vault_key("Password-Forte-1!", &dir, &salt).is_err(), "vault.kdf corrotto non ripiega sui default");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_password_source() {
+        assert_eq!(import_password_source(true, None).unwrap(), "backup");
+        assert_eq!(import_password_source(true, Some("ignorata")).unwrap(), "backup");
+        assert_eq!(import_password_source(false, Some("Nuova-Password-1!")).unwrap(), "entered");
+        assert!(import_password_source(false, None).is_err(), "manca la nuova password");
+        assert!(import_password_source(false, Some("debole")).is_err(), "policy applicata");
+    }
+
+    #[test]
+    fn test_key_format_precheck() {
+        let well_formed = "LXFW.eyJjIjoieCJ9.c2lnbmF0dXJh";
+        assert!(key_format_error(well_formed).is_none());
+        assert!(key_format_error(&format!("  {}\n", well_formed)).is_none(), "spazi incollati");
+        assert!(key_format_error("LXRP.eyJjIjoieCJ9.c2lnbmF0dXJh").is_some(), "prefisso errato");
+        assert!(key_format_error("LXFW.eyJjIjoieCJ9").is_some(), "parti mancanti");
+        assert!(key_format_error("LXFW.eyJj*IjoieCJ9.c2lnbmF0dXJh").is_some(), "base64 non valido");
+        assert!(key_format_error("LXFW..c2lnbmF0dXJh").is_some(), "payload vuoto");
+        assert_eq!(is_valid_key_format(well_formed.into())["valid"], true);
+    }
+
+    #[test]
+    fn test_adversity_map_client_and_counterparty() {
+        let vault = json!({
+            "contacts": [{"id": "c1", "name": "Mario Rossi"}, {"id": "c2", "name": "Beta Srl"}],
+            "practices": [
+                {"id": "p1", "client": "Mario Rossi", "clientId": "c1", "counterparty": "Beta Srl", "counterpartyId": "c2"},
+                {"id": "p2", "client": "Anna Bianchi", "clientId": "c3", "counterparty": "Mario Rossi", "counterpartyId": "c1"},
+                {"id": "p3", "client": "Gamma Spa", "roles": [{"contactId": "c1", "role": "opposing_counsel"}, {"contactId": "c1", "role": "teste"}]},
+                {"id": "p4", "client": "Delta", "confidential": true, "counterpartyId": "c1"}
+            ]
+        });
+        let map = build_adversity_map(&vault, "c1", false).unwrap();
+        assert_eq!(map["asClient"].as_array().unwrap().len(), 1);
+        assert_eq!(map["asClient"][0]["id"], "p1");
+        assert_eq!(map["asCounterparty"].as_array().unwrap().len(), 2);
+        assert_eq!(map["adverseRoles"][0]["role"], "opposing_counsel");
+        assert_eq!(map["otherRoles"][0]["role"], "teste");
+        assert_eq!(map["adverseToClients"], json!(["Anna Bianchi", "Gamma Spa"]), "mai i clienti dei fascicoli riservati");
+        assert!(map["asCounterparty"][1].get("client").is_none(), "fascicolo riservato ridotto all'id");
+        assert!(build_adversity_map(&vault, "c9", false).is_err());
+        let revealed = build_adversity_map(&vault, "c1", true).unwrap();
+        assert_eq!(revealed["asCounterparty"][1]["client"], "Delta", "con riautenticazione: dettagli visibili");
+        assert_eq!(revealed["adverseToClients"], json!(["Anna Bianchi", "Gamma Spa", "Delta"]));
+    }
+
+    #[test]
+    fn test_profile_slug_and_dirs() {
+        assert_eq!(profile_slug("  Giulia Bianchi ").as_deref(), Some("giulia-bianchi"));
+        assert_eq!(profile_slug("../../etc/passwd").as_deref(), Some("etc-passwd"), "nessun separatore sopravvive");
+        assert_eq!(profile_slug("..").as_deref(), None);
+        assert_eq!(profile_slug("Studio_2024!!").as_deref(), Some("studio-2024"));
+        assert_eq!(profile_slug(&"a".repeat(100)).unwrap().len(), MAX_PROFILE_SLUG_LEN);
+
+        let sec = std::env::temp_dir().join(format!("lexflow-profiles-{}", rand::random::<u64>()));
+        assert_eq!(profile_data_dir(&sec, DEFAULT_PROFILE), sec.join(DEFAULT_VAULT_DIR));
+        assert_eq!(profile_lockout_dir(&sec, DEFAULT_PROFILE), sec);
+        assert!(!profile_lockout_dir(&sec, "anna").starts_with(profile_data_dir(&sec, "anna")), "blocco fuori dal vault");
+        fs::create_dir_all(profile_data_dir(&sec, "marco")).unwrap();
+        fs::create_dir_all(profile_data_dir(&sec, "anna")).unwrap();
+        assert_eq!(list_profile_slugs(&sec), vec!["default", "anna", "marco"]);
+        assert_eq!(read_active_profile(&sec), DEFAULT_PROFILE);
+        fs::write(sec.join(ACTIVE_PROFILE_FILE), "marco").unwrap();
+        assert_eq!(read_active_profile(&sec), "marco");
+        fs::write(sec.join(ACTIVE_PROFILE_FILE), "sparito").unwrap();
+        assert_eq!(read_active_profile(&sec), DEFAULT_PROFILE, "profilo rimosso: si torna al default");
+        let _ = fs::remove_dir_all(&sec);
+    }
+
+    #[test]
+    fn test_autolock_countdown_ticks() {
+        let threshold = Duration::from_secs(300);
+        let at = |secs: u64| Duration::from_secs(secs);
+        assert_eq!(autolock_tick(at(100), threshold), AutolockTick::Idle);
+        assert_eq!(autolock_tick(at(240), threshold), AutolockTick::Warn(60));
+        assert_eq!(autolock_tick(Duration::from_millis(299_500), threshold), AutolockTick::Warn(1), "arrotonda per eccesso");
+        assert_eq!(autolock_tick(at(300), threshold), AutolockTick::Lock);
+        assert_eq!(autolock_sleep(at(0), threshold), at(AUTOLOCK_IDLE_TICK_SECS));
+        assert_eq!(autolock_sleep(at(230), threshold), at(10), "non salta l'inizio del conto alla rovescia");
+        assert_eq!(autolock_sleep(at(250), threshold), at(1), "ultimo minuto: ogni secondo");
+    }
+
+    #[test]
+    fn test_practice_secret_round_trip_and_excluded_from_bulk_load() {
+        let (old_key, new_key) = ([1u8; 32], [2u8; 32]);
+        let sealed = seal_secret(&old_key, "p1", "PEC", "s3greto-pec").unwrap();
+        assert_eq!(open_secret(&old_key, "p1", "PEC", &sealed).unwrap().as_str(), "s3greto-pec");
+        assert!(open_secret(&old_key, "p1", "Portale", &sealed).is_err(), "l'etichetta è AAD");
+        assert!(open_secret(&old_key, "p2", "PEC", &sealed).is_err(), "il fascicolo è AAD");
+
+        let mut vault = json!({
+            "practices": [{"id": "p1", "client": "Rossi"}],
+            "secrets": [{"practiceId": "p1", "label": "PEC", "sealed": sealed}],
+        });
+        assert_eq!(secret_index(&vault, "p1", "PEC"), Some(0));
+        let (visible, _) = visible_practices(vault["practices"].as_array().unwrap());
+        assert!(!json!(visible).to_string().contains("PEC"), "i segreti non escono col caricamento dei fascicoli");
+
+        // Password change: reopened under the new key only
+        reseal_secrets(&mut vault, Some(&old_key), Some(&new_key)).unwrap();
+        let resealed = vault["secrets"][0]["sealed"].as_str().unwrap().to_string();
+        assert!(vault["secrets"][0].get("value").is_none());
+        assert_eq!(open_secret(&new_key, "p1", "PEC", &resealed).unwrap().as_str(), "s3greto-pec");
+        assert!(open_secret(&old_key, "p1", "PEC", &resealed).is_err());
+
+        // Backups carry them with the practices, opened inside the backup's own encryption
+        reseal_secrets(&mut vault, Some(&new_key), None).unwrap();
+        let opts = ExportOptions { include_attachments: true, include_audit: false,
+            sections: VAULT_SECTIONS.iter().map(|s| s.to_string()).collect() };
+        let backup = build_export_payload(&vault, None, &opts);
+        assert_eq!(backup["secrets"][0]["value"], "s3greto-pec");
+        let only_contacts = ExportOptions { sections: vec!["contacts".into()], ..opts };
+        assert!(build_export_payload(&vault, None, &only_contacts).get("secrets").is_none());
+    }
+
+    #[test]
+    fn test_simulate_restore_in_memory() {
+        let salt = [4u8; BACKUP_SALT_LEN];
+        let payload = json!({
+            "schemaVersion": VAULT_SCHEMA_VERSION,
+            "practices": [{"id": "p1"}, {"id": "p2"}], "agenda": [], "timeLogs": [], "invoices": [], "contacts": [{"id": "c1"}],
+        });
+        let key = derive_secure_key("Backup-Password-1!", &salt).unwrap();
+        let mut raw = salt.to_vec();
+        raw.extend(encrypt_data(&key, payload.to_string().as_bytes()).unwrap());
+
+        let path = std::env::temp_dir().join(format!("lexflow-simulate-{}.lex", rand::random::<u64>()));
+        fs::write(&path, &raw).unwrap();
+        let ok = simulate_restore(path.to_string_lossy().into(), "Backup-Password-1!".into());
+        assert_eq!(ok["success"], true, "{}", ok);
+        assert_eq!(ok["counts"]["practices"], 2);
+        assert_eq!(ok["counts"]["contacts"], 1);
+
+        let wrong = simulate_restore(path.to_string_lossy().into(), "Sbagliata-1!".into());
+        assert_eq!(wrong["success"], false);
+        assert_eq!(fs::read(&path).unwrap(), raw, "nessun effetto sul file");
+        assert_eq!(simulate_restore_bytes(&raw[..10], "x")["success"], false);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_autolock_mode_setting() {
+        assert_eq!(autolock_mode(&json!({"autolockMode": "prompt"})), AutolockMode::Prompt);
+        assert_eq!(autolock_mode(&json!({"autolockMode": "lock"})), AutolockMode::Lock);
+        assert_eq!(autolock_mode(&json!({"autolockMode": "boh"})), AutolockMode::Lock, "valore ignoto: blocco");
+        assert_eq!(autolock_mode(&json!({})), AutolockMode::Lock, "predefinito: blocco immediato");
+
+        // "prompt": the threshold opens the grace instead of locking, the key stays until it expires
+        let start = Instant::now();
+        let threshold = Duration::from_secs(300);
+        let at_threshold = autolock_tick(threshold, threshold);
+        let grace = Duration::from_secs(AUTOLOCK_GRACE_SECS);
+        assert_eq!(autolock_action(None, at_threshold, start, || AutolockMode::Prompt), AutolockAction::StartGrace(start + grace));
+        assert_eq!(autolock_action(None, at_threshold, start, || AutolockMode::Lock), AutolockAction::Lock, "modalità blocco: subito");
+        let deadline = start + grace;
+        assert_eq!(autolock_action(Some(deadline), at_threshold, start + grace / 2, || AutolockMode::Prompt), AutolockAction::Wait);
+        assert_eq!(autolock_action(Some(deadline), at_threshold, deadline, || AutolockMode::Prompt), AutolockAction::Lock, "grazia scaduta: blocco");
+        assert_eq!(autolock_action(None, autolock_tick(Duration::from_secs(100), threshold), start, || panic!("modalità letta solo alla soglia")), AutolockAction::Wait);
+
+        // A re-auth during the grace closes it and restarts the idle clock
+        let grace_until = Mutex::new(Some(deadline));
+        let last_activity = Mutex::new(start - threshold);
+        assert!(end_autolock_grace(&grace_until, &last_activity));
+        assert!(grace_until.lock().unwrap().is_none());
+        let idle = last_activity.lock().unwrap().elapsed();
+        assert_eq!(autolock_action(None, autolock_tick(idle, threshold), Instant::now(), || AutolockMode::Prompt), AutolockAction::Wait, "riautenticato: nessun blocco");
+        assert!(!end_autolock_grace(&grace_until, &last_activity), "senza grazia aperta non cambia nulla");
+    }
+
+    #[test]
+    fn test_purge_trash_entries_by_age() {
+        let now = chrono::Utc::now();
+        let old = (now - chrono::Duration::days(40)).to_rfc3339();
+        let recent = (now - chrono::Duration::days(2)).to_rfc3339();
+        let mut vault = json!({
+            "practices": [{"id": "p3"}],
+            "trash": [
+                {"deletedAt": old, "practice": {"id": "p1"}},
+                {"deletedAt": recent, "practice": {"id": "p2"}},
+                {"deletedAt": "boh", "practice": {"id": "p4"}},
+            ],
+            "secrets": [{"practiceId": "p1", "label": "PEC"}, {"practiceId": "p2", "label": "PEC"}, {"practiceId": "p3", "label": "PEC"}],
+        });
+        assert_eq!(purge_trash_entries(&mut vault, now - chrono::Duration::days(30)), 1);
+        assert!(trash_index(&vault, "p1").is_none());
+        assert!(trash_index(&vault, "p2").is_some());
+        assert!(trash_index(&vault, "p4").is_some(), "data illeggibile: l'elemento resta");
+        let owners: Vec<&str> = vault["secrets"].as_array().unwrap().iter().map(|e| e["practiceId"].as_str().unwrap()).collect();
+        assert_eq!(owners, vec!["p2", "p3"], "i segreti del fascicolo eliminato spariscono");
+        assert_eq!(purge_trash_entries(&mut vault, now), 1, "0 giorni: svuota tutto ciò che ha una data");
+
+        // A practice deleted by a list save: its secrets go, a trashed one keeps them
+        let mut vault = json!({
+            "practices": [{"id": "p1"}, {"id": "p2"}],
+            "trash": [{"deletedAt": recent, "practice": {"id": "p3"}}],
+            "secrets": [{"practiceId": "p1", "label": "PEC"}, {"practiceId": "p2", "label": "PEC"}, {"practiceId": "p3", "label": "PEC"}],
+        });
+        let mut pending = serde_json::Map::new();
+        pending.insert("practices".into(), json!([{"id": "p2"}]));
+        apply_pending_saves(&mut vault, pending);
+        let owners: Vec<&str> = vault["secrets"].as_array().unwrap().iter().map(|e| e["practiceId"].as_str().unwrap()).collect();
+        assert_eq!(owners, vec!["p2", "p3"], "i segreti del fascicolo rimosso dalla lista spariscono");
+        assert_eq!(prune_orphaned_secrets(&mut vault), 0);
+        assert_eq!(purge_trash_entries(&mut json!({}), now), 0);
+        assert_eq!(trash_retention_days(&json!({})), TRASH_RETENTION_DAYS);
+        assert_eq!(trash_retention_days(&json!({"trashRetentionDays": 7})), 7);
+    }
+
+    #[test]
+    fn test_feature_entitlements_gate() {
+        let now = 1_800_000_000_000u64;
+        let tiered = json!({"keyVersion": "ed25519-burned", "features": ["pct"]});
+        assert!(authorize_feature(Some(&tiered), "pct", now, LICENSE_GRACE_DAYS).is_ok(), "modulo incluso: consentito");
+        let err = authorize_feature(Some(&tiered), FEATURE_BILLING, now, LICENSE_GRACE_DAYS).unwrap_err();
+        assert!(err.starts_with("FeatureNotLicensed"), "modulo assente: respinto ({})", err);
+        let legacy = json!({"keyVersion": "ed25519-burned"});
+        assert!(authorize_feature(Some(&legacy), FEATURE_BILLING, now, LICENSE_GRACE_DAYS).is_ok(), "token senza features: set predefinito");
+        assert!(authorize_feature(None, FEATURE_BILLING, now, LICENSE_GRACE_DAYS).is_err(), "nessuna licenza: respinto");
+
+        // Expiry: usable through the grace window, refused after it
+        let expiry = now - 3 * DAY_MS as u64;
+        let dated = json!({"keyVersion": "ed25519-burned", "features": ["billing"], "expiryMs": expiry});
+        assert!(authorize_feature(Some(&dated), FEATURE_BILLING, expiry - 1, LICENSE_GRACE_DAYS).is_ok(), "licenza valida");
+        assert!(authorize_feature(Some(&dated), FEATURE_BILLING, now, LICENSE_GRACE_DAYS).is_ok(), "periodo di tolleranza");
+        let err = authorize_feature(Some(&dated), FEATURE_BILLING, now, 2).unwrap_err();
+        assert!(err.starts_with("LicenseExpired"), "scaduta oltre la tolleranza: respinto ({})", err);
+        let payload = URL_SAFE_NO_PAD.encode(br#"{"c":"x","e":1,"id":"k","f":["billing","caldav"]}"#);
+        let raw = json!({"key": format!("LXFW.{}.sig", payload)});
+        assert_eq!(license_entitlements(&raw), vec!["billing", "caldav"], "record legacy: dal token");
+    }
+
+    #[test]
+    fn test_find_duplicate_practices_clusters_same_client() {
+        let practices = vec![
+            json!({"id": "p1", "clientId": "c1", "client": "Rossi Srl", "object": "Recupero crediti fornitura 2025", "status": "active"}),
+            json!({"id": "p2", "clientId": "c1", "client": "Rossi Srl", "object": "Recupero crediti - fornitura 2025", "status": "active"}),
+            json!({"id": "p3", "clientId": "c1", "client": "Rossi Srl", "object": "Separazione consensuale", "status": "active"}),
+            json!({"id": "p4", "clientId": "c2", "client": "Bianchi", "object": "Recupero crediti fornitura 2025", "status": "active"}),
+        ];
+        let clusters = find_duplicate_clusters(&practices, false);
+        assert_eq!(clusters.len(), 1, "solo p1 e p2 sono duplicati");
+        let ids: Vec<&str> = clusters[0]["practices"].as_array().unwrap().iter().map(|p| p["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["p1", "p2"]);
+        assert_eq!(clusters[0]["clientId"], "c1");
+        assert!(clusters[0]["score"].as_f64().unwrap() >= DUPLICATE_SIMILARITY);
+
+        let mut closed = practices.clone();
+        closed[1]["status"] = json!("closed");
+        assert!(find_duplicate_clusters(&closed, false).is_empty(), "i chiusi sono esclusi di default");
+        assert_eq!(find_duplicate_clusters(&closed, true).len(), 1);
+        assert!(text_similarity("Recupero crediti", "Separazione consensuale") < DUPLICATE_SIMILARITY);
+        assert_eq!(text_similarity("", "x"), 0.0);
+    }
+
+    #[test]
+    fn test_attachment_audit_stats_paths() {
+        let dir = std::env::temp_dir().join(format!("lexflow-attaudit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let present = dir.join("atto.pdf");
+        fs::write(&present, b"%PDF").unwrap();
+        let missing = dir.join("spostato.pdf");
+        let practices = vec![json!({
+            "id": "p1",
+            "attachments": [{"path": present.to_str().unwrap()}, {"path": missing.to_str().unwrap()}, {"path": "relativo/atto.pdf"}],
+            "documents": [{"name": "senza percorso"}],
+            "folderPath": dir.to_str().unwrap(),
+        })];
+        let paths = collect_attachment_paths(&practices);
+        assert_eq!(paths.len(), 3, "il percorso relativo è ignorato");
+        assert_eq!(paths[2].1, "folderPath");
+        assert_eq!(stat_attachment(&present), (true, false));
+        assert_eq!(stat_attachment(&missing), (false, false));
+        #[cfg(unix)]
+        {
+            let link = dir.join("collegamento.pdf");
+            std::os::unix::fs::symlink(&missing, &link).unwrap();
+            assert_eq!(stat_attachment(&link), (false, true), "link a file mancante: rotto");
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_license_token_from_file() {
+        assert_eq!(license_token_from_file(b"\xef\xbb\xbfLXFW.abc.def\r\n").unwrap(), "LXFW.abc.def", "BOM e a capo rimossi");
+        assert_eq!(license_token_from_file(b"  LXFW.abc.def  ").unwrap(), "LXFW.abc.def");
+        assert!(license_token_from_file(b" \n ").is_err());
+        assert!(license_token_from_file(&[0xff, 0xfe, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_adhoc_reminder_fires_at_its_minute_then_pruned() {
+        let at = chrono::Local.with_ymd_and_hms(2026, 5, 4, 15, 30, 0).single().unwrap();
+        let entries = vec![
+            json!({"id": "adhoc-1", "title": "Chiamare Rossi", "body": "Ricorso", "atMs": at.timestamp_millis()}),
+            json!({"id": "adhoc-rotto", "title": "Senza orario"}),
+        ];
+        let (due, ahead) = split_due_adhoc(&entries, at - chrono::Duration::seconds(30));
+        assert!(due.is_empty(), "il minuto prima non scatta");
+        assert_eq!(ahead.len(), 1, "l'elemento malformato viene scartato");
+
+        let (due, ahead) = split_due_adhoc(&ahead, at + chrono::Duration::seconds(10));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, "adhoc-1");
+        assert_eq!(due[0].title, "LexFlow — Chiamare Rossi");
+        assert!(ahead.is_empty(), "dopo l'invio il promemoria è rimosso");
+
+        let schedule = json!({"adhoc": entries});
+        let before = at - chrono::Duration::hours(1);
+        let upcoming = compute_upcoming_notifications(&schedule, &json!({}), before, None, 10);
+        assert_eq!(upcoming.len(), 1);
+        assert_eq!(upcoming[0]["kind"], "adhoc");
+        let focus_end = at + chrono::Duration::minutes(20);
+        let held = compute_upcoming_notifications(&schedule, &json!({}), before, Some(focus_end), 10);
+        assert_eq!(held[0]["atMs"], focus_end.timestamp_millis(), "in focus slitta alla fine");
+        assert_eq!(held[0]["deferredByFocus"], true);
+    }
+
+    #[test]
+    fn test_audit_csv_signature_detects_edits() {
+        let key = [7u8; 32];
+        let entries = vec![
+            json!({"event": "Sblocco Vault", "time": "2026-03-01T09:00:00+01:00"}),
+            json!({"event": "Export, \"completo\"", "time": "2026-03-01T09:05:00+01:00", "ip": "locale"}),
+        ];
+        let (csv, head) = audit_csv(&entries);
+        assert!(csv.contains("\"Export, \"\"completo\"\"\""), "virgole e virgolette racchiuse tra apici");
+        assert!(csv.contains("{\"\"ip\"\":\"\"locale\"\"}"), "i campi extra finiscono in metadata");
+        assert!(csv.contains(",general,,"), "voci pre-schema: categoria general, nessun target");
+        let sig = audit_csv_signature(&key, &csv, &head, entries.len());
+        assert!(verify_audit_csv_sig(&key, &csv, &sig));
+
+        let edited = csv.replace("Sblocco Vault", "Sblocco vault");
+        assert!(!verify_audit_csv_sig(&key, &edited, &sig), "CSV modificato: firma non valida");
+        let truncated: String = csv.lines().take(2).map(|l| format!("{}\n", l)).collect();
+        assert!(!verify_audit_csv_sig(&key, &truncated, &sig), "riga rimossa: firma non valida");
+        assert!(!verify_audit_csv_sig(&[8u8; 32], &csv, &sig), "chiave diversa: firma non valida");
+
+        let (_, head_alt) = audit_csv(&entries[1..]);
+        assert_ne!(head, head_alt, "la catena dipende dalle righe precedenti");
+        let status = audit_chain_status(&[json!({"event": AUDIT_TAMPER_EVENT})], &head, false, -1);
+        assert_eq!(status["intact"], false);
+        assert_eq!(audit_chain_status(&[], &head, false, 3)["intact"], false, "catena HMAC interrotta");
+    }
+
+    #[test]
+    fn test_audit_csv_trailer_detects_edits() {
+        let key = [7u8; 32];
+        let entries = vec![
+            json!({"event": "Sblocco Vault", "time": "2026-03-01T09:00:00+01:00", "category": "auth", "targetId": null}),
+            json!({"event": "Fascicolo modificato", "time": "2026-03-01T09:05:00+01:00", "category": "practice", "targetId": "p-42"}),
+        ];
+        let (csv, head) = audit_csv(&entries);
+        assert!(csv.contains(",practice,p-42,,"), "categoria e target in colonne proprie");
+        let exported = format!("{}{}", csv, audit_csv_trailer(&key, &csv));
+        assert!(verify_audit_csv_trailer(&key, &exported));
+        assert!(!verify_audit_csv_trailer(&key, &csv), "senza trailer: non verificabile");
+        assert!(!verify_audit_csv_trailer(&key, &exported.replace("p-42", "p-43")), "riga modificata");
+        assert!(!verify_audit_csv_trailer(&[8u8; 32], &exported), "chiave diversa");
+        let dropped: String = exported.lines().enumerate().filter(|&(i, _)| i != 1).map(|(_, l)| format!("{}\n", l)).collect();
+        assert!(!verify_audit_csv_trailer(&key, &dropped), "riga rimossa");
+        // The companion .sig still verifies the exported file with its trailer
+        let sig = audit_csv_signature(&key, &csv, &head, entries.len());
+        assert!(verify_audit_csv_sig(&key, &exported, &sig));
+    }
+
+    #[test]
+    fn test_license_grace_window() {
+        let expiry = 1_800_000_000_000u64;
+        let day = DAY_MS as u64;
+        assert_eq!(license_expiry_state(expiry, expiry - day, 14), LicenseExpiry::Valid);
+        assert_eq!(license_expiry_state(expiry, expiry, 14), LicenseExpiry::Valid, "l'ultimo millisecondo è ancora valido");
+        assert_eq!(license_expiry_state(expiry, expiry + 1, 14), LicenseExpiry::Grace(14));
+        assert_eq!(license_expiry_state(expiry, expiry + 13 * day + 1, 14), LicenseExpiry::Grace(1));
+        assert_eq!(license_expiry_state(expiry, expiry + 14 * day + 1, 14), LicenseExpiry::Expired, "dopo la tolleranza: blocco");
+        assert_eq!(license_expiry_state(expiry, expiry + 1, 0), LicenseExpiry::Expired, "tolleranza disattivata");
+        assert_eq!(license_grace_days(&json!({})), LICENSE_GRACE_DAYS);
+        assert_eq!(license_grace_days(&json!({"licenseGraceDays": 3})), 3);
+        assert_eq!(license_grace_days(&json!({"licenseGraceDays": 90})), LICENSE_GRACE_DAYS, "non estendibile oltre il massimo");
+    }
+
+    #[test]
+    fn test_license_renewal_hint() {
+        let expiry = 1_800_000_000_000u64;
+        let day = DAY_MS as u64;
+        let mut far = json!({"activated": true});
+        stamp_license_expiry(&mut far, expiry, expiry - 90 * day);
+        assert_eq!(far["daysUntilExpiry"], 90);
+        assert_eq!(far["expiryMs"], expiry);
+        assert!(far.get("renewSoon").is_none(), "lontano dalla scadenza: nessun avviso");
+        let mut near = json!({"activated": true});
+        stamp_license_expiry(&mut near, expiry, expiry - 30 * day);
+        assert_eq!(near["daysUntilExpiry"], 30);
+        assert_eq!(near["renewSoon"], true, "30 giorni: rinnovo consigliato");
+    }
+
+    #[test]
+    fn test_attachment_recommendation_thresholds() {
+        let vault = 20 * MB;
+        assert_eq!(attachment_recommendation(10 * MB, vault, 10, 200)["recommendation"], "inline", "esattamente alla soglia: nel vault");
+        let over = attachment_recommendation(10 * MB + 1, vault, 10, 200);
+        assert_eq!(over["recommendation"], "external", "un byte oltre la soglia: riferimento esterno");
+        assert_eq!(over["projectedVaultBytes"], vault + inlined_size(10 * MB + 1));
+        assert_eq!(attachment_recommendation(5 * MB, 195 * MB, 10, 200)["recommendation"], "external", "il vault sforerebbe il limite");
+        assert_eq!(inlined_size(3), 4);
+        assert_eq!(inlined_size(4), 8);
+    }
+
+    #[test]
+    fn test_orphaned_attachment_blobs_detected_and_purged() {
+        let mut vault = json!({
+            "practices": [{"id": "p1", "attachments": [{"id": "a1", "name": "atto.pdf"}]}],
+            "trash": [{"deletedAt": "2026-01-01T00:00:00Z", "practice": {"id": "p2", "documents": [{"id": "a2"}]}}],
+            "attachments": {"a1": {"data": "QUJD"}, "a2": {"data": "REVG"}, "orfano": {"data": "R0hJSktM"}},
+        });
+        let orphans = orphaned_attachments(&vault);
+        assert_eq!(orphans.len(), 1, "solo il blob non referenziato è orfano");
+        assert_eq!(orphans[0].0, "orfano");
+        assert!(orphans[0].1 > 0);
+        assert_eq!(purge_orphaned_blobs(&mut vault), vec!["orfano".to_string()]);
+        assert!(vault["attachments"].get("orfano").is_none());
+        assert!(vault["attachments"].get("a2").is_some(), "i fascicoli nel cestino conservano i loro allegati");
+        assert!(orphaned_attachments(&vault).is_empty());
+        assert!(orphaned_attachments(&json!({"practices": []})).is_empty());
+
+        // Encrypted documents: keys and files of purged practices are orphaned too
+        let (live, trashed, gone) = ("a".repeat(32), "b".repeat(32), "c".repeat(32));
+        let vault = json!({
+            "practices": [{"id": "p1", "documents": [{"blobId": live}]}],
+            "trash": [{"deletedAt": "2026-01-01T00:00:00Z", "practice": {"id": "p2", "documents": [{"blobId": trashed}]}}],
+            "documentKeys": {live.clone(): "00", trashed.clone(): "11", gone.clone(): "22"},
+        });
+        assert_eq!(orphaned_document_keys(&vault), vec![gone.clone()]);
+        let dir = std::env::temp_dir().join(format!("lexflow-orphan-docs-{}", rand::random::<u64>()));
+        fs::create_dir_all(dir.join(DOCUMENT_BLOBS_DIR)).unwrap();
+        for id in [&live, &trashed, &gone] {
+            fs::write(document_blob_path(&dir, id).unwrap(), b"sealed").unwrap();
+        }
+        fs::write(dir.join(DOCUMENT_BLOBS_DIR).join("note.txt"), b"x").unwrap();
+        assert_eq!(orphaned_document_files(&dir, &vault), vec![(gone, 6)], "solo i file .blob non referenziati");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_duration_parsing_and_formatting() {
+        for input in [json!("1.5"), json!("1,5"), json!("1:30"), json!("90m"), json!(" 1.5h "), json!(90)] {
+            assert_eq!(parse_duration_minutes(&input), Ok(90), "{} deve valere 90 minuti", input);
+        }
+        assert_eq!(parse_duration_minutes(&json!("2h")), Ok(120));
+        for bad in ["abc", "90", "1:75", "-1.5", "30:00", ""] {
+            assert!(parse_duration_minutes(&json!(bad)).is_err(), "\"{}\" deve essere rifiutato", bad);
+        }
+        assert_eq!(format_duration(90, "hhmm"), "1:30");
+        assert_eq!(format_duration(90, "decimal"), "1,50");
+        assert_eq!(format_duration(90, "long"), "1 h 30 min");
+        assert_eq!(format_duration(45, "long"), "45 min");
+
+        let mut logs = json!([
+            {"id": "t1", "durationMin": "1:30"},
+            {"id": "t2", "duration": "2h"},
+            {"id": "t3", "durationMin": 60},
+            {"id": "t4", "durationMin": "abc"},
+            {"id": "t5", "startedAt": "2026-03-02T09:00:00Z"}
+        ]);
+        let (normalized, warnings) = canonicalize_time_logs(&mut logs);
+        assert_eq!(normalized, 2);
+        assert_eq!(logs[0]["durationMin"], 90);
+        assert_eq!(logs[1]["durationMin"], 120);
+        assert!(logs[1].get("duration").is_none());
+        assert_eq!(logs[3]["durationMin"], "abc", "un valore non valido non viene scartato");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["logId"], "t4");
+        assert!(logs[4].get("durationMin").is_none());
+    }
+
+    #[test]
+    fn test_audit_hash_chain_detects_deletion() {
+        let key = [5u8; 32];
+        let mut logs = vec![json!({"event": "Vecchia voce senza catena", "time": "2026-01-01T09:00:00+01:00"})];
+        for (i, event) in ["Sblocco Vault", "Esportazione", "Password cambiata", "Blocco Vault"].iter().enumerate() {
+            push_chained_audit_entry(&mut logs, &key, json!({"event": event, "time": format!("2026-01-0{}T09:00:00+01:00", i + 2)}));
+        }
+        assert_eq!(verify_audit_chain_entries(&logs, &key), -1, "catena integra");
+        assert_eq!(verify_audit_chain_entries(&logs, &[6u8; 32]), 1, "chiave diversa: catena non verificabile");
+
+        let mut deleted = logs.clone();
+        deleted.remove(2);
+        assert_eq!(verify_audit_chain_entries(&deleted, &key), 2, "voce centrale rimossa");
+        let mut edited = logs.clone();
+        edited[3]["event"] = json!("Modificata");
+        assert_eq!(verify_audit_chain_entries(&edited, &key), 4);
+
+        // Password change: intact links move to the new key, a break stays where it was
+        let new_key = [7u8; 32];
+        let mut rekeyed = logs.clone();
+        rechain_audit_entries(&mut rekeyed, &key, &new_key);
+        assert_eq!(verify_audit_chain_entries(&rekeyed, &new_key), -1);
+        rechain_audit_entries(&mut deleted, &key, &new_key);
+        assert_eq!(verify_audit_chain_entries(&deleted, &new_key), 2);
+        assert_eq!(verify_audit_chain_entries(&[], &key), -1);
+
+        // Restore: imported and local entries interleave by time, shared ones appear once
+        let local = vec![logs[1].clone(), json!({"event": "Solo locale", "time": "2026-01-03T10:00:00+01:00"})];
+        let merged = merge_audit_logs(Some(json!(logs.clone())), local, &new_key);
+        let events: Vec<&str> = merged.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(events, vec!["Vecchia voce senza catena", "Sblocco Vault", "Esportazione", "Solo locale", "Password cambiata", "Blocco Vault"]);
+        assert_eq!(verify_audit_chain_entries(&merged, &new_key), -1, "il registro unito è concatenato con la nuova chiave");
+        assert!(merge_audit_logs(None, Vec::new(), &key).is_empty());
+    }
+
+    #[test]
+    fn test_schedule_tally_backs_off_at_os_limit() {
+        // Stub scheduler that accepts 5 notifications, then refuses like a full OS queue
+        let mut accepted = 0;
+        let mut os = || { if accepted < 5 { accepted += 1; true } else { false } };
+        let mut tally = ScheduleTally::new(MAX_SCHEDULED);
+        for _ in 0..20 { tally.attempt(&mut os); }
+        let report = tally.report();
+        assert_eq!(report["requested"], 20);
+        assert_eq!(report["scheduled"], 5);
+        assert_eq!(report["failed"], SCHEDULE_FAILURE_BACKOFF, "dopo i fallimenti consecutivi non si riprova");
+        assert_eq!(report["dropped"], 20 - 5 - SCHEDULE_FAILURE_BACKOFF);
+        assert_eq!(report["capacity"], 5);
+        assert_eq!(report["limitDetected"], true);
+
+        // An isolated failure is not a cap; the budget still applies
+        let mut calls = 0;
+        let mut flaky = ScheduleTally::new(3);
+        for _ in 0..6 { flaky.attempt(|| { calls += 1; calls != 2 }); }
+        assert_eq!((flaky.scheduled, flaky.failed, flaky.dropped), (3, 1, 2));
+        assert!(!flaky.limit_detected);
+    }
+
+    #[test]
+    fn test_audit_entries_filter_by_category_and_target() {
+        let entries = [
+            json!({"event": "Sblocco Vault", "time": "2026-01-01T09:00:00+01:00"}),
+            audit_entry(AuditCategory::Practice, Some("p1"), "Fascicolo aggiornato"),
+            audit_entry(AuditCategory::Practice, Some("p2"), "Fascicolo spostato nel cestino"),
+            audit_entry(AuditCategory::Auth, None, "Password cambiata"),
+        ];
+        let pick = |c: Option<&str>, t: Option<&str>| entries.iter().filter(|e| audit_entry_matches(e, c, t)).count();
+        assert_eq!(pick(None, None), 4);
+        assert_eq!(pick(Some("practice"), None), 2);
+        assert_eq!(pick(Some("practice"), Some("p1")), 1);
+        assert_eq!(pick(None, Some("p2")), 1);
+        assert_eq!(pick(Some("general"), None), 1, "le voci precedenti allo schema sono \"general\"");
+
+        let legacy = audit_entry_view(&entries[0]);
+        assert_eq!(legacy["category"], "general");
+        assert!(legacy["targetId"].is_null());
+        assert_eq!(audit_entry_view(&entries[1])["targetId"], "p1");
+        assert!(entries[0].get("category").is_none(), "la voce salvata non viene modificata");
+    }
+
+    #[test]
+    fn test_readonly_snapshot_opens_and_rejects_saves() {
+        let vault = json!({
+            "practices": [{"id": "p1", "client": "Rossi"}],
+            "agenda": [], "timeLogs": [], "invoices": [], "contacts": [],
+            "secrets": [{"practiceId": "p1", "label": "PEC", "sealed": "x"}],
+            "documentKeys": {"b1": "00ff"},
+        });
+        let raw = seal_readonly_snapshot(&vault, "Analisi!2026").unwrap();
+        let opened = open_snapshot_bytes(&raw, "Analisi!2026").unwrap();
+        assert!(is_readonly_snapshot(&opened));
+        assert_eq!(opened["practices"][0]["client"], "Rossi");
+        assert!(opened.get("secrets").is_none(), "i segreti non entrano nello snapshot");
+        assert!(opened.get("documentKeys").is_none(), "nemmeno le chiavi dei documenti");
+        assert_eq!(snapshot_write_guard(Some(&opened)), Err(SNAPSHOT_READ_ONLY_ERR.to_string()), "salvataggio rifiutato");
+        assert!(snapshot_write_guard(None).is_ok());
+        assert!(open_snapshot_bytes(&raw, "password-sbagliata").is_err());
+
+        // An ordinary backup is not a snapshot
+        let opts = ExportOptions { include_attachments: true, include_audit: false, sections: VAULT_SECTIONS.iter().map(|s| s.to_string()).collect() };
+        assert!(!is_readonly_snapshot(&build_export_payload(&vault, None, &opts)));
+    }
+
+    #[test]
+    fn test_license_nudge_fires_once_per_threshold() {
+        let thresholds = license_expiry_thresholds(&json!({}));
+        assert_eq!(thresholds, vec![1, 7, 30]);
+        let mut last = None;
+        let mut fired = Vec::new();
+        // 40 simulated days, hourly checks: days left go 35 → -4
+        for day in 0..40i64 {
+            for _hour in 0..24 {
+                if let Some(t) = license_nudge_due(35 - day, &thresholds, day, &mut last) {
+                    fired.push((day, t));
+                }
+            }
+        }
+        // One nudge per threshold reached, then one expired event a day
+        assert_eq!(fired, vec![(5, 30), (28, 7), (34, 1), (35, 0), (36, 0), (37, 0), (38, 0), (39, 0)]);
+
+        let custom = license_expiry_thresholds(&json!({"licenseExpiryThresholds": [14, 0, "x", 14, 3]}));
+        assert_eq!(custom, vec![3, 14]);
+        let mut last = None;
+        assert_eq!(license_nudge_due(20, &custom, 0, &mut last), None);
+        assert_eq!(license_nudge_due(14, &custom, 1, &mut last), Some(14));
+        assert_eq!(license_nudge_due(3, &custom, 1, &mut last), Some(3), "nuova soglia nello stesso giorno");
+        assert_eq!(license_nudge_due(3, &custom, 1, &mut last), None);
+        assert_eq!(license_nudge_due(2, &custom, 2, &mut last), None, "stessa soglia il giorno dopo");
+        // Renewed: out of every threshold, then a later approach nudges again
+        assert_eq!(license_nudge_due(300, &custom, 3, &mut last), None);
+        assert_eq!(license_nudge_due(14, &custom, 289, &mut last), Some(14));
+    }
+
+    #[test]
+    fn test_queue_save_coalesces_to_last_value() {
+        let mut pending = PendingSaves::default();
+        // Only the first update of a window schedules a flush
+        assert!(pending.queue("practices", json!([{"id": "1", "notes": "a"}])));
+        assert!(!pending.queue("practices", json!([{"id": "1", "notes": "ab"}])));
+        assert!(!pending.queue("practices", json!([{"id": "1", "notes": "abc"}])));
+
+        let drained = pending.take();
+        assert_eq!(drained.len(), 1, "tre aggiornamenti della stessa sezione devono produrre una sola scrittura");
+
+        let mut vault = json!({"practices": [], "agenda": [{"id": "x"}]});
+        apply_pending_saves(&mut vault, drained);
+        assert_eq!(vault["practices"][0]["notes"], "abc");
+        assert_eq!(vault["agenda"][0]["id"], "x");
+
+        // Buffer is empty and the next update opens a new window
+        assert!(pending.take().is_empty());
+        assert!(pending.queue("agenda", json!([])));
+
+        // A restore drops what was queued before it: the next flush leaves the restored data alone
+        pending.queue("agenda", json!([{"id": "edit-before-restore"}]));
+        let superseded = pending.take();
+        assert_eq!(superseded.len(), 1);
+        let mut restored = json!({"practices": [{"id": "p-backup"}], "agenda": [{"id": "a-backup"}]});
+        apply_pending_saves(&mut restored, pending.take());
+        assert_eq!(restored["agenda"], json!([{"id": "a-backup"}]), "il vault ripristinato non va sovrascritto");
+        assert!(pending.queue("agenda", json!([])), "dopo il ripristino si apre una nuova finestra");
+    }
+
+    #[test]
+    fn test_failed_flush_restore_keeps_newer_values() {
+        let mut pending = PendingSaves::default();
+        pending.queue("practices", json!("old"));
+        let failed = pending.take();
+        pending.queue("practices", json!("new"));
+        pending.restore(failed);
+        assert_eq!(pending.take().get("practices"), Some(&json!("new")));
+    }
+}
+
+#[tauri::command]
+fn get_settings(state: State<AppState>) -> Value {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    read_settings(&dir)
+}
+
+/// Settings are encrypted with the device key (not the vault key), so the background
+/// scheduler can read them while the vault is locked.
+fn read_settings(data_dir: &std::path::Path) -> Value {
+    let path = data_dir.join(SETTINGS_FILE);
+    if !path.exists() { return json!({}); }
+    // SECURITY FIX (Level-8 C5): reject suspiciously large files before reading into RAM.
+    // A corrupted or maliciously injected 5GB settings file would OOM-kill the process.
+    if let Ok(meta) = path.metadata() {
+        if meta.len() > MAX_SETTINGS_FILE_SIZE {
+            lf_log!("[LexFlow] Settings file troppo grande ({} bytes) — ignorato", meta.len());
+            return json!({});
+        }
+    }
+    // SECURITY FIX (Gemini Audit): use migration-aware decryption (hostname→machine_id)
+    if let Some(dec) = decrypt_local_with_migration(&path) {
+        return serde_json::from_slice(&dec).unwrap_or(json!({}));
+    }
+    // Migration: old plaintext format
+    if let Ok(enc) = fs::read(&path) {
+        if let Ok(text) = std::str::from_utf8(&enc) {
+            if let Ok(val) = serde_json::from_str::<Value>(text) {
+                // Re-encrypt with current key
+                if let Ok(re_enc) = get_local_encryption_key()
+                    .and_then(|key| encrypt_data(&key, &serde_json::to_vec(&val).unwrap_or_default())) {
+                    let _ = atomic_write_with_sync(&path, &re_enc);
+                }
+                return val;
+            }
+        }
+        // File corrotto: salva backup prima di resettare (non perdere dati silenziosamente)
+        let backup_path = path.with_extension("json.corrupt");
+        let _ = fs::copy(&path, &backup_path);
+        lf_log!("[LexFlow] Settings file corrotto — backup salvato in {:?}", backup_path);
+    }
+    json!({})
+}
+
+/// Shallow merge of a settings patch: keys not in `patch` are kept, `null` deletes one.
+/// Changing `protectedSections` loosens or tightens the section gate, so it takes a reauth
+/// token; resending the current list is not a change.
+fn merge_settings(current: &Value, patch: &Value, reauthorized: bool) -> Result<Value, String> {
+    let changes = patch.as_object().ok_or("Le impostazioni devono essere un oggetto JSON")?;
+    let mut merged = if current.is_object() { current.clone() } else { json!({}) };
+    let protected_before = merged.get("protectedSections").cloned().unwrap_or(json!([]));
+    let obj = merged.as_object_mut().ok_or("Impostazioni non valide")?;
+    for (k, v) in changes {
+        if v.is_null() { obj.remove(k); } else { obj.insert(k.clone(), v.clone()); }
+    }
+    let protected_after = merged.get("protectedSections").cloned().unwrap_or(json!([]));
+    if protected_after != protected_before && !reauthorized {
+        return Err(REAUTH_REQUIRED_ERR.into());
+    }
+    Ok(merged)
+}
+
+#[tauri::command]
+fn save_settings(state: State<AppState>, settings: Value, reauth_token: Option<String>) -> Result<bool, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let reauthorized = reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok());
+    let merged = merge_settings(&read_settings(&dir), &settings, reauthorized)?;
+    Ok(write_settings(&dir, &merged))
+}
+
+fn write_settings(data_dir: &std::path::Path, settings: &Value) -> bool {
+    match get_local_encryption_key().and_then(|key| encrypt_data(&key, &serde_json::to_vec(settings).unwrap_or_default())) {
+        Ok(encrypted) => atomic_write_with_sync(&data_dir.join(SETTINGS_FILE), &encrypted).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[tauri::command]
+fn check_license(state: State<AppState>) -> Value {
+    let path = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).join(LICENSE_FILE);
+    let sentinel_path = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).join(LICENSE_SENTINEL_FILE);
+
+    if !path.exists() {
+        // SECURITY: if sentinel exists but license.json was deleted, detect tampering.
+        // The sentinel is an HMAC proof that a license WAS activated on this machine.
+        if sentinel_path.exists() {
+            return json!({
+                "activated": false,
+                "tampered": true,
+                "reason": "File di licenza rimosso o manomesso. Contattare il supporto."
+            });
+        }
+        return json!({"activated": false});
+    }
+    let key = match get_local_encryption_key() {
+        Ok(k) => k,
+        Err(e) => return json!({"activated": false, "reason": e}),
+    };
+    let data: Value = if let Some(dec) = decrypt_local_with_migration(&path) {
+        serde_json::from_slice(&dec).unwrap_or(json!({}))
+    } else if path.exists() {
+        // File exists but cannot be decrypted with ANY key (current or legacy).
+        // Either corrupted or copied from another machine — reject.
+        return json!({"activated": false, "reason": "File licenza corrotto o non valido per questo dispositivo."});
+    } else { return json!({"activated": false}); };
+
+    // SECURITY: verify hardware fingerprint — the license is bound to this machine
+    let current_fp = compute_machine_fingerprint();
+    if let Some(stored_fp) = data.get("machineFingerprint").and_then(|v| v.as_str()) {
+        if stored_fp != current_fp {
+            return json!({"activated": false, "reason": "Licenza attivata su un altro dispositivo."});
+        }
+    }
+    // If no fingerprint stored (pre-v2.6.1 activation), we still accept but
+    // re-encrypt with fingerprint on next check to upgrade silently
+    let needs_fp_upgrade = data.get("machineFingerprint").is_none();
+
+    let key_version = data.get("keyVersion").and_then(|v| v.as_str()).unwrap_or("");
+
+    // ── NEW FORMAT: burned key (v2.6.1+) ──────────────────────────────────
+    // The raw token no longer exists. We verify using stored expiry + HMAC integrity.
+    if key_version == "ed25519-burned" {
+        let token_hmac = data.get("tokenHmac").and_then(|v| v.as_str()).unwrap_or("");
+        let expiry_ms = data.get("expiryMs").and_then(|v| v.as_u64()).unwrap_or(0);
+        let client = data.get("client").and_then(|v| v.as_str()).unwrap_or("Studio Legale").to_string();
+
+        if token_hmac.is_empty() {
+            return json!({"activated": false, "reason": "Dati licenza corrotti."});
+        }
+
+        // Check expiry (a recently expired license gets the read-only grace window)
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let data_dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let grace = match license_expiry_state(expiry_ms, now_ms, license_grace_days(&read_settings(&data_dir))) {
+            LicenseExpiry::Valid => None,
+            LicenseExpiry::Grace(days) => Some(days),
+            LicenseExpiry::Expired => {
+                return json!({"activated": false, "expired": true, "reason": "Licenza scaduta."});
+            }
+        };
+
+        // Silent upgrade: add machineFingerprint if missing
+        if needs_fp_upgrade {
+            let mut upgraded = data.clone();
+            upgraded.as_object_mut().map(|obj| {
+                obj.insert("machineFingerprint".to_string(), json!(current_fp));
+            });
+            if let Ok(bytes) = serde_json::to_vec(&upgraded) {
+                if let Ok(encrypted) = encrypt_data(&key, &bytes) {
+                    let _ = fs::write(&path, encrypted);
+                }
+            }
+        }
+
+        let mut status = json!({
+            "activated": true,
+            "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
+            "client": client,
+            "entitlements": license_entitlements(&data),
+        });
+        stamp_license_expiry(&mut status, expiry_ms, now_ms);
+        if let Some(days) = grace {
+            status["expired"] = json!(true);
+            status["grace"] = json!(true);
+            status["graceRemainingDays"] = json!(days);
+        }
+        return status;
+    }
+
+    // ── LEGACY FORMAT: raw key stored (pre-v2.6.1) ────────────────────────
+    // Re-verify Ed25519 and silently upgrade to burned format.
+    let license_key = data.get("key").and_then(|k| k.as_str()).unwrap_or("");
+
+    if !license_key.is_empty() {
+        let verification = verify_license(license_key.to_string());
+
+        if verification.valid {
+            // ── SILENT UPGRADE: convert legacy → burned format ──
+            // 1. Compute HMAC of the raw token
+            let mut token_mac = <Hmac<Sha256> as Mac>::new_from_slice(&key)
+                .expect("HMAC can take key of any size");
+            token_mac.update(license_key.as_bytes());
+            let token_hmac = hex::encode(token_mac.finalize().into_bytes());
+
+            // 2. Extract expiry from the token payload
+            let expiry_ms: u64 = extract_expiry_ms(license_key).unwrap_or(0);
+            let client = verification.client.unwrap_or_else(|| "Studio Legale".to_string());
+            let key_id = extract_key_id(license_key).unwrap_or_else(|| "legacy".to_string());
+
+            // 3. Build burned record (no raw token)
+            let mut upgraded = json!({
+                "tokenHmac": token_hmac,
+                "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
+                "client": client,
+                "keyVersion": "ed25519-burned",
+                "machineFingerprint": current_fp,
+                "keyId": key_id,
+                "expiryMs": expiry_ms,
+            });
+            if let Some(features) = extract_features(license_key) {
+                upgraded["features"] = json!(features);
+            }
+            if let Ok(bytes) = serde_json::to_vec(&upgraded) {
+                if let Ok(encrypted) = encrypt_data(&key, &bytes) {
+                    let _ = fs::write(&path, encrypted);
+                }
+            }
+
+            // 4. Burn the key so it can never be reused
+            let dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            burn_key(&dir, &compute_burn_hash(license_key, &current_fp));
+
+            let mut status = json!({
+                "activated": true,
+                "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
+                "client": upgraded.get("client").and_then(|c| c.as_str()).unwrap_or("Studio Legale"),
+                "entitlements": license_entitlements(&upgraded),
+            });
+            stamp_license_expiry(&mut status, expiry_ms, now_ms());
+            return status;
+        } else {
+            // Expired legacy token: grace window only, the upgrade waits for a renewal
+            let data_dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            let expiry = extract_expiry_ms(license_key).filter(|_| verification.message == "Licenza scaduta.");
+            if let Some(expiry_ms) = expiry {
+                if let LicenseExpiry::Grace(days) = license_expiry_state(expiry_ms, now_ms(), license_grace_days(&read_settings(&data_dir))) {
+                    let mut status = json!({
+                        "activated": true,
+                        "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
+                        "client": verification.client.unwrap_or_else(|| "Studio Legale".to_string()),
+                        "entitlements": license_entitlements(&data),
+                        "expired": true,
+                        "grace": true,
+                        "graceRemainingDays": days,
+                    });
+                    stamp_license_expiry(&mut status, expiry_ms, now_ms());
+                    return status;
+                }
+            }
+            return json!({"activated": false, "expired": true, "reason": verification.message});
+        }
+    }
+
+    json!({"activated": false})
+}
+
+/// Export the burned-keys registry for a sanctioned move to a new machine.
+/// Gated by the vault password; the same password seals the exported blob.
+#[tauri::command]
+fn export_burn_registry(state: State<AppState>, password: String) -> Result<String, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if check_lockout(&state, &sec_dir, LockoutKind::Vault).is_err() {
+        zeroize_password(password);
+        return Err("Troppi tentativi. Riprova più tardi.".into());
+    }
+    if authenticate_vault_password(&password, &dir).is_err() {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        zeroize_password(password);
+        return Err("Password errata".into());
+    }
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let result = seal_burn_registry(&load_burned_keys(&sec_dir), &password);
+    zeroize_password(password);
+    let _ = append_audit_log(&state, AuditCategory::License, None, "Registro chiavi esportato");
+    result
+}
+
+/// Merge an exported registry into this machine's registry (re-encrypted with the local key).
+#[tauri::command]
+fn import_burn_registry(state: State<AppState>, password: String, content: String) -> Result<Value, String> {
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if check_lockout(&state, &sec_dir, LockoutKind::Vault).is_err() {
+        zeroize_password(password);
+        return Err("Troppi tentativi. Riprova più tardi.".into());
+    }
+    let opened = open_burn_registry(&content, &password);
+    zeroize_password(password);
+    let imported = match opened {
+        Ok(h) => h,
+        Err(e) => {
+            if e == TRANSIT_WRONG_PASSWORD_ERR {
+                record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+            }
+            return Err(e);
+        }
+    };
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let mut local = load_burned_keys(&sec_dir);
+    let added = merge_burn_hashes(&mut local, imported);
+    write_burned_keys(&sec_dir, &local)?;
+    let _ = append_audit_log(&state, AuditCategory::License, None, "Registro chiavi importato");
+    Ok(json!({"success": true, "added": added, "total": local.len()}))
+}
+
+// ─── License receipt (support reissue) ───────────────────────────────────────
+// The raw token is destroyed on activation by design, so a reinstall cannot recover it.
+// The receipt carries only non-secret identifying data — enough for support to look up
+// the keyId and reissue — plus `deviceTag`, an HMAC under the device key. The tag is not
+// a signature: support cannot check it, only this installation can, to confirm a receipt
+// was produced here and not edited. Support trusts the keyId only against its own
+// issuance records. Deterministic: the same license record always yields the same receipt.
+fn build_license_receipt(record: &Value, device_key: &[u8]) -> Result<Value, String> {
+    let field = |k: &str| record.get(k).and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let key_id = field("keyId");
+    if key_id.is_empty() || record.get("keyVersion").and_then(|v| v.as_str()) != Some("ed25519-burned") {
+        return Err("Nessuna licenza attiva".into());
+    }
+    let client = field("client");
+    let activated_at = field("activatedAt");
+    let fingerprint = field("machineFingerprint");
+    let expiry_ms = record.get("expiryMs").and_then(|v| v.as_u64()).unwrap_or(0);
+    let expires_at = chrono::DateTime::<chrono::Utc>::from_timestamp_millis(expiry_ms as i64)
+        .map(|d| d.to_rfc3339()).unwrap_or_default();
+    let canonical = format!("LEXFLOW-RECEIPT-V1:{}:{}:{}:{}:{}", key_id, client, activated_at, expiry_ms, fingerprint);
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(device_key)
+        .expect("HMAC can take key of any size");
+    mac.update(canonical.as_bytes());
+    Ok(json!({
+        "version": 1,
+        "keyId": key_id,
+        "client": client,
+        "activatedAt": activated_at,
+        "expiresAt": expires_at,
+        "expiryMs": expiry_ms,
+        "machineFingerprint": fingerprint,
+        "deviceTag": hex::encode(mac.finalize().into_bytes()),
+    }))
+}
+
+/// Non-secret license receipt the user can send to support to request a reissue.
+/// Gated by the vault password like the other license-adjacent exports.
+#[tauri::command]
+fn get_license_receipt(state: State<AppState>, password: String) -> Result<Value, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if check_lockout(&state, &sec_dir, LockoutKind::Vault).is_err() {
+        zeroize_password(password);
+        return Err("Troppi tentativi. Riprova più tardi.".into());
+    }
+    if authenticate_vault_password(&password, &dir).is_err() {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        zeroize_password(password);
+        return Err("Password errata".into());
+    }
+    zeroize_password(password);
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let record: Value = decrypt_local_with_migration(&sec_dir.join(LICENSE_FILE))
+        .and_then(|dec| serde_json::from_slice(&dec).ok())
+        .ok_or("Nessuna licenza attiva")?;
+    let receipt = build_license_receipt(&record, &get_local_encryption_key()?)?;
+    let _ = append_audit_log(&state, AuditCategory::License, None, "Ricevuta licenza generata");
+    Ok(receipt)
+}
+
+// ─── License deactivation (seat transfer) ────────────────────────────────────
+// Moving to a new machine: the user deactivates here and mails the receipt to support,
+// who reissue a fresh key. license.json and the sentinel go away, the burn entry stays,
+// so the old key still cannot be re-activated anywhere. The receipt's deviceTag (HMAC,
+// device key) binds keyId + fingerprint + deactivatedAt, as in the license receipt: it is
+// checkable on this machine only, not by support, and proves nothing to a third party.
+fn deactivation_mac(device_key: &[u8], key_id: &str, fingerprint: &str, deactivated_at: &str) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(device_key)
+        .expect("HMAC can take key of any size");
+    mac.update(format!("LEXFLOW-DEACTIVATION-V1:{}:{}:{}", key_id, fingerprint, deactivated_at).as_bytes());
+    mac
+}
+
+/// Verify the license on this machine, burn its key if not yet burned, write the receipt
+/// and drop license.json + sentinel. Returns the receipt.
+fn deactivate_license_files(sec_dir: &std::path::Path, fingerprint: &str, deactivated_at: &str) -> Result<Value, String> {
+    let record: Value = decrypt_local_with_migration(&sec_dir.join(LICENSE_FILE))
+        .and_then(|dec| serde_json::from_slice(&dec).ok())
+        .ok_or("Nessuna licenza attiva")?;
+    if record.get("machineFingerprint").and_then(|v| v.as_str()).is_some_and(|fp| fp != fingerprint) {
+        return Err("Licenza attivata su un altro dispositivo".into());
+    }
+    let (key_id, burn_hash) = match record.get("key").and_then(|k| k.as_str()).filter(|k| !k.is_empty()) {
+        Some(token) => (extract_key_id(token).unwrap_or_else(|| "legacy".into()), compute_burn_hash(token, fingerprint)),
+        None => {
+            let key_id = record.get("keyId").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            if key_id.is_empty() { return Err("Dati licenza corrotti.".into()); }
+            let hash = compute_keyid_burn_hash(&key_id);
+            (key_id, hash)
+        }
+    };
+    burn_key(sec_dir, &burn_hash);
+    if !load_burned_keys(sec_dir).contains(&burn_hash) {
+        return Err("Impossibile aggiornare il registro chiavi: licenza non disattivata".into());
+    }
+    let receipt = json!({
+        "version": 1,
+        "keyId": key_id,
+        "client": record.get("client").and_then(|v| v.as_str()).unwrap_or(""),
+        "machineFingerprint": fingerprint,
+        "deactivatedAt": deactivated_at,
+        "deviceTag": hex::encode(deactivation_mac(&get_local_encryption_key()?, &key_id, fingerprint, deactivated_at).finalize().into_bytes()),
+    });
+    let bytes = serde_json::to_vec_pretty(&receipt).map_err(|e| e.to_string())?;
+    atomic_write_with_sync(&sec_dir.join(LICENSE_DEACTIVATION_FILE), &bytes)?;
+    fs::remove_file(sec_dir.join(LICENSE_FILE)).map_err(|e| format!("Rimozione licenza fallita: {}", e))?;
+    let _ = fs::remove_file(sec_dir.join(LICENSE_SENTINEL_FILE));
+    Ok(receipt)
+}
+
+/// Release this machine's seat. Gated by the vault password like get_license_receipt;
+/// the receipt comes back base64-encoded, ready to paste into an email.
+#[tauri::command]
+fn deactivate_license(state: State<AppState>, password: String) -> Result<Value, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if check_lockout(&state, &sec_dir, LockoutKind::Vault).is_err() {
+        zeroize_password(password);
+        return Err("Troppi tentativi. Riprova più tardi.".into());
+    }
+    if authenticate_vault_password(&password, &dir).is_err() {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        zeroize_password(password);
+        return Err("Password errata".into());
+    }
+    zeroize_password(password);
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let receipt = deactivate_license_files(&sec_dir, &compute_machine_fingerprint(), &chrono::Utc::now().to_rfc3339())?;
+    let _ = append_audit_log(&state, AuditCategory::License, None, "Licenza disattivata su questo dispositivo");
+    let encoded = base64::engine::general_purpose::STANDARD.encode(receipt.to_string());
+    Ok(json!({"success": true, "receipt": encoded, "keyId": receipt["keyId"]}))
+}
+
+// ─── Feature entitlements ───────────────────────────────────────────────────
+// A token may carry a signed `f` list of licensed modules. Activation copies it into the
+// (locally encrypted) license record; tokens without the field get DEFAULT_ENTITLEMENTS,
+// every module that existed before tiering. Premium commands call require_feature(), which
+// also refuses once the license is past its expiry and grace window.
+const FEATURE_BILLING: &str = "billing";
+const DEFAULT_ENTITLEMENTS: &[&str] = &[FEATURE_BILLING];
+
+/// Entitlements of a stored license record: burned records carry `features`, legacy ones
+/// still hold the raw token.
+fn license_entitlements(record: &Value) -> Vec<String> {
+    let signed = match record.get("features") {
+        Some(f) => f.as_array().map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect()),
+        None => record.get("key").and_then(|k| k.as_str()).and_then(extract_features),
+    };
+    signed.unwrap_or_else(|| DEFAULT_ENTITLEMENTS.iter().map(|f| f.to_string()).collect())
+}
+
+fn authorize_feature(record: Option<&Value>, feature: &str, now_ms: u64, grace_days: u64) -> Result<(), String> {
+    let licensed = record.map(|r| license_entitlements(r).iter().any(|f| f == feature)).unwrap_or(false);
+    if !licensed {
+        return Err(format!("FeatureNotLicensed: il modulo \"{}\" non è incluso nella licenza", feature));
+    }
+    let expired = record.and_then(license_expiry_ms)
+        .map(|expiry_ms| license_expiry_state(expiry_ms, now_ms, grace_days) == LicenseExpiry::Expired)
+        .unwrap_or(false);
+    if expired {
+        Err(format!("LicenseExpired: licenza scaduta, il modulo \"{}\" non è disponibile", feature))
+    } else {
+        Ok(())
+    }
+}
+
+fn require_feature(state: &State<AppState>, feature: &str) -> Result<(), String> {
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let data_dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let record = decrypt_local_with_migration(&sec_dir.join(LICENSE_FILE))
+        .and_then(|dec| serde_json::from_slice::<Value>(&dec).ok());
+    authorize_feature(record.as_ref(), feature, now_ms(), license_grace_days(&read_settings(&data_dir)))
+}
+
+// ─── License expiry warning ─────────────────────────────────────────────────
+// Informational only: enforcement stays in check_license(). Tiers by whole days left,
+// rounded up so the last partial day still counts as one.
+const DAY_MS: i64 = 86_400_000;
+
+/// Expiry of a stored license record: burned records carry `expiryMs`, legacy ones
+/// still hold the raw token with the expiry in its payload.
+fn license_expiry_ms(record: &Value) -> Option<u64> {
+    let expiry = if record.get("keyVersion").and_then(|v| v.as_str()) == Some("ed25519-burned") {
+        record.get("expiryMs").and_then(|v| v.as_u64())
+    } else {
+        record.get("key").and_then(|k| k.as_str()).and_then(extract_expiry_ms)
+    };
+    expiry.filter(|&e| e > 0)
+}
+
+fn license_days_remaining(expiry_ms: u64, now_ms: u64) -> i64 {
+    let left = expiry_ms as i64 - now_ms as i64;
+    if left > 0 { (left + DAY_MS - 1) / DAY_MS } else { left / DAY_MS }
+}
+
+fn license_warning_tier(days_remaining: i64) -> &'static str {
+    match days_remaining {
+        d if d > 30 => "none",
+        8..=30 => "info",
+        1..=7 => "warning",
+        _ => "expired",
+    }
+}
+
+fn license_warning(record: &Value, now_ms: u64) -> Value {
+    let Some(expiry_ms) = license_expiry_ms(record) else {
+        return json!({"tier": "unknown"});
+    };
+    let days = license_days_remaining(expiry_ms, now_ms);
+    let tier = license_warning_tier(days);
+    let message = match tier {
+        "info" => format!("La licenza scade tra {} giorni. Pianifica il rinnovo.", days),
+        "warning" if days == 1 => "La licenza scade domani. Rinnova ora per evitare il blocco.".to_string(),
+        "warning" => format!("La licenza scade tra {} giorni. Rinnova ora per evitare il blocco.", days),
+        "expired" => "Licenza scaduta. Contatta il supporto per il rinnovo.".to_string(),
+        _ => String::new(),
+    };
+    json!({
+        "tier": tier,
+        "daysRemaining": days,
+        "expiryMs": expiry_ms,
+        "message": message,
+    })
+}
+
+// ─── License grace period ───────────────────────────────────────────────────
+// Past its expiry a license stays usable read-only for settings.licenseGraceDays (by
+// default, and at most, LICENSE_GRACE_DAYS), so a renewal still being paid does not stop
+// the firm overnight. check_license then reports {expired, grace, graceRemainingDays};
+// afterwards the hard block applies. Fingerprint and burned-key checks are unaffected.
+const LICENSE_GRACE_DAYS: u64 = 14;
+
+#[derive(Debug, PartialEq)]
+enum LicenseExpiry { Valid, Grace(i64), Expired }
+
+fn license_grace_days(settings: &Value) -> u64 {
+    settings.get("licenseGraceDays").and_then(|v| v.as_u64()).unwrap_or(LICENSE_GRACE_DAYS).min(LICENSE_GRACE_DAYS)
+}
+
+/// Renewal hint on every activated check_license result: expiryMs, daysUntilExpiry
+/// (negative during the grace window) and renewSoon within RENEW_SOON_DAYS.
+const RENEW_SOON_DAYS: i64 = 30;
+
+fn stamp_license_expiry(status: &mut Value, expiry_ms: u64, now_ms: u64) {
+    let days = license_days_remaining(expiry_ms, now_ms);
+    status["expiryMs"] = json!(expiry_ms);
+    status["daysUntilExpiry"] = json!(days);
+    if days <= RENEW_SOON_DAYS {
+        status["renewSoon"] = json!(true);
+    }
+}
+
+fn license_expiry_state(expiry_ms: u64, now_ms: u64, grace_days: u64) -> LicenseExpiry {
+    if now_ms <= expiry_ms { return LicenseExpiry::Valid; }
+    let grace_end = expiry_ms.saturating_add(grace_days * DAY_MS as u64);
+    if now_ms <= grace_end {
+        LicenseExpiry::Grace(license_days_remaining(grace_end, now_ms))
+    } else {
+        LicenseExpiry::Expired
+    }
+}
+
+/// Advance warning before the license lockout, for progressive UI nags.
+/// `tier`: "none" | "info" | "warning" | "expired", or "unknown" without a readable license.
+#[tauri::command]
+fn license_warning_status(state: State<AppState>) -> Value {
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match decrypt_local_with_migration(&sec_dir.join(LICENSE_FILE))
+        .and_then(|dec| serde_json::from_slice::<Value>(&dec).ok())
+    {
+        Some(record) => license_warning(&record, now_ms()),
+        None => json!({"tier": "unknown"}),
+    }
+}
+
+// ─── License expiry events ──────────────────────────────────────────────────
+// The autolock thread re-reads the license at most every LICENSE_NUDGE_INTERVAL_SECS and
+// emits "lf-license-expiring" once the days left reach a threshold (settings.
+// licenseExpiryThresholds, default LICENSE_EXPIRY_THRESHOLDS), then "lf-license-expired".
+// The tightest reached threshold fires at most once per day; the ledger lives in memory,
+// so a restart nudges again, which is what a freshly opened window wants anyway.
+const LICENSE_EXPIRY_THRESHOLDS: [i64; 3] = [30, 7, 1];
+const LICENSE_NUDGE_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Default)]
+struct LicenseNudge {
+    checked_at: Option<Instant>,
+    /// (threshold, day index) of the last emission; threshold 0 = expired.
+    last: Option<(i64, i64)>,
+}
+
+fn license_expiry_thresholds(settings: &Value) -> Vec<i64> {
+    let mut thresholds: Vec<i64> = settings.get("licenseExpiryThresholds")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|t| t.as_i64()).filter(|&t| t > 0 && t <= 365).collect())
+        .unwrap_or_default();
+    if thresholds.is_empty() {
+        thresholds = LICENSE_EXPIRY_THRESHOLDS.to_vec();
+    }
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    thresholds
+}
+
+/// Threshold to announce for `days_remaining` on `day` (0 = expired), or None. Each
+/// threshold fires once, when it is first reached; after expiry the event repeats daily.
+/// Leaving every threshold (a renewal) clears `last`, so the next approach nudges again.
+fn license_nudge_due(days_remaining: i64, thresholds: &[i64], day: i64, last: &mut Option<(i64, i64)>) -> Option<i64> {
+    if days_remaining <= 0 {
+        if *last == Some((0, day)) { return None; }
+        *last = Some((0, day));
+        return Some(0);
+    }
+    let Some(&threshold) = thresholds.iter().find(|&&t| days_remaining <= t) else {
+        *last = None;
+        return None;
+    };
+    if last.is_some_and(|(t, _)| t == threshold) {
+        return None;
+    }
+    *last = Some((threshold, day));
+    Some(threshold)
+}
+
+/// Evaluates the stored license and emits the due event; returns license_warning plus
+/// `emitted` (event name or null).
+fn license_nudge_now(ah: &AppHandle, state: &AppState) -> Value {
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let data_dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let now = now_ms();
+    let Some(record) = decrypt_local_with_migration(&sec_dir.join(LICENSE_FILE))
+        .and_then(|dec| serde_json::from_slice::<Value>(&dec).ok())
+    else {
+        return json!({"tier": "unknown", "emitted": null});
+    };
+    let mut status = license_warning(&record, now);
+    let Some(days) = status.get("daysRemaining").and_then(|d| d.as_i64()) else {
+        status["emitted"] = Value::Null;
+        return status;
+    };
+    let thresholds = license_expiry_thresholds(&read_settings(&data_dir));
+    let day = now as i64 / DAY_MS;
+    let due = {
+        let mut nudge = state.license_nudge.lock().unwrap_or_else(|e| e.into_inner());
+        license_nudge_due(days, &thresholds, day, &mut nudge.last)
+    };
+    let emitted = match due {
+        Some(0) => Some("lf-license-expired"),
+        Some(_) => Some("lf-license-expiring"),
+        None => None,
+    };
+    if let (Some(event), Some(threshold)) = (emitted, due) {
+        let mut payload = json!({
+            "daysRemaining": days,
+            "expiryMs": status["expiryMs"],
+            "message": status["message"],
+        });
+        if threshold > 0 {
+            payload["threshold"] = json!(threshold);
+        }
+        lf_log!("license expiry nudge: {} ({} days left)", event, days);
+        let _ = ah.emit(event, payload);
+    }
+    status["emitted"] = json!(emitted);
+    status
+}
+
+/// Called from autolock_pass; cheap no-op until LICENSE_NUDGE_INTERVAL_SECS have passed.
+fn license_nudge_pass(ah: &AppHandle, state: &AppState) {
+    {
+        let mut nudge = state.license_nudge.lock().unwrap_or_else(|e| e.into_inner());
+        if nudge.checked_at.map(|t| t.elapsed() < Duration::from_secs(LICENSE_NUDGE_INTERVAL_SECS)).unwrap_or(false) {
+            return;
+        }
+        nudge.checked_at = Some(Instant::now());
+    }
+    license_nudge_now(ah, state);
+}
+
+/// Runs the expiry check immediately (e.g. right after the frontend subscribes).
+/// Same dedup as the background pass: an already-announced threshold is not re-emitted today.
+#[tauri::command]
+fn check_license_expiry(state: State<AppState>, app: AppHandle) -> Value {
+    license_nudge_now(&app, &state)
+}
+
+// NOTE: legacy symmetric license verification (HMAC/XOR secret) has been removed.
+// The project now uses Ed25519-signed license tokens verified by `verify_license`.
+
+// ---------------------------------------------------------------------------
+// Offline Ed25519-signed license verification
+// ---------------------------------------------------------------------------
+// PUBLIC_KEY_BYTES: 32-byte Ed25519 public key for offline license validation.
+// The corresponding private key is stored securely offline (never in source control).
+// To regenerate: pip install cryptography && python3 -c "from cryptography.hazmat.primitives.asymmetric import ed25519; k=ed25519.Ed25519PrivateKey.generate(); print(list(k.public_key().public_bytes(encoding=__import__('cryptography.hazmat.primitives.serialization',fromlist=['Encoding']).Encoding.Raw, format=__import__('cryptography.hazmat.primitives.serialization',fromlist=['PublicFormat']).PublicFormat.Raw)))"
+const PUBLIC_KEY_BYTES: [u8; 32] = [
+    253u8, 163u8, 188u8, 248u8, 70u8, 245u8, 107u8, 254u8,
+    146u8, 8u8, 131u8, 167u8, 183u8, 94u8, 71u8, 224u8,
+    140u8, 237u8, 206u8, 74u8, 116u8, 185u8, 140u8, 0u8,
+    183u8, 15u8, 243u8, 77u8, 117u8, 233u8, 138u8, 84u8,
+];
+
+/// Self-check of an Ed25519 public key: must decompress to a curve point and must not be
+/// of small order (an all-zero or otherwise weak key would accept forged signatures or
+/// reject every real one). Fingerprint = first 8 bytes of SHA-256(key), hex — safe to show.
+fn public_key_status(bytes: &[u8; 32]) -> Value {
+    let valid = VerifyingKey::from_bytes(bytes).map(|k| !k.is_weak()).unwrap_or(false);
+    let digest = <Sha256 as Digest>::digest(bytes);
+    json!({
+        "valid": valid,
+        "fingerprint": hex::encode(&digest[..8]),
+        "algorithm": "ed25519",
+    })
+}
+
+/// Support diagnostic: confirms which public key this client was built with.
+/// Only public material is reported — the client never holds a private key.
+#[tauri::command]
+fn license_system_status() -> Value {
+    public_key_status(&PUBLIC_KEY_BYTES)
+}
+
+#[derive(Deserialize, Serialize)]
+struct LicensePayload {
+    c: String, // client name
+    e: u64,    // expiry in milliseconds since epoch
+    id: String, // unique key id
+    #[serde(default)] // backward compatible: v1 tokens don't have this field
+    n: Option<String>, // anti-replay nonce (128-bit hex, v2+)
+    #[serde(default)] // seat bundle: one signed token, N seat ids (one per machine)
+    s: Option<Vec<String>>,
+    #[serde(default)] // licensed feature modules; absent = DEFAULT_ENTITLEMENTS
+    f: Option<Vec<String>>,
+}
+
+// ─── Seat bundles ───────────────────────────────────────────────────────────
+// A bundle token is a normal LXFW token whose payload also lists seat ids. Each activation
+// claims the first seat not yet in the burned-keys registry and burns only that seat, so the
+// registry (shareable via export/import_burn_registry) is the firm's record of used seats.
+// A second marker, bound to the machine fingerprint, stops one device claiming two seats;
+// being machine-specific it never blocks the bundle on other devices after an import.
+const MAX_BUNDLE_SEATS: usize = 1000;
+
+fn seat_burn_hash(bundle_id: &str, seat_id: &str) -> String {
+    compute_keyid_burn_hash(&format!("{}#seat:{}", bundle_id, seat_id))
+}
+
+fn bundle_machine_hash(bundle_id: &str, fingerprint: &str) -> String {
+    let hash = <Sha256 as Digest>::digest(format!("BURN-BUNDLE-MACHINE-V1:{}:{}", bundle_id, fingerprint).as_bytes());
+    hex::encode(hash)
+}
+
+fn seat_list_error(seats: &[String]) -> Option<&'static str> {
+    let unique: std::collections::HashSet<&str> = seats.iter().map(|s| s.as_str()).collect();
+    if seats.is_empty() || seats.len() > MAX_BUNDLE_SEATS || unique.len() != seats.len()
+        || seats.iter().any(|s| s.trim().is_empty()) {
+        return Some("Pacchetto posti non valido.");
+    }
+    None
+}
+
+/// Pick the seat this machine may claim, given the burned-keys registry.
+fn claim_bundle_seat(burned: &[String], bundle_id: &str, seats: &[String], fingerprint: &str) -> Result<String, String> {
+    if burned.contains(&bundle_machine_hash(bundle_id, fingerprint)) {
+        return Err("Questo dispositivo ha già un posto di questa licenza.".into());
+    }
+    seats.iter()
+        .find(|seat| !burned.contains(&seat_burn_hash(bundle_id, seat)))
+        .cloned()
+        .ok_or_else(|| "Tutti i posti di questa licenza sono già stati assegnati.".into())
+}
+
+fn extract_seat_ids(token: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXFW" { return None; }
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
+    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).ok()?;
+    payload.s
+}
+
+#[derive(Serialize)]
+struct VerificationResult {
+    valid: bool,
+    client: Option<String>,
+    message: String,
+}
+
+#[tauri::command]
+fn verify_license(key_string: String) -> VerificationResult {
+    // Expected format: LXFW.<payload_b64>.<signature_b64>
+    let parts: Vec<&str> = key_string.split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXFW" {
+        return VerificationResult { valid: false, client: None, message: "Formato chiave non valido.".into() };
+    }
+
+    let payload_b64 = parts[1];
+    let signature_b64 = parts[2];
+
+    let payload_bytes = match URL_SAFE_NO_PAD.decode(payload_b64) {
+        Ok(b) => b,
+        Err(_) => return VerificationResult { valid: false, client: None, message: "Errore decodifica payload.".into() },
+    };
+
+    let signature_bytes = match URL_SAFE_NO_PAD.decode(signature_b64) {
+        Ok(b) => b,
+        Err(_) => return VerificationResult { valid: false, client: None, message: "Errore decodifica firma.".into() },
+    };
+
+    let public_key = match VerifyingKey::from_bytes(&PUBLIC_KEY_BYTES) {
+        Ok(k) => k,
+        Err(_) => return VerificationResult { valid: false, client: None, message: "Errore chiave pubblica interna.".into() },
+    };
+
+    let signature = match Signature::from_slice(&signature_bytes) {
+        Ok(s) => s,
+        Err(_) => return VerificationResult { valid: false, client: None, message: "Firma corrotta.".into() },
+    };
+
+    if public_key.verify(payload_b64.as_bytes(), &signature).is_err() {
+        return VerificationResult { valid: false, client: None, message: "Firma non valida o licenza manomessa!".into() };
+    }
+
+    let payload: LicensePayload = match serde_json::from_slice(&payload_bytes) {
+        Ok(p) => p,
+        Err(_) => return VerificationResult { valid: false, client: None, message: "Dati licenza corrotti.".into() },
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    if now > payload.e {
+        return VerificationResult { valid: false, client: Some(payload.c), message: "Licenza scaduta.".into() };
+    }
+
+    if let Some(err) = payload.s.as_deref().and_then(seat_list_error) {
+        return VerificationResult { valid: false, client: Some(payload.c), message: err.into() };
+    }
+
+    VerificationResult { valid: true, client: Some(payload.c), message: "Licenza attivata con successo!".into() }
+}
+
+// Helper: extract key ID from a LXFW token without full verification.
+// Returns the `id` field from the payload JSON, or None if malformed.
+fn extract_key_id(token: &str) -> Option<String> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXFW" { return None; }
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
+    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).ok()?;
+    Some(payload.id)
+}
+
+// Helper: extract the feature list from a LXFW token without full verification.
+fn extract_features(token: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXFW" { return None; }
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
+    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).ok()?;
+    payload.f
+}
+
+// Helper: extract expiry timestamp (ms) from a LXFW token without full verification.
+fn extract_expiry_ms(token: &str) -> Option<u64> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXFW" { return None; }
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
+    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).ok()?;
+    Some(payload.e)
+}
+
+/// Sentinel format: line 1 = HMAC("LEXFLOW-SENTINEL:<fingerprint>:<keyId>:<activatedAt>"),
+/// line 2 = encrypted key ID (hex) so a same-key re-activation can be recognised.
+fn write_license_sentinel(path: &std::path::Path, enc_key: &[u8], fingerprint: &str, key_id: &str, activated_at: &str) -> Result<(), String> {
+    let sentinel_data = format!("LEXFLOW-SENTINEL:{}:{}:{}", fingerprint, key_id, activated_at);
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(enc_key)
+        .expect("HMAC can take key of any size");
+    mac.update(sentinel_data.as_bytes());
+    let sentinel_hmac = hex::encode(mac.finalize().into_bytes());
+    let encrypted_key_id = hex::encode(encrypt_data(enc_key, key_id.as_bytes())?);
+    atomic_write_with_sync(path, format!("{}\n{}", sentinel_hmac, encrypted_key_id).as_bytes())
+}
+
+/// Shape-only check of a license key (LXFW.<b64>.<b64>): no signature, no disk access.
+/// Cheap enough to run on every paste, and lets activation reject typos without
+/// charging them to the lockout counter.
+fn key_format_error(key: &str) -> Option<&'static str> {
+    let parts: Vec<&str> = key.trim().split('.').collect();
+    if parts.len() != 3 {
+        return Some("Formato chiave non valido: attese tre parti separate da punti.");
+    }
+    if parts[0] != "LXFW" {
+        return Some("Formato chiave non valido: la chiave deve iniziare con LXFW.");
+    }
+    if parts[1..].iter().any(|p| p.is_empty() || URL_SAFE_NO_PAD.decode(p).is_err()) {
+        return Some("Formato chiave non valido: caratteri non ammessi.");
+    }
+    None
+}
+
+#[tauri::command]
+fn is_valid_key_format(key: String) -> Value {
+    match key_format_error(&key) {
+        None => json!({"valid": true}),
+        Some(e) => json!({"valid": false, "error": e}),
+    }
+}
+
+#[tauri::command]
+fn activate_license(state: State<AppState>, key: String, _client_name: Option<String>) -> Value {
+    // Malformed input never reaches the lockout counter or the signature check
+    if let Some(e) = key_format_error(&key) {
+        return json!({"success": false, "error": e, "formatError": true});
+    }
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    // Anti brute-force: contatore dedicato alle chiavi licenza (persistito come quello del vault)
+    if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::License) {
+        return locked_json;
+    }
+
+    let key = key.trim().to_string(); // Le chiavi B64 sono case-sensitive, non uppercasiamo
+    let enc_key = match get_local_encryption_key() {
+        Ok(k) => k,
+        Err(e) => return json!({"success": false, "error": e}),
+    };
+
+    let path = sec_dir.join(LICENSE_FILE);
+    let sentinel_path = sec_dir.join(LICENSE_SENTINEL_FILE);
+
+    // ── SECURITY CHECK 1: if sentinel exists but license.json was deleted ──
+    // Someone deleted the license file to try re-activating with a different key.
+    // Block unless the SAME key (same ID) is being re-entered.
+    if !path.exists() && sentinel_path.exists() {
+        // We can still allow re-activation of the SAME key ID that was originally used.
+        // The sentinel stores HMAC("LEXFLOW-SENTINEL:<fingerprint>:<keyId>:<timestamp>")
+        // but we cannot recover the keyId from the HMAC. So we also store the encrypted
+        // key ID in the sentinel for comparison. See sentinel write below.
+        let sentinel_content = fs::read_to_string(&sentinel_path).unwrap_or_default();
+        // Sentinel format: "<hmac_hex>\n<encrypted_key_id_hex>"
+        let sentinel_lines: Vec<&str> = sentinel_content.lines().collect();
+        let stored_key_id_enc = sentinel_lines.get(1).unwrap_or(&"");
+
+        // Try to recover stored key ID (try current key, then legacy)
+        let stored_key_id: Option<String> = if !stored_key_id_enc.is_empty() {
+            hex::decode(stored_key_id_enc).ok()
+                .and_then(|enc_bytes| {
+                    decrypt_data(&enc_key, &enc_bytes).ok()
+                        .or_else(|| {
+                            #[cfg(not(target_os = "android"))]
+                            { decrypt_data(&get_local_encryption_key_legacy(), &enc_bytes).ok() }
+                            #[cfg(target_os = "android")]
+                            { None }
+                        })
+                })
+                .and_then(|dec| String::from_utf8(dec).ok())
+        } else {
+            None
+        };
+
+        let new_key_id = extract_key_id(&key);
+
+        // Allow re-activation ONLY if the same key ID is being used
+        match (stored_key_id.as_deref(), new_key_id.as_deref()) {
+            (Some(old), Some(new_id)) if old == new_id => {
+                // Same key ID — allow re-activation (e.g. user accidentally deleted the file)
+            },
+            _ => {
+                return json!({
+                    "success": false,
+                    "error": "Questa installazione ha già una licenza registrata. Contattare il supporto per assistenza."
+                });
+            }
+        }
+    }
+
+    // ── SECURITY CHECK 2: if license already exists and is valid, block overwrite ──
+    // Prevents replacing a valid license with a pirated/shared key.
+    if path.exists() {
+        if let Some(dec) = decrypt_local_with_migration(&path) {
+            if let Ok(existing) = serde_json::from_slice::<Value>(&dec) {
+                let existing_version = existing.get("keyVersion")
+                    .and_then(|v| v.as_str()).unwrap_or("");
+
+                if existing_version == "ed25519-burned" {
+                    let expiry = existing.get("expiryMs")
+                        .and_then(|v| v.as_u64()).unwrap_or(0);
+                    let now_ms = SystemTime::now()
+                        .duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+                    if now_ms <= expiry {
+                        let existing_id = existing.get("keyId")
+                            .and_then(|v| v.as_str());
+                        let new_id = extract_key_id(&key);
+                        if existing_id.map(|s| s.to_string()) != new_id {
+                            return json!({
+                                "success": false,
+                                "error": "Una licenza valida è già attiva. Non è possibile sostituirla."
+                            });
+                        }
+                    }
+                } else {
+                    let existing_key = existing.get("key")
+                        .and_then(|k| k.as_str()).unwrap_or("");
+                    if !existing_key.is_empty() {
+                        let existing_verification = verify_license(existing_key.to_string());
+                        if existing_verification.valid {
+                            let existing_id = extract_key_id(existing_key);
+                            let new_id = extract_key_id(&key);
+                            if existing_id != new_id {
+                                return json!({
+                                    "success": false,
+                                    "error": "Una licenza valida è già attiva. Non è possibile sostituirla."
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Verifica asimmetrica (Ed25519)
+    let verification = verify_license(key.clone());
+
+    if !verification.valid {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::License);
+        return json!({"success": false, "error": verification.message});
+    }
+
+    clear_lockout(&state, &sec_dir, LockoutKind::License);
+
+    // SECURITY: bind license to THIS machine — cannot be copied to another device
+    let fingerprint = compute_machine_fingerprint();
+
+    // ── SECURITY CHECK 3: burned-key registry ──────────────────────────────
+    // A key can only be activated ONCE. After activation it is "burned" —
+    // the raw token is destroyed and only a verification hash survives.
+    // Even if someone knows the key, they cannot re-use it.
+    if is_key_burned(&sec_dir, &key, &fingerprint) {
+        return json!({
+            "success": false,
+            "error": "Questa chiave è già stata utilizzata e non può essere riattivata."
+        });
+    }
+
+    // ── SECURITY CHECK 4: burned-keys file integrity ──────────────────────
+    // If the sentinel exists (a license was previously activated on this machine)
+    // but the .burned-keys file is missing, someone deleted it to bypass single-use
+    // enforcement. Block activation of any NEW key in this case.
+    if sentinel_path.exists() && !sec_dir.join(BURNED_KEYS_FILE).exists() {
+        // Allow re-activation of the SAME key ID only (handled by CHECK 1 above).
+        // If we reach here, it's a different key → block.
+        return json!({
+            "success": false,
+            "error": "Registro chiavi compromesso. Contattare il supporto per assistenza."
+        });
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+
+    // Il client viene estratto in modo sicuro dal payload firmato
+    let client = verification.client.unwrap_or_else(|| "Studio Legale".to_string());
+
+    // Extract key ID for sentinel storage
+    let key_id = extract_key_id(&key).unwrap_or_else(|| "unknown".to_string());
+
+    // ── SEAT BUNDLE: claim one free seat instead of burning the whole token ──
+    let seat_id = match extract_seat_ids(&key) {
+        Some(seats) => match claim_bundle_seat(&load_burned_keys(&sec_dir), &key_id, &seats, &fingerprint) {
+            Ok(seat) => Some(seat),
+            Err(e) => return json!({"success": false, "error": e}),
+        },
+        None => None,
+    };
+
+    // ── BURN THE KEY: compute verification hash, then destroy raw token ────
+    // We store an HMAC(token) so check_license can verify integrity without
+    // having the raw token. The raw token ceases to exist after this point.
+    let mut token_mac = <Hmac<Sha256> as Mac>::new_from_slice(
+        &enc_key
+    ).expect("HMAC can take key of any size");
+    token_mac.update(key.as_bytes());
+    let token_hmac = hex::encode(token_mac.finalize().into_bytes());
+
+    // Extract payload data BEFORE destroying the token — we need client/expiry
+    // for check_license to work without re-verifying Ed25519
+    let parts: Vec<&str> = key.split('.').collect();
+    let payload_data: Option<LicensePayload> = if parts.len() == 3 {
+        URL_SAFE_NO_PAD.decode(parts[1]).ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+    } else { None };
+
+    let expiry_ms = payload_data.as_ref().map(|p| p.e).unwrap_or(0);
+
+    // Record: NO raw token — only HMAC + extracted payload data
+    let mut record = json!({
+        "tokenHmac": token_hmac,
+        "activatedAt": now,
+        "client": client,
+        "keyVersion": "ed25519-burned",
+        "machineFingerprint": fingerprint,
+        "keyId": key_id,
+        "expiryMs": expiry_ms,
+    });
+    if let Some(seat) = &seat_id {
+        record["seatId"] = json!(seat);
+    }
+    if let Some(features) = payload_data.as_ref().and_then(|p| p.f.clone()) {
+        record["features"] = json!(features);
+    }
+    match encrypt_data(&enc_key, &serde_json::to_vec(&record).unwrap_or_default()) {
+        Ok(encrypted) => {
+            match atomic_write_with_sync(&path, &encrypted) {
+                Ok(_) => {
+                        // SECURITY: write sentinel file — HMAC proof that activation happened.
+                        // This detects if license.json is manually deleted to hack the system.
+                        let _ = write_license_sentinel(&sentinel_path, &enc_key, &fingerprint, &key_id, &now);
+
+                        // ── BURN THE KEY: add to burned-keys registry ──
+                        // After this, the same token can NEVER be activated again.
+                        // Bundles burn only the claimed seat (+ this machine's marker).
+                        match &seat_id {
+                            Some(seat) => {
+                                burn_key(&sec_dir, &seat_burn_hash(&key_id, seat));
+                                burn_key(&sec_dir, &bundle_machine_hash(&key_id, &fingerprint));
+                                json!({"success": true, "client": client, "seatId": seat})
+                            }
+                            None => {
+                                burn_key(&sec_dir, &compute_burn_hash(&key, &fingerprint));
+                                json!({"success": true, "client": client})
+                            }
+                        }
+                    },
+                    Err(e) => json!({"success": false, "error": format!("Errore salvataggio: {}", e)}),
+                }
+        },
+        Err(e) => json!({"success": false, "error": format!("Errore cifratura: {}", e)}),
+    }
+}
+
+// ─── License from file ──────────────────────────────────────────────────────
+// IT distributes tokens as .lxkey files. The file content goes through activate_license
+// unchanged, so burned-key, sentinel, fingerprint and lockout checks all apply.
+const MAX_LICENSE_FILE_BYTES: u64 = 16 * 1024;
+
+/// Token text of a license file: UTF-8, BOM and surrounding whitespace removed.
+fn license_token_from_file(raw: &[u8]) -> Result<String, String> {
+    let text = std::str::from_utf8(raw).map_err(|_| "File licenza non leggibile")?;
+    let token = text.trim_start_matches('\u{feff}').trim();
+    if token.is_empty() { return Err("File licenza vuoto".into()); }
+    Ok(token.to_string())
+}
+
+#[tauri::command]
+async fn activate_license_from_file(app: AppHandle) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("Licenza LexFlow", &["lxkey", "txt"])
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+    let Some(picked) = rx.await.map_err(|e| format!("Dialog error: {}", e))? else {
+        return Ok(json!({"success": false, "cancelled": true}));
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    if fs::metadata(&path).map(|m| m.len() > MAX_LICENSE_FILE_BYTES).unwrap_or(false) {
+        return Ok(json!({"success": false, "error": "File troppo grande per una licenza", "formatError": true}));
+    }
+    let raw = fs::read(&path).map_err(|e| e.to_string())?;
+    match license_token_from_file(&raw) {
+        Ok(token) => Ok(activate_license(app.state::<AppState>(), token, None)),
+        Err(e) => Ok(json!({"success": false, "error": e, "formatError": true})),
+    }
+}
+
+// ─── Signed artifact check ──────────────────────────────────────────────────
+// Lets the user validate an imported seat bundle (LXFW token with seats) or revocation
+// list (LXRV.<payload_b64>.<signature_b64>, payload {"r": [key ids], "i": issued ms})
+// before doing anything with it. Only the signature and shape are checked; nothing is applied.
+
+#[derive(Deserialize)]
+struct RevocationPayload {
+    r: Vec<String>, // revoked key ids
+    #[serde(default)]
+    i: Option<u64>, // issued at, milliseconds since epoch
+}
+
+fn check_signed_artifact(content: &str, kind: &str, public_key: &[u8; 32]) -> Result<Value, String> {
+    let prefix = match kind {
+        "seat-bundle" => "LXFW",
+        "revocation-list" => "LXRV",
+        _ => return Err("Tipo di file non supportato".into()),
+    };
+    let parts: Vec<&str> = content.trim().split('.').collect();
+    if parts.len() != 3 || parts[0] != prefix {
+        return Err("Formato file non valido".into());
+    }
+    let key = VerifyingKey::from_bytes(public_key).map_err(|_| "Errore chiave pubblica interna")?;
+    let sig_bytes = URL_SAFE_NO_PAD.decode(parts[2]).map_err(|_| "Errore decodifica firma")?;
+    let signature = Signature::from_slice(&sig_bytes).map_err(|_| "Firma corrotta")?;
+    key.verify(parts[1].as_bytes(), &signature).map_err(|_| "Firma non valida o file manomesso")?;
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).map_err(|_| "Errore decodifica payload")?;
+    if kind == "seat-bundle" {
+        let payload: LicensePayload = serde_json::from_slice(&payload_bytes).map_err(|_| "Dati licenza corrotti")?;
+        let seats = payload.s.ok_or("La licenza non contiene posti")?;
+        if let Some(err) = seat_list_error(&seats) {
+            return Err(err.into());
+        }
+        Ok(json!({"client": payload.c, "id": payload.id, "expiresAt": payload.e, "seats": seats.len()}))
+    } else {
+        let payload: RevocationPayload = serde_json::from_slice(&payload_bytes).map_err(|_| "Lista revoche corrotta")?;
+        Ok(json!({"revoked": payload.r.len(), "issuedAt": payload.i}))
+    }
+}
+
+#[tauri::command]
+fn verify_signed_artifact(content: String, kind: String) -> Value {
+    match check_signed_artifact(&content, &kind, &PUBLIC_KEY_BYTES) {
+        Ok(summary) => json!({"valid": true, "kind": kind, "summary": summary}),
+        Err(e) => json!({"valid": false, "kind": kind, "error": e}),
+    }
+}
+
+// ─── License state repair (support-assisted) ────────────────────────────────
+// Support issues a one-off code LXRP.<payload_b64>.<signature_b64>, signed with the same
+// Ed25519 key as licenses and bound to this machine's fingerprint, so the repair cannot
+// be self-served to launder a different key into a half-deleted license state.
+
+#[derive(Deserialize)]
+struct SupportCodePayload {
+    a: String,  // action, must be "repair-license"
+    fp: String, // machine fingerprint the code was issued for
+    e: u64,     // expiry in milliseconds since epoch
+}
+
+fn verify_support_code(code: &str, public_key: &[u8; 32], fingerprint: &str, now_ms: u64) -> Result<(), String> {
+    let parts: Vec<&str> = code.trim().split('.').collect();
+    if parts.len() != 3 || parts[0] != "LXRP" {
+        return Err("Formato codice di supporto non valido".into());
+    }
+    let key = VerifyingKey::from_bytes(public_key).map_err(|_| "Errore chiave pubblica interna")?;
+    let sig_bytes = URL_SAFE_NO_PAD.decode(parts[2]).map_err(|_| "Codice di supporto non valido")?;
+    let signature = Signature::from_slice(&sig_bytes).map_err(|_| "Codice di supporto non valido")?;
+    key.verify(parts[1].as_bytes(), &signature).map_err(|_| "Codice di supporto non valido")?;
+    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).map_err(|_| "Codice di supporto non valido")?;
+    let payload: SupportCodePayload = serde_json::from_slice(&payload_bytes).map_err(|_| "Codice di supporto non valido")?;
+    if payload.a != "repair-license" || payload.fp != fingerprint {
+        return Err("Codice di supporto non valido per questo dispositivo".into());
+    }
+    if now_ms > payload.e {
+        return Err("Codice di supporto scaduto".into());
+    }
+    Ok(())
+}
+
+/// Reconcile license.json, the sentinel and the burned-keys registry. The license record
+/// is the source of truth: it must decrypt, belong to this machine and still be valid.
+/// Returns the list of changes made (empty = already consistent).
+fn reconcile_license_files(sec_dir: &std::path::Path, fingerprint: &str, now_ms: u64) -> Result<Vec<String>, String> {
+    let record: Value = decrypt_local_with_migration(&sec_dir.join(LICENSE_FILE))
+        .and_then(|dec| serde_json::from_slice(&dec).ok())
+        .ok_or("Nessuna licenza valida da cui ricostruire lo stato")?;
+    if let Some(stored_fp) = record.get("machineFingerprint").and_then(|v| v.as_str()) {
+        if stored_fp != fingerprint {
+            return Err("Licenza attivata su un altro dispositivo".into());
+        }
+    }
+    let raw_key = record.get("key").and_then(|k| k.as_str()).filter(|k| !k.is_empty());
+    let (key_id, burn_hash) = match raw_key {
+        // Legacy record still holds the token: the real burn-hash can be recomputed
+        Some(token) => {
+            if !verify_license(token.to_string()).valid {
+                return Err("Licenza non valida o scaduta".into());
+            }
+            (extract_key_id(token).unwrap_or_else(|| "legacy".into()), compute_burn_hash(token, fingerprint))
+        }
+        None => {
+            let key_id = record.get("keyId").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            let expiry = record.get("expiryMs").and_then(|v| v.as_u64()).unwrap_or(0);
+            if key_id.is_empty() || now_ms > expiry {
+                return Err("Licenza non valida o scaduta".into());
+            }
+            let hash = compute_keyid_burn_hash(&key_id);
+            (key_id, hash)
+        }
+    };
+
+    let mut changes = Vec::new();
+    let enc_key = get_local_encryption_key()?;
+    let sentinel_path = sec_dir.join(LICENSE_SENTINEL_FILE);
+    if !sentinel_path.exists() {
+        let activated_at = record.get("activatedAt").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        write_license_sentinel(&sentinel_path, &enc_key, fingerprint, &key_id, &activated_at)?;
+        changes.push("Sentinella di attivazione rigenerata".to_string());
+    }
+    if !sec_dir.join(BURNED_KEYS_FILE).exists() {
+        write_burned_keys(sec_dir, &[burn_hash])?;
+        changes.push("Registro chiavi bruciate ricostruito dalla licenza".to_string());
+    }
+    Ok(changes)
+}
+
+#[tauri::command]
+fn repair_license_state(state: State<AppState>, support_code: String) -> Result<Value, String> {
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    check_lockout(&state, &sec_dir, LockoutKind::Vault).map_err(|_| "Troppi tentativi. Riprova più tardi.")?;
+    let fingerprint = compute_machine_fingerprint();
+    if let Err(e) = verify_support_code(&support_code, &PUBLIC_KEY_BYTES, &fingerprint, now_ms()) {
+        record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+        return Err(e);
+    }
+    clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+    let changes = reconcile_license_files(&sec_dir, &fingerprint, now_ms())?;
+    for change in &changes {
+        let _ = append_audit_log(&state, AuditCategory::License, None, &format!("Riparazione licenza: {}", change));
+    }
+    Ok(json!({"success": true, "consistent": changes.is_empty(), "changes": changes}))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  IMPORT / EXPORT
+// ═══════════════════════════════════════════════════════════
+
+/// Bring a decrypted backup up to VAULT_SCHEMA_VERSION. Backups written by a NEWER app
+/// are refused: they may carry fields this version would silently drop on the next save.
+fn migrate_backup_schema(mut val: Value) -> Result<Value, String> {
+    let version = val.get("schemaVersion").and_then(|v| v.as_u64()).unwrap_or(1);
+    if version > VAULT_SCHEMA_VERSION {
+        return Err(format!(
+            "Backup creato con una versione più recente di LexFlow (schema {} — questa app supporta fino a {}). Aggiorna LexFlow prima di importarlo.",
+            version, VAULT_SCHEMA_VERSION
+        ));
+    }
+    if version < 2 {
+        // v1 → v2: sections added after v3.2 may be absent in old backups
+        for section in VAULT_SECTIONS {
+            if !val.get(*section).map(|v| v.is_array()).unwrap_or(false) {
+                val[*section] = json!([]);
+            }
+        }
+    }
+    val["schemaVersion"] = json!(VAULT_SCHEMA_VERSION);
+    Ok(val)
+}
+
+/// What an export contains. Defaults reproduce the historical full backup.
+struct ExportOptions {
+    include_attachments: bool,
+    include_audit: bool,
+    sections: Vec<String>,
+}
+
+/// Build the backup payload and stamp a `manifest` describing what was included,
+/// so import_vault can tell "section omitted on purpose" from "section empty".
+fn build_export_payload(vault: &Value, audit_log: Option<Value>, opts: &ExportOptions) -> Value {
+    let mut out = serde_json::Map::new();
+    for section in &opts.sections {
+        let mut data = vault.get(section.as_str()).cloned().unwrap_or(json!([]));
+        if section == "practices" && !opts.include_attachments {
+            if let Some(list) = data.as_array_mut() {
+                for p in list.iter_mut() {
+                    if let Some(obj) = p.as_object_mut() { obj.remove("attachments"); }
+                }
+            }
+        }
+        out.insert(section.clone(), data);
+    }
+    if opts.sections.iter().any(|s| s == "agenda") {
+        if let Some(data) = vault.get("archivedAgenda") {
+            out.insert("archivedAgenda".into(), data.clone());
+        }
+    }
+    // Secrets, the recycle bin and document keys belong to practices and travel with them
+    if opts.sections.iter().any(|s| s == "practices") {
+        for extra in ["secrets", "trash", "documentKeys"] {
+            if let Some(data) = vault.get(extra) {
+                out.insert(extra.into(), data.clone());
+            }
+        }
+    }
+    let include_audit = opts.include_audit && audit_log.is_some();
+    if include_audit {
+        out.insert("auditLog".into(), audit_log.unwrap_or(json!([])));
+    }
+    let partial = !opts.include_attachments
+        || VAULT_SECTIONS.iter().any(|s| !opts.sections.iter().any(|o| o == s));
+    out.insert("schemaVersion".into(), json!(VAULT_SCHEMA_VERSION));
+    if let Some(min) = vault.get("minAppVersion") {
+        out.insert("minAppVersion".into(), min.clone());
+    }
+    out.insert("manifest".into(), json!({
+        "sections": opts.sections,
+        "includesAttachments": opts.include_attachments,
+        "includesAudit": include_audit,
+        "partial": partial,
+        "createdAt": chrono::Local::now().to_rfc3339(),
+    }));
+    Value::Object(out)
+}
+
+fn is_partial_backup(backup: &Value) -> bool {
+    backup.pointer("/manifest/partial").and_then(|p| p.as_bool()).unwrap_or(false)
+}
+
+/// Apply a partial backup on top of the current vault: sections missing from the backup
+/// are kept, and practices exported without attachments keep their current ones.
+fn merge_partial_backup(current: &Value, mut backup: Value) -> Value {
+    let sections: Vec<String> = backup.pointer("/manifest/sections").and_then(|s| s.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    let with_attachments = backup.pointer("/manifest/includesAttachments").and_then(|b| b.as_bool()).unwrap_or(true);
+    for section in VAULT_SECTIONS {
+        if !sections.iter().any(|s| s == section) {
+            backup[*section] = current.get(*section).cloned().unwrap_or(json!([]));
+        }
+    }
+    if !sections.iter().any(|s| s == "agenda") {
+        backup["archivedAgenda"] = current.get("archivedAgenda").cloned().unwrap_or(json!([]));
+    }
+    if !sections.iter().any(|s| s == "practices") {
+        backup["secrets"] = current.get("secrets").cloned().unwrap_or(json!([]));
+        backup["trash"] = current.get("trash").cloned().unwrap_or(json!([]));
+    }
+    carry_document_keys(current, &mut backup);
+    if !with_attachments {
+        let current_practices = current.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+        if let Some(list) = backup.get_mut("practices").and_then(|p| p.as_array_mut()) {
+            for p in list.iter_mut() {
+                let id = p.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string();
+                let existing = current_practices.iter()
+                    .find(|c| c.get("id").and_then(|i| i.as_str()) == Some(id.as_str()))
+                    .and_then(|c| c.get("attachments")).cloned();
+                if let (Some(att), Some(obj)) = (existing, p.as_object_mut()) {
+                    obj.insert("attachments".into(), att);
+                }
+            }
+        }
+    }
+    backup
+}
+
+/// Drop the export envelope (manifest, schemaVersion, auditLog) so only vault data is
+/// written back to vault.lex.
+fn strip_backup_envelope(val: &mut Value) {
+    if let Some(obj) = val.as_object_mut() {
+        for key in ["manifest", "schemaVersion", "auditLog"] {
+            obj.remove(key);
+        }
+    }
+}
+
+/// Copy document keys the backup does not know about; keys present in both keep the backup's.
+fn carry_document_keys(current: &Value, backup: &mut Value) {
+    let Some(keys) = current.get("documentKeys").and_then(|k| k.as_object()) else { return };
+    if !backup.get("documentKeys").map(|k| k.is_object()).unwrap_or(false) {
+        backup["documentKeys"] = json!({});
+    }
+    if let Some(target) = backup.get_mut("documentKeys").and_then(|k| k.as_object_mut()) {
+        for (blob_id, key) in keys {
+            target.entry(blob_id.clone()).or_insert_with(|| key.clone());
+        }
+    }
+}
+
+/// True when `pwd` opens the vault's own verify tag (or there is no vault yet).
+fn backup_password_matches(state: &State<AppState>, pwd: &str) -> Result<bool, String> {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let salt_path = dir.join(VAULT_SALT_FILE);
+    if !salt_path.exists() { return Ok(true); }
+    let vault_salt = fs::read(&salt_path).map_err(|e| e.to_string())?;
+    let vault_key_check = derive_vault_key(pwd, &dir, &vault_salt)?;
+    let stored_verify = fs::read(dir.join(VAULT_VERIFY_FILE)).unwrap_or_default();
+    Ok(verify_hash_matches(&vault_key_check, &stored_verify))
+}
+
+#[tauri::command]
+async fn export_vault(
+    state: State<'_, AppState>,
+    pwd: String,
+    app: AppHandle,
+    include_attachments: Option<bool>,
+    include_audit: Option<bool>,
+    sections: Option<Vec<String>>,
+) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    // SECURITY FIX (Level-8 A2): verify that `pwd` is the intended backup password by
+    // re-deriving it and checking against vault.verify BEFORE writing the backup.
+    // Without this check, a typo in `pwd` produces a backup encrypted with the wrong key
+    // that is permanently inaccessible — the user has no way to know until they need to restore.
+    // We verify by deriving the key and confirming it opens the vault's own verify tag.
+    if !backup_password_matches(&state, &pwd)? {
+        return Ok(json!({"success": false, "error": "Password errata: il backup non può essere creato con una password diversa da quella del vault."}));
+    }
+    let opts = ExportOptions {
+        include_attachments: include_attachments.unwrap_or(true),
+        include_audit: include_audit.unwrap_or(true),
+        sections: match sections {
+            Some(list) => {
+                if let Some(bad) = list.iter().find(|s| !VAULT_SECTIONS.contains(&s.as_str())) {
+                    return Ok(json!({"success": false, "error": format!("Sezione non valida: {}", bad)}));
+                }
+                list
+            }
+            None => VAULT_SECTIONS.iter().map(|s| s.to_string()).collect(),
+        },
+    };
+    let mut vault = read_vault_internal(&state)?;
+    reseal_secrets(&mut vault, Some(&get_vault_key(&state)?), None)?;
+    let audit_log = if opts.include_audit { get_audit_log(state.clone()).ok() } else { None };
+    let mut data = build_export_payload(&vault, audit_log, &opts);
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    data["manifest"]["kdf"] = json!(read_vault_kdf(&dir)?);
+    let salt = (0..32).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+    let key = derive_secure_key(&pwd, &salt)?;
+    // Zeroizing: plaintext vault azzerato dopo la cifratura
+    let plaintext = Zeroizing::new(serde_json::to_vec(&data).map_err(|e| e.to_string())?);
+    let encrypted = encrypt_data(&key, &plaintext)?;
+    let mut out = salt; out.extend(encrypted);
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file().set_file_name("LexFlow_Backup.lex").save_file(move |file_path| {
+        let _ = tx.send(file_path);
+    });
+    let path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    if let Some(p) = path {
+        fs::write(p.into_path().unwrap(), out).map_err(|e| e.to_string())?;
+        Ok(json!({"success": true}))
+    } else { Ok(json!({"success": false})) }
+}
+
+/// Plain JSON export for leaving LexFlow. The warning comes first in the file (serde_json
+/// sorts keys and "_" sorts before letters) so whoever opens it sees it is unencrypted.
+fn plaintext_export_document(vault: &Value, exported_at: &str) -> Value {
+    json!({"_warning": "UNENCRYPTED", "exportedAt": exported_at, "vault": vault})
+}
+
+#[tauri::command]
+async fn export_plaintext_json(pwd: String, app: AppHandle) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    // Wiped on every return path
+    let pwd = Zeroizing::new(pwd);
+    let pretty = {
+        let state = app.state::<AppState>();
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        // Same brute-force lockout as unlock/verify
+        if let Err(locked_json) = check_lockout(&state, &sec_dir, LockoutKind::Vault) {
+            return Ok(locked_json);
+        }
+        if authenticate_vault_password(&pwd, &dir).is_err() {
+            record_failed_attempt(&state, &sec_dir, LockoutKind::Vault);
+            return Ok(json!({"success": false, "error": "Password errata"}));
+        }
+        clear_lockout(&state, &sec_dir, LockoutKind::Vault);
+        let mut vault = read_vault_internal(&state)?;
+        reseal_secrets(&mut vault, Some(&get_vault_key(&state)?), None)?;
+        let doc = plaintext_export_document(&vault, &chrono::Local::now().to_rfc3339());
+        Zeroizing::new(serde_json::to_vec_pretty(&doc).map_err(|e| e.to_string())?)
+    };
+    drop(pwd);
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file()
+        .add_filter("JSON", &["json"])
+        .set_file_name(format!("LexFlow_Export_{}.json", chrono::Local::now().format("%Y%m%d_%H%M")))
+        .save_file(move |file_path| { let _ = tx.send(file_path); });
+    let Some(p) = rx.await.map_err(|e| format!("Dialog error: {}", e))? else {
+        return Ok(json!({"success": false}));
+    };
+    let path = p.into_path().map_err(|e| format!("Path error: {:?}", e))?;
+    secure_write(&path, &pretty).map_err(|e| e.to_string())?;
+    let _ = append_audit_log(&app.state::<AppState>(), AuditCategory::Exchange, None, "Export in chiaro");
+    Ok(json!({"success": true, "path": path.to_string_lossy()}))
+}
+
+// CAPACITY FIX (Gemini L4-3): increased from 50MB to 500MB to handle large
+// law firm vaults (many practices + attached document paths). OOM risk is
+// minimal: AES-GCM decryption is streaming-friendly and memory is freed immediately.
+const MAX_IMPORT_SIZE: usize = 500 * 1024 * 1024;
+// Backup layout: 32-byte salt || VAULT_MAGIC || nonce || ciphertext || AES-GCM tag
+const BACKUP_SALT_LEN: usize = 32;
+const BACKUP_MIN_LEN: usize = BACKUP_SALT_LEN + VAULT_MAGIC.len() + NONCE_LEN + 16;
+
+/// Password-less structural check of a backup, from its first bytes and total size.
+/// Schema version and manifest live inside the ciphertext, so they are reported as not
+/// readable — wrong password vs. newer version can only be told apart by import_vault.
+fn diagnose_backup_header(header: &[u8], size: u64) -> Value {
+    let magic_ok = header.len() >= BACKUP_SALT_LEN && vault_header_version(&header[BACKUP_SALT_LEN..]).is_some();
+    let (status, message) = if size > MAX_IMPORT_SIZE as u64 {
+        ("too-large", "File troppo grande (max 500MB).")
+    } else if magic_ok && size < BACKUP_MIN_LEN as u64 {
+        ("truncated", "Intestazione corretta ma file incompleto: copia o download interrotto.")
+    } else if magic_ok {
+        ("ok", "Struttura valida. Se l'importazione fallisce, la password è errata, il file è stato alterato o proviene da una versione più recente di LexFlow.")
+    } else if vault_header_version(header).is_some() {
+        ("vault-file", "È il file interno del vault (vault.lex), non un backup: usare Esporta backup.")
+    } else if header.first() == Some(&b'{') || header.first() == Some(&b'[') {
+        ("plaintext-json", "File JSON non cifrato: non è un backup LexFlow.")
+    } else if (size as usize) < BACKUP_SALT_LEN + VAULT_MAGIC.len() {
+        ("truncated", "File troppo corto per essere un backup LexFlow.")
+    } else {
+        ("wrong-format", "Formato non riconosciuto: non è un backup LexFlow.")
+    };
+    json!({
+        "status": status,
+        "message": message,
+        "sizeBytes": size,
+        "minSizeBytes": BACKUP_MIN_LEN,
+        "saltPresent": size >= BACKUP_SALT_LEN as u64,
+        "magicPresent": magic_ok,
+        "lengthPlausible": size >= BACKUP_MIN_LEN as u64 && size <= MAX_IMPORT_SIZE as u64,
+        "schemaVersion": Value::Null,
+        "manifest": Value::Null,
+        "manifestReadable": false,
+    })
+}
+
+// ─── Support diagnostics bundle ─────────────────────────────────────────────
+// Operational log lines (everything printed through lf_log!) are kept in a bounded
+// in-memory ring so a support bundle can include what happened before the bug.
+// Nothing here touches the vault: no contents, keys or passwords, and every path or
+// username is replaced with a placeholder before the bundle leaves the process.
+const MAX_DIAG_LOG_LINES: usize = 500;
+static DIAG_LOG: Mutex<std::collections::VecDeque<String>> = Mutex::new(std::collections::VecDeque::new());
+
+fn record_diag_line(line: String) {
+    let mut log = DIAG_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if log.len() == MAX_DIAG_LOG_LINES { log.pop_front(); }
+    log.push_back(format!("{} {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), line));
+}
+
+fn recent_diag_lines() -> Vec<String> {
+    DIAG_LOG.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+fn support_diagnostics(data_dir: &std::path::Path, security_dir: &std::path::Path, unlocked: bool) -> Value {
+    #[cfg(not(target_os = "android"))]
+    let clone_suspected = current_machine_id_check() == MachineIdCheck::Mismatch;
+    #[cfg(target_os = "android")]
+    let clone_suspected = false;
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "vaultInitialized": data_dir.join(VAULT_SALT_FILE).exists(),
+        "vaultUnlocked": unlocked,
+        "bioEnabled": security_dir.join(BIO_MARKER_FILE).exists(),
+        "licensePresent": security_dir.join(LICENSE_FILE).exists(),
+        "licenseKey": public_key_status(&PUBLIC_KEY_BYTES),
+        "cloneSuspected": clone_suspected,
+        "focusModeActive": focus_until_ms(data_dir).is_some(),
+    })
+}
+
+fn storage_health(data_dir: &std::path::Path) -> Value {
+    let files: serde_json::Map<String, Value> = [
+        VAULT_FILE, VAULT_BACKUP_FILE, VAULT_SALT_FILE, VAULT_VERIFY_FILE, VAULT_KDF_FILE, SETTINGS_FILE,
+        AUDIT_LOG_FILE, NOTIF_SCHEDULE_FILE, NOTIF_HISTORY_FILE, VAULT_META_FILE,
+    ].iter().map(|name| {
+        let meta = fs::metadata(data_dir.join(name)).ok();
+        (name.to_string(), json!({"exists": meta.is_some(), "bytes": meta.map(|m| m.len())}))
+    }).collect();
+    let orphan_tmp = fs::read_dir(data_dir).map(|entries| entries.flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+        .count()).unwrap_or(0);
+    json!({
+        "freeBytes": available_disk_bytes(data_dir),
+        "files": files,
+        "orphanTmpFiles": orphan_tmp,
+        "auditRekeyPending": data_dir.join(AUDIT_REKEY_FILE).exists(),
+    })
+}
+
+/// Placeholders for machine-identifying text, longest needle first so a data dir under
+/// the home dir is reported as <DATA_DIR>, not ~/...
+fn diagnostic_redactions(data_dir: &std::path::Path, security_dir: &std::path::Path) -> Vec<(String, &'static str)> {
+    let mut r: Vec<(String, &'static str)> = vec![
+        (data_dir.to_string_lossy().into_owned(), "<DATA_DIR>"),
+        (security_dir.to_string_lossy().into_owned(), "<SECURITY_DIR>"),
+    ];
+    if let Some(home) = dirs::home_dir() { r.push((home.to_string_lossy().into_owned(), "~")); }
+    r.push((whoami::username(), "<USER>"));
+    r.retain(|(needle, _)| needle.len() >= 3);
+    r.sort_by_key(|(needle, _)| std::cmp::Reverse(needle.len()));
+    r
+}
+
+fn redact_diagnostic_value(value: &mut Value, redactions: &[(String, &'static str)]) {
+    match value {
+        Value::String(s) => {
+            for (needle, placeholder) in redactions {
+                if s.contains(needle.as_str()) { *s = s.replace(needle.as_str(), placeholder); }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| redact_diagnostic_value(v, redactions)),
+        Value::Object(map) => map.values_mut().for_each(|v| redact_diagnostic_value(v, redactions)),
+        _ => {}
+    }
+}
+
+fn build_diagnostics_bundle(
+    generated_at: &str,
+    support: Value,
+    storage: Value,
+    log: Option<Vec<String>>,
+    redactions: &[(String, &'static str)],
+) -> Value {
+    let mut bundle = json!({
+        "format": "lexflow-diagnostics",
+        "version": 1,
+        "generatedAt": generated_at,
+        "capabilities": capabilities(),
+        "support": support,
+        "storage": storage,
+        "log": log.map(Value::from).unwrap_or(Value::Null),
+    });
+    redact_diagnostic_value(&mut bundle, redactions);
+    bundle
+}
+
+#[tauri::command]
+fn get_support_diagnostics(state: State<AppState>) -> Value {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let unlocked = state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    let mut report = support_diagnostics(&dir, &sec_dir, unlocked);
+    report["storage"] = storage_health(&dir);
+    redact_diagnostic_value(&mut report, &diagnostic_redactions(&dir, &sec_dir));
+    report
+}
+
+// The exported bundle is sealed like a snapshot — DIAGNOSTICS_MAGIC || salt || AES-GCM under
+// Argon2id(passphrase) — since redaction cannot catch every identifying log line. The user
+// shares the passphrase with support separately; read_diagnostics_bundle opens it.
+const DIAGNOSTICS_MAGIC: &[u8] = b"LEXDIAG";
+
+fn seal_diagnostics_bundle(bundle: &Value, pwd: &str) -> Result<Vec<u8>, String> {
+    let salt = (0..BACKUP_SALT_LEN).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+    let key = Zeroizing::new(derive_secure_key(pwd, &salt)?);
+    let plaintext = Zeroizing::new(serde_json::to_vec(bundle).map_err(|e| e.to_string())?);
+    let mut out = DIAGNOSTICS_MAGIC.to_vec();
+    out.extend(salt);
+    out.extend(encrypt_data(&key, &plaintext)?);
+    Ok(out)
+}
+
+fn open_diagnostics_bundle(raw: &[u8], pwd: &str) -> Result<Value, String> {
+    let body = raw.strip_prefix(DIAGNOSTICS_MAGIC)
+        .filter(|b| b.len() > BACKUP_SALT_LEN)
+        .ok_or("File non è un pacchetto diagnostico LexFlow")?;
+    let key = Zeroizing::new(derive_secure_key(pwd, &body[..BACKUP_SALT_LEN])?);
+    let plaintext = Zeroizing::new(decrypt_data(&key, &body[BACKUP_SALT_LEN..]).map_err(|_| "Password errata o file corrotto")?);
+    serde_json::from_slice(&plaintext).map_err(|_| "Pacchetto diagnostico non valido".to_string())
+}
+
+/// Saves a redacted support bundle (diagnostics, capabilities, storage health and,
+/// with `include_log`, the recent log lines) sealed with `pwd`, and returns its path.
+#[tauri::command]
+async fn export_diagnostics(app: AppHandle, pwd: String, include_log: bool) -> Result<String, String> {
+    use tauri_plugin_dialog::DialogExt;
+    if let Some(err) = password_policy_error(&pwd) {
+        zeroize_password(pwd);
+        return Err(err.into());
+    }
+    let bundle = {
+        let state = app.state::<AppState>();
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let unlocked = state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+        build_diagnostics_bundle(
+            &chrono::Local::now().to_rfc3339(),
+            support_diagnostics(&dir, &sec_dir, unlocked),
+            storage_health(&dir),
+            include_log.then(recent_diag_lines),
+            &diagnostic_redactions(&dir, &sec_dir),
+        )
+    };
+    let sealed = seal_diagnostics_bundle(&bundle, &pwd);
+    zeroize_password(pwd);
+    let sealed = sealed?;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file()
+        .add_filter("LexFlow Diagnostica", &["lexdiag"])
+        .set_file_name(format!("LexFlow_Diagnostica_{}.lexdiag", chrono::Local::now().format("%Y%m%d_%H%M")))
+        .save_file(move |file_path| { let _ = tx.send(file_path); });
+    let p = rx.await.map_err(|e| format!("Dialog error: {}", e))?
+        .ok_or("Esportazione annullata")?;
+    let path = p.into_path().map_err(|e| format!("Path error: {:?}", e))?;
+    secure_write(&path, &sealed).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Open a bundle written by export_diagnostics, for support.
+#[tauri::command]
+fn read_diagnostics_bundle(path: String, pwd: String) -> Result<Value, String> {
+    let result = fs::read(&path).map_err(|e| format!("File non leggibile: {}", e))
+        .and_then(|raw| open_diagnostics_bundle(&raw, &pwd));
+    zeroize_password(pwd);
+    result
+}
+
+/// Support diagnostic for a backup that will not import. Reads only the size and the
+/// unencrypted header — never the password, never any plaintext.
+#[tauri::command]
+fn diagnose_backup(path: String) -> Result<Value, String> {
+    use std::io::Read;
+    let path = PathBuf::from(path);
+    let size = fs::metadata(&path).map_err(|e| format!("File non leggibile: {}", e))?.len();
+    let mut header = Vec::with_capacity(BACKUP_SALT_LEN + VAULT_MAGIC.len());
+    fs::File::open(&path).map_err(|e| format!("File non leggibile: {}", e))?
+        .take((BACKUP_SALT_LEN + VAULT_MAGIC.len()) as u64)
+        .read_to_end(&mut header)
+        .map_err(|e| e.to_string())?;
+    Ok(diagnose_backup_header(&header, size))
+}
+
+/// Argon2 params the source vault used, from a backup or archive manifest. Older files
+/// predate vault.kdf and restore with the defaults.
+fn manifest_kdf_params(manifest: &Value) -> Result<KdfParams, String> {
+    match manifest.get("kdf") {
+        Some(kdf) => serde_json::from_value::<KdfParams>(kdf.clone())
+            .map_err(|_| "Parametri KDF del backup non validi".to_string())?
+            .validate(),
+        None => Ok(DEFAULT_KDF_PARAMS),
+    }
+}
+
+/// Stage the key material of a restored vault in `dir` — a fresh salt, the kdf params and
+/// the verify tag — and return the new key. Refuses before touching anything while a
+/// read-only snapshot is open: a restore must never land under an analyst's session.
+fn stage_restored_key_files(dir: &std::path::Path, snapshot: Option<&Value>, pwd: &str, kdf: &KdfParams) -> Result<Vec<u8>, String> {
+    snapshot_write_guard(snapshot)?;
+    // Generate new vault salt for the imported vault
+    let mut new_salt = vec![0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut new_salt);
+    let new_key = derive_key_with_params(pwd, &new_salt, kdf)?;
+    stage_key_files(dir, &new_salt, kdf, &make_verify_tag(&new_key))?;
+    Ok(new_key)
+}
+
+/// Replace the vault with `val`, re-keyed under `pwd` with a fresh salt and the source
+/// vault's Argon2 params, and restore the audit log beside it when one was carried.
+/// Same sequence as change_password: key files staged, vault.lex written under the new
+/// key, then the staged files committed. Used by import_vault and import_archive.
+fn restore_vault_files(state: &State<AppState>, pwd: &str, val: &Value, kdf: &KdfParams, imported_audit: Option<Value>) -> Result<(), String> {
+    let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let new_key = {
+        let snapshot = state.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        stage_restored_key_files(&dir, snapshot.as_ref(), pwd, kdf)?
+    };
+    let mut val = val.clone();
+    strip_backup_envelope(&mut val);
+    reseal_secrets(&mut val, None, Some(&new_key))?;
+    // This device's log, readable only under the outgoing key; merged into the restored one
+    let local_audit = read_audit_entries(state).unwrap_or_default();
+    // Safety copy, which also keeps the startup sweep off the staged files
+    let vault_path = dir.join(VAULT_FILE);
+    let vault_backup = dir.join(VAULT_BACKUP_FILE);
+    if vault_path.exists() {
+        let _ = fs::copy(&vault_path, &vault_backup);
+    }
+    // Queued saves are edits of the vault being replaced: a later flush would overwrite
+    // the restored sections with them. They come back only if the restore fails.
+    let superseded = state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).take();
+    // write_vault_internal writes under the key in state; the old one comes back on failure
+    let previous_key = state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).replace(SecureKey(new_key));
+    if let Err(e) = write_vault_internal(state, &val) {
+        *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = previous_key;
+        state.pending_saves.lock().unwrap_or_else(|e| e.into_inner()).restore(superseded);
+        discard_staged_key_files(&dir);
+        let _ = fs::remove_file(&vault_backup);
+        return Err(e);
+    }
+    commit_staged_key_files(&dir)?;
+    let _ = fs::remove_file(&vault_backup);
+    if imported_audit.is_some() || !local_audit.is_empty() {
+        if let Ok(key) = get_vault_key(state) {
+            let merged = merge_audit_logs(imported_audit, local_audit, &key);
+            let plaintext = Zeroizing::new(serde_json::to_vec(&merged).unwrap_or_default());
+            let _audit = state.audit_mutex.lock().unwrap_or_else(|e| e.into_inner());
+            if let Ok(enc) = encrypt_data(&key, &plaintext) {
+                if atomic_write_with_sync(&dir.join(AUDIT_LOG_FILE), &enc).is_ok() {
+                    // The restored log is in the current key — nothing left to re-key
+                    let _ = fs::remove_file(dir.join(AUDIT_REKEY_FILE));
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
-        // Recupera la password salvata dal keyring (non la ritorniamo al JS)
-        let user = whoami::username();
-        let saved_pwd = keyring::Entry::new(BIO_SERVICE, &user)
-            .and_then(|e| e.get_password()).map_err(|e| e.to_string())?;
+/// Audit log after a restore: the imported entries and this device's, in time order without
+/// duplicates (an archive of this same vault repeats them). Neither side's links verify
+/// under the restored key, so the merged log is chained afresh from its first entry; the
+/// import entry appended afterwards marks where the restore happened.
+fn merge_audit_logs(imported: Option<Value>, local: Vec<Value>, key: &[u8]) -> Vec<Value> {
+    let mut seen = std::collections::HashSet::new();
+    let mut entries: Vec<Value> = imported.and_then(|a| a.as_array().cloned()).unwrap_or_default()
+        .into_iter()
+        .chain(local)
+        .filter_map(|mut e| {
+            if let Some(obj) = e.as_object_mut() { obj.remove("prevHash"); }
+            seen.insert(serde_json::to_vec(&e).unwrap_or_default()).then_some(e)
+        })
+        .collect();
+    // Stable: entries without a parseable time keep their relative order at the front
+    entries.sort_by_key(|e| e.get("time").and_then(|t| t.as_str())
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.timestamp_millis())
+        .unwrap_or(i64::MIN));
+    let skip = entries.len().saturating_sub(10000);
+    let mut chained = Vec::with_capacity(entries.len() - skip);
+    for e in entries.into_iter().skip(skip) {
+        push_chained_audit_entry(&mut chained, key, e);
+    }
+    chained
+}
+
+/// Everything import_vault does before touching the disk: size and header checks, key
+/// derivation, decryption, structure validation and schema migration. Pure, so
+/// simulate_restore can run exactly the same path.
+fn open_backup(raw: &[u8], pwd: &str) -> Result<Value, String> {
+    if raw.len() > MAX_IMPORT_SIZE {
+        return Err("File troppo grande (max 500MB)".into());
+    }
+    // Validazione struttura minima: 32 byte salt + VAULT_MAGIC + nonce (12) + tag AES (16)
+    if raw.len() < BACKUP_MIN_LEN {
+        return Err("File non valido o corrotto (dimensione insufficiente)".into());
+    }
+    // Verifica magic nel blocco cifrato (dopo i 32 byte di salt)
+    if vault_header_version(&raw[BACKUP_SALT_LEN..]).is_none() {
+        return Err("File non è un backup LexFlow valido".into());
+    }
+    let key = Zeroizing::new(derive_secure_key(pwd, &raw[..BACKUP_SALT_LEN])?);
+    let decrypted = Zeroizing::new(decrypt_data(&key, &raw[BACKUP_SALT_LEN..]).map_err(|_| "Password errata o file corrotto")?);
+    let val: Value = serde_json::from_slice(&decrypted).map_err(|_| "Struttura backup non valida")?;
+    // Validazione struttura dati vault
+    // A sections-filtered export may contain e.g. only contacts — any known section will do
+    if !VAULT_SECTIONS.iter().any(|s| val.get(*s).is_some()) {
+        return Err("Il file non contiene dati LexFlow validi".into());
+    }
+    // Schema check BEFORE touching the current vault: newer → refuse, older → migrate
+    if let Some(e) = vault_min_version_error(&val, env!("CARGO_PKG_VERSION")) {
+        return Err(e);
+    }
+    let val = migrate_backup_schema(val)?;
+    manifest_kdf_params(val.get("manifest").unwrap_or(&Value::Null))?;
+    Ok(val)
+}
+
+/// Dry run of a restore: reports whether import_vault would accept the file and what it
+/// contains, without writing anything or touching the current vault.
+fn simulate_restore_bytes(raw: &[u8], pwd: &str) -> Value {
+    match open_backup(raw, pwd) {
+        Ok(val) => {
+            let counts: serde_json::Map<String, Value> = VAULT_SECTIONS.iter()
+                .filter_map(|s| val.get(*s).and_then(|v| v.as_array()).map(|a| (s.to_string(), json!(a.len()))))
+                .collect();
+            json!({
+                "success": true,
+                "schemaVersion": val.get("schemaVersion").cloned().unwrap_or(Value::Null),
+                "partial": is_partial_backup(&val),
+                "counts": counts,
+                "secrets": val.get("secrets").and_then(|s| s.as_array()).map(|a| a.len()).unwrap_or(0),
+                "includesAudit": val.get("auditLog").is_some(),
+            })
+        }
+        Err(e) => json!({"success": false, "error": e}),
+    }
+}
+
+#[tauri::command]
+fn simulate_restore(path: String, pwd: String) -> Value {
+    let result = match fs::read(&path) {
+        Ok(raw) => simulate_restore_bytes(&raw, &pwd),
+        Err(e) => json!({"success": false, "error": format!("File non leggibile: {}", e)}),
+    };
+    zeroize_password(pwd);
+    result
+}
+
+// ─── Read-only snapshots ────────────────────────────────────────────────────
+// A copy of the vault for analysts: same envelope as a backup (salt || AES-GCM, key =
+// Argon2id(snapshot password)), manifest.readOnly = true, no audit log and no practice
+// secrets. open_snapshot locks the live vault and serves reads from the decrypted copy;
+// write_vault_internal and queue_save refuse while it is open, and import_vault refuses
+// the file, so a snapshot can never replace the real vault. Locking ends the session.
+const SNAPSHOT_READ_ONLY_ERR: &str = "Snapshot in sola lettura: modifiche non consentite";
+
+fn snapshot_write_guard(snapshot: Option<&Value>) -> Result<(), String> {
+    match snapshot {
+        Some(_) => Err(SNAPSHOT_READ_ONLY_ERR.into()),
+        None => Ok(()),
+    }
+}
+
+fn is_readonly_snapshot(val: &Value) -> bool {
+    val.pointer("/manifest/readOnly").and_then(|r| r.as_bool()).unwrap_or(false)
+}
+
+fn seal_readonly_snapshot(vault: &Value, pwd: &str) -> Result<Vec<u8>, String> {
+    let opts = ExportOptions {
+        include_attachments: true,
+        include_audit: false,
+        sections: VAULT_SECTIONS.iter().map(|s| s.to_string()).collect(),
+    };
+    let mut data = build_export_payload(vault, None, &opts);
+    if let Some(obj) = data.as_object_mut() {
+        obj.remove("secrets");
+        obj.remove("documentKeys");
+    }
+    data["manifest"]["readOnly"] = json!(true);
+    let salt = (0..BACKUP_SALT_LEN).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+    let key = Zeroizing::new(derive_secure_key(pwd, &salt)?);
+    let plaintext = Zeroizing::new(serde_json::to_vec(&data).map_err(|e| e.to_string())?);
+    let mut out = salt;
+    out.extend(encrypt_data(&key, &plaintext)?);
+    Ok(out)
+}
+
+fn open_snapshot_bytes(raw: &[u8], pwd: &str) -> Result<Value, String> {
+    let val = open_backup(raw, pwd)?;
+    if !is_readonly_snapshot(&val) {
+        return Err("Il file non è uno snapshot in sola lettura".into());
+    }
+    Ok(val)
+}
+
+/// Write a read-only snapshot of the unlocked vault, sealed with `pwd` (its own password,
+/// policy-checked, so it can be handed to an analyst). Returns the written path.
+#[tauri::command]
+async fn export_readonly_snapshot(state: State<'_, AppState>, pwd: String, app: AppHandle) -> Result<String, String> {
+    use tauri_plugin_dialog::DialogExt;
+    if let Some(err) = password_policy_error(&pwd) {
+        zeroize_password(pwd);
+        return Err(err.into());
+    }
+    if state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+        zeroize_password(pwd);
+        return Err(SNAPSHOT_READ_ONLY_ERR.into());
+    }
+    let vault = read_vault_internal(&state)?;
+    let sealed = seal_readonly_snapshot(&vault, &pwd);
+    zeroize_password(pwd);
+    let sealed = sealed?;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file()
+        .add_filter("LexFlow Snapshot", &["lex"])
+        .set_file_name(format!("LexFlow_Snapshot_{}.lex", chrono::Local::now().format("%Y%m%d_%H%M")))
+        .save_file(move |file_path| { let _ = tx.send(file_path); });
+    let p = rx.await.map_err(|e| format!("Dialog error: {}", e))?.ok_or("Esportazione annullata")?;
+    let path = p.into_path().map_err(|e| format!("Path error: {:?}", e))?;
+    secure_write(&path, &sealed).map_err(|e| e.to_string())?;
+    let _ = append_audit_log(&state, AuditCategory::Exchange, None, "Snapshot in sola lettura esportato");
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Open a snapshot as a read-only session. The live vault is locked first.
+#[tauri::command]
+fn open_snapshot(state: State<AppState>, path: String, pwd: String) -> Result<Value, String> {
+    let raw = fs::read(&path).map_err(|e| format!("File non leggibile: {}", e));
+    let opened = raw.and_then(|raw| open_snapshot_bytes(&raw, &pwd));
+    zeroize_password(pwd);
+    let mut snapshot = opened?;
+    if let Some(obj) = snapshot.as_object_mut() { obj.remove("auditLog"); }
+    lock_vault_state(&state);
+    let manifest = snapshot.get("manifest").cloned().unwrap_or(Value::Null);
+    *state.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = Some(snapshot);
+    Ok(json!({"success": true, "readOnly": true, "createdAt": manifest.get("createdAt")}))
+}
+
+/// Which password the restored vault is keyed with: the one that opened the backup
+/// ("backup", the default) or a separately entered one ("entered", policy-checked).
+fn import_password_source(keep_backup_password: bool, new_password: Option<&str>) -> Result<&'static str, String> {
+    if keep_backup_password { return Ok("backup"); }
+    let entered = new_password.ok_or("Inserisci la nuova password del vault")?;
+    if let Some(err) = password_policy_error(entered) {
+        return Err(err.into());
+    }
+    Ok("entered")
+}
+
+#[tauri::command]
+async fn import_vault(
+    state: State<'_, AppState>,
+    pwd: String,
+    app: AppHandle,
+    keep_backup_password: Option<bool>,
+    new_password: Option<String>,
+) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    // Both wiped on every return, including the early rejections
+    let pwd = Zeroizing::new(pwd);
+    let new_password = new_password.map(Zeroizing::new);
+    let password_source = match import_password_source(keep_backup_password.unwrap_or(true), new_password.as_deref().map(String::as_str)) {
+        Ok(source) => source,
+        Err(e) => return Ok(json!({"success": false, "error": e})),
+    };
+    if let Err(e) = snapshot_write_guard(state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).as_ref()) {
+        return Ok(json!({"success": false, "error": e}));
+    }
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("LexFlow Backup", &["lex"])
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+    let path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    if let Some(p) = path {
+        let raw = fs::read(p.into_path().unwrap()).map_err(|e| e.to_string())?;
+        let mut val = open_backup(&raw, &pwd)?;
+        if is_readonly_snapshot(&val) {
+            return Ok(json!({"success": false, "error": "Questo file è uno snapshot in sola lettura: aprilo con \"Apri snapshot\""}));
+        }
+        // Partial backup: sections omitted from the export must survive the import, which
+        // requires reading the current vault — only possible while it is unlocked.
+        if is_partial_backup(&val) {
+            let has_vault = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(VAULT_FILE).exists();
+            if has_vault {
+                let mut current = read_vault_internal(&state)
+                    .map_err(|_| "Backup parziale: sblocca il vault prima di importarlo")?;
+                // Kept secrets are resealed under the new key by restore_vault_files
+                reseal_secrets(&mut current, Some(&get_vault_key(&state)?), None)?;
+                val = merge_partial_backup(&current, val);
+            }
+        } else if let Ok(current) = read_vault_internal(&state) {
+            // Document blobs stay on disk across a restore: keep the keys of blobs attached
+            // after the backup was taken, or they could never be opened again.
+            carry_document_keys(&current, &mut val);
+        }
+        // The audit log travels beside the data, never inside vault.lex
+        let imported_audit = val.as_object_mut().and_then(|o| o.remove("auditLog"));
+        // SECURITY FIX (Level-8 C2): import must work even if the vault is currently locked
+        // (e.g. first-run or forgotten password scenario).  Previously write_vault_internal
+        // required vault_key to already be set, causing a Catch-22: you can't unlock a lost
+        // vault, but you can't import a backup either.
+        //
+        // Fix: derive a new vault key from a fresh salt, write all vault files
+        // (salt, verify, vault.lex), then set vault_key. The master password is the one
+        // that decrypted the backup, unless the caller entered a different one.
+        // SECURITY FIX (Gemini Audit): acquire write_mutex to prevent concurrent vault writes.
+        let kdf = manifest_kdf_params(val.get("manifest").unwrap_or(&Value::Null))?;
+        let master = match (password_source, new_password.as_deref()) {
+            ("entered", Some(entered)) => entered.as_str(),
+            _ => pwd.as_str(),
+        };
+        restore_vault_files(&state, master, &val, &kdf, imported_audit)?;
+        let _ = append_audit_log(&state, AuditCategory::Exchange, None, "Vault importato da backup");
+        Ok(json!({"success": true, "passwordSource": password_source}))
+    } else { Ok(json!({"success": false, "cancelled": true})) }
+}
+
+// ─── .lexarchive ───────────────────────────────────────────────────────────
+// Full portable export: vault, audit log and settings in one password-encrypted file.
+// Layout: ARCHIVE_MAGIC || format version (u16 BE) || 32-byte salt || encrypt_data(payload).
+// The payload carries a manifest {formatVersion, schemaVersion, appVersion, exportedAt,
+// fingerprint}; the version is duplicated in clear so a newer archive is refused without
+// asking for the password. Device-bound material (local key, machine id, license,
+// lockout counters) lives in the security dir and is never included. Neither are files:
+// the encrypted document blobs (<data>/blobs) and the external files that attachments[]
+// point at by path stay where they are. The vault's documentKeys do travel, so copying
+// the blobs folder next to the restored vault makes the documents open again. On import
+// the archived audit log is merged with this device's (merge_audit_logs).
+const ARCHIVE_MAGIC: &[u8] = b"LEXARCHIVE";
+const ARCHIVE_FORMAT_VERSION: u16 = 1;
+const ARCHIVE_HEADER_LEN: usize = ARCHIVE_MAGIC.len() + 2;
+
+/// SHA-256 over the archived content, checked again after decryption on import.
+fn archive_fingerprint(vault: &Value, settings: &Value, audit_log: &Value) -> String {
+    let mut h = <Sha256 as Digest>::new();
+    for part in [vault, settings, audit_log] {
+        let bytes = serde_json::to_vec(part).unwrap_or_default();
+        h.update((bytes.len() as u64).to_be_bytes());
+        h.update(&bytes);
+    }
+    hex::encode(h.finalize())
+}
+
+fn build_archive_payload(vault: &Value, settings: &Value, audit_log: Option<Value>) -> Value {
+    let audit_log = audit_log.unwrap_or(Value::Null);
+    json!({
+        "manifest": {
+            "formatVersion": ARCHIVE_FORMAT_VERSION,
+            "schemaVersion": VAULT_SCHEMA_VERSION,
+            "appVersion": env!("CARGO_PKG_VERSION"),
+            "exportedAt": chrono::Local::now().to_rfc3339(),
+            "fingerprint": archive_fingerprint(vault, settings, &audit_log),
+        },
+        "vault": vault,
+        "settings": settings,
+        "auditLog": audit_log,
+    })
+}
+
+fn seal_archive(pwd: &str, payload: &Value) -> Result<Vec<u8>, String> {
+    let mut salt = vec![0u8; BACKUP_SALT_LEN];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut salt);
+    let key = derive_secure_key(pwd, &salt)?;
+    let plaintext = Zeroizing::new(serde_json::to_vec(payload).map_err(|e| e.to_string())?);
+    let mut out = ARCHIVE_MAGIC.to_vec();
+    out.extend(ARCHIVE_FORMAT_VERSION.to_be_bytes());
+    out.extend(salt);
+    out.extend(encrypt_data(&key, &plaintext)?);
+    Ok(out)
+}
+
+/// Decrypt and validate an archive. Newer format or schema → refused, content that does
+/// not match the manifest fingerprint → refused, older vault schema → migrated.
+fn open_archive(raw: &[u8], pwd: &str) -> Result<Value, String> {
+    if raw.len() > MAX_IMPORT_SIZE {
+        return Err("File troppo grande (max 500MB)".into());
+    }
+    if !raw.starts_with(ARCHIVE_MAGIC) || raw.len() < ARCHIVE_HEADER_LEN {
+        return Err("File non è un archivio LexFlow valido".into());
+    }
+    let version = u16::from_be_bytes([raw[ARCHIVE_MAGIC.len()], raw[ARCHIVE_MAGIC.len() + 1]]);
+    if version > ARCHIVE_FORMAT_VERSION {
+        return Err(format!(
+            "Archivio creato con una versione più recente di LexFlow (formato {} — questa app supporta fino a {}). Aggiorna LexFlow prima di importarlo.",
+            version, ARCHIVE_FORMAT_VERSION
+        ));
+    }
+    let body = &raw[ARCHIVE_HEADER_LEN..];
+    if body.len() < BACKUP_MIN_LEN {
+        return Err("File non valido o corrotto (dimensione insufficiente)".into());
+    }
+    let key = derive_secure_key(pwd, &body[..BACKUP_SALT_LEN])?;
+    let decrypted = Zeroizing::new(decrypt_data(&key, &body[BACKUP_SALT_LEN..]).map_err(|_| "Password errata o file corrotto")?);
+    let mut payload: Value = serde_json::from_slice(&decrypted).map_err(|_| "Struttura archivio non valida")?;
+    let manifest = payload.get("manifest").cloned().ok_or("Archivio senza manifest")?;
+    let manifest_version = manifest.get("formatVersion").and_then(|v| v.as_u64()).unwrap_or(0);
+    if manifest_version == 0 || manifest_version > ARCHIVE_FORMAT_VERSION as u64 {
+        return Err(format!(
+            "Versione manifest non supportata ({} — questa app supporta fino a {}). Aggiorna LexFlow prima di importarlo.",
+            manifest_version, ARCHIVE_FORMAT_VERSION
+        ));
+    }
+    let expected = manifest.get("fingerprint").and_then(|f| f.as_str()).unwrap_or("");
+    let actual = archive_fingerprint(
+        payload.get("vault").unwrap_or(&Value::Null),
+        payload.get("settings").unwrap_or(&Value::Null),
+        payload.get("auditLog").unwrap_or(&Value::Null),
+    );
+    if expected != actual {
+        return Err("Archivio alterato: il contenuto non corrisponde all'impronta del manifest".into());
+    }
+    let vault = payload.get_mut("vault").map(Value::take).unwrap_or(Value::Null);
+    if !VAULT_SECTIONS.iter().any(|s| vault.get(*s).is_some()) {
+        return Err("L'archivio non contiene dati LexFlow validi".into());
+    }
+    if let Some(e) = vault_min_version_error(&vault, env!("CARGO_PKG_VERSION")) {
+        return Err(e);
+    }
+    payload["vault"] = migrate_backup_schema(vault)?;
+    Ok(payload)
+}
+
+#[tauri::command]
+async fn export_archive(state: State<'_, AppState>, pwd: String, app: AppHandle) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    // Wiped on every return, including the mismatch and `?` paths
+    let pwd = Zeroizing::new(pwd);
+    if !backup_password_matches(&state, &pwd)? {
+        return Ok(json!({"success": false, "error": "Password errata: l'archivio non può essere creato con una password diversa da quella del vault."}));
+    }
+    let mut vault = read_vault_internal(&state)?;
+    reseal_secrets(&mut vault, Some(&get_vault_key(&state)?), None)?;
+    let audit_log = get_audit_log(state.clone()).ok();
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut payload = build_archive_payload(&vault, &read_settings(&dir), audit_log);
+    payload["manifest"]["kdf"] = json!(read_vault_kdf(&dir)?);
+    let out = seal_archive(&pwd, &payload)?;
+    drop(pwd);
+
+    let file_name = format!("LexFlow_{}.lexarchive", chrono::Local::now().format("%Y%m%d_%H%M"));
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file().set_file_name(&file_name).save_file(move |file_path| {
+        let _ = tx.send(file_path);
+    });
+    let path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    if let Some(p) = path {
+        fs::write(p.into_path().unwrap(), out).map_err(|e| e.to_string())?;
+        Ok(json!({"success": true, "manifest": payload["manifest"]}))
+    } else { Ok(json!({"success": false})) }
+}
+
+#[tauri::command]
+async fn import_archive(state: State<'_, AppState>, pwd: String, app: AppHandle) -> Result<Value, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let pwd = Zeroizing::new(pwd);
+    if let Err(e) = snapshot_write_guard(state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).as_ref()) {
+        return Ok(json!({"success": false, "error": e}));
+    }
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("LexFlow Archive", &["lexarchive"])
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+    let path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    let Some(p) = path else { return Ok(json!({"success": false, "cancelled": true})); };
+    let raw = fs::read(p.into_path().unwrap()).map_err(|e| e.to_string())?;
+    let mut payload = open_archive(&raw, &pwd)?;
+    let audit = payload.get_mut("auditLog").map(Value::take).filter(|a| !a.is_null());
+    let kdf = manifest_kdf_params(&payload["manifest"])?;
+    restore_vault_files(&state, &pwd, &payload["vault"], &kdf, audit)?;
+    // Archived settings win; keys only present on this device are kept
+    if let Some(archived) = payload.get("settings").and_then(|s| s.as_object()) {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut merged = read_settings(&dir);
+        if !merged.is_object() { merged = json!({}); }
+        for (k, v) in archived { merged[k] = v.clone(); }
+        write_settings(&dir, &merged);
+    }
+    let _ = append_audit_log(&state, AuditCategory::Exchange, None, "Vault importato da archivio");
+    Ok(json!({"success": true, "manifest": payload["manifest"]}))
+}
+
+// ═══════════════════════════════════════════════════════════
+//  SYSTEM UTILITIES
+// ═══════════════════════════════════════════════════════════
+
+#[tauri::command]
+fn open_path(app: AppHandle, path: String) {
+    #[cfg(not(target_os = "android"))]
+    {
+        // SECURITY FIX (Gemini Audit v2): sanitize path to prevent RCE.
+        // Only allow opening paths that exist as files/directories on the local filesystem.
+        let p = std::path::Path::new(&path);
+        if !p.exists() || !p.is_absolute() {
+            lf_log!("[LexFlow] SECURITY: open_path refused non-existent/relative path: {:?}", path);
+            return;
+        }
+        // Block URLs, scripts, and executables
+        let lower = path.to_lowercase();
+        if lower.starts_with("http") || lower.starts_with("smb:") || lower.starts_with("ftp:") ||
+           lower.ends_with(".sh") || lower.ends_with(".bat") || lower.ends_with(".cmd") ||
+           lower.ends_with(".exe") || lower.ends_with(".ps1") || lower.ends_with(".scpt") ||
+           lower.ends_with(".app") || lower.ends_with(".command") {
+            lf_log!("[LexFlow] SECURITY: open_path refused potentially dangerous path: {:?}", path);
+            return;
+        }
+        use tauri_plugin_shell::ShellExt;
+        if let Err(e) = app.shell().open(&path, None) {
+            lf_log!("[LexFlow] Failed to open path: {:?}", e);
+        }
+    }
+    #[cfg(target_os = "android")]
+    { let _ = (app, path); }
+}
+
+#[tauri::command]
+async fn select_file(app: AppHandle) -> Result<Option<Value>, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("Documenti", &["pdf", "docx", "doc"])
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+    let file = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    Ok(file.map(|f| {
+        let path = f.clone().into_path().unwrap();
+        let name = path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "file".to_string());
+        json!({"name": name, "path": path.to_string_lossy()})
+    }))
+}
+
+#[tauri::command]
+async fn select_folder(app: AppHandle) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    // Android non supporta pick_folder — fallback a pick_file
+    #[cfg(not(target_os = "android"))]
+    app.dialog()
+        .file()
+        .pick_folder(move |folder_path| {
+            let _ = tx.send(folder_path);
+        });
+    #[cfg(target_os = "android")]
+    app.dialog()
+        .file()
+        .pick_file(move |folder_path| {
+            let _ = tx.send(folder_path);
+        });
+    let folder = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    Ok(folder.map(|f| f.into_path().unwrap().to_string_lossy().to_string()))
+}
+
+#[tauri::command]
+fn window_close(app: AppHandle, state: State<AppState>) {
+    #[cfg(not(target_os = "android"))]
+    {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let setting = read_settings(&dir).get("closeBehavior").and_then(|v| v.as_str()).map(str::to_string);
+        if resolve_close_behavior(setting.as_deref(), std::env::consts::OS) == CloseAction::Quit {
+            return lock_and_exit(&app);
+        }
+    }
+    let _ = flush_pending_saves(&state);
+    *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    #[cfg(not(target_os = "android"))]
+    if let Some(w) = app.get_webview_window("main") { let _ = w.hide(); }
+    #[cfg(target_os = "android")]
+    { let _ = app; }
+}
+
+// ─── Blur / minimize policy ─────────────────────────────────────────────────
+// settings.autosaveOnBlur flushes queued saves when the window loses focus;
+// settings.lockOnMinimize locks the vault when it is minimized. Both act only after the
+// focus loss has lasted BLUR_DEBOUNCE_MS — every focus event bumps an epoch, and a delayed
+// check whose epoch is no longer current (a dialog, a quick alt-tab back) does nothing.
+const BLUR_DEBOUNCE_MS: u64 = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlurAction { None, Flush, Lock }
+
+fn decide_blur_action(lock_on_minimize: bool, autosave_on_blur: bool, blur_epoch: u64, current_epoch: u64, minimized: bool) -> BlurAction {
+    if blur_epoch != current_epoch { return BlurAction::None; }
+    if lock_on_minimize && minimized { return BlurAction::Lock; }
+    if autosave_on_blur { return BlurAction::Flush; }
+    BlurAction::None
+}
+
+// ─── Idle lock behavior ─────────────────────────────────────────────────────
+// settings.onIdleHideWindow: an inactivity lock also hides the window, so a passerby
+// does not even see the lock screen with the firm name. Desktop only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdleLockAction { Lock, LockAndHide }
+
+fn decide_idle_lock_action(settings: &Value, desktop: bool) -> IdleLockAction {
+    let hide = settings.get("onIdleHideWindow").and_then(|v| v.as_bool()).unwrap_or(false);
+    if hide && desktop { IdleLockAction::LockAndHide } else { IdleLockAction::Lock }
+}
+
+// ─── Autolock countdown ─────────────────────────────────────────────────────
+// Idle checks run every AUTOLOCK_IDLE_TICK_SECS until the final minute, then every second
+// so "lf-vault-warning" can carry a live {remainingSecs} countdown.
+const AUTOLOCK_IDLE_TICK_SECS: u64 = 30;
+const AUTOLOCK_WARNING_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AutolockTick { Idle, Warn(u64), Lock }
+
+fn autolock_tick(idle: Duration, threshold: Duration) -> AutolockTick {
+    if idle >= threshold { return AutolockTick::Lock; }
+    let remaining = threshold - idle;
+    if remaining <= Duration::from_secs(AUTOLOCK_WARNING_SECS) {
+        // Round up: "1 second left" until the lock actually happens
+        AutolockTick::Warn(remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0))
+    } else {
+        AutolockTick::Idle
+    }
+}
+
+/// How long to sleep before the next check: never past the start of the countdown.
+fn autolock_sleep(idle: Duration, threshold: Duration) -> Duration {
+    let until_warning = threshold.saturating_sub(idle).saturating_sub(Duration::from_secs(AUTOLOCK_WARNING_SECS));
+    if until_warning.is_zero() { return Duration::from_secs(1); }
+    until_warning.min(Duration::from_secs(AUTOLOCK_IDLE_TICK_SECS))
+}
+
+// ─── Autolock mode ──────────────────────────────────────────────────────────
+// settings.autolockMode "prompt": at the threshold the backend emits
+// "lf-vault-reauth-required" and keeps the key for AUTOLOCK_GRACE_SECS, so unsaved edits
+// survive a password prompt. A valid verify_vault_password cancels the lock; once the
+// grace expires the key is cleared as in "lock" mode (the default).
+const AUTOLOCK_GRACE_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AutolockMode { Lock, Prompt }
+
+fn autolock_mode(settings: &Value) -> AutolockMode {
+    match settings.get("autolockMode").and_then(|v| v.as_str()) {
+        Some("prompt") => AutolockMode::Prompt,
+        _ => AutolockMode::Lock,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AutolockAction { Wait, StartGrace(Instant), Lock }
+
+/// Decide one autolock pass. An open grace waits out its deadline and then locks; without
+/// one, reaching the threshold locks or, in "prompt" mode, opens the grace. `mode` is only
+/// read at the threshold.
+fn autolock_action(grace: Option<Instant>, tick: AutolockTick, now: Instant, mode: impl FnOnce() -> AutolockMode) -> AutolockAction {
+    match (grace, tick) {
+        (Some(deadline), _) if now < deadline => AutolockAction::Wait,
+        (Some(_), _) => AutolockAction::Lock,
+        (None, AutolockTick::Lock) if mode() == AutolockMode::Prompt =>
+            AutolockAction::StartGrace(now + Duration::from_secs(AUTOLOCK_GRACE_SECS)),
+        (None, AutolockTick::Lock) => AutolockAction::Lock,
+        (None, _) => AutolockAction::Wait,
+    }
+}
+
+/// Close an open re-auth grace and count the re-auth as activity; false when none was open.
+fn end_autolock_grace(grace: &Mutex<Option<Instant>>, last_activity: &Mutex<Instant>) -> bool {
+    if grace.lock().unwrap_or_else(|e| e.into_inner()).take().is_none() {
+        return false;
+    }
+    *last_activity.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    true
+}
+
+/// Clear the key after inactivity and tell the frontend; hides the window if configured.
+fn autolock_lock_now(ah: &AppHandle, state: &State<AppState>) {
+    let _ = flush_pending_saves(state);
+    *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    *state.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    let _ = ah.emit("lf-vault-locked", ());
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if decide_idle_lock_action(&read_settings(&dir), IS_DESKTOP) == IdleLockAction::LockAndHide {
+        if let Some(w) = ah.get_webview_window("main") { let _ = w.hide(); }
+    }
+}
 
-        // Esegui internamente lo sblocco del vault esattamente come unlock_vault
-    let dir = _state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-    let sec_dir = _state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-        let salt_path = dir.join(VAULT_SALT_FILE);
-        if !salt_path.exists() { return Ok(json!({"success": false, "error": "Vault non inizializzato"})); }
-        let salt = fs::read(&salt_path).unwrap_or_default();
-        match derive_secure_key(&saved_pwd, &salt) {
-            Ok(k) => {
-                // SECURITY FIX: verify the derived key against vault.verify BEFORE accepting.
-                // If the user changed their password after saving biometrics, the old keyring
-                // password would derive a wrong key. Without this check, the vault would appear
-                // "unlocked" but all data reads would fail with AES decryption errors.
-                let verify_path = dir.join(VAULT_VERIFY_FILE);
-                let stored = fs::read(&verify_path).unwrap_or_default();
-                if !stored.is_empty() && !verify_hash_matches(&k, &stored) {
-                    // Keyring password is stale (user changed password).
-                    // Clear the stale bio credentials so the user isn't stuck in a loop.
-                    let _ = keyring::Entry::new(BIO_SERVICE, &user)
-                        .and_then(|e| e.delete_credential());
-                    let _ = fs::remove_file(dir.join(BIO_MARKER_FILE));
-                    return Ok(json!({
-                        "success": false,
-                        "error": "Password biometrica non più valida. Accedi con la password e riconfigura la biometria."
-                    }));
-                }
-                *(_state.vault_key.lock().unwrap_or_else(|e| e.into_inner())) = Some(SecureKey(k));
-                *(_state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner())) = 0;
-                *(_state.locked_until.lock().unwrap_or_else(|e| e.into_inner())) = None;
-                lockout_clear(&sec_dir);
-                *(_state.last_activity.lock().unwrap_or_else(|e| e.into_inner())) = Instant::now();
-                let _ = append_audit_log(&_state, "Sblocco Vault (biometria)");
-                Ok(json!({"success": true}))
-            },
-            Err(e) => Ok(json!({"success": false, "error": e}))
+/// True when the last mobile sync converted its instants with a different UTC offset.
+fn utc_offset_changed(recorded: Option<i64>, current: i32) -> bool {
+    recorded.map(|o| o != current as i64).unwrap_or(false)
+}
+
+/// Notifications already handed to the OS carry fixed instants computed with the offset
+/// of the last sync: when the device changes zone, re-sync so device-local items follow
+/// the new wall clock. Checked against memory first; the schedule is read only on change.
+fn utc_offset_pass(ah: &AppHandle, state: &State<AppState>) {
+    let offset = chrono::Local::now().offset().local_minus_utc();
+    {
+        let mut last = state.utc_offset.lock().unwrap_or_else(|e| e.into_inner());
+        if *last == Some(offset) { return; }
+        *last = Some(offset);
+    }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let recorded = read_notification_schedule(&dir).and_then(|s| s.get("lastUtcOffset").and_then(|o| o.as_i64()));
+    if utc_offset_changed(recorded, offset) {
+        lf_log!("[LexFlow] Fuso orario cambiato (UTC{:+}s) — notifiche riprogrammate", offset);
+        sync_notifications(ah, &dir);
+    }
+}
+
+/// One pass of the autolock worker (desktop and Android): emits the countdown, locks at
+/// the threshold (or opens the re-auth grace), and returns how long to sleep.
+fn autolock_pass(ah: &AppHandle) -> Duration {
+    let state = ah.state::<AppState>();
+    license_nudge_pass(ah, &state);
+    utc_offset_pass(ah, &state);
+    agenda_archive_pass(ah, &state);
+    if state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).is_none()
+        && state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).is_none() {
+        *state.autolock_grace_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        return Duration::from_secs(60);
+    }
+    let grace = *state.autolock_grace_until.lock().unwrap_or_else(|e| e.into_inner());
+    let minutes = *state.autolock_minutes.lock().unwrap_or_else(|e| e.into_inner());
+    if minutes == 0 && grace.is_none() {
+        return Duration::from_secs(AUTOLOCK_IDLE_TICK_SECS);
+    }
+    let elapsed = state.last_activity.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+    let threshold = Duration::from_secs(minutes as u64 * 60);
+    let tick = if minutes == 0 { AutolockTick::Idle } else { autolock_tick(elapsed, threshold) };
+    let mode = || {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        autolock_mode(&read_settings(&dir))
+    };
+    match autolock_action(grace, tick, Instant::now(), mode) {
+        AutolockAction::Wait if grace.is_some() => Duration::from_secs(1),
+        AutolockAction::Wait => {
+            if let AutolockTick::Warn(remaining_secs) = tick {
+                let _ = ah.emit("lf-vault-warning", json!({"remainingSecs": remaining_secs}));
+            }
+            autolock_sleep(elapsed, threshold)
+        }
+        AutolockAction::StartGrace(deadline) => {
+            *state.autolock_grace_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(deadline);
+            let _ = ah.emit("lf-vault-reauth-required", json!({"graceSecs": AUTOLOCK_GRACE_SECS}));
+            Duration::from_secs(1)
+        }
+        AutolockAction::Lock => {
+            *state.autolock_grace_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            autolock_lock_now(ah, &state);
+            Duration::from_secs(1)
         }
     }
-    #[cfg(target_os = "windows")]
-    {
-        // Windows Hello: verifica biometrica reale tramite UserConsentVerifier WinRT API.
-        // Usa PowerShell per invocare Windows.Security.Credentials.UI.UserConsentVerifier
-        // — più affidabile che controllare solo il keyring senza autenticazione.
-        use std::process::Command;
-        let ps_script = r#"
-Add-Type -AssemblyName System.Runtime.WindowsRuntime
-$asTaskGeneric = ([System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object { $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1' })[0]
-function Await($WinRtTask, $ResultType) {
-    $asTaskSpecific = $asTaskGeneric.MakeGenericMethod($ResultType)
-    $netTask = $asTaskSpecific.Invoke($null, @($WinRtTask))
-    $netTask.Wait(-1) | Out-Null
-    $netTask.Result
 }
-[Windows.Security.Credentials.UI.UserConsentVerifier,Windows.Security.Credentials.UI,ContentType=WindowsRuntime] | Out-Null
-$result = Await ([Windows.Security.Credentials.UI.UserConsentVerifier]::RequestVerificationAsync("LexFlow — Verifica identità")) ([Windows.Security.Credentials.UI.UserConsentVerificationResult])
-if ($result -eq [Windows.Security.Credentials.UI.UserConsentVerificationResult]::Verified) { exit 0 } else { exit 1 }
-"#;
-        // SECURITY FIX (Gemini L1-2): use absolute path to prevent PATH hijacking.
-        // C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe is the canonical location.
-        let status = Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
-            .args(["-NoProfile", "-NonInteractive", "-Command", ps_script])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map_err(|e| e.to_string())?;
-        if !status.success() { return Ok(json!({"success": false, "error": "Windows Hello fallito o non disponibile"})); }
 
-        // Recupera la password salvata dal keyring e sblocca internamente il vault
-        let user = whoami::username();
-        let saved_pwd = keyring::Entry::new(BIO_SERVICE, &user)
-            .and_then(|e| e.get_password()).map_err(|e| e.to_string())?;
+#[tauri::command]
+fn get_autolock_status(state: State<AppState>) -> Value {
+    let unlocked = state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    let idle = state.last_activity.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+    let minutes = *state.autolock_minutes.lock().unwrap_or_else(|e| e.into_inner());
+    let grace = state.autolock_grace_until.lock().unwrap_or_else(|e| e.into_inner())
+        .map(|d| d.saturating_duration_since(Instant::now()).as_secs());
+    let snapshot = state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    json!({"unlocked": unlocked, "idleSecs": idle.as_secs(), "thresholdSecs": minutes as u64 * 60, "graceRemainingSecs": grace, "readOnlySnapshot": snapshot})
+}
 
-    let dir = _state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-    let sec_dir = _state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-        let salt_path = dir.join(VAULT_SALT_FILE);
-        if !salt_path.exists() { return Ok(json!({"success": false, "error": "Vault non inizializzato"})); }
-        let salt = fs::read(&salt_path).unwrap_or_default();
-        match derive_secure_key(&saved_pwd, &salt) {
-            Ok(k) => {
-                // SECURITY FIX: verify the derived key against vault.verify BEFORE accepting.
-                let verify_path = dir.join(VAULT_VERIFY_FILE);
-                let stored = fs::read(&verify_path).unwrap_or_default();
-                if !stored.is_empty() && !verify_hash_matches(&k, &stored) {
-                    let _ = keyring::Entry::new(BIO_SERVICE, &user)
-                        .and_then(|e| e.delete_credential());
-                    let _ = fs::remove_file(dir.join(BIO_MARKER_FILE));
-                    return Ok(json!({
-                        "success": false,
-                        "error": "Password biometrica non più valida. Accedi con la password e riconfigura la biometria."
-                    }));
-                }
-                *(_state.vault_key.lock().unwrap_or_else(|e| e.into_inner())) = Some(SecureKey(k));
-                *(_state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner())) = 0;
-                *(_state.locked_until.lock().unwrap_or_else(|e| e.into_inner())) = None;
-                lockout_clear(&sec_dir);
-                *(_state.last_activity.lock().unwrap_or_else(|e| e.into_inner())) = Instant::now();
-                let _ = append_audit_log(&_state, "Sblocco Vault (biometria)");
-                Ok(json!({"success": true}))
-            },
-            Err(e) => Ok(json!({"success": false, "error": e}))
-        }
+// ─── Close behavior ─────────────────────────────────────────────────────────
+// settings.closeBehavior decides what the window 'X' does. Hiding to tray keeps the
+// notification scheduler alive, but on Linux the tray is often invisible (GNOME without
+// AppIndicator) and the app seems to vanish — so Linux defaults to quitting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CloseAction { Tray, Quit, Ask }
+
+fn resolve_close_behavior(setting: Option<&str>, os: &str) -> CloseAction {
+    match setting {
+        Some("tray") => CloseAction::Tray,
+        Some("quit") => CloseAction::Quit,
+        Some("ask") => CloseAction::Ask,
+        _ if os == "linux" => CloseAction::Quit,
+        _ => CloseAction::Tray,
     }
-    #[cfg(target_os = "android")]
-    {
-        // Su Android il biometric prompt è gestito interamente dal frontend JS
-        // tramite l'Android BiometricPrompt API via tauri-plugin-biometric (futuro)
-        // Per ora restituisce errore che il frontend gestisce con fallback a password
-        Err("android-bio-use-frontend".into())
+}
+
+/// Flush queued saves, drop the key and terminate — used by the tray menu, the 'quit'
+/// close behavior and the frontend prompt.
+fn lock_and_exit(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let _ = flush_pending_saves(&state);
+    *state.reauth_token.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    app.exit(0);
+}
+
+#[tauri::command]
+fn quit_app(app: AppHandle) { lock_and_exit(&app); }
+
+#[tauri::command]
+fn get_app_version(app: AppHandle) -> String { app.package_info().version.to_string() }
+
+#[tauri::command]
+fn is_mac() -> bool { cfg!(target_os = "macos") }
+
+/// Feature flags the frontend can test before calling newer commands, so a new UI
+/// paired with an older backend degrades instead of hitting "command not found".
+/// Add a flag here in the same change that registers the command.
+const CORE_FEATURES: &[&str] = &[
+    "merge_import",
+    "export_options",
+    "attachments",
+    "practice_folders",
+    "confidential_practices",
+    "reauth_token",
+    "debounced_saves",
+    "focus_mode",
+    "area_reminder_defaults",
+    "timelog_overlaps",
+    "client_billing_summary",
+    "invoice_aging",
+    "burn_registry_transfer",
+    "license_receipt",
+    "license_repair",
+    "ics_import",
+    "audit_level",
+    "vcard_export",
+    "firm_activity_report",
+    "password_change_preflight",
+    "protected_sections",
+    "unlock_estimate",
+    "close_behavior",
+    "notification_history",
+    "tags",
+    "conflict_sessions",
+    "backup_diagnosis",
+    "machine_id_regeneration",
+    "lockout_status",
+    "window_blur_policies",
+    "archive",
+    "conflict_check_tracking",
+    "audit_log_paging",
+    "bio_credential_check",
+    "upcoming_notifications",
+    "subject_access_export",
+    "notification_strategy",
+    "license_warning",
+    "legacy_file_migration",
+    "vault_info",
+    "vault_search",
+    "diagnostics_export",
+    "diagnostics_encrypted",
+    "old_identifier_migration",
+    "practice_patch",
+    "idle_hide_window",
+    "vault_integrity",
+    "signed_artifact_check",
+    "plaintext_export",
+    "perf_histogram",
+    "dir_layout_check",
+    "vault_kdf_params",
+    "import_password_choice",
+    "license_key_format_check",
+    "contact_adversity_map",
+    "vault_profiles",
+    "autolock_countdown",
+    "practice_secrets",
+    "practice_trash",
+    "restore_simulation",
+    "autolock_grace_prompt",
+    "license_entitlements",
+    "duplicate_practices",
+    "attachment_audit",
+    "license_from_file",
+    "adhoc_reminders",
+    "audit_csv_export",
+    "license_grace",
+    "license_renewal_hint",
+    "attachment_impact",
+    "orphaned_attachments",
+    "license_deactivation",
+    "duration_minutes",
+    "audit_hash_chain",
+    "notification_capacity",
+    "audit_filters",
+    "readonly_snapshot",
+    "license_expiry_events",
+    "audit_csv_trailer",
+    "encrypted_documents",
+    "agenda_link_repair",
+    "bio_refresh",
+    "recurring_reminders",
+    "notification_snooze",
+    "agenda_archive",
+    "date_repair",
+    "item_time_zones",
+    "cron_catch_up",
+    "practice_proof",
+];
+
+fn capabilities() -> Value {
+    let mut features: Vec<&str> = CORE_FEATURES.to_vec();
+    // Platform-dependent features: only advertise what is compiled in
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        features.push("notifications_native_schedule");
+    } else {
+        features.push("notifications_cron");
+        features.push("tray");
+        features.push("window_controls");
     }
-    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "android")))]
-    {
-        Err("Non supportato su questa piattaforma".into())
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        features.push("biometrics");
     }
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "schemaVersion": VAULT_SCHEMA_VERSION,
+        "features": features,
+    })
 }
 
+/// Startup check for a cloned disk image: true when the stored machine-id was created on
+/// different hardware. The frontend then offers regenerate_machine_id().
 #[tauri::command]
-fn clear_bio(state: State<AppState>) -> bool {
+fn machine_id_status() -> Value {
+    #[cfg(not(target_os = "android"))]
+    { json!({"cloneSuspected": current_machine_id_check() == MachineIdCheck::Mismatch}) }
+    #[cfg(target_os = "android")]
+    { json!({"cloneSuspected": false}) }
+}
+
+/// Give a cloned install its own identity: fresh .machine-id, every local-key file
+/// re-encrypted under the new key. The original machine's license.json + sentinel are
+/// dropped (this clone needs its own seat); the burned-keys registry is carried over.
+/// Staged as *.reenc.tmp before the id is replaced, then renamed into place.
+#[tauri::command]
+fn regenerate_machine_id(state: State<AppState>) -> Result<Value, String> {
     #[cfg(not(target_os = "android"))]
     {
-        let user = whoami::username();
-        if let Ok(e) = keyring::Entry::new(BIO_SERVICE, &user) { let _ = e.delete_credential(); }
-        // Remove marker file
-        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-        let _ = fs::remove_file(dir.join(BIO_MARKER_FILE));
-        true
+        if current_machine_id_check() != MachineIdCheck::Mismatch {
+            return Err("Nessuna clonazione rilevata: ID macchina invariato".into());
+        }
+        let data_dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let files = [
+            data_dir.join(SETTINGS_FILE),
+            data_dir.join(NOTIF_SCHEDULE_FILE),
+            data_dir.join(NOTIF_HISTORY_FILE),
+            sec_dir.join(BURNED_KEYS_FILE),
+        ];
+        let mut id_bytes = [0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
+        let new_id = hex::encode(id_bytes);
+        let new_key = Zeroizing::new(local_key_for_machine_id(&new_id));
+
+        let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for path in files.iter().filter(|p| p.exists()) {
+            let plaintext = match decrypt_local_with_migration(path) {
+                Some(p) => Zeroizing::new(p),
+                None => { lf_log!("[LexFlow] {:?} non decifrabile — non migrato", path); continue; }
+            };
+            let tmp = PathBuf::from(format!("{}.reenc.tmp", path.display()));
+            secure_write(&tmp, &encrypt_data(&new_key, &plaintext)?).map_err(|e| e.to_string())?;
+            staged.push((tmp, path.clone()));
+        }
+
+        let id_dir = machine_id_dir();
+        atomic_write_with_sync(&id_dir.join(MACHINE_ID_FILE), new_id.as_bytes())?;
+        if let Some(ctx) = hardware_context_hash() {
+            atomic_write_with_sync(&id_dir.join(MACHINE_ID_CTX_FILE), ctx.as_bytes())?;
+        }
+        for (tmp, dest) in &staged {
+            fs::rename(tmp, dest).map_err(|e| e.to_string())?;
+        }
+        let license_reset = sec_dir.join(LICENSE_FILE).exists();
+        let _ = fs::remove_file(sec_dir.join(LICENSE_FILE));
+        let _ = fs::remove_file(sec_dir.join(LICENSE_SENTINEL_FILE));
+
+        lf_log!("[LexFlow] ID macchina rigenerato (clonazione rilevata): {} file ricifrati", staged.len());
+        let _ = append_audit_log(&state, AuditCategory::Security, None, "ID macchina rigenerato: clonazione disco rilevata");
+        Ok(json!({"success": true, "reencrypted": staged.len(), "licenseReset": license_reset}))
     }
     #[cfg(target_os = "android")]
     {
         let _ = state;
-        true
+        Err("Non supportato su questa piattaforma".into())
     }
 }
 
-// ═══════════════════════════════════════════════════════════
-//  AUDIT & LOGS
-// ═══════════════════════════════════════════════════════════
-
-fn append_audit_log(state: &State<AppState>, event_name: &str) -> Result<(), String> {
-    let key = match get_vault_key(state) { Ok(k) => k, Err(_) => return Ok(()) };
-    let path = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(AUDIT_LOG_FILE);
-    let mut logs: Vec<Value> = if path.exists() {
-        let enc = fs::read(&path).unwrap_or_default();
-        match decrypt_data(&key, &enc) {
-            Ok(dec) => serde_json::from_slice(&dec).unwrap_or_default(),
-            Err(_) => {
-                // SECURITY FIX (Gemini Audit v2): if audit log decryption fails, the file
-                // has been tampered with. DO NOT silently overwrite it — that would destroy
-                // the entire forensic history. Instead, preserve the corrupted file as evidence
-                // and start a NEW log with a tamper-detection event.
-                let corrupt_backup = path.with_extension("audit.corrupt");
-                let _ = fs::copy(&path, &corrupt_backup);
-                eprintln!("[LexFlow] SECURITY: Audit log decryption failed — tampered? Backup saved to {:?}", corrupt_backup);
-                vec![json!({"event": "AUDIT_LOG_TAMPERING_DETECTED", "time": chrono::Local::now().to_rfc3339()})]
-            }
-        }
-    } else { vec![] };
+#[tauri::command]
+fn get_capabilities() -> Value { capabilities() }
 
-    logs.push(json!({"event": event_name, "time": chrono::Local::now().to_rfc3339()}));
-    if logs.len() > 10000 { logs.remove(0); }
-    let plaintext = Zeroizing::new(serde_json::to_vec(&logs).unwrap_or_default());
-    let enc = encrypt_data(&key, &plaintext)?;
-    atomic_write_with_sync(&path, &enc)?;
-    Ok(())
+/// Restituisce la piattaforma corrente al frontend
+#[tauri::command]
+fn get_platform() -> String {
+    #[cfg(target_os = "android")] { "android".to_string() }
+    #[cfg(target_os = "ios")]     { "ios".to_string() }
+    #[cfg(target_os = "macos")]   { "macos".to_string() }
+    #[cfg(target_os = "windows")] { "windows".to_string() }
+    #[cfg(target_os = "linux")]   { "linux".to_string() }
+    #[cfg(not(any(target_os="android",target_os="ios",target_os="macos",target_os="windows",target_os="linux")))]
+    { "unknown".to_string() }
 }
 
 #[tauri::command]
-fn get_audit_log(state: State<AppState>) -> Result<Value, String> {
-    let key = get_vault_key(&state)?;
-    let path = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(AUDIT_LOG_FILE);
-    if !path.exists() { return Ok(json!([])); }
-    let dec = decrypt_data(&key, &fs::read(path).map_err(|e| e.to_string())?)?;
-    serde_json::from_slice(&dec).map_err(|e| e.to_string())
+async fn select_pdf_save_path(app: AppHandle, default_name: String) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("PDF", &["pdf"])
+        .set_file_name(&default_name)
+        .save_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+    let file_path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
+    match file_path {
+        Some(fp) => {
+            let path = fp.into_path().map_err(|e| format!("Path error: {:?}", e))?;
+            Ok(Some(path.to_string_lossy().into_owned()))
+        },
+        None => Ok(None),
+    }
 }
 
 // ═══════════════════════════════════════════════════════════
-//  SETTINGS & LICENSE
+//  NOTIFICATIONS
 // ═══════════════════════════════════════════════════════════
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_license_verification_full_cycle() {
-        // Questa è la tua licenza di prova (ho aggiunto il prefisso LXFW. richiesto dal codice)
-        // Nota: i campi devono essere "c" (client) ed "e" (expiry) come definito nella tua struct LicensePayload
-    let valid_token = "LXFW.eyJjIjoicGlldHJvX3Rlc3QiLCJlIjoyMDg3NzAzMjcyNTg4LCJpZCI6IjVhOTFiYzNlLWQ0ZjctNGExMi05YzhiLTNmMWU3ZDJhMGI0NSJ9.gCTXtrcIcHatN-GPOQaXhYgyXn9Wn9XtJAArEbaOGNAJX0CP2z0tYJ7EV1DttWRn6MUxdyPZsgkgWcoIoK3aDA";
-
-        // 1. Test verifica positiva
-        let result = verify_license(valid_token.to_string());
-        assert!(result.valid, "La licenza valida è stata respinta! Errore: {}", result.message);
-        assert_eq!(result.client.unwrap(), "pietro_test");
 
-        // 2. Test Anti-Manomissione (cambiamo un solo carattere nella firma)
-        let mut tampered_token = valid_token.to_string();
-        tampered_token.replace_range(tampered_token.len()-5..tampered_token.len()-4, "Z");
-        let tamper_result = verify_license(tampered_token);
-        assert!(!tamper_result.valid, "Sicurezza fallita: la licenza manomessa è stata accettata!");
-        assert_eq!(tamper_result.message, "Firma non valida o licenza manomessa!");
+#[tauri::command]
+fn send_notification(app: AppHandle, title: String, body: String) {
+    // Even though Tauri IPC commands run on the main thread context, we
+    // explicitly use run_on_main_thread to guarantee the NSRunLoop is active
+    // for the XPC call to usernoted (macOS Notification Center daemon).
+    let t = title.clone();
+    let b = body.clone();
+    let ah = app.clone();
+    let _ = app.run_on_main_thread(move || {
+        use tauri_plugin_notification::NotificationExt;
+        if let Err(e) = ah.notification().builder().title(&t).body(&b).show() {
+            lf_log!("[LexFlow] Native notification failed: {:?}, emitting event fallback", e);
+            let _ = ah.emit("show-notification", serde_json::json!({"title": t, "body": b}));
+        }
+    });
+}
 
-        // 3. Test Formato errato
-        let invalid_format = "TOKEN_SENZA_PUNTI";
-        let format_result = verify_license(invalid_format.to_string());
-        assert!(!format_result.valid);
-        assert_eq!(format_result.message, "Formato chiave non valido.");
+/// Test notification — dev-only command to verify the notification pipeline.
+/// Always dispatches via run_on_main_thread for NSRunLoop guarantee.
+#[tauri::command]
+fn test_notification(app: AppHandle) -> bool {
+    let ah = app.clone();
+    match app.run_on_main_thread(move || {
+        use tauri_plugin_notification::NotificationExt;
+        if let Err(e) = ah.notification().builder()
+            .title("LexFlow — Test Notifica")
+            .body("Le notifiche funzionano correttamente!")
+            .show()
+        {
+            lf_log!("[LexFlow] Test notification failed: {:?}", e);
+        }
+    }) {
+        Ok(_) => true,
+        Err(_) => false,
     }
 }
 
-#[tauri::command]
-fn get_settings(state: State<AppState>) -> Value {
-    let path = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(SETTINGS_FILE);
-    if !path.exists() { return json!({}); }
-    // SECURITY FIX (Level-8 C5): reject suspiciously large files before reading into RAM.
-    // A corrupted or maliciously injected 5GB settings file would OOM-kill the process.
-    if let Ok(meta) = path.metadata() {
-        if meta.len() > MAX_SETTINGS_FILE_SIZE {
-            eprintln!("[LexFlow] Settings file troppo grande ({} bytes) — ignorato", meta.len());
-            return json!({});
-        }
+/// Reminder lead time for a schedule item. Fallback chain:
+/// item `remindMinutes` / largest of `reminderOffsets` → `reminderDefaultsByArea[practiceArea]`
+/// from settings → global `preavviso` → 30 minutes.
+fn resolve_remind_minutes(item: &Value, settings: &Value) -> i64 {
+    if let Some(m) = item.get("remindMinutes").and_then(|v| v.as_i64()) {
+        return m;
     }
-    // SECURITY FIX (Gemini Audit): use migration-aware decryption (hostname→machine_id)
-    if let Some(dec) = decrypt_local_with_migration(&path) {
-        return serde_json::from_slice(&dec).unwrap_or(json!({}));
+    let offsets = item.get("reminderOffsets").and_then(|v| v.as_array())
+        .and_then(|a| a.iter().filter_map(|o| o.as_i64()).max());
+    if let Some(m) = offsets {
+        return m;
     }
-    // Migration: old plaintext format
-    if let Ok(enc) = fs::read(&path) {
-        if let Ok(text) = std::str::from_utf8(&enc) {
-            if let Ok(val) = serde_json::from_str::<Value>(text) {
-                // Re-encrypt with current key
-                let key = get_local_encryption_key();
-                if let Ok(re_enc) = encrypt_data(&key, &serde_json::to_vec(&val).unwrap_or_default()) {
-                    let _ = atomic_write_with_sync(&path, &re_enc);
-                }
-                return val;
-            }
-        }
-        // File corrotto: salva backup prima di resettare (non perdere dati silenziosamente)
-        let backup_path = path.with_extension("json.corrupt");
-        let _ = fs::copy(&path, &backup_path);
-        eprintln!("[LexFlow] Settings file corrotto — backup salvato in {:?}", backup_path);
+    let area_default = item.get("practiceArea").and_then(|a| a.as_str())
+        .and_then(|area| settings.get("reminderDefaultsByArea")?.get(area)?.as_i64());
+    if let Some(m) = area_default {
+        return m;
     }
-    json!({})
+    settings.get("preavviso").and_then(|v| v.as_i64()).unwrap_or(DEFAULT_REMIND_MINUTES)
 }
 
-#[tauri::command]
-fn save_settings(state: State<AppState>, settings: Value) -> bool {
-    let path = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).join(SETTINGS_FILE);
-    let key = get_local_encryption_key();
-    match encrypt_data(&key, &serde_json::to_vec(&settings).unwrap_or_default()) {
-        Ok(encrypted) => atomic_write_with_sync(&path, &encrypted).is_ok(),
-        Err(_) => false,
+/// Copy each linked practice's area onto its schedule items. The scheduler runs while
+/// the vault may be locked, so the area has to travel inside the schedule file.
+fn stamp_practice_areas(schedule: &mut Value, practices: &[Value]) {
+    let areas: std::collections::HashMap<&str, &str> = practices.iter().filter_map(|p| {
+        let id = p.get("id")?.as_str()?;
+        // Practices created before areas existed only carry the matter `type`
+        let area = p.get("area").or_else(|| p.get("type"))?.as_str()?;
+        Some((id, area))
+    }).collect();
+    if let Some(items) = schedule.get_mut("items").and_then(|i| i.as_array_mut()) {
+        for item in items.iter_mut() {
+            if item.get("practiceArea").and_then(|a| a.as_str()).is_some() { continue; }
+            let area = item.get("practiceId").and_then(|p| p.as_str()).and_then(|id| areas.get(id));
+            if let Some(area) = area {
+                item["practiceArea"] = json!(area);
+            }
+        }
     }
 }
 
 #[tauri::command]
-fn check_license(state: State<AppState>) -> Value {
-    let path = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).join(LICENSE_FILE);
-    let sentinel_path = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).join(LICENSE_SENTINEL_FILE);
-
-    if !path.exists() {
-        // SECURITY: if sentinel exists but license.json was deleted, detect tampering.
-        // The sentinel is an HMAC proof that a license WAS activated on this machine.
-        if sentinel_path.exists() {
-            return json!({
-                "activated": false,
-                "tampered": true,
-                "reason": "File di licenza rimosso o manomesso. Contattare il supporto."
-            });
-        }
-        return json!({"activated": false});
+fn sync_notification_schedule(app: AppHandle, state: State<AppState>, mut schedule: Value) -> bool {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Ok(vault) = read_vault_internal(&state) {
+        let practices = vault.get("practices").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+        stamp_practice_areas(&mut schedule, &practices);
     }
-    let key = get_local_encryption_key();
-    let data: Value = if let Some(dec) = decrypt_local_with_migration(&path) {
-        serde_json::from_slice(&dec).unwrap_or(json!({}))
-    } else if path.exists() {
-        // File exists but cannot be decrypted with ANY key (current or legacy).
-        // Either corrupted or copied from another machine — reject.
-        return json!({"activated": false, "reason": "File licenza corrotto o non valido per questo dispositivo."});
-    } else { return json!({"activated": false}); };
-
-    // SECURITY: verify hardware fingerprint — the license is bound to this machine
-    let current_fp = compute_machine_fingerprint();
-    if let Some(stored_fp) = data.get("machineFingerprint").and_then(|v| v.as_str()) {
-        if stored_fp != current_fp {
-            return json!({"activated": false, "reason": "Licenza attivata su un altro dispositivo."});
+    let written = {
+        let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        // The frontend does not know about ad-hoc reminders, snoozes, focus-deferred
+        // reminders or the offset of the last mobile sync: keep the stored ones
+        if schedule.is_object() {
+            let stored = read_notification_schedule(&dir);
+            for key in ["adhoc", "snoozes", "focusDeferred", "lastUtcOffset"] {
+                if schedule.get(key).is_some() { continue; }
+                if let Some(kept) = stored.as_ref().and_then(|s| s.get(key).cloned()) {
+                    schedule[key] = kept;
+                }
+            }
         }
+        write_notification_schedule(&dir, &schedule).is_ok()
+    };
+    if written {
+        // ── TRIGGER: re-sync OS notification queue after data change ──
+        sync_notifications(&app, &dir);
     }
-    // If no fingerprint stored (pre-v2.6.1 activation), we still accept but
-    // re-encrypt with fingerprint on next check to upgrade silently
-    let needs_fp_upgrade = data.get("machineFingerprint").is_none();
-
-    let key_version = data.get("keyVersion").and_then(|v| v.as_str()).unwrap_or("");
-
-    // ── NEW FORMAT: burned key (v2.6.1+) ──────────────────────────────────
-    // The raw token no longer exists. We verify using stored expiry + HMAC integrity.
-    if key_version == "ed25519-burned" {
-        let token_hmac = data.get("tokenHmac").and_then(|v| v.as_str()).unwrap_or("");
-        let expiry_ms = data.get("expiryMs").and_then(|v| v.as_u64()).unwrap_or(0);
-        let client = data.get("client").and_then(|v| v.as_str()).unwrap_or("Studio Legale").to_string();
+    written
+}
 
-        if token_hmac.is_empty() {
-            return json!({"activated": false, "reason": "Dati licenza corrotti."});
-        }
+fn write_notification_schedule(data_dir: &std::path::Path, schedule: &Value) -> Result<(), String> {
+    let plaintext = serde_json::to_vec(schedule).map_err(|e| e.to_string())?;
+    let encrypted = encrypt_data(&get_local_encryption_key()?, &plaintext)?;
+    atomic_write_with_sync(&data_dir.join(NOTIF_SCHEDULE_FILE), &encrypted)
+}
 
-        // Check expiry
-        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
-        if now_ms > expiry_ms {
-            return json!({"activated": false, "expired": true, "reason": "Licenza scaduta."});
+/// Decrypt notification schedule with local machine key
+fn read_notification_schedule(data_dir: &PathBuf) -> Option<Value> {
+    let path = data_dir.join(NOTIF_SCHEDULE_FILE);
+    if !path.exists() { return None; }
+    // SECURITY FIX (Level-8 C5): size guard before reading into RAM.
+    if let Ok(meta) = path.metadata() {
+        if meta.len() > MAX_SETTINGS_FILE_SIZE {
+            lf_log!("[LexFlow] Notification schedule file troppo grande ({} bytes) — ignorato", meta.len());
+            return None;
         }
-
-        // Silent upgrade: add machineFingerprint if missing
-        if needs_fp_upgrade {
-            let mut upgraded = data.clone();
-            upgraded.as_object_mut().map(|obj| {
-                obj.insert("machineFingerprint".to_string(), json!(current_fp));
-            });
-            if let Ok(bytes) = serde_json::to_vec(&upgraded) {
-                if let Ok(encrypted) = encrypt_data(&key, &bytes) {
-                    let _ = fs::write(&path, encrypted);
+    }
+    // SECURITY FIX (Gemini Audit): use migration-aware decryption (hostname→machine_id)
+    if let Some(decrypted) = decrypt_local_with_migration(&path) {
+        return serde_json::from_slice(&decrypted).ok();
+    }
+    // Migration: old plaintext format → re-encrypt
+    if let Ok(encrypted) = fs::read(&path) {
+        if let Ok(text) = std::str::from_utf8(&encrypted) {
+            if let Ok(val) = serde_json::from_str::<Value>(text) {
+                if let Ok(enc) = get_local_encryption_key()
+                    .and_then(|key| encrypt_data(&key, &serde_json::to_vec(&val).unwrap_or_default())) {
+                    let _ = atomic_write_with_sync(&path, &enc);
                 }
+                return Some(val);
             }
         }
-
-        return json!({
-            "activated": true,
-            "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
-            "client": client,
-        });
     }
+    None
+}
 
-    // ── LEGACY FORMAT: raw key stored (pre-v2.6.1) ────────────────────────
-    // Re-verify Ed25519 and silently upgrade to burned format.
-    let license_key = data.get("key").and_then(|k| k.as_str()).unwrap_or("");
-
-    if !license_key.is_empty() {
-        let verification = verify_license(license_key.to_string());
-
-        if verification.valid {
-            // ── SILENT UPGRADE: convert legacy → burned format ──
-            // 1. Compute HMAC of the raw token
-            let mut token_mac = <Hmac<Sha256> as Mac>::new_from_slice(&key)
-                .expect("HMAC can take key of any size");
-            token_mac.update(license_key.as_bytes());
-            let token_hmac = hex::encode(token_mac.finalize().into_bytes());
-
-            // 2. Extract expiry from the token payload
-            let expiry_ms: u64 = extract_expiry_ms(license_key).unwrap_or(0);
-            let client = verification.client.unwrap_or_else(|| "Studio Legale".to_string());
-            let key_id = extract_key_id(license_key).unwrap_or_else(|| "legacy".to_string());
-
-            // 3. Build burned record (no raw token)
-            let upgraded = json!({
-                "tokenHmac": token_hmac,
-                "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
-                "client": client,
-                "keyVersion": "ed25519-burned",
-                "machineFingerprint": current_fp,
-                "keyId": key_id,
-                "expiryMs": expiry_ms,
-            });
-            if let Ok(bytes) = serde_json::to_vec(&upgraded) {
-                if let Ok(encrypted) = encrypt_data(&key, &bytes) {
-                    let _ = fs::write(&path, encrypted);
-                }
-            }
+// ─── Legacy plaintext import from an arbitrary path ─────────────────────────
+// Same migration as read_settings()/read_notification_schedule(), for files left
+// behind by very old builds or copied from another machine layout.
+fn legacy_file_target(kind: &str) -> Option<&'static str> {
+    match kind {
+        "settings" => Some(SETTINGS_FILE),
+        "schedule" => Some(NOTIF_SCHEDULE_FILE),
+        _ => None,
+    }
+}
 
-            // 4. Burn the key so it can never be reused
-            let dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-            burn_key(&dir, &compute_burn_hash(license_key, &current_fp));
+/// A legacy settings file is merged into the current settings like save_settings (so it
+/// cannot drop keys it predates, nor change protectedSections without `reauthorized`);
+/// a schedule replaces the current one.
+fn import_legacy_plaintext(data_dir: &std::path::Path, kind: &str, source: &std::path::Path, reauthorized: bool) -> Result<Value, String> {
+    let target = legacy_file_target(kind).ok_or("Tipo di file non supportato")?;
+    let meta = fs::metadata(source).map_err(|_| "File di origine non trovato".to_string())?;
+    if !meta.is_file() { return Err("Il percorso indicato non è un file".into()); }
+    if meta.len() > MAX_SETTINGS_FILE_SIZE { return Err("File troppo grande".into()); }
+    let raw = fs::read(source).map_err(|e| e.to_string())?;
+    let val: Value = std::str::from_utf8(&raw).ok()
+        .and_then(|text| serde_json::from_str(text).ok())
+        .ok_or("Il file non è un JSON in chiaro valido")?;
+    if !val.is_object() { return Err("Formato non valido: atteso un oggetto JSON".into()); }
+    let val = if kind == "settings" { merge_settings(&read_settings(data_dir), &val, reauthorized)? } else { val };
+    let key = get_local_encryption_key()?;
+    let enc = encrypt_data(&key, &serde_json::to_vec(&val).map_err(|e| e.to_string())?)?;
+    atomic_write_with_sync(&data_dir.join(target), &enc)?;
+    Ok(json!({"success": true, "kind": kind, "bytes": raw.len()}))
+}
 
-            return json!({
-                "activated": true,
-                "activatedAt": data.get("activatedAt").cloned().unwrap_or(Value::Null),
-                "client": upgraded.get("client").and_then(|c| c.as_str()).unwrap_or("Studio Legale"),
-            });
-        } else {
-            return json!({"activated": false, "expired": true, "reason": verification.message});
+/// Imports a legacy plaintext settings or schedule file ("settings" | "schedule") from
+/// `source_path` and stores it encrypted at the canonical location. Needs an unlocked
+/// vault or a reauth token.
+#[tauri::command]
+fn migrate_legacy_file(app: AppHandle, state: State<AppState>, kind: String, source_path: String, reauth_token: Option<String>) -> Value {
+    let reauthorized = reauth_token.as_deref().is_some_and(|t| check_reauth_token(&state, t).is_ok());
+    if !reauthorized && get_vault_key(&state).is_err() {
+        return json!({"success": false, "error": "Sblocca il vault per importare file legacy"});
+    }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match import_legacy_plaintext(&dir, &kind, std::path::Path::new(&source_path), reauthorized) {
+        Ok(result) => {
+            if kind == "schedule" { sync_notifications(&app, &dir); }
+            let _ = append_audit_log(&state, AuditCategory::Exchange, None, &format!("File legacy importato ({})", kind));
+            result
         }
+        Err(e) => json!({"success": false, "error": e}),
     }
-
-    json!({"activated": false})
 }
 
-// NOTE: legacy symmetric license verification (HMAC/XOR secret) has been removed.
-// The project now uses Ed25519-signed license tokens verified by `verify_license`.
+// ─── Fired-notification history ─────────────────────────────────────────────
+// Records what the scheduler actually delivered so missed/duplicate reminders can be
+// diagnosed. Entries hold only what the notification itself showed; local-key encrypted
+// like the schedule. Mobile reminders are handed to the OS queue ahead of time, so only
+// the desktop cron writes here.
+fn read_notification_history(data_dir: &std::path::Path) -> Vec<Value> {
+    let path = data_dir.join(NOTIF_HISTORY_FILE);
+    if !path.exists() { return Vec::new(); }
+    if path.metadata().map(|m| m.len() > MAX_SETTINGS_FILE_SIZE).unwrap_or(true) { return Vec::new(); }
+    decrypt_local_with_migration(&path)
+        .and_then(|dec| serde_json::from_slice::<Vec<Value>>(&dec).ok())
+        .unwrap_or_default()
+}
 
-// ---------------------------------------------------------------------------
-// Offline Ed25519-signed license verification
-// ---------------------------------------------------------------------------
-// PUBLIC_KEY_BYTES: 32-byte Ed25519 public key for offline license validation.
-// The corresponding private key is stored securely offline (never in source control).
-// To regenerate: pip install cryptography && python3 -c "from cryptography.hazmat.primitives.asymmetric import ed25519; k=ed25519.Ed25519PrivateKey.generate(); print(list(k.public_key().public_bytes(encoding=__import__('cryptography.hazmat.primitives.serialization',fromlist=['Encoding']).Encoding.Raw, format=__import__('cryptography.hazmat.primitives.serialization',fromlist=['PublicFormat']).PublicFormat.Raw)))"
-const PUBLIC_KEY_BYTES: [u8; 32] = [
-    253u8, 163u8, 188u8, 248u8, 70u8, 245u8, 107u8, 254u8,
-    146u8, 8u8, 131u8, 167u8, 183u8, 94u8, 71u8, 224u8,
-    140u8, 237u8, 206u8, 74u8, 116u8, 185u8, 140u8, 0u8,
-    183u8, 15u8, 243u8, 77u8, 117u8, 233u8, 138u8, 84u8,
-];
+fn record_fired_notification(data_dir: &std::path::Path, kind: &str, id: &str, title: &str, body: &str) -> Result<(), String> {
+    let mut entries = read_notification_history(data_dir);
+    entries.push(json!({
+        "firedAt": chrono::Local::now().to_rfc3339(),
+        "kind": kind,
+        "id": id,
+        "title": title,
+        "body": body,
+    }));
+    if entries.len() > MAX_NOTIF_HISTORY {
+        entries.drain(..entries.len() - MAX_NOTIF_HISTORY);
+    }
+    let plaintext = serde_json::to_vec(&entries).map_err(|e| e.to_string())?;
+    let encrypted = encrypt_data(&get_local_encryption_key()?, &plaintext)?;
+    atomic_write_with_sync(&data_dir.join(NOTIF_HISTORY_FILE), &encrypted)
+}
 
-#[derive(Deserialize, Serialize)]
-struct LicensePayload {
-    c: String, // client name
-    e: u64,    // expiry in milliseconds since epoch
-    id: String, // unique key id
-    #[serde(default)] // backward compatible: v1 tokens don't have this field
-    n: Option<String>, // anti-replay nonce (128-bit hex, v2+)
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn log_fired_notification(app: &AppHandle, kind: &str, id: &str, title: &str, body: &str) {
+    let state = app.state::<AppState>();
+    let _guard = state.notif_history_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(e) = record_fired_notification(&dir, kind, id, title, body) {
+        lf_log!("[LexFlow] Storico notifiche non aggiornato: {}", e);
+    }
 }
 
-#[derive(Serialize)]
-struct VerificationResult {
-    valid: bool,
-    client: Option<String>,
-    message: String,
+/// Recent fired notifications, newest first.
+#[tauri::command]
+fn get_notification_history(state: State<AppState>) -> Value {
+    let _guard = state.notif_history_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut entries = read_notification_history(&dir);
+    entries.reverse();
+    json!(entries)
 }
 
 #[tauri::command]
-fn verify_license(key_string: String) -> VerificationResult {
-    // Expected format: LXFW.<payload_b64>.<signature_b64>
-    let parts: Vec<&str> = key_string.split('.').collect();
-    if parts.len() != 3 || parts[0] != "LXFW" {
-        return VerificationResult { valid: false, client: None, message: "Formato chiave non valido.".into() };
+fn clear_notification_history(state: State<AppState>) -> Result<(), String> {
+    let _guard = state.notif_history_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    clear_notification_history_file(&dir)
+}
+
+fn clear_notification_history_file(data_dir: &std::path::Path) -> Result<(), String> {
+    let path = data_dir.join(NOTIF_HISTORY_FILE);
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
     }
+    Ok(())
+}
 
-    let payload_b64 = parts[1];
-    let signature_b64 = parts[2];
+// ─── Focus mode ─────────────────────────────────────────────────────────────
+// While active only `urgency: "critical"` reminders fire. Briefings are skipped;
+// normal reminders are held back and re-fired when focus ends if the event is
+// still ahead (desktop cron), or rescheduled at focus end (mobile AOT).
 
-    let payload_bytes = match URL_SAFE_NO_PAD.decode(payload_b64) {
-        Ok(b) => b,
-        Err(_) => return VerificationResult { valid: false, client: None, message: "Errore decodifica payload.".into() },
-    };
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
 
-    let signature_bytes = match URL_SAFE_NO_PAD.decode(signature_b64) {
-        Ok(b) => b,
-        Err(_) => return VerificationResult { valid: false, client: None, message: "Errore decodifica firma.".into() },
-    };
+/// Returns the focus end (unix ms) if focus mode is currently active.
+fn focus_until_ms(data_dir: &std::path::Path) -> Option<u64> {
+    let until = fs::read_to_string(data_dir.join(FOCUS_MODE_FILE)).ok()?
+        .trim().parse::<u64>().ok()?;
+    if until > now_ms() { Some(until) } else { None }
+}
 
-    let public_key = match VerifyingKey::from_bytes(&PUBLIC_KEY_BYTES) {
-        Ok(k) => k,
-        Err(_) => return VerificationResult { valid: false, client: None, message: "Errore chiave pubblica interna.".into() },
-    };
+fn is_critical_reminder(item: &Value) -> bool {
+    item.get("urgency").and_then(|u| u.as_str()) == Some("critical")
+}
 
-    let signature = match Signature::from_slice(&signature_bytes) {
-        Ok(s) => s,
-        Err(_) => return VerificationResult { valid: false, client: None, message: "Firma corrotta.".into() },
-    };
+/// Whether a reminder may fire now. Briefings are never critical.
+fn reminder_allowed(item: &Value, focus_active: bool) -> bool {
+    !focus_active || is_critical_reminder(item)
+}
 
-    if public_key.verify(payload_b64.as_bytes(), &signature).is_err() {
-        return VerificationResult { valid: false, client: None, message: "Firma non valida o licenza manomessa!".into() };
+#[tauri::command]
+fn set_focus_mode(app: AppHandle, state: State<AppState>, until_ms: u64) -> Result<Value, String> {
+    let now = now_ms();
+    if until_ms <= now {
+        return Err("La fine della modalità focus deve essere nel futuro".into());
     }
+    let until = until_ms.min(now + MAX_FOCUS_MODE_MS);
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    atomic_write_with_sync(&dir.join(FOCUS_MODE_FILE), until.to_string().as_bytes())?;
+    sync_notifications(&app, &dir);
+    Ok(json!({"active": true, "untilMs": until}))
+}
 
-    let payload: LicensePayload = match serde_json::from_slice(&payload_bytes) {
-        Ok(p) => p,
-        Err(_) => return VerificationResult { valid: false, client: None, message: "Dati licenza corrotti.".into() },
-    };
+#[tauri::command]
+fn clear_focus_mode(app: AppHandle, state: State<AppState>) -> bool {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let _ = fs::remove_file(dir.join(FOCUS_MODE_FILE));
+    sync_notifications(&app, &dir);
+    true
+}
 
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
-    if now > payload.e {
-        return VerificationResult { valid: false, client: Some(payload.c), message: "Licenza scaduta.".into() };
+#[tauri::command]
+fn get_focus_mode(state: State<AppState>) -> Value {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match focus_until_ms(&dir) {
+        Some(until) => json!({"active": true, "untilMs": until}),
+        None => json!({"active": false}),
     }
+}
 
-    VerificationResult { valid: true, client: Some(payload.c), message: "Licenza attivata con successo!".into() }
+// ─── Shared schedule computation ────────────────────────────────────────────
+// The desktop cron, the mobile AOT sync and upcoming_notifications() all derive fire
+// times and texts from these helpers, so the preview never diverges from what fires.
+const REMINDER_TITLE: &str = "LexFlow — Promemoria";
+const UPCOMING_HORIZON_DAYS: i64 = 14;
+const MAX_UPCOMING: usize = 100;
+
+struct PlannedNotification {
+    at: chrono::DateTime<chrono::Local>,
+    kind: &'static str,
+    id: String,
+    title: String,
+    body: String,
+    /// Schedule item behind a reminder (Null for briefings)
+    item: Value,
 }
 
-// Helper: extract key ID from a LXFW token without full verification.
-// Returns the `id` field from the payload JSON, or None if malformed.
-fn extract_key_id(token: &str) -> Option<String> {
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 3 || parts[0] != "LXFW" { return None; }
-    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
-    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).ok()?;
-    Some(payload.id)
+// ─── Item time zones ────────────────────────────────────────────────────────
+// An agenda item may carry `timezone` (IANA name, e.g. "America/New_York"): its date and
+// times are then wall-clock time in that zone, so its reminder keeps the same instant when
+// the device moves to another zone. Zone rules come from the tz database compiled in by
+// chrono-tz, identical on every platform; an unknown zone falls back to device-local time,
+// like an item without a timezone.
+fn load_time_zone(name: &str) -> Option<chrono_tz::Tz> {
+    name.parse().ok()
 }
 
-// Helper: extract expiry timestamp (ms) from a LXFW token without full verification.
-fn extract_expiry_ms(token: &str) -> Option<u64> {
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 3 || parts[0] != "LXFW" { return None; }
-    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
-    let payload: LicensePayload = serde_json::from_slice(&payload_bytes).ok()?;
-    Some(payload.e)
+fn item_time_zone(item: &Value) -> Option<&str> {
+    item.get("timezone").and_then(|t| t.as_str()).filter(|t| !t.is_empty())
 }
 
-#[tauri::command]
-fn activate_license(state: State<AppState>, key: String, _client_name: Option<String>) -> Value {
-    // Anti brute-force: usa lo stesso lockout del vault
-    if let Some(until) = *state.locked_until.lock().unwrap_or_else(|e| e.into_inner()) {
-        if Instant::now() < until {
-            return json!({"success": false, "locked": true, "remaining": (until - Instant::now()).as_secs()});
-        }
-    }
+/// `date` `time` as wall-clock time in `tz`, device-local when tz is absent or unknown.
+/// A time repeated by a DST change resolves to its first occurrence; one skipped by it, to
+/// the same wall time an hour later.
+fn zoned_datetime(date: &str, time: &str, tz: Option<&str>) -> Option<chrono::DateTime<chrono::Local>> {
+    let Some(zone) = tz.and_then(load_time_zone) else { return local_datetime(date, time) };
+    let dt = chrono::NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").ok()?;
+    let at = zone.from_local_datetime(&dt).earliest()
+        .or_else(|| zone.from_local_datetime(&(dt + chrono::Duration::hours(1))).earliest())?;
+    Some(at.with_timezone(&chrono::Local))
+}
 
-    let key = key.trim().to_string(); // Le chiavi B64 sono case-sensitive, non uppercasiamo
+fn local_datetime(date: &str, time: &str) -> Option<chrono::DateTime<chrono::Local>> {
+    let dt = chrono::NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").ok()?;
+    chrono::Local.from_local_datetime(&dt).single()
+}
 
-    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-    let path = sec_dir.join(LICENSE_FILE);
-    let sentinel_path = sec_dir.join(LICENSE_SENTINEL_FILE);
+/// Briefing at `time_str` on `date`: morning → the whole day, afternoon → from 13:00,
+/// evening (18:00+) → the next day.
+fn briefing_notification(items: &[Value], date: chrono::NaiveDate, time_str: &str) -> Option<PlannedNotification> {
+    if time_str.len() < 5 { return None; }
+    let date_str = date.format("%Y-%m-%d").to_string();
+    let at = local_datetime(&date_str, time_str)?;
+    let next_day = (date + chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+    let briefing_hour: u32 = time_str.split(':').next()
+        .and_then(|h| h.parse().ok()).unwrap_or(8);
+    let (filter_date, time_from, period_label) = if briefing_hour < 12 {
+        (date_str.as_str(), "00:00", "oggi")
+    } else if briefing_hour < 18 {
+        (date_str.as_str(), "13:00", "questo pomeriggio")
+    } else {
+        (next_day.as_str(), "00:00", "domani")
+    };
+    let mut relevant_items: Vec<&Value> = items.iter().filter(|i| {
+        let d = i.get("date").and_then(|d| d.as_str()).unwrap_or("");
+        let t = i.get("time").and_then(|t| t.as_str()).unwrap_or("00:00");
+        let done = i.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
+        d == filter_date && !done && t >= time_from
+    }).collect();
+    relevant_items.sort_by(|a, b| {
+        let ta = a.get("time").and_then(|v| v.as_str()).unwrap_or("");
+        let tb = b.get("time").and_then(|v| v.as_str()).unwrap_or("");
+        ta.cmp(tb)
+    });
+    let relevant_count = relevant_items.len();
+    let title = if relevant_count == 0 {
+        format!("LexFlow — Nessun impegno {}", period_label)
+    } else {
+        format!("LexFlow — {} impegn{} {}", relevant_count,
+            if relevant_count == 1 { "o" } else { "i" }, period_label)
+    };
+    let body = if relevant_count == 0 {
+        format!("Nessun impegno in programma per {}.", period_label)
+    } else {
+        let mut lines: Vec<String> = Vec::new();
+        for item in relevant_items.iter().take(4) {
+            let t = item.get("time").and_then(|v| v.as_str()).unwrap_or("");
+            let name = item.get("title").and_then(|v| v.as_str()).unwrap_or("Impegno");
+            if !t.is_empty() { lines.push(format!("• {} — {}", t, name)); }
+            else { lines.push(format!("• {}", name)); }
+        }
+        if relevant_count > 4 { lines.push(format!("  …e altri {}", relevant_count - 4)); }
+        lines.join("\n")
+    };
+    Some(PlannedNotification {
+        at, kind: "briefing", id: format!("{} {}", date_str, time_str), title, body, item: Value::Null,
+    })
+}
 
-    // ── SECURITY CHECK 1: if sentinel exists but license.json was deleted ──
-    // Someone deleted the license file to try re-activating with a different key.
-    // Block unless the SAME key (same ID) is being re-entered.
-    if !path.exists() && sentinel_path.exists() {
-        // We can still allow re-activation of the SAME key ID that was originally used.
-        // The sentinel stores HMAC("LEXFLOW-SENTINEL:<fingerprint>:<keyId>:<timestamp>")
-        // but we cannot recover the keyId from the HMAC. So we also store the encrypted
-        // key ID in the sentinel for comparison. See sentinel write below.
-        let enc_key = get_local_encryption_key();
-        let sentinel_content = fs::read_to_string(&sentinel_path).unwrap_or_default();
-        // Sentinel format: "<hmac_hex>\n<encrypted_key_id_hex>"
-        let sentinel_lines: Vec<&str> = sentinel_content.lines().collect();
-        let stored_key_id_enc = sentinel_lines.get(1).unwrap_or(&"");
+/// (event time, reminder time) for a schedule item, None if completed or undated.
+fn reminder_times(item: &Value, settings: &Value) -> Option<(chrono::DateTime<chrono::Local>, chrono::DateTime<chrono::Local>)> {
+    let item_date = item.get("date").and_then(|d| d.as_str()).unwrap_or("");
+    let item_time = item.get("time").and_then(|t| t.as_str()).unwrap_or("");
+    let completed = item.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
+    if completed || item_time.len() < 5 { return None; }
+    let tz = item_time_zone(item);
+    let item_local = zoned_datetime(item_date, item_time, tz)?;
+    let default_time = item_local - chrono::Duration::minutes(resolve_remind_minutes(item, settings));
+    let remind_time = item.get("customRemindTime")
+        .and_then(|v| v.as_str()).filter(|s| s.len() >= 5)
+        .and_then(|crt| zoned_datetime(item_date, crt, tz))
+        .unwrap_or(default_time);
+    Some((item_local, remind_time))
+}
 
-        // Try to recover stored key ID (try current key, then legacy)
-        let stored_key_id: Option<String> = if !stored_key_id_enc.is_empty() {
-            hex::decode(stored_key_id_enc).ok()
-                .and_then(|enc_bytes| {
-                    decrypt_data(&enc_key, &enc_bytes).ok()
-                        .or_else(|| {
-                            #[cfg(not(target_os = "android"))]
-                            { decrypt_data(&get_local_encryption_key_legacy(), &enc_bytes).ok() }
-                            #[cfg(target_os = "android")]
-                            { None }
-                        })
-                })
-                .and_then(|dec| String::from_utf8(dec).ok())
-        } else {
-            None
+/// Reminder text as of `at` ("tra 30 minuti" is relative to when it is shown).
+fn reminder_notification(item: &Value, event: chrono::DateTime<chrono::Local>, at: chrono::DateTime<chrono::Local>) -> PlannedNotification {
+    let item_title = item.get("title").and_then(|t| t.as_str()).unwrap_or("Impegno");
+    let item_time = item.get("time").and_then(|t| t.as_str()).unwrap_or("");
+    let diff = (event - at).num_minutes();
+    let time_desc = if diff < 0 { "già iniziato".to_string() }
+        else if diff == 0 { "adesso!".to_string() }
+        else if diff < 60 { format!("tra {} minuti", diff) }
+        else {
+            let h = diff / 60; let m = diff % 60;
+            if m == 0 { format!("tra {} or{}", h, if h == 1 { "a" } else { "e" }) }
+            else { format!("tra {}h {:02}min", h, m) }
         };
+    PlannedNotification {
+        at,
+        kind: "reminder",
+        id: item.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string(),
+        title: REMINDER_TITLE.to_string(),
+        body: format!("{} — {} ({})", item_title, item_time, time_desc),
+        item: item.clone(),
+    }
+}
 
-        let new_key_id = extract_key_id(&key);
+/// Focus mode: a non-critical reminder inside the window moves to the focus end,
+/// or is dropped if the event itself is over by then.
+fn focus_adjusted_remind_time(
+    item: &Value,
+    event: chrono::DateTime<chrono::Local>,
+    remind: chrono::DateTime<chrono::Local>,
+    focus_end: Option<chrono::DateTime<chrono::Local>>,
+) -> Option<chrono::DateTime<chrono::Local>> {
+    match focus_end {
+        Some(f) if remind < f && !reminder_allowed(item, true) => if event <= f { None } else { Some(f) },
+        _ => Some(remind),
+    }
+}
 
-        // Allow re-activation ONLY if the same key ID is being used
-        match (stored_key_id.as_deref(), new_key_id.as_deref()) {
-            (Some(old), Some(new_id)) if old == new_id => {
-                // Same key ID — allow re-activation (e.g. user accidentally deleted the file)
-            },
-            _ => {
-                return json!({
-                    "success": false,
-                    "error": "Questa installazione ha già una licenza registrata. Contattare il supporto per assistenza."
-                });
-            }
+// ─── Recurring agenda items ─────────────────────────────────────────────────
+// `recurrence` takes an RRULE subset: FREQ=DAILY|WEEKLY|MONTHLY, optional INTERVAL=n and
+// UNTIL (YYYYMMDD, time part ignored, or YYYY-MM-DD). The item's own date is the first
+// occurrence; a monthly rule skips months without that day, as RFC 5545 does. Any other
+// part (COUNT, BYDAY, ...) makes the rule invalid and the item stays a one-shot.
+// Schedulers work on expanded copies, one per occurrence in the horizon, with id
+// "<id>@<date>". `completedOccurrences` lists the dates marked done; `completed` on the
+// item only covers its first occurrence, so ticking one off never mutes the series.
+const MAX_RECURRENCE_INTERVAL: u32 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecurrenceFreq { Daily, Weekly, Monthly }
+
+#[derive(Debug, PartialEq)]
+struct Recurrence {
+    freq: RecurrenceFreq,
+    interval: u32,
+    until: Option<chrono::NaiveDate>,
+}
+
+fn parse_recurrence(rule: &str) -> Option<Recurrence> {
+    let rule = rule.trim();
+    let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+    let (mut freq, mut interval, mut until) = (None, 1u32, None);
+    for part in rule.split(';').filter(|p| !p.trim().is_empty()) {
+        let (key, value) = part.split_once('=')?;
+        let value = value.trim();
+        match key.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => freq = Some(match value.to_ascii_uppercase().as_str() {
+                "DAILY" => RecurrenceFreq::Daily,
+                "WEEKLY" => RecurrenceFreq::Weekly,
+                "MONTHLY" => RecurrenceFreq::Monthly,
+                _ => return None,
+            }),
+            "INTERVAL" => interval = value.parse().ok().filter(|n| (1..=MAX_RECURRENCE_INTERVAL).contains(n))?,
+            "UNTIL" => until = Some(if value.contains('-') {
+                chrono::NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()?
+            } else {
+                chrono::NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?
+            }),
+            _ => return None,
         }
     }
+    Some(Recurrence { freq: freq?, interval, until })
+}
 
-    // ── SECURITY CHECK 2: if license already exists and is valid, block overwrite ──
-    // Prevents replacing a valid license with a pirated/shared key.
-    if path.exists() {
-        if let Some(dec) = decrypt_local_with_migration(&path) {
-            if let Ok(existing) = serde_json::from_slice::<Value>(&dec) {
-                let existing_version = existing.get("keyVersion")
-                    .and_then(|v| v.as_str()).unwrap_or("");
-
-                if existing_version == "ed25519-burned" {
-                    let expiry = existing.get("expiryMs")
-                        .and_then(|v| v.as_u64()).unwrap_or(0);
-                    let now_ms = SystemTime::now()
-                        .duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
-                    if now_ms <= expiry {
-                        let existing_id = existing.get("keyId")
-                            .and_then(|v| v.as_str());
-                        let new_id = extract_key_id(&key);
-                        if existing_id.map(|s| s.to_string()) != new_id {
-                            return json!({
-                                "success": false,
-                                "error": "Una licenza valida è già attiva. Non è possibile sostituirla."
-                            });
-                        }
-                    }
-                } else {
-                    let existing_key = existing.get("key")
-                        .and_then(|k| k.as_str()).unwrap_or("");
-                    if !existing_key.is_empty() {
-                        let existing_verification = verify_license(existing_key.to_string());
-                        if existing_verification.valid {
-                            let existing_id = extract_key_id(existing_key);
-                            let new_id = extract_key_id(&key);
-                            if existing_id != new_id {
-                                return json!({
-                                    "success": false,
-                                    "error": "Una licenza valida è già attiva. Non è possibile sostituirla."
-                                });
-                            }
-                        }
-                    }
-                }
+/// Occurrence dates of a series starting on `start` that fall within [from, to].
+fn recurrence_dates(start: chrono::NaiveDate, rule: &Recurrence, from: chrono::NaiveDate, to: chrono::NaiveDate) -> Vec<chrono::NaiveDate> {
+    use chrono::Datelike as _;
+    let end = rule.until.map(|u| u.min(to)).unwrap_or(to);
+    let mut out = Vec::new();
+    let step_days = match rule.freq {
+        RecurrenceFreq::Daily => rule.interval as i64,
+        RecurrenceFreq::Weekly => 7 * rule.interval as i64,
+        RecurrenceFreq::Monthly => {
+            let month_index = |d: chrono::NaiveDate| d.year() as i64 * 12 + d.month0() as i64;
+            let (base, step) = (month_index(start), rule.interval as i64);
+            let mut m = base + (month_index(from) - base).max(0) / step * step;
+            while m <= month_index(end) {
+                let day = chrono::NaiveDate::from_ymd_opt(m.div_euclid(12) as i32, m.rem_euclid(12) as u32 + 1, start.day());
+                if let Some(d) = day.filter(|d| *d >= from && *d <= end) { out.push(d); }
+                m += step;
             }
+            return out;
         }
+    };
+    let mut k = (from - start).num_days().max(0) / step_days;
+    while let Some(d) = start.checked_add_signed(chrono::Duration::days(k * step_days)) {
+        if d > end { break; }
+        if d >= from { out.push(d); }
+        k += 1;
     }
+    out
+}
 
-    // Verifica asimmetrica (Ed25519)
-    let verification = verify_license(key.clone());
+fn occurrence_completed(item: &Value, date: &str) -> bool {
+    let first_done = item.get("date").and_then(|d| d.as_str()) == Some(date)
+        && item.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
+    first_done || item.get("completedOccurrences").and_then(|c| c.as_array())
+        .map(|list| list.iter().any(|d| d.as_str() == Some(date)))
+        .unwrap_or(false)
+}
 
-    if !verification.valid {
-        let mut att = state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner());
-        *att += 1;
-        if *att >= MAX_FAILED_ATTEMPTS {
-            *state.locked_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + Duration::from_secs(LOCKOUT_SECS));
+/// Recurring items replaced by their occurrences within [from, to]; the rest unchanged.
+fn expand_recurring_items(items: &[Value], from: chrono::NaiveDate, to: chrono::NaiveDate) -> Vec<Value> {
+    let mut out = Vec::with_capacity(items.len());
+    for item in items {
+        let rule = item.get("recurrence").and_then(|r| r.as_str()).and_then(parse_recurrence);
+        let start = item.get("date").and_then(|d| d.as_str())
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let (Some(rule), Some(start)) = (rule, start) else {
+            out.push(item.clone());
+            continue;
+        };
+        let id = item.get("id").and_then(|i| i.as_str()).unwrap_or("");
+        for date in recurrence_dates(start, &rule, from, to) {
+            let date_str = date.format("%Y-%m-%d").to_string();
+            let mut occurrence = item.clone();
+            if let Some(obj) = occurrence.as_object_mut() {
+                obj.remove("recurrence");
+                obj.remove("completedOccurrences");
+                obj.insert("completed".into(), json!(occurrence_completed(item, &date_str)));
+                obj.insert("id".into(), json!(format!("{}@{}", id, date_str)));
+                obj.insert("occurrenceOf".into(), json!(id));
+                obj.insert("date".into(), json!(date_str));
+            }
+            out.push(occurrence);
         }
-        return json!({"success": false, "error": verification.message});
     }
+    out
+}
 
-    *state.failed_attempts.lock().unwrap_or_else(|e| e.into_inner()) = 0;
+/// Schedule items as the schedulers see them from `now` to the end of the horizon.
+fn schedule_items_in_horizon(items: &[Value], now: chrono::DateTime<chrono::Local>) -> Vec<Value> {
+    let today = now.date_naive();
+    expand_recurring_items(items, today, today + chrono::Duration::days(UPCOMING_HORIZON_DAYS))
+}
 
-    // SECURITY: bind license to THIS machine — cannot be copied to another device
-    let fingerprint = compute_machine_fingerprint();
+/// Add or remove `date` in the item's completedOccurrences (kept sorted).
+fn set_occurrence_done(item: &mut Value, date: &str, done: bool) {
+    let mut dates: Vec<String> = item.get("completedOccurrences").and_then(|c| c.as_array())
+        .map(|list| list.iter().filter_map(|d| d.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    dates.retain(|d| d != date);
+    if done { dates.push(date.to_string()); }
+    dates.sort();
+    item["completedOccurrences"] = json!(dates);
+}
 
-    // ── SECURITY CHECK 3: burned-key registry ──────────────────────────────
-    // A key can only be activated ONCE. After activation it is "burned" —
-    // the raw token is destroyed and only a verification hash survives.
-    // Even if someone knows the key, they cannot re-use it.
-    if is_key_burned(&sec_dir, &key, &fingerprint) {
-        return json!({
-            "success": false,
-            "error": "Questa chiave è già stata utilizzata e non può essere riattivata."
-        });
+/// Mark one occurrence of a recurring agenda item done (or not), in the vault and in the
+/// schedule file, so the next occurrences keep firing.
+#[tauri::command]
+fn set_occurrence_completed(app: AppHandle, state: State<AppState>, item_id: String, date: String, completed: bool, reauth_token: Option<String>) -> Result<Value, String> {
+    if chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").is_err() {
+        return Err("Data non valida (atteso AAAA-MM-GG)".into());
     }
-
-    // ── SECURITY CHECK 4: burned-keys file integrity ──────────────────────
-    // If the sentinel exists (a license was previously activated on this machine)
-    // but the .burned-keys file is missing, someone deleted it to bypass single-use
-    // enforcement. Block activation of any NEW key in this case.
-    if sentinel_path.exists() && !sec_dir.join(BURNED_KEYS_FILE).exists() {
-        // Allow re-activation of the SAME key ID only (handled by CHECK 1 above).
-        // If we reach here, it's a different key → block.
-        return json!({
-            "success": false,
-            "error": "Registro chiavi compromesso. Contattare il supporto per assistenza."
-        });
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let done = {
+        let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        flush_pending_saves_locked(&state)?;
+        let mut vault = read_vault_internal(&state)?;
+        let item = vault.get_mut("agenda").and_then(|a| a.as_array_mut())
+            .and_then(|list| list.iter_mut().find(|i| i.get("id").and_then(|v| v.as_str()) == Some(item_id.as_str())))
+            .ok_or("Impegno non trovato")?;
+        if item.get("recurrence").and_then(|r| r.as_str()).and_then(parse_recurrence).is_none() {
+            return Err("L'impegno non è ricorrente".into());
+        }
+        set_occurrence_done(item, &date, completed);
+        let done = item["completedOccurrences"].clone();
+        write_vault_internal(&state, &vault)?;
+        done
+    };
+    {
+        let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut schedule) = read_notification_schedule(&dir) {
+            let item = schedule.get_mut("items").and_then(|i| i.as_array_mut())
+                .and_then(|list| list.iter_mut().find(|i| i.get("id").and_then(|v| v.as_str()) == Some(item_id.as_str())));
+            if let Some(item) = item {
+                set_occurrence_done(item, &date, completed);
+                if let Err(e) = write_notification_schedule(&dir, &schedule) {
+                    lf_log!("[LexFlow] Occorrenza non aggiornata nel calendario notifiche: {}", e);
+                }
+            }
+        }
     }
+    sync_notifications(&app, &dir);
+    let _ = append_audit_event(&state, AuditKind::Data, AuditCategory::Agenda, Some(&item_id), "Occorrenza impegno aggiornata");
+    Ok(json!({"success": true, "completedOccurrences": done}))
+}
 
-    let now = chrono::Utc::now().to_rfc3339();
-
-    // Il client viene estratto in modo sicuro dal payload firmato
-    let client = verification.client.unwrap_or_else(|| "Studio Legale".to_string());
-
-    // Extract key ID for sentinel storage
-    let key_id = extract_key_id(&key).unwrap_or_else(|| "unknown".to_string());
+// ─── Agenda archive ─────────────────────────────────────────────────────────
+// With settings.archiveCompletedAfterDays > 0, completed agenda items dated more than that
+// many days ago move from vault["agenda"] to vault["archivedAgenda"] — on unlock and then
+// hourly from the autolock worker — and leave the notification schedule, so neither the
+// frontend sync nor the cron scans them again. Recurring items are never archived: their
+// later occurrences may still be open. The agenda view shows archived items read-only;
+// an agenda save from a list loaded before the pass cannot bring them back.
+const AGENDA_ARCHIVE_INTERVAL_SECS: u64 = 3600;
+
+fn archive_completed_after_days(settings: &Value) -> u64 {
+    settings.get("archiveCompletedAfterDays").and_then(|v| v.as_u64()).unwrap_or(0)
+}
 
-    // ── BURN THE KEY: compute verification hash, then destroy raw token ────
-    // We store an HMAC(token) so check_license can verify integrity without
-    // having the raw token. The raw token ceases to exist after this point.
-    let mut token_mac = <Hmac<Sha256> as Mac>::new_from_slice(
-        &get_local_encryption_key()
-    ).expect("HMAC can take key of any size");
-    token_mac.update(key.as_bytes());
-    let token_hmac = hex::encode(token_mac.finalize().into_bytes());
+/// Move completed, non-recurring items dated before `cutoff` to archivedAgenda (replacing
+/// an archived copy with the same id); returns the archived ids.
+fn archive_completed_agenda(vault: &mut Value, cutoff: chrono::NaiveDate, archived_at: &str) -> Vec<String> {
+    let Some(list) = vault.get_mut("agenda").and_then(|a| a.as_array_mut()) else { return Vec::new() };
+    let (old, kept): (Vec<Value>, Vec<Value>) = std::mem::take(list).into_iter().partition(|item| {
+        let done = item.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
+        let recurring = item.get("recurrence").and_then(|r| r.as_str()).and_then(parse_recurrence).is_some();
+        let date = item.get("date").and_then(|d| d.as_str())
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        done && !recurring && date.map(|d| d < cutoff).unwrap_or(false)
+    });
+    *list = kept;
+    if old.is_empty() { return Vec::new(); }
+    if !vault.get("archivedAgenda").map(|a| a.is_array()).unwrap_or(false) {
+        vault["archivedAgenda"] = json!([]);
+    }
+    let archive = vault["archivedAgenda"].as_array_mut().expect("archivedAgenda is an array");
+    let mut ids = Vec::with_capacity(old.len());
+    for mut item in old {
+        let id = item.get("id").and_then(|i| i.as_str()).unwrap_or("").to_string();
+        item["archivedAt"] = json!(archived_at);
+        archive.retain(|a| id.is_empty() || a.get("id").and_then(|i| i.as_str()) != Some(id.as_str()));
+        archive.push(item);
+        ids.push(id);
+    }
+    ids
+}
 
-    // Extract payload data BEFORE destroying the token — we need client/expiry
-    // for check_license to work without re-verifying Ed25519
-    let parts: Vec<&str> = key.split('.').collect();
-    let payload_data: Option<LicensePayload> = if parts.len() == 3 {
-        URL_SAFE_NO_PAD.decode(parts[1]).ok()
-            .and_then(|b| serde_json::from_slice(&b).ok())
-    } else { None };
+/// Drop schedule items whose id is in `ids`; true if any was removed.
+fn drop_schedule_items(schedule: &mut Value, ids: &[String]) -> bool {
+    let Some(items) = schedule.get_mut("items").and_then(|i| i.as_array_mut()) else { return false };
+    let before = items.len();
+    items.retain(|i| !i.get("id").and_then(|v| v.as_str()).map(|id| ids.iter().any(|a| a == id)).unwrap_or(false));
+    items.len() != before
+}
 
-    let expiry_ms = payload_data.as_ref().map(|p| p.e).unwrap_or(0);
+/// Drop from an incoming agenda the items already moved to `archived`.
+fn drop_archived_agenda_items(mut agenda: Value, archived: &Value) -> Value {
+    let ids: std::collections::HashSet<&str> = archived.as_array().map(|a| a.iter().filter_map(|i| i.get("id").and_then(|v| v.as_str())).collect())
+        .unwrap_or_default();
+    if ids.is_empty() { return agenda; }
+    if let Some(items) = agenda.as_array_mut() {
+        items.retain(|i| !i.get("id").and_then(|v| v.as_str()).is_some_and(|id| ids.contains(id)));
+    }
+    agenda
+}
 
-    // Record: NO raw token — only HMAC + extracted payload data
-    let record = json!({
-        "tokenHmac": token_hmac,
-        "activatedAt": now,
-        "client": client,
-        "keyVersion": "ed25519-burned",
-        "machineFingerprint": fingerprint,
-        "keyId": key_id,
-        "expiryMs": expiry_ms,
-    });
-    let enc_key = get_local_encryption_key();
-    match encrypt_data(&enc_key, &serde_json::to_vec(&record).unwrap_or_default()) {
-        Ok(encrypted) => {
-            match atomic_write_with_sync(&path, &encrypted) {
-                Ok(_) => {
-                        // SECURITY: write sentinel file — HMAC proof that activation happened.
-                        // This detects if license.json is manually deleted to hack the system.
-                        // Format: line 1 = HMAC(sentinel_data), line 2 = encrypted key ID
-                        let sentinel_data = format!("LEXFLOW-SENTINEL:{}:{}:{}", fingerprint, key_id, now);
-                        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&enc_key)
-                            .expect("HMAC can take key of any size");
-                        mac.update(sentinel_data.as_bytes());
-                        let sentinel_hmac = hex::encode(mac.finalize().into_bytes());
+/// Opportunistic archival run (unlock and background); returns how many items moved.
+/// Failures are only logged.
+fn archive_completed_agenda_pass(state: &State<AppState>) -> usize {
+    *state.agenda_archived_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let days = archive_completed_after_days(&read_settings(&dir));
+    if days == 0 { return 0; }
+    let cutoff = chrono::Local::now().date_naive() - chrono::Duration::days(days.min(36_500) as i64);
+    let archived = {
+        let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        if flush_pending_saves_locked(state).is_err() { return 0; }
+        let Ok(mut vault) = read_vault_internal(state) else { return 0 };
+        let archived = archive_completed_agenda(&mut vault, cutoff, &chrono::Utc::now().to_rfc3339());
+        if archived.is_empty() { return 0; }
+        if let Err(e) = write_vault_internal(state, &vault) {
+            lf_log!("[LexFlow] Archiviazione agenda non riuscita: {}", e);
+            return 0;
+        }
+        archived
+    };
+    {
+        let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut schedule) = read_notification_schedule(&dir) {
+            if drop_schedule_items(&mut schedule, &archived) {
+                if let Err(e) = write_notification_schedule(&dir, &schedule) {
+                    lf_log!("[LexFlow] Impegni archiviati non rimossi dal calendario notifiche: {}", e);
+                }
+            }
+        }
+    }
+    let _ = append_audit_log(state, AuditCategory::Agenda, None, &format!("Agenda: {} impegni completati archiviati", archived.len()));
+    archived.len()
+}
 
-                        // Encrypt key ID so it can be recovered for re-activation check
-                        let encrypted_key_id = encrypt_data(&enc_key, key_id.as_bytes())
-                            .map(|e| hex::encode(e))
-                            .unwrap_or_default();
+/// Background archival from the autolock worker, at most once per
+/// AGENDA_ARCHIVE_INTERVAL_SECS while the vault is unlocked. Emits `lf-agenda-archived`
+/// {count} so the open agenda reloads.
+fn agenda_archive_pass(ah: &AppHandle, state: &State<AppState>) {
+    if state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).is_none()
+        || state.snapshot.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+        return;
+    }
+    let recent = matches!(*state.agenda_archived_at.lock().unwrap_or_else(|e| e.into_inner()),
+        Some(at) if at.elapsed() < Duration::from_secs(AGENDA_ARCHIVE_INTERVAL_SECS));
+    if recent { return; }
+    let count = archive_completed_agenda_pass(state);
+    if count > 0 {
+        let _ = ah.emit("lf-agenda-archived", json!({"count": count}));
+    }
+}
 
-                        let sentinel_content = format!("{}\n{}", sentinel_hmac, encrypted_key_id);
-                        let _ = atomic_write_with_sync(&sentinel_path, sentinel_content.as_bytes());
+/// Archived agenda items, for the history view.
+#[tauri::command]
+fn load_archived_agenda(state: State<AppState>, reauth_token: Option<String>) -> Result<Value, String> {
+    require_section_access(&state, "agenda", reauth_token.as_deref())?;
+    let vault = read_vault_internal(&state)?;
+    Ok(vault.get("archivedAgenda").cloned().unwrap_or(json!([])))
+}
 
-                        // ── BURN THE KEY: add to burned-keys registry ──
-                        // After this, the same token can NEVER be activated again.
-                        burn_key(&sec_dir, &compute_burn_hash(&key, &fingerprint));
+// ─── Ad-hoc reminders ───────────────────────────────────────────────────────
+// schedule["adhoc"]: [{id, title, body, atMs}], one-off "remind me at" entries not tied to
+// an agenda item. The desktop cron fires each one once (late, if the app was closed at the
+// time) and removes it; on mobile the OS fires the AOT copy and the next sync prunes it.
+// Focus mode holds them back until it ends, as for non-critical reminders.
+const MAX_ADHOC_REMINDERS: usize = 100;
+const MAX_ADHOC_TITLE_LEN: usize = 200;
+const MAX_ADHOC_BODY_LEN: usize = 1000;
+const MAX_ADHOC_AHEAD_MS: u64 = 365 * 86_400_000;
+
+fn adhoc_notification(entry: &Value) -> Option<PlannedNotification> {
+    let at = chrono::Local.timestamp_millis_opt(entry.get("atMs")?.as_i64()?).single()?;
+    Some(PlannedNotification {
+        at,
+        kind: "adhoc",
+        id: entry.get("id")?.as_str()?.to_string(),
+        title: format!("LexFlow — {}", entry.get("title")?.as_str()?),
+        body: entry.get("body").and_then(|b| b.as_str()).unwrap_or("").to_string(),
+        item: Value::Null,
+    })
+}
 
-                        json!({"success": true, "client": client})
-                    },
-                    Err(e) => json!({"success": false, "error": format!("Errore salvataggio: {}", e)}),
-                }
-        },
-        Err(e) => json!({"success": false, "error": format!("Errore cifratura: {}", e)}),
+/// When an ad-hoc reminder actually fires: at its time, or at the focus end if muted.
+fn adhoc_fire_time(
+    at: chrono::DateTime<chrono::Local>,
+    focus_end: Option<chrono::DateTime<chrono::Local>>,
+) -> chrono::DateTime<chrono::Local> {
+    match focus_end {
+        Some(f) if at < f => f,
+        _ => at,
     }
 }
 
-// ═══════════════════════════════════════════════════════════
-//  IMPORT / EXPORT
-// ═══════════════════════════════════════════════════════════
+/// Ad-hoc entries due by the minute containing `now`, and those still ahead.
+/// Unreadable entries end up in neither list.
+#[cfg_attr(any(target_os = "android", target_os = "ios"), allow(dead_code))]
+fn split_due_adhoc(entries: &[Value], now: chrono::DateTime<chrono::Local>) -> (Vec<PlannedNotification>, Vec<Value>) {
+    let minute = now.format("%Y-%m-%d %H:%M").to_string();
+    let mut due = Vec::new();
+    let mut ahead = Vec::new();
+    for entry in entries {
+        match adhoc_notification(entry) {
+            Some(n) if n.at.format("%Y-%m-%d %H:%M").to_string() <= minute => due.push(n),
+            Some(_) => ahead.push(entry.clone()),
+            None => {}
+        }
+    }
+    (due, ahead)
+}
 
-#[tauri::command]
-async fn export_vault(state: State<'_, AppState>, pwd: String, app: AppHandle) -> Result<Value, String> {
-    use tauri_plugin_dialog::DialogExt;
-    // SECURITY FIX (Level-8 A2): verify that `pwd` is the intended backup password by
-    // re-deriving it and checking against vault.verify BEFORE writing the backup.
-    // Without this check, a typo in `pwd` produces a backup encrypted with the wrong key
-    // that is permanently inaccessible — the user has no way to know until they need to restore.
-    // We verify by deriving the key and confirming it opens the vault's own verify tag.
+/// Schedule a one-off reminder at `at_ms` (unix ms). Lives in the schedule file, so it
+/// fires while the vault is locked, like agenda reminders.
+#[tauri::command]
+fn schedule_adhoc_reminder(app: AppHandle, state: State<AppState>, title: String, body: String, at_ms: u64) -> Value {
+    let title = title.trim().to_string();
+    if title.is_empty() || title.chars().count() > MAX_ADHOC_TITLE_LEN {
+        return json!({"success": false, "error": "Titolo del promemoria non valido"});
+    }
+    if body.chars().count() > MAX_ADHOC_BODY_LEN {
+        return json!({"success": false, "error": "Testo del promemoria troppo lungo"});
+    }
+    let now = now_ms();
+    if at_ms <= now || at_ms > now + MAX_ADHOC_AHEAD_MS {
+        return json!({"success": false, "error": "Orario del promemoria non valido"});
+    }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut id_bytes = [0u8; 8];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
+    let id = format!("adhoc-{}", hex::encode(id_bytes));
     {
-        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-        let salt_path = dir.join(VAULT_SALT_FILE);
-        if salt_path.exists() {
-            let vault_salt = fs::read(&salt_path).map_err(|e| e.to_string())?;
-            let vault_key_check = derive_secure_key(&pwd, &vault_salt)?;
-            let stored_verify = fs::read(dir.join(VAULT_VERIFY_FILE)).unwrap_or_default();
-            if !verify_hash_matches(&vault_key_check, &stored_verify) {
-                return Ok(json!({"success": false, "error": "Password errata: il backup non può essere creato con una password diversa da quella del vault."}));
+        let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        let mut schedule = read_notification_schedule(&dir).filter(|s| s.is_object()).unwrap_or(json!({}));
+        if !schedule.get("adhoc").map(|a| a.is_array()).unwrap_or(false) {
+            schedule["adhoc"] = json!([]);
+        }
+        if let Some(list) = schedule["adhoc"].as_array_mut() {
+            if list.len() >= MAX_ADHOC_REMINDERS {
+                return json!({"success": false, "error": "Troppi promemoria programmati"});
             }
+            list.push(json!({"id": id, "title": title, "body": body, "atMs": at_ms}));
+        }
+        if let Err(e) = write_notification_schedule(&dir, &schedule) {
+            return json!({"success": false, "error": e});
         }
     }
-    let data = read_vault_internal(&state)?;
-    let salt = (0..32).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
-    let key = derive_secure_key(&pwd, &salt)?;
-    // Zeroizing: plaintext vault azzerato dopo la cifratura
-    let plaintext = Zeroizing::new(serde_json::to_vec(&data).map_err(|e| e.to_string())?);
-    let encrypted = encrypt_data(&key, &plaintext)?;
-    let mut out = salt; out.extend(encrypted);
+    sync_notifications(&app, &dir);
+    json!({"success": true, "id": id, "atMs": at_ms})
+}
 
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    app.dialog().file().set_file_name("LexFlow_Backup.lex").save_file(move |file_path| {
-        let _ = tx.send(file_path);
-    });
-    let path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
-    if let Some(p) = path {
-        fs::write(p.into_path().unwrap(), out).map_err(|e| e.to_string())?;
-        Ok(json!({"success": true}))
-    } else { Ok(json!({"success": false})) }
+/// Desktop cron: remove the due ad-hoc reminders from the schedule and return them.
+/// If the removal cannot be saved nothing fires, rather than firing every minute.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn take_due_adhoc(app: &AppHandle, data_dir: &std::path::Path, now: chrono::DateTime<chrono::Local>) -> Vec<PlannedNotification> {
+    let state = app.state::<AppState>();
+    let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut schedule) = read_notification_schedule(&data_dir.to_path_buf()) else { return Vec::new() };
+    let entries = schedule.get("adhoc").and_then(|a| a.as_array()).cloned().unwrap_or_default();
+    let (due, ahead) = split_due_adhoc(&entries, now);
+    if ahead.len() == entries.len() { return due; }
+    schedule["adhoc"] = json!(ahead);
+    if let Err(e) = write_notification_schedule(data_dir, &schedule) {
+        lf_log!("[LexFlow Cron] Promemoria ad-hoc non rimossi: {}", e);
+        return Vec::new();
+    }
+    due
 }
 
-#[tauri::command]
-async fn import_vault(state: State<'_, AppState>, pwd: String, app: AppHandle) -> Result<Value, String> {
-    use tauri_plugin_dialog::DialogExt;
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    app.dialog()
-        .file()
-        .add_filter("LexFlow Backup", &["lex"])
-        .pick_file(move |file_path| {
-            let _ = tx.send(file_path);
-        });
-    let path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
-    if let Some(p) = path {
-        let raw = fs::read(p.into_path().unwrap()).map_err(|e| e.to_string())?;
-        // CAPACITY FIX (Gemini L4-3): increased from 50MB to 500MB to handle large
-        // law firm vaults (many practices + attached document paths). OOM risk is
-        // minimal: AES-GCM decryption is streaming-friendly and memory is freed immediately.
-        const MAX_IMPORT_SIZE: usize = 500 * 1024 * 1024;
-        if raw.len() > MAX_IMPORT_SIZE {
-            return Err("File troppo grande (max 500MB)".into());
-        }
-        // Validazione struttura minima: 32 byte salt + VAULT_MAGIC + nonce (12) + tag AES (16)
-        let min_len = 32 + VAULT_MAGIC.len() + NONCE_LEN + 16;
-        if raw.len() < min_len {
-            return Err("File non valido o corrotto (dimensione insufficiente)".into());
-        }
-        // Verifica magic nel blocco cifrato (dopo i 32 byte di salt)
-        let magic_start = 32;
-        if !raw[magic_start..].starts_with(VAULT_MAGIC) {
-            return Err("File non è un backup LexFlow valido".into());
-        }
-        let salt = &raw[..32];
-        let encrypted = &raw[32..];
-        let key = derive_secure_key(&pwd, salt)?;
-        let decrypted = decrypt_data(&key, encrypted).map_err(|_| "Password errata o file corrotto")?;
-        let val: Value = serde_json::from_slice(&decrypted).map_err(|_| "Struttura backup non valida")?;
-        // Validazione struttura dati vault
-        if val.get("practices").is_none() && val.get("agenda").is_none() {
-            return Err("Il file non contiene dati LexFlow validi".into());
-        }
-        // SECURITY FIX (Level-8 C2): import must work even if the vault is currently locked
-        // (e.g. first-run or forgotten password scenario).  Previously write_vault_internal
-        // required vault_key to already be set, causing a Catch-22: you can't unlock a lost
-        // vault, but you can't import a backup either.
-        //
-        // Fix: derive a new vault key from `pwd` + a fresh salt, write all vault files
-        // (salt, verify, vault.lex) from the backup's own credentials, then set vault_key.
-        // This means the imported vault's master password becomes `pwd` as entered here.
-        // SECURITY FIX (Gemini Audit): acquire write_mutex to prevent concurrent vault writes.
-        let _guard = state.write_mutex.lock().unwrap_or_else(|e| e.into_inner());
-        {
-            let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-            // Generate new vault salt for the imported vault
-            let mut new_salt = vec![0u8; 32];
-            rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut new_salt);
-            let new_key = derive_secure_key(&pwd, &new_salt)?;
-            // Write salt with mode 0600
-            secure_write(&dir.join(VAULT_SALT_FILE), &new_salt).map_err(|e| e.to_string())?;
-            // Write verify tag
-            let verify_tag = make_verify_tag(&new_key);
-            secure_write(&dir.join(VAULT_VERIFY_FILE), &verify_tag).map_err(|e| e.to_string())?;
-            // Set the vault key in state so write_vault_internal can use it
-            *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = Some(SecureKey(new_key));
-        }
-        write_vault_internal(&state, &val)?;
-        let _ = append_audit_log(&state, "Vault importato da backup");
-        // SECURITY FIX (Gemini Audit): safe password zeroing — no UB
-        zeroize_password(pwd);
-        Ok(json!({"success": true}))
-    } else { Ok(json!({"success": false, "cancelled": true})) }
+// ─── Snoozed reminders ──────────────────────────────────────────────────────
+// schedule["snoozes"]: {notification id → untilMs}. A snoozed reminder does not fire at its
+// own time; it fires once when the snooze ends (held back by focus mode like ad-hoc
+// reminders) and the entry is cleared: by the desktop cron when it fires, by the next
+// mobile sync once the OS has. Ids are the fired ones, "<id>@<date>" for recurring items.
+const MAX_SNOOZE_MINUTES: u32 = 24 * 60;
+
+fn snooze_entries(schedule: &Value) -> Vec<(String, chrono::DateTime<chrono::Local>)> {
+    schedule.get("snoozes").and_then(|s| s.as_object()).into_iter().flatten()
+        .filter_map(|(id, until)| Some((id.clone(), chrono::Local.timestamp_millis_opt(until.as_i64()?).single()?)))
+        .collect()
 }
 
-// ═══════════════════════════════════════════════════════════
-//  SYSTEM UTILITIES
-// ═══════════════════════════════════════════════════════════
+/// The reminder for `id` re-planned at `at`; None once the item is gone or completed.
+fn snoozed_notification(items: &[Value], settings: &Value, id: &str, at: chrono::DateTime<chrono::Local>) -> Option<PlannedNotification> {
+    let item = items.iter().find(|i| i.get("id").and_then(|v| v.as_str()) == Some(id))?;
+    let (event, _) = reminder_times(item, settings)?;
+    Some(reminder_notification(item, event, at))
+}
 
+/// Snooze the reminder `item_id` (as fired) for `minutes`, replacing an earlier snooze.
 #[tauri::command]
-fn open_path(app: AppHandle, path: String) {
-    #[cfg(not(target_os = "android"))]
+fn snooze_notification(app: AppHandle, state: State<AppState>, item_id: String, minutes: u32) -> Value {
+    if minutes == 0 || minutes > MAX_SNOOZE_MINUTES {
+        return json!({"success": false, "error": "Durata del posticipo non valida"});
+    }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let until_ms = now_ms() + minutes as u64 * 60_000;
     {
-        // SECURITY FIX (Gemini Audit v2): sanitize path to prevent RCE.
-        // Only allow opening paths that exist as files/directories on the local filesystem.
-        let p = std::path::Path::new(&path);
-        if !p.exists() || !p.is_absolute() {
-            eprintln!("[LexFlow] SECURITY: open_path refused non-existent/relative path: {:?}", path);
-            return;
+        let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(mut schedule) = read_notification_schedule(&dir).filter(|s| s.is_object()) else {
+            return json!({"success": false, "error": "Promemoria non trovato"});
+        };
+        let items = schedule.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+        let known = schedule_items_in_horizon(&items, chrono::Local::now()).iter()
+            .any(|i| i.get("id").and_then(|v| v.as_str()) == Some(item_id.as_str()));
+        if !known {
+            return json!({"success": false, "error": "Promemoria non trovato"});
         }
-        // Block URLs, scripts, and executables
-        let lower = path.to_lowercase();
-        if lower.starts_with("http") || lower.starts_with("smb:") || lower.starts_with("ftp:") ||
-           lower.ends_with(".sh") || lower.ends_with(".bat") || lower.ends_with(".cmd") ||
-           lower.ends_with(".exe") || lower.ends_with(".ps1") || lower.ends_with(".scpt") ||
-           lower.ends_with(".app") || lower.ends_with(".command") {
-            eprintln!("[LexFlow] SECURITY: open_path refused potentially dangerous path: {:?}", path);
-            return;
+        if !schedule.get("snoozes").map(|s| s.is_object()).unwrap_or(false) {
+            schedule["snoozes"] = json!({});
         }
-        use tauri_plugin_shell::ShellExt;
-        if let Err(e) = app.shell().open(&path, None) {
-            eprintln!("[LexFlow] Failed to open path: {:?}", e);
+        schedule["snoozes"][item_id.as_str()] = json!(until_ms);
+        if let Err(e) = write_notification_schedule(&dir, &schedule) {
+            return json!({"success": false, "error": e});
         }
     }
-    #[cfg(target_os = "android")]
-    { let _ = (app, path); }
+    sync_notifications(&app, &dir);
+    json!({"success": true, "id": item_id, "snoozeUntil": until_ms})
 }
 
-#[tauri::command]
-async fn select_file(app: AppHandle) -> Result<Option<Value>, String> {
-    use tauri_plugin_dialog::DialogExt;
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    app.dialog()
-        .file()
-        .add_filter("Documenti", &["pdf", "docx", "doc"])
-        .pick_file(move |file_path| {
-            let _ = tx.send(file_path);
-        });
-    let file = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
-    Ok(file.map(|f| {
-        let path = f.clone().into_path().unwrap();
-        let name = path.file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "file".to_string());
-        json!({"name": name, "path": path.to_string_lossy()})
-    }))
+/// Desktop cron: clear the snoozes ending by the minute containing `now` and return the
+/// reminders to fire. As with ad-hoc reminders, nothing fires if the clear cannot be saved.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn take_due_snoozes(app: &AppHandle, data_dir: &std::path::Path, now: chrono::DateTime<chrono::Local>) -> Vec<PlannedNotification> {
+    let state = app.state::<AppState>();
+    let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut schedule) = read_notification_schedule(&data_dir.to_path_buf()) else { return Vec::new() };
+    let minute = now.format("%Y-%m-%d %H:%M").to_string();
+    let due: Vec<(String, chrono::DateTime<chrono::Local>)> = snooze_entries(&schedule).into_iter()
+        .filter(|(_, until)| until.format("%Y-%m-%d %H:%M").to_string() <= minute)
+        .collect();
+    if due.is_empty() { return Vec::new(); }
+    if let Some(snoozes) = schedule.get_mut("snoozes").and_then(|s| s.as_object_mut()) {
+        for (id, _) in &due { snoozes.remove(id); }
+    }
+    if let Err(e) = write_notification_schedule(data_dir, &schedule) {
+        lf_log!("[LexFlow Cron] Posticipi non rimossi: {}", e);
+        return Vec::new();
+    }
+    let items = schedule.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let items = schedule_items_in_horizon(&items, now);
+    let settings = read_settings(data_dir);
+    due.iter().filter_map(|(id, _)| snoozed_notification(&items, &settings, id, now)).collect()
 }
 
-#[tauri::command]
-async fn select_folder(app: AppHandle) -> Result<Option<String>, String> {
-    use tauri_plugin_dialog::DialogExt;
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    // Android non supporta pick_folder — fallback a pick_file
-    #[cfg(not(target_os = "android"))]
-    app.dialog()
-        .file()
-        .pick_folder(move |folder_path| {
-            let _ = tx.send(folder_path);
-        });
-    #[cfg(target_os = "android")]
-    app.dialog()
-        .file()
-        .pick_file(move |folder_path| {
-            let _ = tx.send(folder_path);
-        });
-    let folder = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
-    Ok(folder.map(|f| f.into_path().unwrap().to_string_lossy().to_string()))
+// ─── Focus-deferred reminders ───────────────────────────────────────────────
+// schedule["focusDeferred"]: [{eventAtMs, item}], reminders the desktop cron held back while
+// focus mode was on. Kept in the schedule file like ad-hoc entries and snoozes, so a
+// restart during focus does not lose them; the cron re-fires and clears them once focus
+// ends, skipping events that have already started.
+#[cfg_attr(any(target_os = "android", target_os = "ios"), allow(dead_code))]
+fn focus_deferred_entries(schedule: &Value) -> Vec<(chrono::DateTime<chrono::Local>, Value)> {
+    schedule.get("focusDeferred").and_then(|d| d.as_array()).into_iter().flatten()
+        .filter_map(|e| Some((
+            chrono::Local.timestamp_millis_opt(e.get("eventAtMs")?.as_i64()?).single()?,
+            e.get("item").filter(|i| i.is_object())?.clone(),
+        )))
+        .collect()
 }
 
-#[tauri::command]
-fn window_close(app: AppHandle, state: State<AppState>) {
-    *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
-    #[cfg(not(target_os = "android"))]
-    if let Some(w) = app.get_webview_window("main") { let _ = w.hide(); }
-    #[cfg(target_os = "android")]
-    { let _ = app; }
+/// Add a held-back reminder, once per (item id, event time).
+#[cfg_attr(any(target_os = "android", target_os = "ios"), allow(dead_code))]
+fn push_focus_deferred(schedule: &mut Value, event_time: chrono::DateTime<chrono::Local>, item: Value) {
+    if !schedule.get("focusDeferred").map(|d| d.is_array()).unwrap_or(false) {
+        schedule["focusDeferred"] = json!([]);
+    }
+    let at = event_time.timestamp_millis();
+    let Some(list) = schedule["focusDeferred"].as_array_mut() else { return };
+    let id = item.get("id").cloned();
+    if list.iter().any(|e| e.get("eventAtMs").and_then(|v| v.as_i64()) == Some(at) && e["item"].get("id").cloned() == id) {
+        return;
+    }
+    list.push(json!({"eventAtMs": at, "item": item}));
 }
 
-#[tauri::command]
-fn get_app_version(app: AppHandle) -> String { app.package_info().version.to_string() }
-
-#[tauri::command]
-fn is_mac() -> bool { cfg!(target_os = "macos") }
-
-/// Restituisce la piattaforma corrente al frontend
-#[tauri::command]
-fn get_platform() -> String {
-    #[cfg(target_os = "android")] { "android".to_string() }
-    #[cfg(target_os = "ios")]     { "ios".to_string() }
-    #[cfg(target_os = "macos")]   { "macos".to_string() }
-    #[cfg(target_os = "windows")] { "windows".to_string() }
-    #[cfg(target_os = "linux")]   { "linux".to_string() }
-    #[cfg(not(any(target_os="android",target_os="ios",target_os="macos",target_os="windows",target_os="linux")))]
-    { "unknown".to_string() }
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn defer_for_focus(app: &AppHandle, data_dir: &std::path::Path, event_time: chrono::DateTime<chrono::Local>, item: Value) {
+    let state = app.state::<AppState>();
+    let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut schedule) = read_notification_schedule(&data_dir.to_path_buf()).filter(|s| s.is_object()) else { return };
+    push_focus_deferred(&mut schedule, event_time, item);
+    if let Err(e) = write_notification_schedule(data_dir, &schedule) {
+        lf_log!("[LexFlow Cron] Promemoria rinviato non salvato: {}", e);
+    }
 }
 
-#[tauri::command]
-async fn select_pdf_save_path(app: AppHandle, default_name: String) -> Result<Option<String>, String> {
-    use tauri_plugin_dialog::DialogExt;
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    app.dialog()
-        .file()
-        .add_filter("PDF", &["pdf"])
-        .set_file_name(&default_name)
-        .save_file(move |file_path| {
-            let _ = tx.send(file_path);
-        });
-    let file_path = rx.await.map_err(|e| format!("Dialog error: {}", e))?;
-    match file_path {
-        Some(fp) => {
-            let path = fp.into_path().map_err(|e| format!("Path error: {:?}", e))?;
-            Ok(Some(path.to_string_lossy().into_owned()))
-        },
-        None => Ok(None),
+/// Desktop cron, focus over: clear the held-back reminders and return them. As with
+/// snoozes, nothing fires if the clear cannot be saved.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn take_focus_deferred(app: &AppHandle, data_dir: &std::path::Path) -> Vec<(chrono::DateTime<chrono::Local>, Value)> {
+    let state = app.state::<AppState>();
+    let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut schedule) = read_notification_schedule(&data_dir.to_path_buf()) else { return Vec::new() };
+    if schedule.get("focusDeferred").is_none() { return Vec::new(); }
+    let entries = focus_deferred_entries(&schedule);
+    if let Some(obj) = schedule.as_object_mut() { obj.remove("focusDeferred"); }
+    if let Err(e) = write_notification_schedule(data_dir, &schedule) {
+        lf_log!("[LexFlow Cron] Promemoria rinviati non rimossi: {}", e);
+        return Vec::new();
     }
+    entries
 }
 
-// ═══════════════════════════════════════════════════════════
-//  NOTIFICATIONS
-// ═══════════════════════════════════════════════════════════
+/// What the cron fires in the minute containing `now`, before focus-mode filtering:
+/// briefings first, then reminders in schedule order.
+#[cfg_attr(any(target_os = "android", target_os = "ios"), allow(dead_code))]
+fn due_notifications(briefing_times: &[Value], items: &[Value], settings: &Value, now: chrono::DateTime<chrono::Local>) -> Vec<PlannedNotification> {
+    let items = &schedule_items_in_horizon(items, now);
+    let minute = now.format("%Y-%m-%d %H:%M").to_string();
+    let same_minute = |at: &chrono::DateTime<chrono::Local>| at.format("%Y-%m-%d %H:%M").to_string() == minute;
+    let mut out: Vec<PlannedNotification> = briefing_times.iter()
+        .filter_map(|bt| briefing_notification(items, now.date_naive(), bt.as_str()?))
+        .filter(|b| same_minute(&b.at))
+        .collect();
+    for item in items {
+        if let Some((event, remind)) = reminder_times(item, settings) {
+            if same_minute(&remind) { out.push(reminder_notification(item, event, remind)); }
+        }
+    }
+    out
+}
 
-#[tauri::command]
-fn send_notification(app: AppHandle, title: String, body: String) {
-    // Even though Tauri IPC commands run on the main thread context, we
-    // explicitly use run_on_main_thread to guarantee the NSRunLoop is active
-    // for the XPC call to usernoted (macOS Notification Center daemon).
-    let t = title.clone();
-    let b = body.clone();
-    let ah = app.clone();
-    let _ = app.run_on_main_thread(move || {
-        use tauri_plugin_notification::NotificationExt;
-        if let Err(e) = ah.notification().builder().title(&t).body(&b).show() {
-            eprintln!("[LexFlow] Native notification failed: {:?}, emitting event fallback", e);
-            let _ = ah.emit("show-notification", serde_json::json!({"title": t, "body": b}));
+/// Notifications due in any of `minutes` (oldest first, the last one being the current
+/// minute). Reminders from missed minutes are reworded as of `now`; of the missed
+/// briefings only the latest is kept, and only if none is due now — an older digest of
+/// the day is noise.
+fn catch_up_notifications(
+    briefing_times: &[Value],
+    items: &[Value],
+    settings: &Value,
+    minutes: &[chrono::DateTime<chrono::Local>],
+    now: chrono::DateTime<chrono::Local>,
+) -> Vec<PlannedNotification> {
+    let Some((&current, missed)) = minutes.split_last() else { return Vec::new() };
+    let mut missed_briefing = None;
+    let mut out = Vec::new();
+    for &minute in missed {
+        for n in due_notifications(briefing_times, items, settings, minute) {
+            if n.kind == "briefing" { missed_briefing = Some(n); continue; }
+            match reminder_times(&n.item, settings) {
+                Some((event, _)) => out.push(reminder_notification(&n.item, event, now)),
+                None => out.push(n),
+            }
         }
-    });
+    }
+    let due_now = due_notifications(briefing_times, items, settings, current);
+    if let Some(b) = missed_briefing.filter(|_| !due_now.iter().any(|n| n.kind == "briefing")) {
+        out.insert(0, b);
+    }
+    out.extend(due_now);
+    out
 }
 
-/// Test notification — dev-only command to verify the notification pipeline.
-/// Always dispatches via run_on_main_thread for NSRunLoop guarantee.
-#[tauri::command]
-fn test_notification(app: AppHandle) -> bool {
-    let ah = app.clone();
-    match app.run_on_main_thread(move || {
-        use tauri_plugin_notification::NotificationExt;
-        if let Err(e) = ah.notification().builder()
-            .title("LexFlow — Test Notifica")
-            .body("Le notifiche funzionano correttamente!")
-            .show()
-        {
-            eprintln!("[LexFlow] Test notification failed: {:?}", e);
+/// The next `count` notifications after `now`, in firing order, with focus mode applied
+/// the way the cron/AOT paths apply it.
+fn compute_upcoming_notifications(
+    schedule: &Value,
+    settings: &Value,
+    now: chrono::DateTime<chrono::Local>,
+    focus_end: Option<chrono::DateTime<chrono::Local>>,
+    count: usize,
+) -> Vec<Value> {
+    let briefing_times = schedule.get("briefingTimes").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let items = schedule.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let items = schedule_items_in_horizon(&items, now);
+    let horizon = now + chrono::Duration::days(UPCOMING_HORIZON_DAYS);
+    let mut planned: Vec<(PlannedNotification, bool)> = Vec::new();
+    for day in 0..UPCOMING_HORIZON_DAYS {
+        let date = now.date_naive() + chrono::Duration::days(day);
+        for bt in briefing_times.iter().filter_map(|b| b.as_str()) {
+            let Some(b) = briefing_notification(&items, date, bt) else { continue };
+            if focus_end.map(|f| b.at < f).unwrap_or(false) { continue; }
+            planned.push((b, false));
         }
-    }) {
-        Ok(_) => true,
-        Err(_) => false,
     }
+    let snoozes = snooze_entries(schedule);
+    for item in &items {
+        let Some((event, remind)) = reminder_times(item, settings) else { continue };
+        if event > horizon { continue; }
+        let id = item.get("id").and_then(|i| i.as_str()).unwrap_or("");
+        if let Some((_, until)) = snoozes.iter().find(|(s, _)| s == id) {
+            let at = adhoc_fire_time(*until, focus_end);
+            planned.push((reminder_notification(item, event, at), at != *until));
+            continue;
+        }
+        let Some(at) = focus_adjusted_remind_time(item, event, remind, focus_end) else { continue };
+        planned.push((reminder_notification(item, event, at), at != remind));
+    }
+    for entry in schedule.get("adhoc").and_then(|v| v.as_array()).into_iter().flatten() {
+        let Some(mut n) = adhoc_notification(entry) else { continue };
+        let at = adhoc_fire_time(n.at, focus_end);
+        let deferred = at != n.at;
+        n.at = at;
+        planned.push((n, deferred));
+    }
+    planned.retain(|(p, _)| p.at > now && p.at <= horizon);
+    // Stable: within one minute briefings stay ahead of reminders, as in the cron
+    planned.sort_by_key(|(p, _)| p.at.format("%Y-%m-%d %H:%M").to_string());
+    planned.into_iter().take(count).map(|(p, deferred)| json!({
+        "at": p.at.to_rfc3339(),
+        "atMs": p.at.timestamp_millis(),
+        "kind": p.kind,
+        "id": p.id,
+        "title": p.title,
+        "body": p.body,
+        "deferredByFocus": deferred,
+    })).collect()
 }
 
+/// Next fire events for the "upcoming reminders" view. Reads only local-key files,
+/// so it works while the vault is locked.
 #[tauri::command]
-fn sync_notification_schedule(app: AppHandle, state: State<AppState>, schedule: Value) -> bool {
+fn upcoming_notifications(state: State<AppState>, count: usize) -> Value {
     let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
-    let key = get_local_encryption_key();
-    let plaintext = serde_json::to_vec(&schedule).unwrap_or_default();
-    match encrypt_data(&key, &plaintext) {
-        Ok(encrypted) => {
-            let written = atomic_write_with_sync(&dir.join(NOTIF_SCHEDULE_FILE), &encrypted).is_ok();
-            if written {
-                // ── TRIGGER: re-sync OS notification queue after data change ──
-                sync_notifications(&app, &dir);
-            }
-            written
-        },
-        Err(_) => false,
+    let Some(schedule) = read_notification_schedule(&dir) else { return json!([]) };
+    let settings = read_settings(&dir);
+    let focus_end = focus_until_ms(&dir)
+        .and_then(|ms| chrono::Local.timestamp_millis_opt(ms as i64).single());
+    json!(compute_upcoming_notifications(&schedule, &settings, chrono::Local::now(), focus_end, count.min(MAX_UPCOMING)))
+}
+
+// ─── Desktop cron wake strategy ─────────────────────────────────────────────
+// "poll" wakes every minute; "next-wake" sleeps until the next planned fire (capped so
+// schedule edits are still picked up). "auto" polls on mains power and switches to
+// next-wake on battery, where a once-a-minute timer keeps the CPU out of deep idle.
+const NOTIFICATION_STRATEGIES: [&str; 3] = ["auto", "poll", "next-wake"];
+const CRON_POLL_SECS: u64 = 60;
+const MIN_CRON_POLL_SECS: u64 = 15;
+const CRON_MAX_WAKE_SECS: u64 = 15 * 60;
+/// After a sleep, missed minutes are replayed at most this far back.
+const CRON_MAX_CATCHUP_MINUTES: i64 = 24 * 60;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+const POWER_RECHECK_SECS: u64 = 5 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery found or platform tool unavailable: treated as mains power.
+    Unknown,
+}
+
+impl PowerSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            PowerSource::Ac => "ac",
+            PowerSource::Battery => "battery",
+            PowerSource::Unknown => "unknown",
+        }
     }
 }
 
-/// Decrypt notification schedule with local machine key
-fn read_notification_schedule(data_dir: &PathBuf) -> Option<Value> {
-    let path = data_dir.join(NOTIF_SCHEDULE_FILE);
-    if !path.exists() { return None; }
-    // SECURITY FIX (Level-8 C5): size guard before reading into RAM.
-    if let Ok(meta) = path.metadata() {
-        if meta.len() > MAX_SETTINGS_FILE_SIZE {
-            eprintln!("[LexFlow] Notification schedule file troppo grande ({} bytes) — ignorato", meta.len());
-            return None;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NotificationStrategy {
+    Poll,
+    NextWake,
+}
+
+impl NotificationStrategy {
+    fn as_str(self) -> &'static str {
+        match self {
+            NotificationStrategy::Poll => "poll",
+            NotificationStrategy::NextWake => "next-wake",
         }
     }
-    // SECURITY FIX (Gemini Audit): use migration-aware decryption (hostname→machine_id)
-    if let Some(decrypted) = decrypt_local_with_migration(&path) {
-        return serde_json::from_slice(&decrypted).ok();
+}
+
+/// Configured strategy, falling back to "auto" for missing or unknown values.
+fn notification_strategy_setting(settings: &Value) -> &str {
+    settings.get("notificationStrategy").and_then(|v| v.as_str())
+        .filter(|s| NOTIFICATION_STRATEGIES.contains(s))
+        .unwrap_or("auto")
+}
+
+/// "poll" interval from settings.cronIntervalSecs, clamped to [15 s, 15 min].
+fn cron_poll_secs(settings: &Value) -> u64 {
+    settings.get("cronIntervalSecs").and_then(|v| v.as_u64())
+        .map(|s| s.clamp(MIN_CRON_POLL_SECS, CRON_MAX_WAKE_SECS))
+        .unwrap_or(CRON_POLL_SECS)
+}
+
+/// Minutes the cron has to check at `now`: every minute after `last` up to the current
+/// one, so reminders due while the machine slept are still delivered, capped at
+/// CRON_MAX_CATCHUP_MINUTES. Empty within an already processed minute; a clock moved
+/// backwards restarts from the current minute.
+fn cron_minutes_to_process(
+    last: Option<chrono::DateTime<chrono::Local>>,
+    now: chrono::DateTime<chrono::Local>,
+) -> Vec<chrono::DateTime<chrono::Local>> {
+    use chrono::Timelike as _;
+    let current = now.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(now);
+    let first = match last {
+        Some(last) if last == current => return Vec::new(),
+        Some(last) if last < current => (last + chrono::Duration::minutes(1))
+            .max(current - chrono::Duration::minutes(CRON_MAX_CATCHUP_MINUTES - 1)),
+        _ => current,
+    };
+    let count = (current - first).num_minutes() + 1;
+    (0..count).map(|i| first + chrono::Duration::minutes(i)).collect()
+}
+
+fn select_notification_strategy(setting: &str, power: PowerSource) -> NotificationStrategy {
+    match (setting, power) {
+        ("poll", _) => NotificationStrategy::Poll,
+        ("next-wake", _) => NotificationStrategy::NextWake,
+        (_, PowerSource::Battery) => NotificationStrategy::NextWake,
+        _ => NotificationStrategy::Poll,
     }
-    // Migration: old plaintext format → re-encrypt
-    if let Ok(encrypted) = fs::read(&path) {
-        if let Ok(text) = std::str::from_utf8(&encrypted) {
-            if let Ok(val) = serde_json::from_str::<Value>(text) {
-                let key = get_local_encryption_key();
-                if let Ok(enc) = encrypt_data(&key, &serde_json::to_vec(&val).unwrap_or_default()) {
-                    let _ = atomic_write_with_sync(&path, &enc);
+}
+
+/// How long the cron sleeps before its next check. Next-wake lands one second into
+/// the fire minute so the minute-granular matching in due_notifications() sees it.
+fn cron_wait(
+    strategy: NotificationStrategy,
+    poll_secs: u64,
+    now: chrono::DateTime<chrono::Local>,
+    next_fire: Option<chrono::DateTime<chrono::Local>>,
+) -> std::time::Duration {
+    let secs = match (strategy, next_fire) {
+        (NotificationStrategy::Poll, _) => poll_secs,
+        (NotificationStrategy::NextWake, None) => CRON_MAX_WAKE_SECS,
+        (NotificationStrategy::NextWake, Some(at)) => {
+            ((at - now).num_seconds() + 1).clamp(1, CRON_MAX_WAKE_SECS as i64) as u64
+        }
+    };
+    std::time::Duration::from_secs(secs)
+}
+
+fn detect_power_source() -> PowerSource {
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(entries) = fs::read_dir("/sys/class/power_supply") else { return PowerSource::Unknown };
+        let mut has_battery = false;
+        for entry in entries.flatten() {
+            let read = |f: &str| fs::read_to_string(entry.path().join(f)).map(|s| s.trim().to_string()).unwrap_or_default();
+            match read("type").as_str() {
+                "Mains" if read("online") == "1" => return PowerSource::Ac,
+                "Battery" => {
+                    has_battery = true;
+                    if read("status") == "Discharging" { return PowerSource::Battery; }
                 }
-                return Some(val);
+                _ => {}
             }
         }
+        if has_battery { PowerSource::Ac } else { PowerSource::Unknown }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let Ok(out) = std::process::Command::new("/usr/bin/pmset").args(["-g", "batt"]).output() else {
+            return PowerSource::Unknown;
+        };
+        let text = String::from_utf8_lossy(&out.stdout);
+        if text.contains("'Battery Power'") { PowerSource::Battery }
+        else if text.contains("'AC Power'") { PowerSource::Ac }
+        else { PowerSource::Unknown }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        // BatteryStatus: 1 = discharging, 2 = on AC; empty when there is no battery
+        let Ok(out) = std::process::Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", "(Get-CimInstance Win32_Battery).BatteryStatus"])
+            .output() else { return PowerSource::Unknown };
+        match String::from_utf8_lossy(&out.stdout).lines().next().map(|l| l.trim()) {
+            Some("1") => PowerSource::Battery,
+            Some("") | None => PowerSource::Unknown,
+            Some(_) => PowerSource::Ac,
+        }
     }
-    None
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        PowerSource::Unknown
+    }
+}
+
+fn notification_strategy_status(settings: &Value, power: PowerSource) -> Value {
+    let setting = notification_strategy_setting(settings);
+    json!({
+        "strategy": setting,
+        "effective": select_notification_strategy(setting, power).as_str(),
+        "power": power.as_str(),
+    })
+}
+
+#[tauri::command]
+fn get_notification_strategy(state: State<AppState>) -> Value {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    notification_strategy_status(&read_settings(&dir), detect_power_source())
+}
+
+/// Overrides the desktop cron wake strategy ("auto", "poll" or "next-wake").
+/// The running cron picks it up on its next wake. No effect on mobile (OS-scheduled).
+#[tauri::command]
+fn set_notification_strategy(state: State<AppState>, strategy: String) -> Result<Value, String> {
+    if !NOTIFICATION_STRATEGIES.contains(&strategy.as_str()) {
+        return Err("Strategia di notifica non valida".into());
+    }
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut settings = read_settings(&dir);
+    if !settings.is_object() { settings = json!({}); }
+    settings["notificationStrategy"] = json!(strategy);
+    if !write_settings(&dir, &settings) {
+        return Err("Impossibile salvare le impostazioni".into());
+    }
+    Ok(notification_strategy_status(&settings, detect_power_source()))
 }
 
 // ═══════════════════════════════════════════════════════════
@@ -2225,12 +13048,90 @@ fn read_notification_schedule(data_dir: &PathBuf) -> Option<Value> {
 //
 // DESKTOP (macOS/Windows/Linux): tauri-plugin-notification (via notify-rust)
 //   IGNORES Schedule::At and fires immediately.  Instead we run a single async
-//   Tokio cron job that wakes once per minute, checks the JSON state, and fires
+//   Tokio cron job that wakes once per minute (or, in next-wake mode, at the next
+//   planned fire), checks the JSON state, and fires
 //   notifications in real-time.  Zero threads, zero sleeps, zero CPU waste.
 //
 //   On macOS the App Nap hack (NSProcessInfo.beginActivityWithOptions) prevents
 //   the OS from freezing the async timer when the window is hidden.
 
+// ── MOBILE: pending-notification capacity ─────────────────────────────────
+// MAX_SCHEDULED is a budget, not the platform limit (iOS keeps ~64 pending, some Android
+// builds fewer). Every schedule attempt goes through a ScheduleTally: after
+// SCHEDULE_FAILURE_BACKOFF consecutive show() errors the OS is taken to be full, the
+// capacity drops to what was accepted and the remaining candidates count as dropped.
+const MAX_SCHEDULED: usize = 60;
+const SCHEDULE_FAILURE_BACKOFF: usize = 3;
+
+#[cfg_attr(not(any(target_os = "android", target_os = "ios")), allow(dead_code))]
+struct ScheduleTally {
+    capacity: usize,
+    requested: usize,
+    scheduled: usize,
+    failed: usize,
+    dropped: usize,
+    consecutive_failures: usize,
+    limit_detected: bool,
+}
+
+#[cfg_attr(not(any(target_os = "android", target_os = "ios")), allow(dead_code))]
+impl ScheduleTally {
+    fn new(capacity: usize) -> Self {
+        ScheduleTally { capacity, requested: 0, scheduled: 0, failed: 0, dropped: 0, consecutive_failures: 0, limit_detected: false }
+    }
+
+    /// Count one candidate and, if there is room, run `schedule` (true = accepted by the OS).
+    fn attempt(&mut self, schedule: impl FnOnce() -> bool) -> bool {
+        self.requested += 1;
+        if self.scheduled >= self.capacity {
+            self.dropped += 1;
+            return false;
+        }
+        if schedule() {
+            self.scheduled += 1;
+            self.consecutive_failures = 0;
+            return true;
+        }
+        self.failed += 1;
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= SCHEDULE_FAILURE_BACKOFF {
+            self.capacity = self.scheduled;
+            self.limit_detected = true;
+        }
+        false
+    }
+
+    fn report(&self) -> Value {
+        json!({
+            "requested": self.requested,
+            "scheduled": self.scheduled,
+            "failed": self.failed,
+            "dropped": self.dropped,
+            "capacity": self.capacity,
+            "budget": MAX_SCHEDULED,
+            "limitDetected": self.limit_detected,
+            "syncedAt": chrono::Local::now().to_rfc3339(),
+        })
+    }
+}
+
+/// Scheduled vs. requested in the last mobile sync (runs one if none happened yet).
+/// Desktop fires from the cron job and has no pending-notification cap.
+#[tauri::command]
+fn notification_capacity_report(state: State<AppState>, app: AppHandle) -> Value {
+    if cfg!(not(any(target_os = "android", target_os = "ios"))) {
+        return json!({"aot": false, "budget": MAX_SCHEDULED, "limitDetected": false});
+    }
+    if state.notification_capacity.lock().unwrap_or_else(|e| e.into_inner()).is_none() {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        sync_notifications(&app, &dir);
+    }
+    let report = state.notification_capacity.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut report = report.unwrap_or_else(|| json!({"requested": 0, "scheduled": 0, "dropped": 0}));
+    report["aot"] = json!(true);
+    report
+}
+
 // ── MOBILE: Native AOT scheduling ─────────────────────────────────────────
 #[cfg(any(target_os = "android", target_os = "ios"))]
 fn sync_notifications(app: &AppHandle, data_dir: &std::path::Path) {
@@ -2238,9 +13139,9 @@ fn sync_notifications(app: &AppHandle, data_dir: &std::path::Path) {
 
     // Cancel all pending
     if let Err(e) = app.notification().cancel_all() {
-        eprintln!("[LexFlow Sync] cancel_all error (non-critical): {:?}", e);
+        lf_log!("[LexFlow Sync] cancel_all error (non-critical): {:?}", e);
     } else {
-        eprintln!("[LexFlow Sync] All pending notifications cancelled ✓");
+        lf_log!("[LexFlow Sync] All pending notifications cancelled ✓");
     }
 
     let schedule_data: serde_json::Value = match read_notification_schedule(
@@ -2248,7 +13149,7 @@ fn sync_notifications(app: &AppHandle, data_dir: &std::path::Path) {
     ) {
         Some(v) => v,
         None => {
-            eprintln!("[LexFlow Sync] No schedule file — nothing to schedule");
+            lf_log!("[LexFlow Sync] No schedule file — nothing to schedule");
             return;
         }
     };
@@ -2257,12 +13158,14 @@ fn sync_notifications(app: &AppHandle, data_dir: &std::path::Path) {
         .and_then(|v| v.as_array()).cloned().unwrap_or_default();
     let items = schedule_data.get("items")
         .and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let settings = read_settings(data_dir);
 
     let now = chrono::Local::now();
-    let tomorrow = (now + chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
-    const MAX_SCHEDULED: i32 = 60;
-    let horizon = now + chrono::Duration::days(14);
-    let mut scheduled_count: i32 = 0;
+    let items = schedule_items_in_horizon(&items, now);
+    let focus_end = focus_until_ms(data_dir)
+        .and_then(|ms| chrono::Local.timestamp_millis_opt(ms as i64).single());
+    let horizon = now + chrono::Duration::days(UPCOMING_HORIZON_DAYS);
+    let mut tally = ScheduleTally::new(MAX_SCHEDULED);
 
     let chrono_to_offset = |dt: chrono::DateTime<chrono::Local>| -> Option<time::OffsetDateTime> {
         let ts = dt.timestamp();
@@ -2282,136 +13185,101 @@ fn sync_notifications(app: &AppHandle, data_dir: &std::path::Path) {
 
     // Schedule briefings
     for bt in &briefing_times {
-        if scheduled_count >= MAX_SCHEDULED { break; }
         let time_str = match bt.as_str() {
-            Some(s) if s.len() >= 5 => s,
-            _ => continue,
+            Some(s) => s,
+            None => continue,
         };
         for day_offset in 0..=1i64 {
-            if scheduled_count >= MAX_SCHEDULED { break; }
-            let target_date = now.date_naive() + chrono::Duration::days(day_offset);
-            let date_str = target_date.format("%Y-%m-%d").to_string();
-            let dt_str = format!("{} {}", date_str, time_str);
-            let target_dt = match chrono::NaiveDateTime::parse_from_str(&dt_str, "%Y-%m-%d %H:%M") {
-                Ok(dt) => dt, Err(_) => continue,
+            let briefing = match briefing_notification(&items, now.date_naive() + chrono::Duration::days(day_offset), time_str) {
+                Some(b) => b, None => continue,
             };
-            let target_local = match chrono::Local.from_local_datetime(&target_dt).single() {
+            if briefing.at <= now || briefing.at > horizon { continue; }
+            // Focus mode: briefings inside the focus window are muted
+            if focus_end.map(|f| briefing.at < f).unwrap_or(false) { continue; }
+            let offset_dt = match chrono_to_offset(briefing.at) {
                 Some(t) => t, None => continue,
             };
-            if target_local <= now || target_local > horizon { continue; }
-            let offset_dt = match chrono_to_offset(target_local) {
-                Some(t) => t, None => continue,
-            };
-            let briefing_hour: u32 = time_str.split(':').next()
-                .and_then(|h| h.parse().ok()).unwrap_or(8);
-            let (filter_date, time_from, period_label) = if briefing_hour < 12 {
-                (date_str.as_str(), "00:00", "oggi")
-            } else if briefing_hour < 18 {
-                (date_str.as_str(), "13:00", "questo pomeriggio")
-            } else {
-                if day_offset == 0 { (&tomorrow as &str, "00:00", "domani") }
-                else { continue; }
-            };
-            let relevant_count = items.iter().filter(|i| {
-                let d = i.get("date").and_then(|d| d.as_str()).unwrap_or("");
-                let t = i.get("time").and_then(|t| t.as_str()).unwrap_or("00:00");
-                let done = i.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
-                d == filter_date && !done && t >= time_from
-            }).count();
-            let title = if relevant_count == 0 {
-                format!("LexFlow — Nessun impegno {}", period_label)
-            } else {
-                format!("LexFlow — {} impegn{} {}", relevant_count,
-                    if relevant_count == 1 { "o" } else { "i" }, period_label)
-            };
-            let body_str = if relevant_count == 0 {
-                format!("Nessun impegno in programma per {}.", period_label)
-            } else {
-                let mut relevant_items: Vec<&serde_json::Value> = items.iter()
-                    .filter(|i| {
-                        let d = i.get("date").and_then(|d| d.as_str()).unwrap_or("");
-                        let t = i.get("time").and_then(|t| t.as_str()).unwrap_or("00:00");
-                        let done = i.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
-                        d == filter_date && !done && t >= time_from
-                    }).collect();
-                relevant_items.sort_by(|a, b| {
-                    let ta = a.get("time").and_then(|v| v.as_str()).unwrap_or("");
-                    let tb = b.get("time").and_then(|v| v.as_str()).unwrap_or("");
-                    ta.cmp(tb)
-                });
-                let mut lines: Vec<String> = Vec::new();
-                for item in relevant_items.iter().take(4) {
-                    let t = item.get("time").and_then(|v| v.as_str()).unwrap_or("");
-                    let name = item.get("title").and_then(|v| v.as_str()).unwrap_or("Impegno");
-                    if !t.is_empty() { lines.push(format!("• {} — {}", t, name)); }
-                    else { lines.push(format!("• {}", name)); }
-                }
-                if relevant_count > 4 { lines.push(format!("  …e altri {}", relevant_count - 4)); }
-                lines.join("\n")
-            };
-            let notif_id = hash_id(&format!("briefing-{}-{}", date_str, time_str));
+            let notif_id = hash_id(&format!("briefing-{}", briefing.id.replace(' ', "-")));
             let sched = tauri_plugin_notification::Schedule::At {
                 date: offset_dt, repeating: false, allow_while_idle: true,
             };
-            if app.notification().builder().id(notif_id).title(&title).body(&body_str)
-                .schedule(sched).show().is_ok() {
-                scheduled_count += 1;
-            }
+            tally.attempt(|| app.notification().builder().id(notif_id).title(&briefing.title).body(&briefing.body)
+                .schedule(sched).show().is_ok());
         }
     }
 
-    // Schedule per-item reminders
+    // Schedule per-item reminders; a snoozed one fires at the end of its snooze instead
+    let snoozes = snooze_entries(&schedule_data);
+    let mut fired_snoozes = 0;
     for item in &items {
-        if scheduled_count >= MAX_SCHEDULED { break; }
-        let item_date = item.get("date").and_then(|d| d.as_str()).unwrap_or("");
-        let item_time = item.get("time").and_then(|t| t.as_str()).unwrap_or("");
-        let item_title = item.get("title").and_then(|t| t.as_str()).unwrap_or("Impegno");
-        let item_id = item.get("id").and_then(|i| i.as_str()).unwrap_or("");
-        let completed = item.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
-        if completed || item_time.len() < 5 { continue; }
-        let item_dt_str = format!("{} {}", item_date, item_time);
-        let item_dt = match chrono::NaiveDateTime::parse_from_str(&item_dt_str, "%Y-%m-%d %H:%M") {
-            Ok(dt) => dt, Err(_) => continue,
-        };
-        let item_local = match chrono::Local.from_local_datetime(&item_dt).single() {
+        let (item_local, remind_time) = match reminder_times(item, &settings) {
             Some(t) => t, None => continue,
         };
         if item_local > horizon { continue; }
-        let custom_remind_time = item.get("customRemindTime")
-            .and_then(|v| v.as_str()).filter(|s| s.len() >= 5);
-        let remind_min = item.get("remindMinutes").and_then(|v| v.as_i64()).unwrap_or(30);
-        let remind_time = if let Some(crt) = custom_remind_time {
-            let crt_str = format!("{} {}", item_date, crt);
-            chrono::NaiveDateTime::parse_from_str(&crt_str, "%Y-%m-%d %H:%M")
-                .ok().and_then(|dt| chrono::Local.from_local_datetime(&dt).single())
-                .unwrap_or(item_local - chrono::Duration::minutes(remind_min))
-        } else {
-            item_local - chrono::Duration::minutes(remind_min)
+        let id = item.get("id").and_then(|i| i.as_str()).unwrap_or("");
+        let snoozed = snoozes.iter().find(|(s, _)| s == id).map(|(_, until)| adhoc_fire_time(*until, focus_end));
+        if snoozed.map(|at| at <= now).unwrap_or(false) { fired_snoozes += 1; continue; }
+        let remind_time = match snoozed.or_else(|| focus_adjusted_remind_time(item, item_local, remind_time, focus_end)) {
+            Some(t) => t, None => continue,
         };
         if remind_time <= now { continue; }
         let offset_dt = match chrono_to_offset(remind_time) {
             Some(t) => t, None => continue,
         };
-        let diff = (item_local - remind_time).num_minutes().max(0);
-        let time_desc = if diff == 0 { "adesso!".to_string() }
-            else if diff < 60 { format!("tra {} minuti", diff) }
-            else {
-                let h = diff / 60; let m = diff % 60;
-                if m == 0 { format!("tra {} or{}", h, if h == 1 { "a" } else { "e" }) }
-                else { format!("tra {}h {:02}min", h, m) }
-            };
-        let body = format!("{} — {} ({})", item_title, item_time, time_desc);
-        let notif_id = hash_id(&format!("remind-{}-{}-{}", item_date, item_id, item_time));
+        let reminder = reminder_notification(item, item_local, remind_time);
+        let item_date = item.get("date").and_then(|d| d.as_str()).unwrap_or("");
+        let item_time = item.get("time").and_then(|t| t.as_str()).unwrap_or("");
+        let notif_id = hash_id(&format!("remind-{}-{}-{}", item_date, reminder.id, item_time));
+        let sched = tauri_plugin_notification::Schedule::At {
+            date: offset_dt, repeating: false, allow_while_idle: true,
+        };
+        tally.attempt(|| app.notification().builder().id(notif_id).title(&reminder.title)
+            .body(&reminder.body).schedule(sched).show().is_ok());
+    }
+
+    // Ad-hoc reminders; the ones the OS has already fired are pruned below
+    let adhoc = schedule_data.get("adhoc")
+        .and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let mut fired_adhoc = 0;
+    for entry in &adhoc {
+        let Some(n) = adhoc_notification(entry) else { fired_adhoc += 1; continue };
+        let at = adhoc_fire_time(n.at, focus_end);
+        if at <= now { fired_adhoc += 1; continue; }
+        let offset_dt = match chrono_to_offset(at) {
+            Some(t) => t, None => continue,
+        };
         let sched = tauri_plugin_notification::Schedule::At {
             date: offset_dt, repeating: false, allow_while_idle: true,
         };
-        if app.notification().builder().id(notif_id).title("LexFlow — Promemoria")
-            .body(&body).schedule(sched).show().is_ok() {
-            scheduled_count += 1;
+        tally.attempt(|| app.notification().builder().id(hash_id(n.id.as_str())).title(&n.title)
+            .body(&n.body).schedule(sched).show().is_ok());
+    }
+    // Record the offset these instants were converted with, for utc_offset_pass
+    let offset = now.offset().local_minus_utc();
+    let offset_stale = schedule_data.get("lastUtcOffset").and_then(|o| o.as_i64()) != Some(offset as i64);
+    if fired_adhoc > 0 || fired_snoozes > 0 || offset_stale {
+        let state = app.state::<AppState>();
+        let _guard = state.schedule_mutex.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut current) = read_notification_schedule(&data_dir.to_path_buf()) {
+            if let Some(list) = current.get_mut("adhoc").and_then(|a| a.as_array_mut()) {
+                list.retain(|e| adhoc_notification(e).map(|n| adhoc_fire_time(n.at, focus_end) > now).unwrap_or(false));
+            }
+            if let Some(map) = current.get_mut("snoozes").and_then(|s| s.as_object_mut()) {
+                map.retain(|_, until| until.as_i64()
+                    .and_then(|ms| chrono::Local.timestamp_millis_opt(ms).single())
+                    .map(|at| adhoc_fire_time(at, focus_end) > now)
+                    .unwrap_or(false));
+            }
+            current["lastUtcOffset"] = json!(offset);
+            if let Err(e) = write_notification_schedule(data_dir, &current) {
+                lf_log!("[LexFlow Sync] Promemoria ad-hoc non rimossi: {}", e);
+            }
         }
     }
 
-    eprintln!("[LexFlow Sync] ══ Mobile AOT sync: {}/{} notifications scheduled ══", scheduled_count, MAX_SCHEDULED);
+    lf_log!("[LexFlow Sync] ══ Mobile AOT sync: {}/{} notifications scheduled ({} failed, {} dropped) ══",
+        tally.scheduled, tally.requested, tally.failed, tally.dropped);
+    *app.state::<AppState>().notification_capacity.lock().unwrap_or_else(|e| e.into_inner()) = Some(tally.report());
 }
 
 // ── DESKTOP: stub — scheduling is handled by the async cron job ────────────
@@ -2426,20 +13294,33 @@ fn sync_notifications(_app: &AppHandle, _data_dir: &std::path::Path) {
 async fn desktop_cron_job(app: AppHandle) {
     use tauri_plugin_notification::NotificationExt;
 
-    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
-    let mut last_processed_minute = String::new();
+    let mut wait = std::time::Duration::ZERO;
+    // Minute ticks for "poll" on a fixed schedule (no drift); Skip so a pass that overruns
+    // or a sleep of the machine does not replay a burst of missed ticks
+    let mut ticks = tokio::time::interval(std::time::Duration::from_secs(CRON_POLL_SECS));
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut power = (detect_power_source(), Instant::now());
+    let mut last_processed: Option<chrono::DateTime<chrono::Local>> = None;
 
-    eprintln!("[LexFlow Cron] Desktop cron job started — checking every 60s");
+    lf_log!("[LexFlow Cron] Desktop cron job started — power: {}", power.0.as_str());
 
     loop {
-        interval.tick().await;
+        if wait == ticks.period() {
+            ticks.tick().await;
+        } else {
+            tokio::time::sleep(wait).await;
+            ticks.reset();
+        }
 
         let now = chrono::Local::now();
-        let current_minute = now.format("%Y-%m-%d %H:%M").to_string();
 
-        // Avoid double-firing within the same minute
-        if current_minute == last_processed_minute { continue; }
-        last_processed_minute = current_minute.clone();
+        // Every minute since the last check (more than one after a sleep), never twice
+        let minutes = cron_minutes_to_process(last_processed, now);
+        let Some(&current_minute) = minutes.last() else { continue };
+        if minutes.len() > 1 && last_processed.is_some() {
+            lf_log!("[LexFlow Cron] Ripresa dopo {} minuti — recupero dei promemoria persi", minutes.len() - 1);
+        }
+        last_processed = Some(current_minute);
 
         // Read data_dir from managed state
         let data_dir = {
@@ -2448,6 +13329,57 @@ async fn desktop_cron_job(app: AppHandle) {
             dir
         };
 
+        if power.1.elapsed().as_secs() >= POWER_RECHECK_SECS {
+            power = (detect_power_source(), Instant::now());
+        }
+        wait = next_cron_wait(&data_dir, power.0, now);
+
+        let focus_active = focus_until_ms(&data_dir).is_some();
+        let focus_deferred = if focus_active { Vec::new() } else { take_focus_deferred(&app, &data_dir) };
+        if !focus_deferred.is_empty() {
+            for (event_time, item) in focus_deferred {
+                if event_time <= now { continue; } // no longer relevant
+                let reminder = reminder_notification(&item, event_time, now);
+                log_fired_notification(&app, "reminder", &reminder.id, &reminder.title, &reminder.body);
+                let app_clone = app.clone();
+                let _ = app.run_on_main_thread(move || {
+                    let _ = app_clone.notification().builder()
+                        .title(&reminder.title)
+                        .body(&reminder.body)
+                        .show();
+                });
+            }
+            lf_log!("[LexFlow Cron] Focus mode ended — deferred reminders re-fired");
+        }
+
+        // ── Ad-hoc reminders: fired once, then removed from the schedule ──
+        if !focus_active {
+            for n in take_due_adhoc(&app, &data_dir, now) {
+                log_fired_notification(&app, n.kind, &n.id, &n.title, &n.body);
+                let app_clone = app.clone();
+                let _ = app.run_on_main_thread(move || {
+                    let _ = app_clone.notification().builder()
+                        .title(&n.title)
+                        .body(&n.body)
+                        .show();
+                });
+            }
+        }
+
+        // ── Snoozed reminders: fired once when the snooze ends, then cleared ──
+        if !focus_active {
+            for n in take_due_snoozes(&app, &data_dir, now) {
+                log_fired_notification(&app, n.kind, &n.id, &n.title, &n.body);
+                let app_clone = app.clone();
+                let _ = app.run_on_main_thread(move || {
+                    let _ = app_clone.notification().builder()
+                        .title(&n.title)
+                        .body(&n.body)
+                        .show();
+                });
+            }
+        }
+
         // ── Read notification schedule ──
         let schedule_data: serde_json::Value = match read_notification_schedule(&data_dir) {
             Some(v) => v,
@@ -2458,139 +13390,65 @@ async fn desktop_cron_job(app: AppHandle) {
             .and_then(|v| v.as_array()).cloned().unwrap_or_default();
         let items = schedule_data.get("items")
             .and_then(|v| v.as_array()).cloned().unwrap_or_default();
+        let settings = read_settings(&data_dir);
 
-        let today = now.format("%Y-%m-%d").to_string();
-        let tomorrow = (now + chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
-
-        // ── Check briefings: does any briefing fire THIS minute? ──
-        for bt in &briefing_times {
-            let time_str = match bt.as_str() {
-                Some(s) if s.len() >= 5 => s,
-                _ => continue,
-            };
-
-            let briefing_key = format!("{} {}", today, time_str);
-            if briefing_key != current_minute { continue; }
-
-            // This briefing fires NOW
-            let briefing_hour: u32 = time_str.split(':').next()
-                .and_then(|h| h.parse().ok()).unwrap_or(8);
-
-            let (filter_date, time_from, period_label) = if briefing_hour < 12 {
-                (today.as_str(), "00:00", "oggi")
-            } else if briefing_hour < 18 {
-                (today.as_str(), "13:00", "questo pomeriggio")
-            } else {
-                (tomorrow.as_str(), "00:00", "domani")
-            };
-
-            let relevant_count = items.iter().filter(|i| {
-                let d = i.get("date").and_then(|d| d.as_str()).unwrap_or("");
-                let t = i.get("time").and_then(|t| t.as_str()).unwrap_or("00:00");
-                let done = i.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
-                d == filter_date && !done && t >= time_from
-            }).count();
-
-            let title = if relevant_count == 0 {
-                format!("LexFlow — Nessun impegno {}", period_label)
-            } else {
-                format!("LexFlow — {} impegn{} {}", relevant_count,
-                    if relevant_count == 1 { "o" } else { "i" }, period_label)
-            };
-
-            let body_str = if relevant_count == 0 {
-                format!("Nessun impegno in programma per {}.", period_label)
-            } else {
-                let mut relevant_items: Vec<&serde_json::Value> = items.iter()
-                    .filter(|i| {
-                        let d = i.get("date").and_then(|d| d.as_str()).unwrap_or("");
-                        let t = i.get("time").and_then(|t| t.as_str()).unwrap_or("00:00");
-                        let done = i.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
-                        d == filter_date && !done && t >= time_from
-                    }).collect();
-                relevant_items.sort_by(|a, b| {
-                    let ta = a.get("time").and_then(|v| v.as_str()).unwrap_or("");
-                    let tb = b.get("time").and_then(|v| v.as_str()).unwrap_or("");
-                    ta.cmp(tb)
-                });
-                let mut lines: Vec<String> = Vec::new();
-                for item in relevant_items.iter().take(4) {
-                    let t = item.get("time").and_then(|v| v.as_str()).unwrap_or("");
-                    let name = item.get("title").and_then(|v| v.as_str()).unwrap_or("Impegno");
-                    if !t.is_empty() { lines.push(format!("• {} — {}", t, name)); }
-                    else { lines.push(format!("• {}", name)); }
+        let snoozed = snooze_entries(&schedule_data);
+        for n in catch_up_notifications(&briefing_times, &items, &settings, &minutes, now) {
+            if n.kind == "reminder" && snoozed.iter().any(|(id, _)| *id == n.id) {
+                lf_log!("[LexFlow Cron] Reminder snoozed: {}", n.id);
+                continue;
+            }
+            if n.kind == "briefing" && focus_active {
+                lf_log!("[LexFlow Cron] Briefing muted (focus mode): {}", n.id);
+                continue;
+            }
+            if n.kind == "reminder" && !reminder_allowed(&n.item, focus_active) {
+                lf_log!("[LexFlow Cron] Reminder deferred (focus mode): {}", n.id);
+                if let Some((event_time, _)) = reminder_times(&n.item, &settings) {
+                    defer_for_focus(&app, &data_dir, event_time, n.item);
                 }
-                if relevant_count > 4 { lines.push(format!("  …e altri {}", relevant_count - 4)); }
-                lines.join("\n")
-            };
-
+                continue;
+            }
+            log_fired_notification(&app, n.kind, &n.id, &n.title, &n.body);
             let app_clone = app.clone();
-            let title_clone = title.clone();
-            let body_clone = body_str.clone();
+            let (title, body) = (n.title.clone(), n.body.clone());
             let _ = app.run_on_main_thread(move || {
                 let _ = app_clone.notification().builder()
-                    .title(&title_clone)
-                    .body(&body_clone)
+                    .title(&title)
+                    .body(&body)
                     .show();
             });
-            eprintln!("[LexFlow Cron] ✓ Briefing fired: {}", briefing_key);
+            lf_log!("[LexFlow Cron] ✓ {} fired: {}", n.kind, n.id);
         }
+    }
+}
 
-        // ── Check per-item reminders: does any reminder fire THIS minute? ──
-        for item in &items {
-            let item_date = item.get("date").and_then(|d| d.as_str()).unwrap_or("");
-            let item_time = item.get("time").and_then(|t| t.as_str()).unwrap_or("");
-            let item_title = item.get("title").and_then(|t| t.as_str()).unwrap_or("Impegno");
-            let completed = item.get("completed").and_then(|c| c.as_bool()).unwrap_or(false);
-            if completed || item_time.len() < 5 { continue; }
-
-            let item_dt_str = format!("{} {}", item_date, item_time);
-            let item_dt = match chrono::NaiveDateTime::parse_from_str(&item_dt_str, "%Y-%m-%d %H:%M") {
-                Ok(dt) => dt, Err(_) => continue,
-            };
-            let item_local = match chrono::Local.from_local_datetime(&item_dt).single() {
-                Some(t) => t, None => continue,
-            };
-
-            // Determine fire time
-            let custom_remind_time = item.get("customRemindTime")
-                .and_then(|v| v.as_str()).filter(|s| s.len() >= 5);
-            let remind_min = item.get("remindMinutes").and_then(|v| v.as_i64()).unwrap_or(30);
-
-            let remind_time = if let Some(crt) = custom_remind_time {
-                let crt_str = format!("{} {}", item_date, crt);
-                chrono::NaiveDateTime::parse_from_str(&crt_str, "%Y-%m-%d %H:%M")
-                    .ok().and_then(|dt| chrono::Local.from_local_datetime(&dt).single())
-                    .unwrap_or(item_local - chrono::Duration::minutes(remind_min))
-            } else {
-                item_local - chrono::Duration::minutes(remind_min)
-            };
-
-            let fire_minute = remind_time.format("%Y-%m-%d %H:%M").to_string();
-            if fire_minute != current_minute { continue; }
-
-            // This reminder fires NOW
-            let diff = (item_local - remind_time).num_minutes().max(0);
-            let time_desc = if diff == 0 { "adesso!".to_string() }
-                else if diff < 60 { format!("tra {} minuti", diff) }
-                else {
-                    let h = diff / 60; let m = diff % 60;
-                    if m == 0 { format!("tra {} or{}", h, if h == 1 { "a" } else { "e" }) }
-                    else { format!("tra {}h {:02}min", h, m) }
-                };
-            let body = format!("{} — {} ({})", item_title, item_time, time_desc);
-
-            let app_clone = app.clone();
-            let body_clone = body.clone();
-            let _ = app.run_on_main_thread(move || {
-                let _ = app_clone.notification().builder()
-                    .title("LexFlow — Promemoria")
-                    .body(&body_clone)
-                    .show();
-            });
-            eprintln!("[LexFlow Cron] ✓ Reminder fired: {} → {}", item_title, fire_minute);
-        }
+/// Sleep before the cron's next check. With reminders held back by focus mode, the end of
+/// focus is also a wake point so they are re-fired on time.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn next_cron_wait(
+    data_dir: &std::path::Path,
+    power: PowerSource,
+    now: chrono::DateTime<chrono::Local>,
+) -> std::time::Duration {
+    let settings = read_settings(data_dir);
+    let strategy = select_notification_strategy(notification_strategy_setting(&settings), power);
+    if strategy == NotificationStrategy::Poll {
+        return cron_wait(strategy, cron_poll_secs(&settings), now, None);
     }
+    let focus_end = focus_until_ms(data_dir)
+        .and_then(|ms| chrono::Local.timestamp_millis_opt(ms as i64).single());
+    let schedule = read_notification_schedule(&data_dir.to_path_buf());
+    let has_deferred = schedule.as_ref().is_some_and(|s| !focus_deferred_entries(s).is_empty());
+    let next_planned = schedule
+        .map(|schedule| compute_upcoming_notifications(&schedule, &settings, now, focus_end, 1))
+        .and_then(|next| next.first().and_then(|n| n.get("atMs")).and_then(|v| v.as_i64()))
+        .and_then(|ms| chrono::Local.timestamp_millis_opt(ms).single());
+    let next_fire = match (next_planned, focus_end.filter(|_| has_deferred)) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    cron_wait(strategy, cron_poll_secs(&settings), now, next_fire)
 }
 
 // ═══════════════════════════════════════════════════════════
@@ -2663,6 +13521,238 @@ fn show_main_window(app: AppHandle) {
     { let _ = app; }
 }
 
+// ─── Old app identifier migration ───────────────────────────────────────────
+// Runs at startup and on demand (support, after the user restores an old profile).
+// Only ever copies into missing destinations. The vault files form one set: salt,
+// verify tag and ciphertext must match, so they are copied only into a location that
+// has no vault at all; a vault created under the new identifier is never mixed with
+// the old one.
+const OLD_APP_IDENTIFIER: &str = "com.technojaw.lexflow";
+
+#[cfg(not(target_os = "android"))]
+fn old_identifier_base() -> PathBuf {
+    dirs::data_dir().unwrap_or_default().join(OLD_APP_IDENTIFIER)
+}
+
+fn migrate_old_identifier_dirs(old_base: &std::path::Path, data_dir: &std::path::Path, security_dir: &std::path::Path) -> Value {
+    if !old_base.is_dir() {
+        return json!({"found": false, "copied": [], "skipped": []});
+    }
+    let mut copied: Vec<String> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+    let mut candidates: Vec<(PathBuf, PathBuf, String)> = Vec::new();
+
+    let old_vault = old_base.join("lexflow-vault");
+    let new_has_vault = data_dir.join(VAULT_FILE).exists() || data_dir.join(VAULT_SALT_FILE).exists();
+    let vault_set = [VAULT_FILE, VAULT_SALT_FILE, VAULT_VERIFY_FILE, VAULT_KDF_FILE, AUDIT_LOG_FILE, VAULT_BACKUP_FILE];
+    let mut vault_conflict = false;
+    if let Ok(entries) = fs::read_dir(&old_vault) {
+        let _ = fs::create_dir_all(data_dir);
+        for entry in entries.flatten().filter(|e| e.path().is_file()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(".tmp") { continue; }
+            let label = format!("lexflow-vault/{}", name);
+            if new_has_vault && vault_set.contains(&name.as_str()) {
+                vault_conflict = true;
+                skipped.push(label);
+                continue;
+            }
+            candidates.push((entry.path(), data_dir.join(&name), label));
+        }
+    }
+    for sec_file in &[LICENSE_FILE, LICENSE_SENTINEL_FILE, BURNED_KEYS_FILE, LOCKOUT_FILE] {
+        let old_path = old_base.join(sec_file);
+        if old_path.is_file() {
+            candidates.push((old_path, security_dir.join(sec_file), sec_file.to_string()));
+        }
+    }
+    for (from, to, label) in candidates {
+        if to.exists() {
+            skipped.push(label);
+        } else if fs::copy(&from, &to).is_ok() {
+            copied.push(label);
+        }
+    }
+    json!({"found": true, "copied": copied, "skipped": skipped, "vaultConflict": vault_conflict})
+}
+
+/// On-demand re-run of the startup migration from the old app identifier.
+/// `vaultConflict`: an old vault exists but a vault already lives in the new location.
+#[tauri::command]
+fn migrate_from_old_identifier(state: State<AppState>) -> Value {
+    #[cfg(not(target_os = "android"))]
+    {
+        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let report = migrate_old_identifier_dirs(&old_identifier_base(), &dir, &sec_dir);
+        lf_log!("[LexFlow] Migrazione identificativo su richiesta: {}", report);
+        report
+    }
+    #[cfg(target_os = "android")]
+    {
+        let _ = state;
+        json!({"found": false, "copied": [], "skipped": []})
+    }
+}
+
+// ─── Directory layout invariant ─────────────────────────────────────────────
+// Security files sit in the parent of the vault dir so that resetting the vault cannot
+// erase them. A botched migration (or an Android path mix-up) can make the two coincide
+// or nest the wrong way round; this reports it instead of silently weakening the design.
+const SECURITY_ONLY_FILES: [&str; 5] = [LICENSE_FILE, LICENSE_SENTINEL_FILE, BURNED_KEYS_FILE, LOCKOUT_FILE, LICENSE_LOCKOUT_FILE];
+const VAULT_ONLY_FILES: [&str; 4] = [VAULT_FILE, VAULT_SALT_FILE, VAULT_VERIFY_FILE, VAULT_KDF_FILE];
+
+fn dir_is_writable(dir: &std::path::Path) -> bool {
+    let probe = dir.join(".layout-probe");
+    let ok = fs::write(&probe, b"").is_ok();
+    let _ = fs::remove_file(&probe);
+    ok
+}
+
+fn dir_layout_issues(data_dir: &std::path::Path, security_dir: &std::path::Path) -> Vec<String> {
+    let mut issues = Vec::new();
+    if security_dir.starts_with(data_dir) {
+        issues.push("La cartella di sicurezza coincide con quella del vault o è al suo interno".to_string());
+    }
+    for (label, dir) in [("vault", data_dir), ("sicurezza", security_dir)] {
+        if !dir_is_writable(dir) {
+            issues.push(format!("Cartella {} non scrivibile: {}", label, dir.display()));
+        }
+    }
+    if data_dir != security_dir {
+        issues.extend(SECURITY_ONLY_FILES.iter()
+            .filter(|f| data_dir.join(f).exists())
+            .map(|f| format!("{} si trova nella cartella del vault", f)));
+        issues.extend(VAULT_ONLY_FILES.iter()
+            .filter(|f| security_dir.join(f).exists())
+            .map(|f| format!("{} si trova nella cartella di sicurezza", f)));
+    }
+    issues
+}
+
+#[tauri::command]
+fn check_dir_layout(state: State<AppState>) -> Value {
+    let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let issues = dir_layout_issues(&dir, &sec_dir);
+    json!({
+        "ok": issues.is_empty(),
+        "dataDir": dir.to_string_lossy(),
+        "securityDir": sec_dir.to_string_lossy(),
+        "issues": issues,
+    })
+}
+
+// ─── Vault profiles ─────────────────────────────────────────────────────────
+// People sharing one OS account each get an isolated vault. A named profile lives in
+// security_dir/profiles/<slug>/ with its own salt, verify tag, vault and settings; the
+// default profile is the original lexflow-vault dir. Each profile's vault lockout counter
+// sits outside its data dir (profile-lockouts/<slug>/), so a vault reset cannot clear it.
+const DEFAULT_PROFILE: &str = "default";
+const DEFAULT_VAULT_DIR: &str = "lexflow-vault";
+const PROFILES_DIR: &str = "profiles";
+const PROFILE_LOCKOUTS_DIR: &str = "profile-lockouts";
+const ACTIVE_PROFILE_FILE: &str = ".active-profile";
+const MAX_PROFILE_SLUG_LEN: usize = 40;
+
+/// Lowercase ASCII letters and digits joined by single dashes — no separators or dots
+/// can survive, so a slug is always one path component.
+fn profile_slug(name: &str) -> Option<String> {
+    let mut slug = String::new();
+    for c in name.trim().to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_end_matches('-').chars().take(MAX_PROFILE_SLUG_LEN).collect();
+    let slug = slug.trim_end_matches('-').to_string();
+    (!slug.is_empty()).then_some(slug)
+}
+
+fn profile_data_dir(security_dir: &std::path::Path, slug: &str) -> PathBuf {
+    if slug == DEFAULT_PROFILE {
+        security_dir.join(DEFAULT_VAULT_DIR)
+    } else {
+        security_dir.join(PROFILES_DIR).join(slug)
+    }
+}
+
+fn profile_lockout_dir(security_dir: &std::path::Path, slug: &str) -> PathBuf {
+    if slug == DEFAULT_PROFILE {
+        security_dir.to_path_buf()
+    } else {
+        security_dir.join(PROFILE_LOCKOUTS_DIR).join(slug)
+    }
+}
+
+fn list_profile_slugs(security_dir: &std::path::Path) -> Vec<String> {
+    let mut named: Vec<String> = fs::read_dir(security_dir.join(PROFILES_DIR))
+        .map(|entries| entries.flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+            .filter(|n| profile_slug(n).as_deref() == Some(n.as_str()) && n != DEFAULT_PROFILE)
+            .collect())
+        .unwrap_or_default();
+    named.sort();
+    std::iter::once(DEFAULT_PROFILE.to_string()).chain(named).collect()
+}
+
+/// Profile to open at startup: the last one switched to, if it still exists.
+fn read_active_profile(security_dir: &std::path::Path) -> String {
+    fs::read_to_string(security_dir.join(ACTIVE_PROFILE_FILE)).ok()
+        .and_then(|s| profile_slug(&s))
+        .filter(|slug| profile_data_dir(security_dir, slug).is_dir())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+#[tauri::command]
+fn list_profiles(state: State<AppState>) -> Value {
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let active = state.active_profile.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let profiles: Vec<Value> = list_profile_slugs(&sec_dir).into_iter().map(|slug| json!({
+        "name": slug,
+        "initialized": profile_data_dir(&sec_dir, &slug).join(VAULT_SALT_FILE).exists(),
+        "active": slug == active,
+    })).collect();
+    json!({"active": active, "profiles": profiles})
+}
+
+#[tauri::command]
+fn create_profile(state: State<AppState>, name: String) -> Result<Value, String> {
+    let slug = profile_slug(&name).ok_or("Nome profilo non valido")?;
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let dir = profile_data_dir(&sec_dir, &slug);
+    if slug == DEFAULT_PROFILE || dir.exists() {
+        return Err("Esiste già un profilo con questo nome".into());
+    }
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(profile_lockout_dir(&sec_dir, &slug)).map_err(|e| e.to_string())?;
+    lf_log!("[LexFlow] Profilo creato: {}", slug);
+    Ok(json!({"success": true, "name": slug}))
+}
+
+/// Lock the current vault, then point data_dir at another profile. The choice persists.
+#[tauri::command]
+fn switch_profile(state: State<AppState>, name: String) -> Result<Value, String> {
+    let slug = profile_slug(&name).ok_or("Nome profilo non valido")?;
+    let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let dir = profile_data_dir(&sec_dir, &slug);
+    if slug != DEFAULT_PROFILE && !dir.is_dir() {
+        return Err("Profilo inesistente".into());
+    }
+    let _ = fs::create_dir_all(&dir);
+    lock_vault_state(&state);
+    // The in-memory counter belonged to the previous profile
+    *state.vault_lockout.lock().unwrap_or_else(|e| e.into_inner()) = LockoutCounter::default();
+    *state.data_dir.lock().unwrap_or_else(|e| e.into_inner()) = dir.clone();
+    *state.active_profile.lock().unwrap_or_else(|e| e.into_inner()) = slug.clone();
+    atomic_write_with_sync(&sec_dir.join(ACTIVE_PROFILE_FILE), slug.as_bytes()).map_err(|e| e.to_string())?;
+    lf_log!("[LexFlow] Profilo attivo: {}", slug);
+    Ok(json!({"success": true, "name": slug, "initialized": dir.join(VAULT_SALT_FILE).exists()}))
+}
+
 // ═══════════════════════════════════════════════════════════
 //  APP RUNNER
 // ═══════════════════════════════════════════════════════════
@@ -2677,7 +13767,7 @@ pub fn run() {
     let data_dir = dirs::data_dir()
         .unwrap()
         .join("com.pietrolongo.lexflow")
-        .join("lexflow-vault");
+        .join(DEFAULT_VAULT_DIR);
 
     // security_dir: parent of vault — security files live here so vault reset cannot erase them
     #[cfg(not(target_os = "android"))]
@@ -2696,29 +13786,9 @@ pub fn run() {
     // ── MIGRATION: move data from old identifier (com.technojaw.lexflow) to new one ──
     #[cfg(not(target_os = "android"))]
     {
-        let old_base = dirs::data_dir().unwrap().join("com.technojaw.lexflow");
-        if old_base.exists() && old_base.is_dir() {
-            // Migrate vault directory
-            let old_vault = old_base.join("lexflow-vault");
-            if old_vault.exists() && !data_dir.join(VAULT_FILE).exists() {
-                // Copy all files from old vault to new vault
-                if let Ok(entries) = fs::read_dir(&old_vault) {
-                    for entry in entries.flatten() {
-                        let dest = data_dir.join(entry.file_name());
-                        if !dest.exists() {
-                            let _ = fs::copy(entry.path(), &dest);
-                        }
-                    }
-                }
-            }
-            // Migrate security files from old base
-            for sec_file in &[LICENSE_FILE, LICENSE_SENTINEL_FILE, BURNED_KEYS_FILE, LOCKOUT_FILE] {
-                let old_path = old_base.join(sec_file);
-                let new_path = security_dir.join(sec_file);
-                if old_path.exists() && !new_path.exists() {
-                    let _ = fs::copy(&old_path, &new_path);
-                }
-            }
+        let report = migrate_old_identifier_dirs(&old_identifier_base(), &data_dir, &security_dir);
+        if let Some(n) = report["copied"].as_array().map(|c| c.len()).filter(|n| *n > 0) {
+            lf_log!("[LexFlow] Migrazione da {}: {} file copiati", OLD_APP_IDENTIFIER, n);
         }
     }
 
@@ -2733,6 +13803,21 @@ pub fn run() {
             let _ = fs::remove_file(&old_path);
         }
     }
+    // ── SELF-CHECK: a wrong key pasted at build time would silently reject every license ──
+    let key_status = public_key_status(&PUBLIC_KEY_BYTES);
+    if key_status["valid"].as_bool() != Some(true) {
+        lf_log!("[LexFlow] ⚠️ LICENSE PUBLIC KEY INVALID (fp {}) — all activations will fail", key_status["fingerprint"]);
+    } else {
+        lf_log!("[LexFlow] License public key OK (fp {}) ✓", key_status["fingerprint"]);
+    }
+
+    // ── STARTUP SWEEP: remove crash debris (.vault.tmp, .salt.tmp, settings.tmp, …) ──
+    for dir in [&data_dir, &security_dir] {
+        for removed in sweep_stale_tmp_files(dir, Duration::from_secs(STALE_TMP_AGE_SECS)) {
+            lf_log!("[LexFlow] Rimosso file temporaneo orfano: {:?}", removed);
+        }
+    }
+
     // data_dir_for_sync: used in setup() to perform initial notification sync.
     #[cfg(not(target_os = "android"))]
     let data_dir_for_scheduler = data_dir.clone();
@@ -2749,13 +13834,78 @@ pub fn run() {
             data_dir: Mutex::new(data_dir),
             security_dir: Mutex::new(security_dir),
             vault_key: Mutex::new(None),
-            failed_attempts: Mutex::new(0),
-            locked_until: Mutex::new(None),
+            vault_lockout: Mutex::new(LockoutCounter::default()),
+            license_lockout: Mutex::new(LockoutCounter::default()),
             last_activity: Mutex::new(Instant::now()),
             autolock_minutes: Mutex::new(5),
+            autolock_grace_until: Mutex::new(None),
             write_mutex: Mutex::new(()),
+            pending_saves: Mutex::new(PendingSaves::default()),
+            save_debounce_ms: Mutex::new(DEFAULT_SAVE_DEBOUNCE_MS),
+            reauth_token: Mutex::new(None),
+            audit_mutex: Mutex::new(()),
+            kdf_benchmark: Mutex::new(None),
+            notif_history_mutex: Mutex::new(()),
+            schedule_mutex: Mutex::new(()),
+            snapshot: Mutex::new(None),
+            notification_capacity: Mutex::new(None),
+            license_nudge: Mutex::new(LicenseNudge::default()),
+            utc_offset: Mutex::new(None),
+            agenda_archived_at: Mutex::new(None),
+            vault_generation: std::sync::atomic::AtomicU64::new(0),
+            conflict_sessions: Mutex::new(std::collections::HashMap::new()),
+            auth_gate: AuthGate::new(),
+            perf_stats: Mutex::new(PerfStats::default()),
+            active_profile: Mutex::new(DEFAULT_PROFILE.to_string()),
         })
         .setup(move |app| {
+            // ── ANDROID: resolve data dirs + canonical device id FIRST ──
+            // Must precede every local-key operation (settings, license, schedule).
+            #[cfg(target_os = "android")]
+            {
+                // Risolvi il path reale tramite Tauri PathResolver — nessun hardcoded path.
+                // app_data_dir() = /data/data/<pkg>/files/ (privato, senza root).
+                if let Ok(real_dir) = app.path().app_data_dir() {
+                    // Older builds may have written .device_id in one of these guesses
+                    let legacy: Vec<PathBuf> = [
+                        dirs::data_dir().map(|d| d.join("com.pietrolongo.lexflow")),
+                        std::env::temp_dir().parent().map(|p| p.join("com.pietrolongo.lexflow")),
+                    ].into_iter().flatten().collect();
+                    match ensure_device_id(&real_dir, &legacy) {
+                        Ok(_) => { let _ = ANDROID_DATA_DIR.set(real_dir.clone()); }
+                        Err(e) => lf_log!("[LexFlow] ⚠️ ID dispositivo non inizializzato: {}", e),
+                    }
+                    let vault_dir = real_dir.join(DEFAULT_VAULT_DIR);
+                    let _ = fs::create_dir_all(&vault_dir);
+                    *app.state::<AppState>().data_dir.lock().unwrap_or_else(|e| e.into_inner()) = vault_dir.clone();
+                    *app.state::<AppState>().security_dir.lock().unwrap_or_else(|e| e.into_inner()) = real_dir.clone();
+                    // ── AHEAD-OF-TIME SYNC on Android ──
+                    sync_notifications(&app.handle(), &vault_dir);
+                }
+            }
+
+            // ── PROFILES: reopen the profile that was active at the last switch ──
+            {
+                let state = app.state::<AppState>();
+                let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                let active = read_active_profile(&sec_dir);
+                if active != DEFAULT_PROFILE {
+                    *state.data_dir.lock().unwrap_or_else(|e| e.into_inner()) = profile_data_dir(&sec_dir, &active);
+                    lf_log!("[LexFlow] Profilo attivo: {}", active);
+                }
+                *state.active_profile.lock().unwrap_or_else(|e| e.into_inner()) = active;
+            }
+
+            // ── LAYOUT INVARIANT: security files must stay outside the vault dir ──
+            {
+                let state = app.state::<AppState>();
+                let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                let sec_dir = state.security_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                for issue in dir_layout_issues(&dir, &sec_dir) {
+                    lf_log!("[LexFlow] ⚠️ Layout cartelle: {}", issue);
+                }
+            }
+
             // ── NOTIFICATION PERMISSION (native, at startup) ──
             // On macOS, permission is bound to the app's code signature. During development
             // (ad-hoc signing), each rebuild changes the signature, causing macOS Notification
@@ -2769,24 +13919,24 @@ pub fn run() {
             {
                 use tauri_plugin_notification::NotificationExt;
                 let state = app.notification().permission_state();
-                eprintln!("[LexFlow] Notification permission state: {:?}", state);
+                lf_log!("[LexFlow] Notification permission state: {:?}", state);
                 match state {
                     Ok(tauri_plugin_notification::PermissionState::Granted) => {
-                        eprintln!("[LexFlow] Notifications already granted ✓");
+                        lf_log!("[LexFlow] Notifications already granted ✓");
                     }
                     Ok(tauri_plugin_notification::PermissionState::Denied) => {
                         // DO NOT call request_permission() here — Apple will ignore it
                         // and may permanently silence the app's XPC notification daemon.
-                        eprintln!("[LexFlow] ⚠️ Notifications DENIED by user/system.");
-                        eprintln!("[LexFlow] → User must enable manually: System Settings → Notifications → LexFlow");
+                        lf_log!("[LexFlow] ⚠️ Notifications DENIED by user/system.");
+                        lf_log!("[LexFlow] → User must enable manually: System Settings → Notifications → LexFlow");
                         // Emit to frontend so we can show an in-app banner
                         let _ = app.emit("notification-permission-denied", ());
                     }
                     _ => {
                         // Unknown/NotDetermined — safe to request
-                        eprintln!("[LexFlow] Notifications unknown — requesting permission...");
+                        lf_log!("[LexFlow] Notifications unknown — requesting permission...");
                         let result = app.notification().request_permission();
-                        eprintln!("[LexFlow] Permission request result: {:?}", result);
+                        lf_log!("[LexFlow] Permission request result: {:?}", result);
                     }
                 }
             }
@@ -2805,7 +13955,7 @@ pub fn run() {
                 let _ = std::process::Command::new("defaults")
                     .args(["write", &bundle_id, "NSAppSleepDisabled", "-bool", "YES"])
                     .output();
-                eprintln!("[LexFlow] macOS App Nap disabled via defaults write ✓");
+                lf_log!("[LexFlow] macOS App Nap disabled via defaults write ✓");
             }
 
             // Launch the desktop cron job (single async task, zero threads)
@@ -2817,20 +13967,6 @@ pub fn run() {
                 });
             }
 
-            #[cfg(target_os = "android")]
-            {
-                // Risolvi il path reale tramite Tauri PathResolver — nessun hardcoded path.
-                // app_data_dir() = /data/data/<pkg>/files/ (privato, senza root).
-                if let Ok(real_dir) = app.path().app_data_dir() {
-                    let vault_dir = real_dir.join("lexflow-vault");
-                    let _ = fs::create_dir_all(&vault_dir);
-                    *app.state::<AppState>().data_dir.lock().unwrap_or_else(|e| e.into_inner()) = vault_dir.clone();
-                    *app.state::<AppState>().security_dir.lock().unwrap_or_else(|e| e.into_inner()) = real_dir.clone();
-                    // ── AHEAD-OF-TIME SYNC on Android ──
-                    sync_notifications(&app.handle(), &vault_dir);
-                }
-            }
-
             #[cfg(not(target_os = "android"))]
             {
                 // NOTE: set_content_protected(true) removed — causes SIGABRT crash on
@@ -2841,44 +13977,13 @@ pub fn run() {
 
                 // Auto-lock thread con sleep adattivo:
                 // - vault bloccato → dorme 60s (nessun lavoro da fare, risparmia CPU)
-                // - vault sbloccato → dorme 30s (controlla inattività)
-                // RACE CONDITION FIX (L7 #5): emit "lf-vault-warning" 30s before locking
-                // so the frontend can show a "saving..." notice and the user can ping activity.
-                // This prevents data loss when the user is mid-form at the autolock boundary.
+                // - vault sbloccato → dorme fino a 30s (controlla inattività), 1s nell'ultimo minuto
+                // RACE CONDITION FIX (L7 #5): emit "lf-vault-warning" {remainingSecs} on every
+                // tick of the final minute so the frontend can show a countdown and a "saving..."
+                // notice, and the user can ping activity before the autolock boundary.
                 let ah = app.handle().clone();
-                std::thread::spawn(move || {
-                    loop {
-                        let state = ah.state::<AppState>();
-                        let is_unlocked = state.vault_key.lock()
-                            .map(|k| k.is_some()).unwrap_or(false);
-                        if !is_unlocked {
-                            drop(state);
-                            std::thread::sleep(Duration::from_secs(60));
-                            continue;
-                        }
-                        let minutes = state.autolock_minutes.lock()
-                            .map(|m| *m).unwrap_or(5);
-                        let last = state.last_activity.lock()
-                            .map(|l| *l).unwrap_or_else(|_| Instant::now());
-                        drop(state);
-                        std::thread::sleep(Duration::from_secs(30));
-                        if minutes == 0 { continue; }
-                        let elapsed = Instant::now().duration_since(last);
-                        let threshold = Duration::from_secs(minutes as u64 * 60);
-                        // Warning: 30s before actual lock
-                        if elapsed >= threshold.saturating_sub(Duration::from_secs(30))
-                            && elapsed < threshold
-                        {
-                            let _ = ah.emit("lf-vault-warning", ());
-                        }
-                        if elapsed >= threshold {
-                            let state2 = ah.state::<AppState>();
-                            if let Ok(mut key) = state2.vault_key.lock() {
-                                *key = None;
-                            }
-                            let _ = ah.emit("lf-vault-locked", ());
-                        }
-                    }
+                std::thread::spawn(move || loop {
+                    std::thread::sleep(autolock_pass(&ah));
                 });
 
                 // Show main window after setup
@@ -2891,18 +13996,57 @@ pub fn run() {
                 let app_handle = app.handle().clone();
                 if let Some(w) = app.get_webview_window("main") {
                     let w_clone = w.clone();
+                    let focus_epoch = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
                     w.on_window_event(move |event| {
                         match event {
                             // Privacy shield: emit blur event so frontend can obscure content
                             tauri::WindowEvent::Focused(focused) => {
                                 let _ = app_handle.emit("lf-blur", !focused);
+                                let epoch = focus_epoch.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                                if !*focused {
+                                    let (ah, win, fe) = (app_handle.clone(), w_clone.clone(), focus_epoch.clone());
+                                    std::thread::spawn(move || {
+                                        std::thread::sleep(Duration::from_millis(BLUR_DEBOUNCE_MS));
+                                        let state = ah.state::<AppState>();
+                                        let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                                        let settings = read_settings(&dir);
+                                        let flag = |k: &str| settings.get(k).and_then(|v| v.as_bool()).unwrap_or(false);
+                                        let action = decide_blur_action(
+                                            flag("lockOnMinimize"), flag("autosaveOnBlur"), epoch,
+                                            fe.load(std::sync::atomic::Ordering::SeqCst), win.is_minimized().unwrap_or(false),
+                                        );
+                                        match action {
+                                            BlurAction::Lock if get_vault_key(&state).is_ok() => {
+                                                lock_vault_state(&state);
+                                                let _ = ah.emit("lf-vault-locked", ());
+                                            }
+                                            BlurAction::Flush | BlurAction::Lock => { let _ = flush_pending_saves(&state); }
+                                            BlurAction::None => {}
+                                        }
+                                    });
+                                }
                             }
-                            // SYSTEM TRAY FIX: intercept the 'X' close button — hide the window
-                            // instead of terminating the process so the notification scheduler
-                            // keeps running in the background.  The user can quit via tray menu.
+                            // SYSTEM TRAY FIX: intercept the 'X' close button — by default hide the
+                            // window so the notification scheduler keeps running in the background
+                            // (user quits via tray menu). settings.closeBehavior can override.
                             tauri::WindowEvent::CloseRequested { api, .. } => {
-                                api.prevent_close();
-                                let _ = w_clone.hide();
+                                let state = app_handle.state::<AppState>();
+                                let dir = state.data_dir.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                                let settings = read_settings(&dir);
+                                match resolve_close_behavior(settings.get("closeBehavior").and_then(|v| v.as_str()), std::env::consts::OS) {
+                                    CloseAction::Tray => {
+                                        api.prevent_close();
+                                        let _ = w_clone.hide();
+                                    }
+                                    CloseAction::Quit => {
+                                        api.prevent_close();
+                                        lock_and_exit(&app_handle);
+                                    }
+                                    CloseAction::Ask => {
+                                        api.prevent_close();
+                                        let _ = app_handle.emit("lf-close-requested", ());
+                                    }
+                                }
                             }
                             _ => {}
                         }
@@ -2933,12 +14077,8 @@ pub fn run() {
                                     let _ = w.set_focus();
                                 }
                             }
-                            "quit" => {
-                                // Lock vault before exiting so key is not in memory
-                                let state = app.state::<AppState>();
-                                *state.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
-                                app.exit(0);
-                            }
+                            // Lock vault before exiting so key is not in memory
+                            "quit" => lock_and_exit(app),
                             _ => {}
                         })
                         // Left-click directly on the tray icon → show window
@@ -2960,34 +14100,10 @@ pub fn run() {
             {
                 // Android: stesso pattern sleep adattivo del desktop —
                 // meno wakeup quando il vault è bloccato = risparmio batteria
-                // RACE CONDITION FIX (L7 #5): warning event 30s before lock
+                // RACE CONDITION FIX (L7 #5): countdown events during the final minute
                 let ah = app.handle().clone();
-                std::thread::spawn(move || {
-                    loop {
-                        let state = ah.state::<AppState>();
-                        let is_unlocked = state.vault_key.lock().unwrap_or_else(|e| e.into_inner()).is_some();
-                        if !is_unlocked {
-                            std::thread::sleep(Duration::from_secs(60));
-                            continue;
-                        }
-                        let minutes = *state.autolock_minutes.lock().unwrap_or_else(|e| e.into_inner());
-                        let last = *state.last_activity.lock().unwrap_or_else(|e| e.into_inner());
-                        drop(state);
-                        std::thread::sleep(Duration::from_secs(30));
-                        if minutes == 0 { continue; }
-                        let elapsed = Instant::now().duration_since(last);
-                        let threshold = Duration::from_secs(minutes as u64 * 60);
-                        if elapsed >= threshold.saturating_sub(Duration::from_secs(30))
-                            && elapsed < threshold
-                        {
-                            let _ = ah.emit("lf-vault-warning", ());
-                        }
-                        if elapsed >= threshold {
-                            let state2 = ah.state::<AppState>();
-                            *state2.vault_key.lock().unwrap_or_else(|e| e.into_inner()) = None;
-                            let _ = ah.emit("lf-vault-locked", ());
-                        }
-                    }
+                std::thread::spawn(move || loop {
+                    std::thread::sleep(autolock_pass(&ah));
                 });
             }
 
@@ -2996,29 +14112,88 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Vault
             vault_exists,
+            estimated_unlock_ms,
             unlock_vault,
+            get_vault_info,
             lock_vault,
+            get_autolock_status,
             reset_vault,
             change_password,
+            dry_run_change_password,
             verify_vault_password,
+            request_reauth_token,
             get_audit_log,
+            get_audit_log_page,
+            get_audit_log_filtered,
+            verify_audit_chain,
+            export_audit_csv,
+            verify_audit_csv_export,
+            verify_audit_csv,
             // Data
             load_practices,
+            hidden_practices_count,
+            load_confidential_practices,
             save_practices,
+            link_practice_folder,
+            list_practice_documents,
+            audit_attachments,
             load_agenda,
+            validate_agenda_links,
+            repair_agenda_links,
+            set_occurrence_completed,
+            snooze_notification,
+            load_archived_agenda,
+            practice_proof,
+            verify_practice_proof,
             save_agenda,
+            import_agenda_ics,
             get_summary,
             // Conflict Check (v3.2.0)
             check_conflict,
+            contact_adversity_map,
+            find_duplicate_practices,
+            search_vault,
+            update_practice,
+            soft_delete_practice,
+            restore_practice,
+            list_trash,
+            purge_trash,
+            set_practice_secret,
+            get_practice_secret,
+            list_practice_secrets,
+            verify_vault_integrity,
+            validate_dates,
+            repair_dates,
+            get_perf_stats,
+            reset_perf_stats,
+            practices_missing_conflict_check,
+            confirm_conflict_check,
+            begin_conflict_session,
+            conflict_query,
+            end_conflict_session,
+            filter_by_tag,
+            list_tags,
             // Time Tracking (v3.3.0)
             load_time_logs,
             save_time_logs,
+            find_timelog_overlaps,
+            convert_duration,
             // Invoices / Billing (v3.4.0)
             load_invoices,
             save_invoices,
+            client_billing_summary,
+            invoice_aging,
+            firm_activity_report,
             // Contacts Registry (v3.5.0)
             load_contacts,
             save_contacts,
+            export_contact,
+            client_data_export,
+            // Debounced saves
+            queue_save,
+            flush_saves,
+            set_save_debounce_ms,
+            get_save_debounce_ms,
             // Settings
             get_settings,
             save_settings,
@@ -3028,26 +14203,75 @@ pub fn run() {
             save_bio,
             bio_login,
             clear_bio,
+            verify_bio_credential,
+            refresh_bio_after_password_change,
             // Files
             select_file,
+            estimate_attachment_impact,
+            find_orphaned_attachments,
+            purge_orphaned_attachments,
+            attach_document,
+            read_document,
+            delete_document,
             select_folder,
             open_path,
             select_pdf_save_path,
             // Notifications
             send_notification,
             sync_notification_schedule,
+            get_notification_history,
+            clear_notification_history,
             test_notification,
+            set_focus_mode,
+            clear_focus_mode,
+            get_focus_mode,
+            get_notification_strategy,
+            migrate_legacy_file,
+            set_notification_strategy,
+            upcoming_notifications,
+            schedule_adhoc_reminder,
+            notification_capacity_report,
             // License
             check_license,
             verify_license,
+            is_valid_key_format,
+            verify_signed_artifact,
             activate_license,
+            activate_license_from_file,
+            license_system_status,
+            export_burn_registry,
+            import_burn_registry,
+            get_license_receipt,
+            deactivate_license,
+            license_warning_status,
+            check_license_expiry,
+            repair_license_state,
             // Import / Export
             export_vault,
+            export_plaintext_json,
             import_vault,
+            export_archive,
+            import_archive,
+            diagnose_backup,
+            simulate_restore,
+            export_readonly_snapshot,
+            open_snapshot,
+            get_support_diagnostics,
+            migrate_from_old_identifier,
+            check_dir_layout,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            export_diagnostics,
+            read_diagnostics_bundle,
             // Platform
             is_mac,
             get_app_version,
             get_platform,
+            get_capabilities,
+            lockout_status,
+            machine_id_status,
+            regenerate_machine_id,
             // Security & Content Protection
             set_content_protection,
             ping_activity,
@@ -3057,6 +14281,7 @@ pub fn run() {
             window_minimize,
             window_maximize,
             window_close,
+            quit_app,
             show_main_window,
         ])
         .build(tauri::generate_context!())